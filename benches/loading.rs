@@ -0,0 +1,116 @@
+//! Benchmarks the decoding of tiff tiles, which the `TiffLoader` runs on the `AsyncComputeTaskPool`,
+//! and the computation of their normals, which is split into row blocks on the `ComputeTaskPool`.
+//!
+//! Run them with `cargo bench --bench loading`, see the loading latency section in `docs/implementation.md`.
+
+use bevy::{
+    math::Vec3,
+    tasks::{block_on, AsyncComputeTaskPool, ComputeTaskPool, TaskPool},
+};
+use bevy_terrain::{
+    formats::tiff::{decode_tiff, TiffLoaderSettings},
+    terrain_data::compute_normals,
+};
+use bytemuck::pod_collect_to_vec;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::io::Cursor;
+use tiff::encoder::{colortype::Gray16, TiffEncoder};
+
+/// The count of tiles, which are loaded at the same time, e.g. when a new region of the terrain becomes visible.
+const TILE_COUNT: usize = 4;
+
+/// Encodes a 16 bit heightmap of the size with a smooth ramp, as a striped and uncompressed tiff file.
+fn encode_heightmap(size: u32) -> Vec<u8> {
+    let data = (0..size * size)
+        .map(|index| ((index % size + index / size) * u16::MAX as u32 / (2 * size)) as u16)
+        .collect::<Vec<_>>();
+
+    let mut bytes = Cursor::new(Vec::new());
+    TiffEncoder::new(&mut bytes)
+        .unwrap()
+        .write_image::<Gray16>(size, size, &data)
+        .unwrap();
+
+    bytes.into_inner()
+}
+
+fn tiff_decode(c: &mut Criterion) {
+    let task_pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+
+    let mut group = c.benchmark_group("tiff_decode");
+    group.sample_size(10);
+
+    for size in [1024, 4096] {
+        let bytes = encode_heightmap(size);
+
+        group.bench_with_input(BenchmarkId::new("sequential", size), &bytes, |b, bytes| {
+            b.iter(|| {
+                for _ in 0..TILE_COUNT {
+                    decode_tiff(bytes.clone(), TiffLoaderSettings::default()).unwrap();
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("task_pool", size), &bytes, |b, bytes| {
+            b.iter(|| {
+                let tasks = (0..TILE_COUNT)
+                    .map(|_| {
+                        let bytes = bytes.clone();
+                        task_pool.spawn(async move {
+                            decode_tiff(bytes, TiffLoaderSettings::default()).unwrap()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                for task in tasks {
+                    block_on(task);
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Computes the normals on the calling thread, with the same central differences as [`compute_normals`].
+fn compute_normals_sequential(heights: &[u16], size: usize, height_scale: f32) -> Vec<Vec3> {
+    let height = |x: usize, y: usize| heights[y * size + x] as f32 / u16::MAX as f32;
+
+    (0..size * size)
+        .map(|index| {
+            let (x, y) = (index % size, index / size);
+
+            let dx = height((x + 1).min(size - 1), y) - height(x.saturating_sub(1), y);
+            let dy = height(x, (y + 1).min(size - 1)) - height(x, y.saturating_sub(1));
+
+            Vec3::new(-dx * height_scale, 2.0, -dy * height_scale).normalize()
+        })
+        .collect()
+}
+
+fn normals(c: &mut Criterion) {
+    ComputeTaskPool::get_or_init(TaskPool::default);
+
+    let mut group = c.benchmark_group("compute_normals");
+    group.sample_size(10);
+
+    for size in [1024, 4096] {
+        let image = decode_tiff(encode_heightmap(size), TiffLoaderSettings::default()).unwrap();
+        let heights: Vec<u16> = pod_collect_to_vec(&image.data);
+
+        group.bench_with_input(
+            BenchmarkId::new("sequential", size),
+            &heights,
+            |b, heights| b.iter(|| compute_normals_sequential(heights, size as usize, 100.0)),
+        );
+
+        group.bench_with_input(BenchmarkId::new("task_pool", size), &image, |b, image| {
+            b.iter(|| compute_normals(image, 100.0))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, tiff_decode, normals);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+use bevy::{
+    app::AppExit,
+    image::{CompressedImageFormats, ImageSampler, ImageType},
+    math::DVec3,
+    prelude::*,
+    render::render_asset::RenderAssetUsages,
+};
+use bevy_terrain::{math::Coordinate, prelude::*};
+use std::fs;
+
+const HEIGHTMAP: &str = "assets/terrains/planar/source/height.png";
+const TERRAIN_SIZE: f64 = 1000.0;
+const HEIGHT: f32 = 250.0;
+
+/// The terrain model, its tile atlas and its heightmap, which are all that is required to query heights in the main world.
+///
+/// The atlas is created from the terrain config, so the heights are remapped like on the GPU (e.g. by the height scale).
+#[derive(Resource)]
+struct HeightQuery {
+    model: TerrainModel,
+    tile_atlas: TileAtlas,
+    heightmap: Handle<Image>,
+}
+
+fn main() {
+    // Runs without a window and without the render world, e.g. on a server.
+    App::new()
+        .add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Image>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, query_heights)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let config = TerrainConfig {
+        model: TerrainModel::planar(DVec3::new(0.0, -100.0, 0.0), TERRAIN_SIZE, 0.0, HEIGHT),
+        ..default()
+    };
+    let tile_atlas = TileAtlas::new(&config).expect("Invalid terrain config.");
+
+    // Keep the heightmap on the CPU, since it is never uploaded to the GPU.
+    let heightmap = Image::from_buffer(
+        &fs::read(HEIGHTMAP).expect("Failed to read the heightmap."),
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        false,
+        ImageSampler::Default,
+        RenderAssetUsages::MAIN_WORLD,
+    )
+    .expect("Failed to decode the heightmap.");
+
+    commands.insert_resource(HeightQuery {
+        model: config.model,
+        tile_atlas,
+        heightmap: images.add(heightmap),
+    });
+}
+
+fn query_heights(
+    query: Res<HeightQuery>,
+    images: Res<Assets<Image>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let model = &query.model;
+    let heightmap = images.get(&query.heightmap).unwrap();
+
+    for world_position in [
+        DVec3::new(0.0, 0.0, 0.0),
+        DVec3::new(-250.0, 0.0, 250.0),
+        DVec3::new(400.0, 0.0, -100.0),
+    ] {
+        let coordinate = Coordinate::from_world_position(world_position, model);
+        let height = query
+            .tile_atlas
+            .sample_heightmap(coordinate, heightmap, &images);
+        let normal = query
+            .tile_atlas
+            .sample_normal(coordinate, heightmap, &images);
+
+        info!(
+            "Height at {world_position}: {height}, surface position: {}, normal: {normal}",
+            coordinate.world_position(model, height)
+        );
+    }
+
+    exit.send(AppExit::Success);
+}
@@ -72,8 +72,20 @@ fn setup(
     // Configure the quality settings of the terrain view. Adapt the settings to your liking.
     let view_config = TerrainViewConfig::default();
 
-    let tile_atlas = TileAtlas::new(&config);
-    let tile_tree = TileTree::new(&tile_atlas, &view_config);
+    let tile_atlas = match TileAtlas::new(&config) {
+        Ok(tile_atlas) => tile_atlas,
+        Err(error) => {
+            error!("The terrain config is invalid: {error}.");
+            return;
+        }
+    };
+    let tile_tree = match TileTree::new(&tile_atlas, &view_config) {
+        Ok(tile_tree) => tile_tree,
+        Err(error) => {
+            error!("The terrain view config is invalid: {error}.");
+            return;
+        }
+    };
 
     commands.spawn_big_space(ReferenceFrame::default(), |root| {
         let frame = root.frame().clone();
@@ -7,7 +7,11 @@ const LOD_COUNT: u32 = 4;
 
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, TerrainPlugin, TerrainPreprocessPlugin))
+        .add_plugins((
+            DefaultPlugins,
+            TerrainPlugin,
+            TerrainPreprocessPlugin::default(),
+        ))
         .add_systems(Startup, setup)
         .run();
 }
@@ -33,7 +37,13 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         ..default()
     });
 
-    let mut tile_atlas = TileAtlas::new(&config);
+    let mut tile_atlas = match TileAtlas::new(&config) {
+        Ok(tile_atlas) => tile_atlas,
+        Err(error) => {
+            error!("The terrain config is invalid: {error}.");
+            return;
+        }
+    };
 
     let preprocessor = Preprocessor::new()
         .clear_attachment(0, &mut tile_atlas)
@@ -10,7 +10,7 @@ fn main() {
         .add_plugins((
             DefaultPlugins.build().disable::<TransformPlugin>(),
             TerrainPlugin,
-            TerrainPreprocessPlugin,
+            TerrainPreprocessPlugin::default(),
         ))
         .add_systems(Startup, setup)
         .run();
@@ -31,7 +31,13 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         ..default()
     });
 
-    let mut tile_atlas = TileAtlas::new(&config);
+    let mut tile_atlas = match TileAtlas::new(&config) {
+        Ok(tile_atlas) => tile_atlas,
+        Err(error) => {
+            error!("The terrain config is invalid: {error}.");
+            return;
+        }
+    };
 
     let preprocessor = Preprocessor::new()
         .clear_attachment(0, &mut tile_atlas)
@@ -1,19 +1,27 @@
 //! Contains a debug resource and systems controlling it to visualize different internal
 //! data of the plugin.
 use crate::{
-    debug::camera::camera_controller, terrain_data::tile_tree::TileTree,
+    debug::camera::camera_controller,
+    math::{Coordinate, TerrainModelApproximation, TileCoordinate},
+    render::tiling_prepass::TILING_PREPASS_DIAGNOSTIC,
+    terrain_data::{tile_atlas::TileAtlas, tile_tree::TileTree},
     terrain_view::TerrainViewComponents,
 };
 use bevy::{
     asset::LoadState,
+    diagnostic::{DiagnosticPath, DiagnosticsStore},
+    math::DVec2,
     prelude::*,
-    render::{render_resource::*, Extract, RenderApp},
+    render::{diagnostic::RenderDiagnosticsPlugin, render_resource::*, Extract, RenderApp},
     transform::TransformSystem,
     window::PrimaryWindow,
 };
 
 pub mod camera;
 
+/// The coarsest mip level, through which [`DebugTerrain::force_mip_level`] is cycled.
+const MAX_FORCED_MIP_LEVEL: u32 = 7;
+
 #[derive(Asset, AsBindGroup, TypePath, Clone, Default)]
 pub struct DebugTerrainMaterial {}
 
@@ -24,12 +32,25 @@ pub struct TerrainDebugPlugin;
 
 impl Plugin for TerrainDebugPlugin {
     fn build(&self, app: &mut App) {
+        // records the GPU time of the terrain passes, see [`DebugTerrain::gpu_timings`]
+        if !app.is_plugin_added::<RenderDiagnosticsPlugin>() {
+            app.add_plugins(RenderDiagnosticsPlugin);
+        }
+
         app.init_resource::<DebugTerrain>()
+            .init_resource::<TerrainGpuTimings>()
             .init_resource::<LoadingImages>()
             .add_systems(Startup, (debug_lighting, debug_window))
             .add_systems(
                 Update,
-                (toggle_debug, update_view_parameter, finish_loading_images),
+                (
+                    toggle_debug,
+                    update_view_parameter,
+                    finish_loading_images,
+                    draw_origin_tiles,
+                    draw_side_boundaries,
+                    update_gpu_timings,
+                ),
             )
             .add_systems(
                 PostUpdate,
@@ -42,15 +63,27 @@ impl Plugin for TerrainDebugPlugin {
     }
 }
 
+/// Freezes the refinement of the tile trees of the terrain entity, like [`DebugTerrain::freeze`] does for all terrains.
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct FreezeTerrain;
+
 #[derive(Clone, Resource)]
 pub struct DebugTerrain {
+    /// Draws the terrain with the line polygon mode, by specializing the pipeline with [`TerrainPipelineFlags::WIREFRAME`](crate::render::terrain_material::TerrainPipelineFlags::WIREFRAME).
+    /// The specialized pipelines are cached, so toggling this does not recompile the shaders.
     pub wireframe: bool,
+    /// Draws the vertices of the tiles as points, which reveals where they land after the projection and displacement.
+    pub show_points: bool,
     pub show_data_lod: bool,
     pub show_geometry_lod: bool,
     pub show_tile_tree: bool,
     pub show_pixels: bool,
     pub show_uv: bool,
     pub show_normals: bool,
+    pub show_lod: bool,
+    /// Colors each tile by the ratio of its view distance and the distance at which the refinement subdivides it,
+    /// green for tiles below the threshold and red for tiles, which exceed it, but were not subdivided.
+    pub show_screen_error: bool,
     pub morph: bool,
     pub blend: bool,
     pub tile_tree_lod: bool,
@@ -58,6 +91,20 @@ pub struct DebugTerrain {
     pub sample_grad: bool,
     pub high_precision: bool,
     pub freeze: bool,
+    pub frustum_culling: bool,
+    /// Draws the tiles culled by the frustum culling tinted red, instead of discarding them.
+    /// Freeze the refinement and move the view to inspect them, since they lie outside of the view frustum.
+    pub show_culled_tiles: bool,
+    /// Draws the origin tile of the [`TerrainModelApproximation`] on each side and the location below the view.
+    pub show_origin_tile: bool,
+    /// Outlines the six sides of spherical terrains, in a distinct color per side.
+    pub show_side_boundaries: bool,
+    /// Samples all attachments at this mip level, instead of the one selected by the screen size of the tiles.
+    pub force_mip_level: Option<u32>,
+    /// Records the tile drawn at the pixel picked with [`TileTree::pick_tile`](crate::terrain_data::tile_tree::TileTree::pick_tile).
+    pub tile_picking: bool,
+    /// Measures the GPU time of the tiling prepass and the main opaque pass with timestamp queries, see [`TerrainGpuTimings`].
+    pub gpu_timings: bool,
     pub test1: bool,
     pub test2: bool,
     pub test3: bool,
@@ -67,12 +114,15 @@ impl Default for DebugTerrain {
     fn default() -> Self {
         Self {
             wireframe: false,
+            show_points: false,
             show_data_lod: false,
             show_geometry_lod: false,
             show_tile_tree: false,
             show_pixels: false,
             show_uv: false,
             show_normals: false,
+            show_lod: false,
+            show_screen_error: false,
             morph: true,
             blend: true,
             tile_tree_lod: false,
@@ -80,6 +130,13 @@ impl Default for DebugTerrain {
             sample_grad: true,
             high_precision: true,
             freeze: false,
+            frustum_culling: true,
+            show_culled_tiles: false,
+            show_origin_tile: false,
+            show_side_boundaries: false,
+            force_mip_level: None,
+            tile_picking: false,
+            gpu_timings: false,
             test1: false,
             test2: false,
             test3: false,
@@ -87,6 +144,39 @@ impl Default for DebugTerrain {
     }
 }
 
+/// The GPU time of the terrain passes in milliseconds, averaged over the recent frames.
+///
+/// The times are measured while [`DebugTerrain::gpu_timings`] is enabled. They stay `None` otherwise,
+/// or if the device does not support timestamp queries inside passes (e.g. on Metal, WebGPU and WebGL2).
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct TerrainGpuTimings {
+    /// The GPU time of the tiling prepass, which refines and culls the tiles of all terrain views.
+    pub tiling_prepass: Option<f64>,
+    /// The GPU time of the main opaque pass, which draws the terrains next to all other opaque meshes.
+    pub main_opaque_pass: Option<f64>,
+}
+
+pub(crate) fn update_gpu_timings(
+    debug: Res<DebugTerrain>,
+    diagnostics: Option<Res<DiagnosticsStore>>,
+    mut gpu_timings: ResMut<TerrainGpuTimings>,
+) {
+    let elapsed_gpu = |span: &str| {
+        let path = DiagnosticPath::from_components(["render", span, "elapsed_gpu"]);
+
+        diagnostics.as_ref()?.get(&path)?.smoothed()
+    };
+
+    *gpu_timings = if debug.gpu_timings {
+        TerrainGpuTimings {
+            tiling_prepass: elapsed_gpu(TILING_PREPASS_DIAGNOSTIC),
+            main_opaque_pass: elapsed_gpu("main_opaque_pass_3d"),
+        }
+    } else {
+        default()
+    };
+}
+
 pub fn extract_debug(mut debug: ResMut<DebugTerrain>, extracted_debug: Extract<Res<DebugTerrain>>) {
     *debug = extracted_debug.clone();
 }
@@ -99,6 +189,13 @@ pub fn toggle_debug(input: Res<ButtonInput<KeyCode>>, mut debug: ResMut<DebugTer
             if debug.wireframe { "on" } else { "off" }
         )
     }
+    if input.just_pressed(KeyCode::Period) {
+        debug.show_points = !debug.show_points;
+        println!(
+            "Toggled the points view {}.",
+            if debug.show_points { "on" } else { "off" }
+        )
+    }
     if input.just_pressed(KeyCode::KeyL) {
         debug.show_data_lod = !debug.show_data_lod;
         println!(
@@ -141,6 +238,20 @@ pub fn toggle_debug(input: Res<ButtonInput<KeyCode>>, mut debug: ResMut<DebugTer
             if debug.show_normals { "on" } else { "off" }
         )
     }
+    if input.just_pressed(KeyCode::KeyV) {
+        debug.show_lod = !debug.show_lod;
+        println!(
+            "Toggled the LOD tint view {}.",
+            if debug.show_lod { "on" } else { "off" }
+        )
+    }
+    if input.just_pressed(KeyCode::Digit4) {
+        debug.show_screen_error = !debug.show_screen_error;
+        println!(
+            "Toggled the screen error view {}.",
+            if debug.show_screen_error { "on" } else { "off" }
+        )
+    }
     if input.just_pressed(KeyCode::KeyM) {
         debug.morph = !debug.morph;
         println!(
@@ -190,6 +301,49 @@ pub fn toggle_debug(input: Res<ButtonInput<KeyCode>>, mut debug: ResMut<DebugTer
             if debug.freeze { "Froze" } else { "Unfroze" }
         )
     }
+    if input.just_pressed(KeyCode::KeyC) {
+        debug.frustum_culling = !debug.frustum_culling;
+        println!(
+            "Toggled frustum culling {}.",
+            if debug.frustum_culling { "on" } else { "off" }
+        )
+    }
+    if input.just_pressed(KeyCode::Comma) {
+        debug.show_culled_tiles = !debug.show_culled_tiles;
+        println!(
+            "Toggled the culled tiles view {}.",
+            if debug.show_culled_tiles { "on" } else { "off" }
+        )
+    }
+    if input.just_pressed(KeyCode::KeyR) {
+        debug.show_origin_tile = !debug.show_origin_tile;
+        println!(
+            "Toggled the origin tile gizmos {}.",
+            if debug.show_origin_tile { "on" } else { "off" }
+        )
+    }
+    if input.just_pressed(KeyCode::KeyA) {
+        debug.show_side_boundaries = !debug.show_side_boundaries;
+        println!(
+            "Toggled the side boundary gizmos {}.",
+            if debug.show_side_boundaries {
+                "on"
+            } else {
+                "off"
+            }
+        )
+    }
+    if input.just_pressed(KeyCode::KeyD) {
+        debug.force_mip_level = match debug.force_mip_level {
+            None => Some(0),
+            Some(level) if level < MAX_FORCED_MIP_LEVEL => Some(level + 1),
+            Some(_) => None,
+        };
+        match debug.force_mip_level {
+            Some(level) => println!("Forced the mip level {level}."),
+            None => println!("Stopped forcing the mip level."),
+        }
+    }
     if input.just_pressed(KeyCode::Digit1) {
         debug.test1 = !debug.test1;
         println!(
@@ -211,6 +365,108 @@ pub fn toggle_debug(input: Res<ButtonInput<KeyCode>>, mut debug: ResMut<DebugTer
             if debug.test3 { "on" } else { "off" }
         )
     }
+    if input.just_pressed(KeyCode::Digit5) {
+        debug.gpu_timings = !debug.gpu_timings;
+        println!(
+            "Toggled the GPU timings {}.",
+            if debug.gpu_timings { "on" } else { "off" }
+        )
+    }
+}
+
+/// Outlines the origin tile, from which the shader extrapolates the relative positions, on each side of the terrain.
+/// The location below the view, i.e. the view st coordinate, is marked by a circle.
+pub fn draw_origin_tiles(
+    debug: Res<DebugTerrain>,
+    mut gizmos: Gizmos,
+    tile_atlases: Query<&TileAtlas>,
+    terrain_model_approximations: Res<TerrainViewComponents<TerrainModelApproximation>>,
+) {
+    if !debug.show_origin_tile {
+        return;
+    }
+
+    for (&(terrain, _view), approximation) in terrain_model_approximations.iter() {
+        let Ok(tile_atlas) = tile_atlases.get(terrain) else {
+            continue;
+        };
+
+        let model = &tile_atlas.model;
+        let origin_count = TileCoordinate::count(approximation.origin_lod()) as f64;
+        let height = approximation.approximate_height();
+
+        for side in model.active_sides() {
+            let parameter = &approximation.sides()[side as usize];
+            let origin_xy = parameter.origin_xy.as_dvec2();
+
+            let world_position = |st: DVec2, height: f32| {
+                Coordinate::new(side, st / origin_count)
+                    .world_position(model, height)
+                    .as_vec3()
+            };
+
+            let corners = [DVec2::ZERO, DVec2::X, DVec2::ONE, DVec2::Y, DVec2::ZERO]
+                .map(|corner| world_position(origin_xy + corner, height));
+            gizmos.linestrip(corners, Color::srgb(1.0, 0.5, 0.0));
+
+            let view_st = origin_xy + parameter.origin_uv.as_dvec2();
+            let view_position = world_position(view_st, height);
+            let view_normal = world_position(view_st, height + 1.0) - view_position;
+            let radius = corners[0].distance(corners[2]) * 0.05;
+
+            gizmos.circle(
+                Isometry3d::new(
+                    view_position,
+                    Quat::from_rotation_arc(Vec3::Z, view_normal.normalize()),
+                ),
+                radius,
+                Color::srgb(1.0, 0.0, 0.0),
+            );
+        }
+    }
+}
+
+/// Outlines the sides of the cube sphere, to show where the projection switches between them.
+/// The boundaries are drawn at the maximum height of the terrain, so they are not occluded by it.
+pub fn draw_side_boundaries(
+    debug: Res<DebugTerrain>,
+    mut gizmos: Gizmos,
+    tile_atlases: Query<&TileAtlas>,
+) {
+    const SEGMENT_COUNT: u32 = 32;
+
+    if !debug.show_side_boundaries {
+        return;
+    }
+
+    for tile_atlas in &tile_atlases {
+        let model = &tile_atlas.model;
+
+        if !model.is_spherical() {
+            continue;
+        }
+
+        for side in model.active_sides() {
+            let color = Color::hsl(side as f32 * 60.0, 1.0, 0.5);
+
+            // The boundaries are curved, thus each edge is subdivided into segments.
+            let boundary = [DVec2::ZERO, DVec2::X, DVec2::ONE, DVec2::Y, DVec2::ZERO]
+                .windows(2)
+                .flat_map(|edge| {
+                    let (start, end) = (edge[0], edge[1]);
+                    (0..SEGMENT_COUNT)
+                        .map(move |segment| start.lerp(end, segment as f64 / SEGMENT_COUNT as f64))
+                })
+                .chain([DVec2::ZERO])
+                .map(|st| {
+                    Coordinate::new(side, st)
+                        .world_position(model, tile_atlas.max_height())
+                        .as_vec3()
+                });
+
+            gizmos.linestrip(boundary, color);
+        }
+    }
 }
 
 pub fn update_view_parameter(
@@ -249,11 +505,11 @@ pub fn update_view_parameter(
         }
 
         if input.just_pressed(KeyCode::KeyX) && tile_tree.grid_size > 2 {
-            tile_tree.grid_size -= 2;
+            tile_tree.grid_size /= 2;
             println!("Decreased the grid size to {}.", tile_tree.grid_size);
         }
         if input.just_pressed(KeyCode::KeyJ) {
-            tile_tree.grid_size += 2;
+            tile_tree.grid_size *= 2;
             println!("Increased the grid size to {}.", tile_tree.grid_size);
         }
     }
@@ -311,3 +567,46 @@ fn finish_loading_images(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        diagnostic::{Diagnostic, DiagnosticMeasurement},
+        utils::Instant,
+    };
+
+    #[test]
+    fn gpu_timings_are_read_from_the_diagnostics() {
+        let mut app = App::new();
+        app.init_resource::<DebugTerrain>()
+            .init_resource::<TerrainGpuTimings>()
+            .init_resource::<DiagnosticsStore>()
+            .add_systems(Update, update_gpu_timings);
+
+        // only the tiling prepass is measured, like on a device without timestamp queries in the main pass
+        let path =
+            DiagnosticPath::from_components(["render", TILING_PREPASS_DIAGNOSTIC, "elapsed_gpu"]);
+        let mut diagnostic = Diagnostic::new(path);
+        diagnostic.add_measurement(DiagnosticMeasurement {
+            time: Instant::now(),
+            value: 0.5,
+        });
+        app.world_mut()
+            .resource_mut::<DiagnosticsStore>()
+            .add(diagnostic);
+
+        app.update();
+
+        let gpu_timings = *app.world().resource::<TerrainGpuTimings>();
+        assert_eq!(gpu_timings.tiling_prepass, None);
+        assert_eq!(gpu_timings.main_opaque_pass, None);
+
+        app.world_mut().resource_mut::<DebugTerrain>().gpu_timings = true;
+        app.update();
+
+        let gpu_timings = *app.world().resource::<TerrainGpuTimings>();
+        assert_eq!(gpu_timings.tiling_prepass, Some(0.5));
+        assert_eq!(gpu_timings.main_opaque_pass, None);
+    }
+}
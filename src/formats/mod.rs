@@ -1,9 +1,15 @@
 pub mod tiff;
 
-use crate::math::TileCoordinate;
-use anyhow::Result;
+#[cfg(feature = "tiff_loader")]
+use crate::formats::tiff::{TiffLoader, TiffLoaderSettings};
+use crate::{formats::tiff::GeoTiffMetadata, math::TileCoordinate};
+use anyhow::{anyhow, Result};
+use bevy::{
+    asset::{AssetPath, LoadState},
+    prelude::*,
+};
 use bincode::{config, Decode, Encode};
-use std::{fs, path::Path};
+use std::{fs, path::Path, thread, time::Duration};
 
 #[derive(Encode, Decode, Debug)]
 pub struct TC {
@@ -34,3 +40,63 @@ impl TC {
         Ok(())
     }
 }
+
+/// Loads the image asset at the path (relative to the asset folder) and returns it, once it is decoded.
+///
+/// This drives a headless app with an asset server until the load finishes, so tests and tools can
+/// assert on the decoded contents of the loaders without setting up an app themselves.
+/// TIFF files are decoded by the [`TiffLoader`] with the default settings, use [`load_tiff`] to pass others.
+pub fn load_image<'a>(path: impl Into<AssetPath<'a>>) -> Result<Image> {
+    let mut app = image_app();
+    let handle = app.world().resource::<AssetServer>().load(path.into());
+
+    wait_for_image(&mut app, handle)
+}
+
+/// Loads the TIFF file at the path (relative to the asset folder) with the settings, like [`load_image`].
+#[cfg(feature = "tiff_loader")]
+pub fn load_tiff<'a>(
+    path: impl Into<AssetPath<'a>>,
+    settings: TiffLoaderSettings,
+) -> Result<Image> {
+    let mut app = image_app();
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load_with_settings(path.into(), move |s: &mut TiffLoaderSettings| *s = settings);
+
+    wait_for_image(&mut app, handle)
+}
+
+fn image_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        ImagePlugin::default(),
+    ))
+    .init_asset::<GeoTiffMetadata>();
+    #[cfg(feature = "tiff_loader")]
+    app.init_asset_loader::<TiffLoader>();
+    app.finish();
+    app.cleanup();
+    app
+}
+
+fn wait_for_image(app: &mut App, handle: Handle<Image>) -> Result<Image> {
+    loop {
+        app.update();
+
+        match app.world().resource::<AssetServer>().load_state(&handle) {
+            LoadState::Loaded => break,
+            LoadState::Failed(error) => return Err(anyhow!(error)),
+            // the loaders run on the task pools, so wait for them instead of spinning
+            _ => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+
+    app.world_mut()
+        .resource_mut::<Assets<Image>>()
+        .remove(&handle)
+        .ok_or_else(|| anyhow!("the loaded image was removed"))
+}
@@ -1,62 +1,1507 @@
+use crate::math::TerrainModel;
+#[cfg(feature = "tiff_loader")]
 use bevy::{
     asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    tasks::AsyncComputeTaskPool,
+};
+use bevy::{
+    image::TextureError,
+    math::DVec3,
     prelude::*,
     render::{
         render_asset::RenderAssetUsages,
         render_resource::{Extent3d, TextureDimension, TextureFormat},
     },
-    image::TextureError
 };
-use bytemuck::cast_slice;
-use std::io::Cursor;
-use tiff::decoder::{Decoder, DecodingResult};
+use bytemuck::{cast_slice, pod_collect_to_vec};
+use itertools::iproduct;
+#[cfg(feature = "tiff_loader")]
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Seek};
+use tiff::{
+    decoder::{ifd::Value, ChunkType, Decoder, DecodingResult},
+    tags::{
+        CompressionMethod, PhotometricInterpretation, PlanarConfiguration, ResolutionUnit,
+        SampleFormat, Tag,
+    },
+    ColorType, TiffError, TiffUnsupportedError,
+};
+
+/// The texture formats, into which the [`TiffLoader`] can store the decoded heights or colors.
+///
+/// The decoded samples are stored as is, so the format has to match their sample format and size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "tiff_loader", derive(Serialize, Deserialize))]
+pub enum TiffTextureFormat {
+    /// For 16 bit unsigned heights, as well as signed 16 and 32 bit heights, which are offset into the unsigned range.
+    /// Signed 32 bit heights outside of the 16 bit range are rejected with an error.
+    /// 8 bit heights are widened to 16 bit, by mapping their full range onto the 16 bit range.
+    /// This is the format expected by the preprocessor for [`AttachmentFormat::R16`](crate::terrain_data::AttachmentFormat::R16) attachments.
+    R16Unorm,
+    /// For 8 bit unsigned heights, which are stored as is (e.g. for stylized terrains).
+    ///
+    /// The heights are limited to 256 steps of `(max_height - min_height) / 255`, which results in visible terraces
+    /// on smooth slopes. The preprocessor widens them onto the 16 bit range of
+    /// [`AttachmentFormat::R16`](crate::terrain_data::AttachmentFormat::R16) attachments.
+    R8Unorm,
+    /// For 32 bit floating point heights, e.g. DEMs storing their heights in meters.
+    R32Float,
+    /// For 8 bit RGB and RGBA images, e.g. albedo tiles.
+    ///
+    /// RGB images are expanded with an opaque alpha channel.
+    /// Colors with associated (premultiplied) alpha, as declared by the `ExtraSamples` tag, are un-premultiplied.
+    Rgba8UnormSrgb,
+}
+
+impl TiffTextureFormat {
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            TiffTextureFormat::R16Unorm => TextureFormat::R16Unorm,
+            TiffTextureFormat::R8Unorm => TextureFormat::R8Unorm,
+            TiffTextureFormat::R32Float => TextureFormat::R32Float,
+            TiffTextureFormat::Rgba8UnormSrgb => TextureFormat::Rgba8UnormSrgb,
+        }
+    }
+
+    /// Whether samples of the sample format and color type (i.e. the bits per sample) can be stored in this format.
+    ///
+    /// The sample format and color type determine the variant of the [`DecodingResult`] of the image.
+    fn supports(self, sample_format: SampleFormat, color_type: ColorType) -> bool {
+        match (sample_format, color_type) {
+            (SampleFormat::Uint, ColorType::Gray(8)) => {
+                matches!(
+                    self,
+                    TiffTextureFormat::R16Unorm | TiffTextureFormat::R8Unorm
+                )
+            }
+            (SampleFormat::Uint, ColorType::Gray(16))
+            | (SampleFormat::Int, ColorType::Gray(16 | 32)) => self == TiffTextureFormat::R16Unorm,
+            (SampleFormat::IEEEFP, ColorType::Gray(32)) => self == TiffTextureFormat::R32Float,
+            (SampleFormat::Uint, ColorType::RGB(8) | ColorType::RGBA(8)) => {
+                self == TiffTextureFormat::Rgba8UnormSrgb
+            }
+            _ => false,
+        }
+    }
+}
 
+/// The private tag, in which GDAL stores the nodata value of the image.
+const GDAL_NODATA_TAG: u16 = 42113;
+/// The private tag, in which GDAL stores additional metadata as XML, including the scale and offset of the bands.
+const GDAL_METADATA_TAG: u16 = 42112;
+
+/// The linear transform, which GDAL stores to convert the samples into physical values (e.g. elevations in meters).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GdalScaleOffset {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Default for GdalScaleOffset {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl GdalScaleOffset {
+    /// Converts the stored sample into its physical value.
+    pub fn apply(self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+}
+
+/// The settings of the [`TiffLoader`].
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "tiff_loader", derive(Serialize, Deserialize))]
+pub struct TiffLoaderSettings {
+    /// Selects the image of the (Big)TIFF file, that should be loaded.
+    ///
+    /// GIS tools store the overview (pyramid) levels as additional images in the same file,
+    /// where level 0 is the full resolution image. Use [`overview_level_count`] to query the available levels.
+    /// If the first image lists `SubIFDs`, those are its overview levels instead of the following images.
+    pub overview_level: u32,
+    /// Pads the width and height of the image to the next power of two, by replicating the last column and row.
+    ///
+    /// The tiles of the terrain subdivide the image evenly, so its dimensions should be a power of two.
+    /// Real DEMs often have arbitrary sizes (e.g. 3601×3601 for SRTM), which would be sampled out of
+    /// bounds at the edges otherwise.
+    pub pad_to_power_of_two: bool,
+    /// Decimates the image using a box filter, until neither its width nor height exceed this dimension.
+    ///
+    /// This allows loading DEMs, which are larger than the maximum texture dimension of the GPU.
+    /// The heights are averaged at full 16 bit precision.
+    pub max_dimension: Option<u32>,
+    /// Overrides the format of the loaded image, which is inferred from the `SampleFormat` and `BitsPerSample` tags otherwise.
+    ///
+    /// Unsigned 8 bit images are inferred as [`TiffTextureFormat::R8Unorm`] (or [`TiffTextureFormat::Rgba8UnormSrgb`] with three or four channels),
+    /// unsigned 16 bit and signed 16 and 32 bit images as [`TiffTextureFormat::R16Unorm`]
+    /// and 32 bit IEEE floating point images as [`TiffTextureFormat::R32Float`].
+    /// Images of other sample formats, as well as overrides which do not match the samples
+    /// (e.g. floating point samples stored as [`TiffTextureFormat::R16Unorm`]), are rejected with an error.
+    pub texture_format: Option<TiffTextureFormat>,
+    /// The sample value, which marks missing data (e.g. -32768 for SRTM or -9999 for many float DEMs).
+    ///
+    /// Samples equal to it are replaced with zero, which is sea level for signed and floating point heights.
+    /// The `GDAL_NODATA` tag written by GDAL takes precedence, if it is present and holds a number.
+    pub nodata: Option<f64>,
+}
+
+/// The label of the [`GeoTiffMetadata`], which the [`TiffLoader`] adds to each loaded image.
+///
+/// Load it with the label appended to the path, e.g. `asset_server.load("dem.tif#geotiff")`.
+pub const GEOTIFF_METADATA_LABEL: &str = "geotiff";
+
+/// Loads (Big)TIFF files as images, see [`decode_tiff`].
+///
+/// The georeferencing of each image is added as a [`GeoTiffMetadata`] sub-asset labeled [`GEOTIFF_METADATA_LABEL`].
+/// Its height range falls back to the range of the decoded samples, if the file stores no statistics.
+/// Requires the `tiff_loader` feature, which is enabled by default.
+#[cfg(feature = "tiff_loader")]
 #[derive(Default)]
 pub struct TiffLoader;
+#[cfg(feature = "tiff_loader")]
 impl AssetLoader for TiffLoader {
     type Asset = Image;
-    type Settings = ();
+    type Settings = TiffLoaderSettings;
     type Error = TextureError;
     async fn load<'a>(
         &'a self,
         reader: &'a mut Reader<'_>,
-        _settings: &'a Self::Settings,
-        _load_context: &'a mut LoadContext<'_>,
+        settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext<'_>,
     ) -> Result<Image, Self::Error> {
         let mut bytes = Vec::new();
-        reader.read_to_end(&mut bytes).await.unwrap();
-
-        let mut decoder = Decoder::new(Cursor::new(bytes)).unwrap();
-
-        let (width, height) = decoder.dimensions().unwrap();
-
-        let data = match decoder.read_image().unwrap() {
-            DecodingResult::U8(data) => cast_slice(&data).to_vec(),
-            DecodingResult::U16(data) => cast_slice(&data).to_vec(),
-            DecodingResult::U32(data) => cast_slice(&data).to_vec(),
-            DecodingResult::U64(data) => cast_slice(&data).to_vec(),
-            DecodingResult::F32(data) => cast_slice(&data).to_vec(),
-            DecodingResult::F64(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I8(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I16(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I32(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I64(data) => cast_slice(&data).to_vec(),
-        };
-
-        Ok(Image::new(
-            Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            TextureDimension::D2,
-            data,
-            TextureFormat::R16Unorm,
-            RenderAssetUsages::default(),
-        ))
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|error| TextureError::InvalidData(error.to_string()))?;
+
+        let settings = *settings;
+
+        let mut metadata = geotiff_metadata(&bytes)?;
+
+        // Decoding large DEMs takes seconds, so we move it off the asset IO task.
+        let (image, sample_range) = AsyncComputeTaskPool::get()
+            .spawn(async move { decode(bytes, settings) })
+            .await?;
+
+        if let Some((min_height, max_height)) = sample_range {
+            metadata.min_height.get_or_insert(min_height);
+            metadata.max_height.get_or_insert(max_height);
+        }
+
+        load_context.add_labeled_asset(GEOTIFF_METADATA_LABEL.to_string(), metadata);
+
+        Ok(image)
     }
 
     fn extensions(&self) -> &[&str] {
         &["tif", "tiff"]
     }
 }
+
+/// Converts the tiff error into a texture error, naming the compression scheme if it is not supported.
+fn texture_error(error: TiffError) -> TextureError {
+    match error {
+        TiffError::UnsupportedError(TiffUnsupportedError::UnsupportedCompressionMethod(method)) => {
+            unsupported_compression(method)
+        }
+        error => TextureError::InvalidData(error.to_string()),
+    }
+}
+
+fn unsupported_compression(method: CompressionMethod) -> TextureError {
+    TextureError::UnsupportedTextureFormat(format!(
+        "tiff compression scheme {method:?} is not supported, use none, LZW, Deflate or PackBits instead"
+    ))
+}
+
+/// The tag, in which the first image lists the offsets of its overview levels, if they are not chained after it.
+const SUB_IFDS_TAG: u16 = 330;
+
+/// Reads the offsets of the overview levels stored as SubIFDs of the current image.
+fn sub_ifd_offsets<R: Read + Seek>(decoder: &mut Decoder<R>) -> Result<Vec<u64>, TextureError> {
+    Ok(decoder
+        .find_tag_unsigned_vec(Tag::Unknown(SUB_IFDS_TAG))
+        .map_err(texture_error)?
+        .unwrap_or_default())
+}
+
+/// Points the header of the (Big)TIFF file at the IFD at the offset, so that decoding starts with it.
+///
+/// The tiff crate only steps through the chained images, so SubIFDs can not be reached otherwise.
+fn point_header_at_ifd(bytes: &mut [u8], offset: u64) -> Result<(), TextureError> {
+    let little_endian = bytes.starts_with(b"II");
+    let version = [bytes[2], bytes[3]];
+    let bigtiff = if little_endian {
+        u16::from_le_bytes(version) == 43
+    } else {
+        u16::from_be_bytes(version) == 43
+    };
+
+    let (range, offset) = if bigtiff {
+        (8..16, offset.to_le_bytes().to_vec())
+    } else {
+        let offset = u32::try_from(offset).map_err(|_| {
+            TextureError::InvalidData(format!("tiff SubIFD offset {offset} exceeds 32 bits"))
+        })?;
+        (4..8, offset.to_le_bytes().to_vec())
+    };
+
+    let header = &mut bytes[range];
+    header.copy_from_slice(&offset);
+
+    if !little_endian {
+        header.reverse();
+    }
+
+    Ok(())
+}
+
+/// Counts the overview levels (images) of the (Big)TIFF file, including the full resolution image.
+pub fn overview_level_count(bytes: &[u8]) -> Result<u32, TextureError> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(texture_error)?;
+
+    let sub_ifds = sub_ifd_offsets(&mut decoder)?;
+    if !sub_ifds.is_empty() {
+        return Ok(sub_ifds.len() as u32 + 1);
+    }
+
+    let mut count = 1;
+
+    while decoder.more_images() {
+        decoder.next_image().map_err(texture_error)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Creates a decoder positioned at the overview level, which is either a SubIFD of the first image or a following image.
+fn seek_to_overview_level(
+    mut bytes: Vec<u8>,
+    overview_level: u32,
+) -> Result<Decoder<Cursor<Vec<u8>>>, TextureError> {
+    let level_count_error = |level_count: usize| {
+        TextureError::InvalidData(format!(
+            "tiff overview level {overview_level} requested, but the file only contains {level_count} levels"
+        ))
+    };
+
+    if overview_level > 0 {
+        let sub_ifds =
+            sub_ifd_offsets(&mut Decoder::new(Cursor::new(&bytes)).map_err(texture_error)?)?;
+
+        if !sub_ifds.is_empty() {
+            let offset = *sub_ifds
+                .get(overview_level as usize - 1)
+                .ok_or_else(|| level_count_error(sub_ifds.len() + 1))?;
+
+            point_header_at_ifd(&mut bytes, offset)?;
+
+            return Decoder::new(Cursor::new(bytes)).map_err(texture_error);
+        }
+    }
+
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(texture_error)?;
+
+    for level in 0..overview_level {
+        if !decoder.more_images() {
+            return Err(level_count_error(level as usize + 1));
+        }
+
+        decoder.next_image().map_err(texture_error)?;
+    }
+
+    Ok(decoder)
+}
+
+/// Reads the resolution tag as pixels per unit, ignoring missing and non positive resolutions.
+fn resolution(decoder: &mut Decoder<Cursor<&[u8]>>, tag: Tag) -> Result<Option<f64>, TextureError> {
+    let resolution = match decoder.find_tag(tag).map_err(texture_error)? {
+        Some(Value::Rational(numerator, denominator)) if denominator != 0 => {
+            numerator as f64 / denominator as f64
+        }
+        _ => return Ok(None),
+    };
+
+    Ok((resolution > 0.0).then_some(resolution))
+}
+
+/// Derives the physical side length in meters of the full resolution image, from its `XResolution`, `YResolution`
+/// and `ResolutionUnit` tags, which non geo heightmaps exported with the correct DPI carry.
+///
+/// This can be used as the `side_length` of a planar [`TerrainModel`](crate::math::TerrainModel), where the longer
+/// side of non square images is used. Returns `None`, if the tags are absent or the unit is arbitrary,
+/// in which case the scale has to be specified explicitly.
+pub fn physical_side_length(bytes: &[u8]) -> Result<Option<f64>, TextureError> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(texture_error)?;
+
+    let meters_per_unit = match decoder
+        .find_tag_unsigned::<u16>(Tag::ResolutionUnit)
+        .map_err(texture_error)?
+        .and_then(ResolutionUnit::from_u16)
+    {
+        Some(ResolutionUnit::Inch) => 0.0254,
+        Some(ResolutionUnit::Centimeter) => 0.01,
+        _ => return Ok(None),
+    };
+
+    let (Some(x_resolution), Some(y_resolution)) = (
+        resolution(&mut decoder, Tag::XResolution)?,
+        resolution(&mut decoder, Tag::YResolution)?,
+    ) else {
+        return Ok(None);
+    };
+
+    let (width, height) = decoder.dimensions().map_err(texture_error)?;
+
+    let side_length = (width as f64 / x_resolution).max(height as f64 / y_resolution);
+
+    Ok(Some(side_length * meters_per_unit))
+}
+
+/// Pads the image to the new size, by replicating its last column and row.
+fn pad_edges(
+    data: &[u8],
+    (width, height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+) -> Vec<u8> {
+    let pixel_size = data.len() / (width * height) as usize;
+    let row_size = width as usize * pixel_size;
+
+    let mut padded = Vec::with_capacity(new_width as usize * new_height as usize * pixel_size);
+
+    for y in 0..height as usize {
+        let row = &data[y * row_size..(y + 1) * row_size];
+        let last_pixel = &row[row_size - pixel_size..];
+
+        padded.extend_from_slice(row);
+
+        for _ in width..new_width {
+            padded.extend_from_slice(last_pixel);
+        }
+    }
+
+    let new_row_size = new_width as usize * pixel_size;
+    let last_row = padded.len() - new_row_size;
+
+    for _ in height..new_height {
+        padded.extend_from_within(last_row..last_row + new_row_size);
+    }
+
+    padded
+}
+
+/// Reorders the pixels according to the TIFF orientation tag, so that the first row is the top and the first column the left.
+/// Orientations 5 to 8 store the image transposed, which swaps its width and height.
+fn orient(data: Vec<u8>, (width, height): (u32, u32), orientation: u16) -> (Vec<u8>, (u32, u32)) {
+    if orientation == 1 || !(1..=8).contains(&orientation) {
+        return (data, (width, height));
+    }
+
+    let pixel_size = data.len() / (width * height) as usize;
+
+    let (new_width, new_height) = if orientation >= 5 {
+        (height, width)
+    } else {
+        (width, height)
+    };
+
+    let mut oriented = Vec::with_capacity(data.len());
+
+    for (y, x) in iproduct!(0..new_height, 0..new_width) {
+        let (source_x, source_y) = match orientation {
+            2 => (width - 1 - x, y),
+            3 => (width - 1 - x, height - 1 - y),
+            4 => (x, height - 1 - y),
+            5 => (y, x),
+            6 => (y, new_width - 1 - x),
+            7 => (new_height - 1 - y, new_width - 1 - x),
+            _ => (new_height - 1 - y, x),
+        };
+
+        let index = (source_y * width + source_x) as usize * pixel_size;
+        oriented.extend_from_slice(&data[index..index + pixel_size]);
+    }
+
+    (oriented, (new_width, new_height))
+}
+
+/// Checks whether the first extra sample (i.e. the alpha channel) is associated with the colors, meaning they are premultiplied.
+fn associated_alpha(decoder: &mut Decoder<Cursor<Vec<u8>>>) -> Result<bool, TextureError> {
+    let extra_sample = match decoder.find_tag(Tag::ExtraSamples).map_err(texture_error)? {
+        Some(Value::List(values)) => values.into_iter().next(),
+        value => value,
+    };
+
+    Ok(extra_sample.and_then(|value| value.into_u64().ok()) == Some(1))
+}
+
+/// Parses the nodata value from the `GDAL_NODATA` tag, which GDAL stores as an ASCII string (e.g. "-9999" or "nan").
+fn gdal_nodata<R: Read + Seek>(decoder: &mut Decoder<R>) -> Result<Option<f64>, TextureError> {
+    match decoder
+        .find_tag(Tag::Unknown(GDAL_NODATA_TAG))
+        .map_err(texture_error)?
+    {
+        Some(Value::Ascii(nodata)) => Ok(nodata.trim_matches(char::from(0)).trim().parse().ok()),
+        _ => Ok(None),
+    }
+}
+
+/// Parses the value of the metadata item of the first band with the attribute (e.g. `role="scale"`) from the GDAL metadata XML,
+/// which looks like `<Item name="SCALE" sample="0" role="scale">0.1</Item>`.
+fn gdal_metadata_item(metadata: &str, attribute: &str) -> Option<f64> {
+    metadata.split("<Item").skip(1).find_map(|item| {
+        let (attributes, value) = item.split_once('>')?;
+        let (value, _) = value.split_once('<')?;
+
+        let first_band = !attributes.contains("sample=") || attributes.contains(r#"sample="0""#);
+        let has_attribute = attributes.contains(attribute);
+
+        (first_band && has_attribute).then(|| value.trim().parse().ok())?
+    })
+}
+
+/// Reads the scale and offset of the first band from the `GDAL_METADATA` tag.
+///
+/// Returns `None`, if the tag is absent or stores neither of them.
+fn gdal_scale_offset<R: Read + Seek>(
+    decoder: &mut Decoder<R>,
+) -> Result<Option<GdalScaleOffset>, TextureError> {
+    let Some(Value::Ascii(metadata)) = decoder
+        .find_tag(Tag::Unknown(GDAL_METADATA_TAG))
+        .map_err(texture_error)?
+    else {
+        return Ok(None);
+    };
+
+    let scale = gdal_metadata_item(&metadata, r#"role="scale""#);
+    let offset = gdal_metadata_item(&metadata, r#"role="offset""#);
+
+    if scale.is_none() && offset.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(GdalScaleOffset {
+        scale: scale.unwrap_or(1.0),
+        offset: offset.unwrap_or(0.0),
+    }))
+}
+
+/// Reads the scale and offset GDAL stores to convert the samples of the first image into physical values.
+///
+/// Returns `None`, if they are absent, in which case the samples are physical values already.
+pub fn scale_offset(bytes: &[u8]) -> Result<Option<GdalScaleOffset>, TextureError> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(texture_error)?;
+
+    gdal_scale_offset(&mut decoder)
+}
+
+/// Derives the physical heights, onto which the lowest and highest normalized texel of the decoded image are mapped,
+/// from the scale and offset stored by GDAL.
+///
+/// These can be used as the `min_height` and `max_height` of the [`TerrainModel`](crate::math::TerrainModel), so that
+/// integer heights map to real meters. Note that a negative scale yields a `min_height` above the `max_height`.
+/// Returns `None` for floating point images, whose samples are converted by the decoder instead,
+/// for color images and if the scale and offset are absent.
+pub fn height_range(
+    bytes: &[u8],
+    settings: TiffLoaderSettings,
+) -> Result<Option<(f64, f64)>, TextureError> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(texture_error)?;
+
+    let Some(scale_offset) = gdal_scale_offset(&mut decoder)? else {
+        return Ok(None);
+    };
+
+    let texture_format = texture_format(&mut decoder, settings)?;
+
+    // signed heights are offset into the unsigned range and 8 bit heights are widened onto the full 16 bit range
+    let (min_sample, max_sample) =
+        match (texture_format, decoder.colortype().map_err(texture_error)?) {
+            (TiffTextureFormat::R8Unorm, _) | (TiffTextureFormat::R16Unorm, ColorType::Gray(8)) => {
+                (0.0, 255.0)
+            }
+            (TiffTextureFormat::R16Unorm, ColorType::Gray(16))
+                if sample_format(&mut decoder)? == SampleFormat::Int =>
+            {
+                (i16::MIN as f64, i16::MAX as f64)
+            }
+            (TiffTextureFormat::R16Unorm, ColorType::Gray(32)) => {
+                (i16::MIN as f64, i16::MAX as f64)
+            }
+            (TiffTextureFormat::R16Unorm, _) => (0.0, u16::MAX as f64),
+            _ => return Ok(None),
+        };
+
+    Ok(Some((
+        scale_offset.apply(min_sample),
+        scale_offset.apply(max_sample),
+    )))
+}
+
+/// The georeferencing and statistics of a GeoTIFF image, see [`geotiff_metadata`].
+///
+/// The fields are `None`, if the corresponding tags are absent.
+/// The [`TiffLoader`] adds it to each image as the sub-asset labeled [`GEOTIFF_METADATA_LABEL`].
+#[derive(Asset, TypePath, Clone, Copy, Debug, Default, PartialEq)]
+pub struct GeoTiffMetadata {
+    /// The width and height of the image in pixels.
+    pub size: UVec2,
+    /// The size of a pixel in model units along the x, y and z axes, from the `ModelPixelScaleTag`.
+    pub pixel_scale: Option<DVec3>,
+    /// The model coordinates of the top left corner of the image, from the first tie point of the `ModelTiepointTag`.
+    pub origin: Option<DVec3>,
+    /// The nodata value from the `GDAL_NODATA` tag.
+    pub nodata: Option<f64>,
+    /// The minimum height of the image, from the statistics GDAL stores in the `GDAL_METADATA` tag.
+    ///
+    /// The metadata added by the [`TiffLoader`] falls back to the smallest decoded sample, excluding the nodata samples.
+    pub min_height: Option<f64>,
+    /// The maximum height of the image, from the statistics GDAL stores in the `GDAL_METADATA` tag.
+    ///
+    /// The metadata added by the [`TiffLoader`] falls back to the largest decoded sample, excluding the nodata samples.
+    pub max_height: Option<f64>,
+}
+
+impl GeoTiffMetadata {
+    /// The side length of the image in model units, where the longer side of non square images is used.
+    pub fn side_length(&self) -> Option<f64> {
+        let pixel_scale = self.pixel_scale?;
+
+        Some((pixel_scale.x * self.size.x as f64).max(pixel_scale.y * self.size.y as f64))
+    }
+
+    /// Creates a planar [`TerrainModel`] centered at the position, which matches the extent and height range of the image.
+    ///
+    /// The height range only matches images, whose heights are stored as is (i.e. as [`TiffTextureFormat::R32Float`]),
+    /// use [`height_range`] for integer images instead.
+    /// Returns `None`, if the pixel scale or the statistics are absent.
+    pub fn planar_model(&self, position: DVec3) -> Option<TerrainModel> {
+        Some(TerrainModel::planar(
+            position,
+            self.side_length()?,
+            self.min_height? as f32,
+            self.max_height? as f32,
+        ))
+    }
+}
+
+/// Reads the double values of the tag, ignoring missing tags and tags with fewer values.
+fn doubles<const N: usize>(
+    decoder: &mut Decoder<Cursor<&[u8]>>,
+    tag: Tag,
+) -> Result<Option<[f64; N]>, TextureError> {
+    let Some(value) = decoder.find_tag(tag).map_err(texture_error)? else {
+        return Ok(None);
+    };
+
+    let values = value.into_f64_vec().map_err(texture_error)?;
+
+    Ok(values.get(..N).map(|values| values.try_into().unwrap()))
+}
+
+/// Reads the georeferencing of the full resolution image from its `ModelPixelScaleTag`, `ModelTiepointTag`,
+/// `GDAL_NODATA` and `GDAL_METADATA` tags.
+///
+/// The minimum and maximum height are the statistics GDAL computes over the samples, converted with the
+/// scale and offset (see [`scale_offset`]). They are not computed when missing, since that requires decoding the image,
+/// but the metadata added by the [`TiffLoader`] is completed with the range of the decoded samples.
+/// Use [`GeoTiffMetadata::planar_model`] to build the [`TerrainModel`] of the image.
+pub fn geotiff_metadata(bytes: &[u8]) -> Result<GeoTiffMetadata, TextureError> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(texture_error)?;
+
+    let (width, height) = decoder.dimensions().map_err(texture_error)?;
+
+    let pixel_scale = doubles::<3>(&mut decoder, Tag::ModelPixelScaleTag)?.map(DVec3::from_array);
+
+    // the tie point maps the raster point (i, j, k) to the model point (x, y, z)
+    let origin = doubles::<6>(&mut decoder, Tag::ModelTiepointTag)?.map(|[i, j, _, x, y, z]| {
+        let scale = pixel_scale.unwrap_or(DVec3::ONE);
+
+        // the raster y axis points down, while the model y axis points up
+        DVec3::new(x - i * scale.x, y + j * scale.y, z)
+    });
+
+    let nodata = gdal_nodata(&mut decoder)?;
+    let scale_offset = gdal_scale_offset(&mut decoder)?.unwrap_or_default();
+
+    let metadata = match decoder
+        .find_tag(Tag::Unknown(GDAL_METADATA_TAG))
+        .map_err(texture_error)?
+    {
+        Some(Value::Ascii(metadata)) => metadata,
+        _ => String::new(),
+    };
+
+    let statistic = |name: &str| {
+        gdal_metadata_item(&metadata, &format!(r#"name="{name}""#))
+            .map(|value| scale_offset.apply(value))
+    };
+
+    Ok(GeoTiffMetadata {
+        size: UVec2::new(width, height),
+        pixel_scale,
+        origin,
+        nodata,
+        min_height: statistic("STATISTICS_MINIMUM"),
+        max_height: statistic("STATISTICS_MAXIMUM"),
+    })
+}
+
+/// Converts the floating point samples into physical values with the scale and offset, skipping the nodata samples.
+///
+/// Integer samples are kept as is, since they are normalized by the texture format (see [`height_range`]).
+fn apply_scale_offset(
+    samples: &mut DecodingResult,
+    scale_offset: GdalScaleOffset,
+    nodata: Option<f64>,
+) {
+    let is_nodata = |value: f64| {
+        nodata.is_some_and(|nodata| value == nodata || (value.is_nan() && nodata.is_nan()))
+    };
+
+    match samples {
+        DecodingResult::F32(data) => {
+            for sample in data {
+                if !is_nodata(*sample as f64) {
+                    *sample = scale_offset.apply(*sample as f64) as f32;
+                }
+            }
+        }
+        DecodingResult::F64(data) => {
+            for sample in data {
+                if !is_nodata(*sample) {
+                    *sample = scale_offset.apply(*sample);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the samples, which equal the nodata value, with zero.
+fn replace_nodata(samples: &mut DecodingResult, nodata: f64) {
+    fn replace<T: Copy + Default>(data: &mut [T], is_nodata: impl Fn(T) -> bool) {
+        for sample in data {
+            if is_nodata(*sample) {
+                *sample = T::default();
+            }
+        }
+    }
+
+    // NaN does not equal itself, but GDAL uses it as the nodata value of many float DEMs
+    let is_nodata = |value: f64| value == nodata || (value.is_nan() && nodata.is_nan());
+
+    match samples {
+        DecodingResult::U8(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::U16(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::U32(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::U64(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::F32(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::F64(data) => replace(data, is_nodata),
+        DecodingResult::I8(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::I16(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::I32(data) => replace(data, |sample| is_nodata(sample as f64)),
+        DecodingResult::I64(data) => replace(data, |sample| is_nodata(sample as f64)),
+    }
+}
+
+/// Reads the sample format of the first channel, which defaults to unsigned integers.
+fn sample_format<R: Read + Seek>(decoder: &mut Decoder<R>) -> Result<SampleFormat, TextureError> {
+    // The tag stores one sample format per channel, which are the same for all channels we support.
+    Ok(
+        match decoder.find_tag(Tag::SampleFormat).map_err(texture_error)? {
+            Some(Value::List(values)) => values.into_iter().next(),
+            value => value,
+        }
+        .and_then(|value| value.into_u16().ok())
+        .map_or(SampleFormat::Uint, SampleFormat::from_u16_exhaustive),
+    )
+}
+
+/// Selects the texture format of the image, which is either the override of the settings or inferred from
+/// the sample format and the color type (i.e. the bits per sample) of the image.
+///
+/// Unlike the decoded samples alone, the `SampleFormat` tag explicitly distinguishes IEEE floats from unsigned and signed integers.
+/// Overrides, that can not store the samples, are rejected.
+fn texture_format<R: Read + Seek>(
+    decoder: &mut Decoder<R>,
+    settings: TiffLoaderSettings,
+) -> Result<TiffTextureFormat, TextureError> {
+    let sample_format = sample_format(decoder)?;
+
+    let color_type = decoder.colortype().map_err(texture_error)?;
+
+    if let Some(texture_format) = settings.texture_format {
+        return if texture_format.supports(sample_format, color_type) {
+            Ok(texture_format)
+        } else {
+            Err(TextureError::UnsupportedTextureFormat(format!(
+                "tiff images with {color_type:?} samples of the {sample_format:?} sample format can not be stored as {texture_format:?}"
+            )))
+        };
+    }
+
+    match (sample_format, color_type) {
+        (SampleFormat::Uint, ColorType::Gray(8)) => Ok(TiffTextureFormat::R8Unorm),
+        (SampleFormat::Uint, ColorType::RGB(8) | ColorType::RGBA(8)) => {
+            Ok(TiffTextureFormat::Rgba8UnormSrgb)
+        }
+        (SampleFormat::Uint, ColorType::Gray(16)) | (SampleFormat::Int, ColorType::Gray(16 | 32)) => {
+            Ok(TiffTextureFormat::R16Unorm)
+        }
+        (SampleFormat::IEEEFP, ColorType::Gray(32)) => Ok(TiffTextureFormat::R32Float),
+        (sample_format, color_type) => Err(TextureError::UnsupportedTextureFormat(format!(
+            "tiff images with {color_type:?} samples of the {sample_format:?} sample format can not be stored in a supported texture format"
+        ))),
+    }
+}
+
+/// Divides the colors of the RGBA texels by their alpha, where fully transparent texels are kept.
+fn unpremultiply(data: &mut [u8]) {
+    for texel in data.chunks_exact_mut(4) {
+        let alpha = texel[3] as u32;
+
+        if alpha != 0 {
+            for channel in &mut texel[..3] {
+                *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+}
+
+/// Expands the RGB texels with an opaque alpha channel.
+fn expand_rgb(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(3)
+        .flat_map(|texel| [texel[0], texel[1], texel[2], u8::MAX])
+        .collect()
+}
+
+/// Maps 8 bit heights onto the full 16 bit range, where 255 * 257 = 65535.
+fn widen_unsigned(data: &[u8]) -> Vec<u16> {
+    data.iter().map(|&height| height as u16 * 257).collect()
+}
+
+/// Maps signed heights to unsigned ones, by offsetting them by 2^15, which preserves their order.
+///
+/// Thus sea level (zero) is stored at the center (0.5) of the R16Unorm range, and bathymetry below it.
+/// Choose a `min_height` of `-max_height` for the terrain, to map sea level back to a height of zero.
+fn offset_signed(data: &[i16]) -> Vec<u16> {
+    data.iter()
+        .map(|&height| (height as i32 - i16::MIN as i32) as u16)
+        .collect()
+}
+
+/// Downsamples the R16 image by the integer factor, by averaging each block of `factor`×`factor` texels.
+/// The blocks at the right and bottom edge may be partial.
+fn box_filter(data: &[u8], (width, height): (u32, u32), factor: u32) -> (Vec<u8>, (u32, u32)) {
+    let data: Vec<u16> = pod_collect_to_vec(data);
+
+    let new_width = width.div_ceil(factor);
+    let new_height = height.div_ceil(factor);
+
+    let mut downsampled = Vec::with_capacity(new_width as usize * new_height as usize);
+
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let mut sum = 0u64;
+            let mut count = 0u64;
+
+            for sy in y * factor..((y + 1) * factor).min(height) {
+                for sx in x * factor..((x + 1) * factor).min(width) {
+                    sum += data[(sy * width + sx) as usize] as u64;
+                    count += 1;
+                }
+            }
+
+            downsampled.push(((sum + count / 2) / count) as u16);
+        }
+    }
+
+    (cast_slice(&downsampled).to_vec(), (new_width, new_height))
+}
+
+/// Computes the range of the samples of single channel images, excluding the nodata and NaN samples.
+fn sample_range(samples: &DecodingResult, nodata: Option<f64>) -> Option<(f64, f64)> {
+    fn range<T: Copy>(
+        data: &[T],
+        nodata: Option<f64>,
+        value: impl Fn(T) -> f64,
+    ) -> Option<(f64, f64)> {
+        data.iter()
+            .map(|&sample| value(sample))
+            .filter(|&value| !value.is_nan() && Some(value) != nodata)
+            .fold(None, |range, value| {
+                Some(range.map_or((value, value), |(min, max): (f64, f64)| {
+                    (min.min(value), max.max(value))
+                }))
+            })
+    }
+
+    match samples {
+        DecodingResult::U8(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::U16(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::U32(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::U64(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::F32(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::F64(data) => range(data, nodata, |sample| sample),
+        DecodingResult::I8(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::I16(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::I32(data) => range(data, nodata, |sample| sample as f64),
+        DecodingResult::I64(data) => range(data, nodata, |sample| sample as f64),
+    }
+}
+
+/// Merges the two optional sample ranges.
+fn merge_ranges(a: Option<(f64, f64)>, b: Option<(f64, f64)>) -> Option<(f64, f64)> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.max(b.1))),
+        (range, None) | (None, range) => range,
+    }
+}
+
+/// Converts the decoded samples into the texels of the texture format, applying the scale and offset and replacing the nodata samples first.
+///
+/// Also returns the range of the stored samples of single channel images, excluding the nodata samples (see [`sample_range`]).
+/// 32 bit signed heights have to fit into 16 bit, since they are stored like 16 bit signed heights (see [`offset_signed`]).
+fn convert_samples(
+    mut samples: DecodingResult,
+    texture_format: TiffTextureFormat,
+    color_type: ColorType,
+    associated_alpha: bool,
+    nodata: Option<f64>,
+    scale_offset: Option<GdalScaleOffset>,
+) -> Result<(Vec<u8>, Option<(f64, f64)>), TextureError> {
+    let range = match color_type {
+        ColorType::Gray(_) => sample_range(&samples, nodata),
+        _ => None,
+    };
+
+    if let Some(scale_offset) = scale_offset {
+        apply_scale_offset(&mut samples, scale_offset, nodata);
+    }
+
+    if let Some(nodata) = nodata {
+        replace_nodata(&mut samples, nodata);
+    }
+
+    let data = match samples {
+        DecodingResult::U8(mut data) => match texture_format {
+            TiffTextureFormat::R8Unorm => data,
+            TiffTextureFormat::Rgba8UnormSrgb if color_type == ColorType::RGB(8) => {
+                expand_rgb(&data)
+            }
+            TiffTextureFormat::Rgba8UnormSrgb => {
+                if associated_alpha {
+                    unpremultiply(&mut data);
+                }
+
+                data
+            }
+            _ => cast_slice(&widen_unsigned(&data)).to_vec(),
+        },
+        DecodingResult::U16(data) => cast_slice(&data).to_vec(),
+        DecodingResult::U32(data) => cast_slice(&data).to_vec(),
+        DecodingResult::U64(data) => cast_slice(&data).to_vec(),
+        DecodingResult::F32(data) => cast_slice(&data).to_vec(),
+        DecodingResult::F64(data) => cast_slice(&data).to_vec(),
+        DecodingResult::I8(data) => cast_slice(&data).to_vec(),
+        DecodingResult::I16(data) => cast_slice(&offset_signed(&data)).to_vec(),
+        DecodingResult::I32(data) => {
+            let data = data
+                .iter()
+                .map(|&height| {
+                    i16::try_from(height).map_err(|_| {
+                        TextureError::UnsupportedTextureFormat(format!(
+                            "the 32 bit signed tiff height {height} exceeds the 16 bit range of R16Unorm textures, convert the image to 32 bit floats instead"
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            cast_slice(&offset_signed(&data)).to_vec()
+        }
+        DecodingResult::I64(data) => cast_slice(&data).to_vec(),
+    };
+
+    Ok((data, range))
+}
+
+/// Reads the striped image strip by strip, converting the samples of each strip before the next one is decoded.
+///
+/// Unlike reading the whole image at once, this never holds the decoded and the converted samples of the entire image
+/// at the same time, which roughly halves the peak memory for tall DEMs.
+fn read_strips(
+    decoder: &mut Decoder<Cursor<Vec<u8>>>,
+    texture_format: TiffTextureFormat,
+    color_type: ColorType,
+    associated_alpha: bool,
+    nodata: Option<f64>,
+    scale_offset: Option<GdalScaleOffset>,
+) -> Result<(Vec<u8>, Option<(f64, f64)>), TextureError> {
+    let strip_count = decoder.strip_count().map_err(texture_error)?;
+
+    let mut data = Vec::new();
+    let mut range = None;
+
+    for strip in 0..strip_count {
+        let samples = decoder.read_chunk(strip).map_err(texture_error)?;
+        let (strip_data, strip_range) = convert_samples(
+            samples,
+            texture_format,
+            color_type,
+            associated_alpha,
+            nodata,
+            scale_offset,
+        )?;
+
+        data.extend(strip_data);
+        range = merge_ranges(range, strip_range);
+    }
+
+    Ok((data, range))
+}
+
+/// Decodes the overview level of the tiff file into a single channel image.
+/// Both TIFF and BigTIFF files, which are uncompressed or LZW, Deflate and PackBits compressed, are supported.
+/// The texture format is inferred from the samples, unless it is overridden (see [`TiffLoaderSettings::texture_format`]).
+/// Unsigned 8 bit samples are widened to 16 bit, if they are stored as [`TiffTextureFormat::R16Unorm`],
+/// and RGB images are expanded to RGBA.
+/// Signed 16 and 32 bit heights are offset by 2^15 into the unsigned range,
+/// where 32 bit heights outside of the 16 bit range are rejected.
+/// Indexed color (palette) images are rejected, since their samples are no heights.
+/// Floating point samples are converted with the scale and offset stored by GDAL, if present (see [`height_range`] for integers).
+pub fn decode_tiff(bytes: Vec<u8>, settings: TiffLoaderSettings) -> Result<Image, TextureError> {
+    decode(bytes, settings).map(|(image, _)| image)
+}
+
+/// Decodes the tiff file like [`decode_tiff`] and also returns the range of the physical heights of single channel images,
+/// which excludes the nodata samples and is converted with the scale and offset stored by GDAL.
+fn decode(
+    bytes: Vec<u8>,
+    settings: TiffLoaderSettings,
+) -> Result<(Image, Option<(f64, f64)>), TextureError> {
+    let mut decoder = seek_to_overview_level(bytes, settings.overview_level)?;
+
+    // Check the compression upfront, to fail with a descriptive error instead of deep inside the decoder.
+    let compression = decoder
+        .find_tag_unsigned::<u16>(Tag::Compression)
+        .map_err(texture_error)?
+        .map_or(
+            CompressionMethod::None,
+            CompressionMethod::from_u16_exhaustive,
+        );
+
+    match compression {
+        CompressionMethod::None
+        | CompressionMethod::LZW
+        | CompressionMethod::Deflate
+        | CompressionMethod::OldDeflate
+        | CompressionMethod::PackBits => {}
+        method => return Err(unsupported_compression(method)),
+    }
+
+    let (width, height) = decoder.dimensions().map_err(texture_error)?;
+
+    if width == 0 || height == 0 {
+        return Err(TextureError::InvalidData(format!(
+            "tiff image has a degenerate size of {width}x{height}"
+        )));
+    }
+
+    // The samples of indexed color images are indices into a color map, which have no meaning as heights.
+    // The decoder does not report them as a color type, so the photometric interpretation is checked upfront.
+    let photometric_interpretation = decoder
+        .find_tag_unsigned::<u16>(Tag::PhotometricInterpretation)
+        .map_err(texture_error)?
+        .and_then(PhotometricInterpretation::from_u16);
+
+    if photometric_interpretation == Some(PhotometricInterpretation::RGBPalette) {
+        let bits = decoder
+            .find_tag_unsigned::<u16>(Tag::BitsPerSample)
+            .map_err(texture_error)?
+            .unwrap_or(1);
+
+        return Err(TextureError::UnsupportedTextureFormat(format!(
+            "tiff images with indexed colors ({bits} bit palette) can not be loaded as heightmaps, convert them to grayscale instead"
+        )));
+    }
+
+    let color_type = decoder.colortype().map_err(texture_error)?;
+
+    let orientation = decoder
+        .find_tag_unsigned::<u16>(Tag::Orientation)
+        .map_err(texture_error)?
+        .unwrap_or(1);
+
+    let associated_alpha = associated_alpha(&mut decoder)?;
+    let nodata = gdal_nodata(&mut decoder)?.or(settings.nodata);
+    let scale_offset = gdal_scale_offset(&mut decoder)?;
+
+    let tiff_texture_format = texture_format(&mut decoder, settings)?;
+
+    // The strips of planar images store one channel each, so only interleaved (chunky) images are read strip by strip.
+    let planar_configuration = decoder
+        .find_tag_unsigned::<u16>(Tag::PlanarConfiguration)
+        .map_err(texture_error)?
+        .and_then(PlanarConfiguration::from_u16)
+        .unwrap_or(PlanarConfiguration::Chunky);
+
+    let (data, sample_range) = match (decoder.get_chunk_type(), planar_configuration) {
+        (ChunkType::Strip, PlanarConfiguration::Chunky) => read_strips(
+            &mut decoder,
+            tiff_texture_format,
+            color_type,
+            associated_alpha,
+            nodata,
+            scale_offset,
+        )?,
+        _ => convert_samples(
+            decoder.read_image().map_err(texture_error)?,
+            tiff_texture_format,
+            color_type,
+            associated_alpha,
+            nodata,
+            scale_offset,
+        )?,
+    };
+
+    let texel_count = width as usize * height as usize;
+
+    if data.is_empty() || !data.len().is_multiple_of(texel_count) {
+        return Err(TextureError::InvalidData(format!(
+            "tiff image data of {} bytes does not match its size of {width}x{height}",
+            data.len()
+        )));
+    }
+
+    let texture_format = tiff_texture_format.texture_format();
+    let texel_size = data.len() / texel_count;
+    let format_size = texture_format.block_copy_size(None).unwrap() as usize;
+
+    if texel_size != format_size {
+        return Err(TextureError::InvalidData(format!(
+            "tiff image with {texel_size} bytes per texel can not be stored as {texture_format:?} with {format_size} bytes per texel"
+        )));
+    }
+
+    let (mut data, (width, height)) = orient(data, (width, height), orientation);
+
+    let (width, height) = match settings.max_dimension {
+        Some(max_dimension) if width.max(height) > max_dimension => {
+            if tiff_texture_format != TiffTextureFormat::R16Unorm {
+                return Err(TextureError::InvalidData(format!(
+                    "tiff images can only be downsampled, when they are stored as {:?}",
+                    TiffTextureFormat::R16Unorm
+                )));
+            }
+
+            let factor = width.max(height).div_ceil(max_dimension.max(1));
+
+            warn_once!(
+                "The tiff image ({width}x{height}) exceeds the maximum dimension of {max_dimension} and is downsampled by a factor of {factor}."
+            );
+
+            let (downsampled, size) = box_filter(&data, (width, height), factor);
+            data = downsampled;
+            size
+        }
+        _ => (width, height),
+    };
+
+    let (width, height) = if settings.pad_to_power_of_two {
+        let size = (width.next_power_of_two(), height.next_power_of_two());
+        data = pad_edges(&data, (width, height), size);
+        size
+    } else {
+        (width, height)
+    };
+
+    let image = Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        texture_format,
+        RenderAssetUsages::default(),
+    );
+
+    // the range is measured in stored samples, which are converted into physical heights like the statistics of GDAL
+    let scale_offset = scale_offset.unwrap_or_default();
+    let height_range = sample_range.map(|(min, max)| {
+        let (min, max) = (scale_offset.apply(min), scale_offset.apply(max));
+
+        (min.min(max), min.max(max))
+    });
+
+    Ok((image, height_range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::encoder::{
+        colortype::{self, Gray16, Gray8, GrayI16, GrayI32, RGBA8},
+        compression::{Compression, Deflate, Lzw, Uncompressed},
+        TiffEncoder, TiffValue,
+    };
+
+    /// Encodes the samples as a tiff image, where the tags are written after (and thus override) the default ones.
+    fn encode<C: colortype::ColorType, D: Compression>(
+        (width, height): (u32, u32),
+        data: &[C::Inner],
+        compression: D,
+        tags: &[(Tag, u16)],
+    ) -> Vec<u8>
+    where
+        [C::Inner]: TiffValue,
+    {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+        let mut image = encoder
+            .new_image_with_compression::<C, D>(width, height, compression)
+            .unwrap();
+
+        for &(tag, value) in tags {
+            image.encoder().write_tag(tag, value).unwrap();
+        }
+
+        image.write_data(data).unwrap();
+
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn compressed_images_match_uncompressed() {
+        let heights = (0..64 * 64).map(|index| index * 16).collect::<Vec<u16>>();
+
+        for bytes in [
+            encode::<Gray16, _>((64, 64), &heights, Lzw, &[]),
+            encode::<Gray16, _>((64, 64), &heights, Deflate::default(), &[]),
+        ] {
+            let image = decode_tiff(bytes, TiffLoaderSettings::default()).unwrap();
+
+            assert_eq!(image.texture_descriptor.format, TextureFormat::R16Unorm);
+            assert_eq!(image.data, cast_slice::<u16, u8>(&heights));
+        }
+    }
+
+    #[test]
+    fn unsupported_compression_is_named() {
+        // the samples are stored uncompressed, but the tag declares them as JPEG compressed
+        let bytes = encode::<Gray16, _>(
+            (4, 4),
+            &[0; 16],
+            Uncompressed,
+            &[(Tag::Compression, CompressionMethod::ModernJPEG.to_u16())],
+        );
+
+        let Err(TextureError::UnsupportedTextureFormat(message)) =
+            decode_tiff(bytes, TiffLoaderSettings::default())
+        else {
+            panic!("the JPEG compressed image was not rejected as unsupported");
+        };
+
+        assert!(message.contains("ModernJPEG"), "{message}");
+    }
+
+    #[test]
+    fn eight_bit_ramps_stay_monotonic() {
+        let ramp = (0..=255).collect::<Vec<u8>>();
+        let bytes = encode::<Gray8, _>((16, 16), &ramp, Uncompressed, &[]);
+
+        let image = decode_tiff(bytes.clone(), TiffLoaderSettings::default()).unwrap();
+
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R8Unorm);
+        assert_eq!(image.data, ramp);
+
+        let settings = TiffLoaderSettings {
+            texture_format: Some(TiffTextureFormat::R16Unorm),
+            ..default()
+        };
+        let image = decode_tiff(bytes, settings).unwrap();
+        let heights = pod_collect_to_vec::<u8, u16>(&image.data);
+
+        // the widened heights span the full 16 bit range
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R16Unorm);
+        assert_eq!((heights[0], heights[255]), (0, u16::MAX));
+        assert!(heights.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn associated_alpha_is_unpremultiplied() {
+        let premultiplied = [100, 50, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255];
+
+        // the first extra sample declares associated alpha (1) or unassociated alpha (2)
+        let decode = |extra_samples| {
+            let bytes = encode::<RGBA8, _>(
+                (3, 1),
+                &premultiplied,
+                Uncompressed,
+                &[(Tag::ExtraSamples, extra_samples)],
+            );
+
+            decode_tiff(bytes, TiffLoaderSettings::default()).unwrap()
+        };
+
+        let image = decode(1);
+
+        assert_eq!(
+            image.texture_descriptor.format,
+            TextureFormat::Rgba8UnormSrgb
+        );
+        // the colors are divided by their alpha, which leaves transparent and opaque texels unchanged
+        assert_eq!(image.data, [199, 100, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255]);
+
+        assert_eq!(decode(2).data, premultiplied);
+    }
+
+    #[test]
+    fn palette_images_are_rejected() {
+        // the samples are stored as grayscale, but the tag declares them as indices into a color map
+        let bytes = encode::<Gray8, _>(
+            (4, 4),
+            &[0; 16],
+            Uncompressed,
+            &[(
+                Tag::PhotometricInterpretation,
+                PhotometricInterpretation::RGBPalette.to_u16(),
+            )],
+        );
+
+        let Err(TextureError::UnsupportedTextureFormat(message)) =
+            decode_tiff(bytes, TiffLoaderSettings::default())
+        else {
+            panic!("the palette image was not rejected as unsupported");
+        };
+
+        assert!(message.contains("8 bit palette"), "{message}");
+    }
+
+    #[test]
+    fn overview_levels_are_read_from_sub_ifds() {
+        let full = (0..16).map(|index| index * 1000).collect::<Vec<u16>>();
+        let overview = [7u16, 8, 9, 10];
+
+        let mut bytes = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+        let mut image = encoder.new_image::<Gray16>(4, 4).unwrap();
+        // a placeholder for the offset of the overview, which is only known once it is written
+        image
+            .encoder()
+            .write_tag(Tag::Unknown(SUB_IFDS_TAG), 0u16)
+            .unwrap();
+        image.write_data(&full).unwrap();
+        encoder.write_image::<Gray16>(2, 2, &overview).unwrap();
+        let mut bytes = bytes.into_inner();
+
+        // turn the chained second image into a SubIFD of the first one
+        assert!(bytes.starts_with(b"II"));
+        let read_u16 = |bytes: &[u8], at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let first_ifd = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let entry_count = read_u16(&bytes, first_ifd) as usize;
+        let next_ifd = first_ifd + 2 + 12 * entry_count;
+        let overview_ifd = u16::try_from(u32::from_le_bytes(
+            bytes[next_ifd..next_ifd + 4].try_into().unwrap(),
+        ))
+        .unwrap();
+
+        let entry = (0..entry_count)
+            .map(|index| first_ifd + 2 + 12 * index)
+            .find(|&entry| read_u16(&bytes, entry) == SUB_IFDS_TAG)
+            .unwrap();
+        bytes[entry + 8..entry + 10].copy_from_slice(&overview_ifd.to_le_bytes());
+        bytes[next_ifd..next_ifd + 4].fill(0);
+
+        assert_eq!(overview_level_count(&bytes).unwrap(), 2);
+
+        let decode_level = |overview_level| {
+            let settings = TiffLoaderSettings {
+                overview_level,
+                ..default()
+            };
+            decode_tiff(bytes.clone(), settings)
+        };
+
+        assert_eq!(decode_level(0).unwrap().data, cast_slice::<u16, u8>(&full));
+
+        let image = decode_level(1).unwrap();
+        assert_eq!(image.size(), UVec2::new(2, 2));
+        assert_eq!(image.data, cast_slice::<u16, u8>(&overview));
+
+        assert!(matches!(decode_level(2), Err(TextureError::InvalidData(_))));
+    }
+
+    #[test]
+    fn orientation_puts_the_top_left_pixel_first() {
+        // the stored rows, as they appear in the file
+        let stored = [0, 1, 2, 3, 4, 5];
+
+        for (orientation, size, expected) in [
+            (1, (3, 2), [0, 1, 2, 3, 4, 5]),
+            (3, (3, 2), [5, 4, 3, 2, 1, 0]),
+            (4, (3, 2), [3, 4, 5, 0, 1, 2]),
+            (6, (2, 3), [3, 0, 4, 1, 5, 2]),
+            (8, (2, 3), [2, 5, 1, 4, 0, 3]),
+        ] {
+            let bytes = encode::<Gray16, _>(
+                (3, 2),
+                &stored,
+                Uncompressed,
+                &[(Tag::Orientation, orientation)],
+            );
+            let image = decode_tiff(bytes, TiffLoaderSettings::default()).unwrap();
+
+            assert_eq!(image.size(), UVec2::from(size), "{orientation}");
+            assert_eq!(
+                image.data,
+                cast_slice::<u16, u8>(&expected),
+                "{orientation}"
+            );
+        }
+    }
+
+    #[test]
+    fn signed_heights_are_offset_monotonically() {
+        let heights = (-8..8).map(|height| height * 1000).collect::<Vec<i32>>();
+        let expected = heights
+            .iter()
+            .map(|&height| (height + 32768) as u16)
+            .collect::<Vec<u16>>();
+
+        for bytes in [
+            encode::<GrayI16, _>(
+                (4, 4),
+                &heights
+                    .iter()
+                    .map(|&height| height as i16)
+                    .collect::<Vec<_>>(),
+                Uncompressed,
+                &[],
+            ),
+            encode::<GrayI32, _>((4, 4), &heights, Uncompressed, &[]),
+        ] {
+            let image = decode_tiff(bytes, TiffLoaderSettings::default()).unwrap();
+
+            assert_eq!(image.texture_descriptor.format, TextureFormat::R16Unorm);
+            assert_eq!(image.data, cast_slice::<u16, u8>(&expected));
+        }
+
+        // heights beyond the 16 bit range are rejected instead of clamped
+        let bytes = encode::<GrayI32, _>((2, 1), &[0, 40_000], Uncompressed, &[]);
+
+        assert!(matches!(
+            decode_tiff(bytes, TiffLoaderSettings::default()),
+            Err(TextureError::UnsupportedTextureFormat(_))
+        ));
+    }
+
+    #[test]
+    fn striped_images_are_decoded_strip_by_strip() {
+        let (width, height) = (8, 7);
+        let heights = (0..width * height)
+            .map(|index| (index * 997) as u16)
+            .collect::<Vec<_>>();
+
+        // three rows per strip, so the last strip is only partially filled
+        let mut bytes = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+        let mut image = encoder
+            .new_image_with_compression::<Gray16, _>(width, height, Lzw)
+            .unwrap();
+        image.rows_per_strip(3).unwrap();
+        image.write_data(&heights).unwrap();
+        let bytes = bytes.into_inner();
+
+        let mut decoder = Decoder::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(decoder.get_chunk_type(), ChunkType::Strip);
+        assert_eq!(decoder.strip_count().unwrap(), 3);
+
+        let image = decode_tiff(bytes, TiffLoaderSettings::default()).unwrap();
+
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R16Unorm);
+        assert_eq!((image.width(), image.height()), (width, height));
+        assert_eq!(image.data, cast_slice::<u16, u8>(&heights));
+    }
+
+    #[test]
+    fn gdal_scale_and_offset_map_samples_to_meters() {
+        fn encode_with_metadata<C: colortype::ColorType>(
+            data: &[C::Inner],
+            metadata: &str,
+        ) -> Vec<u8>
+        where
+            [C::Inner]: TiffValue,
+        {
+            let mut bytes = Cursor::new(Vec::new());
+            let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+            let mut image = encoder.new_image::<C>(2, 2).unwrap();
+            image
+                .encoder()
+                .write_tag(Tag::Unknown(GDAL_METADATA_TAG), metadata)
+                .unwrap();
+            image.write_data(data).unwrap();
+
+            bytes.into_inner()
+        }
+
+        // the items of the second band are ignored
+        let metadata = concat!(
+            "<GDALMetadata>",
+            r#"<Item name="SCALE" sample="1" role="scale">5</Item>"#,
+            r#"<Item name="SCALE" sample="0" role="scale">0.1</Item>"#,
+            r#"<Item name="OFFSET" sample="0" role="offset">-50</Item>"#,
+            "</GDALMetadata>",
+        );
+
+        let integer_bytes = encode_with_metadata::<Gray16>(&[0, 500, 1000, u16::MAX], metadata);
+
+        assert_eq!(
+            scale_offset(&integer_bytes).unwrap(),
+            Some(GdalScaleOffset {
+                scale: 0.1,
+                offset: -50.0
+            })
+        );
+
+        // the integers stay normalized, so the height range maps them onto meters
+        let (min_height, max_height) = height_range(&integer_bytes, TiffLoaderSettings::default())
+            .unwrap()
+            .unwrap();
+        assert!((min_height + 50.0).abs() < 1e-9);
+        assert!((max_height - 6503.5).abs() < 1e-9);
+
+        // floats are converted into meters while decoding
+        let float_bytes =
+            encode_with_metadata::<colortype::Gray32Float>(&[0.0, 10.0, 1000.0, 250.0], metadata);
+        let image = decode_tiff(float_bytes, TiffLoaderSettings::default()).unwrap();
+
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R32Float);
+        for (height, expected) in cast_slice::<u8, f32>(&image.data)
+            .iter()
+            .zip([-50.0, -49.0, 50.0, -25.0])
+        {
+            assert!((height - expected).abs() < 1e-4, "{height} != {expected}");
+        }
+
+        // without the metadata, the samples are used as is
+        let plain_bytes = encode_with_metadata::<Gray16>(
+            &[0, 500, 1000, u16::MAX],
+            "<GDALMetadata></GDALMetadata>",
+        );
+
+        assert_eq!(scale_offset(&plain_bytes).unwrap(), None);
+        assert_eq!(
+            height_range(&plain_bytes, TiffLoaderSettings::default()).unwrap(),
+            None
+        );
+    }
+}
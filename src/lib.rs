@@ -67,20 +67,27 @@ pub mod prelude {
     pub use crate::{
         debug::{
             camera::{DebugCameraBundle, DebugCameraController},
-            DebugTerrainMaterial, LoadingImages, TerrainDebugPlugin,
+            DebugTerrainMaterial, FreezeTerrain, LoadingImages, TerrainDebugPlugin,
+            TerrainGpuTimings,
         },
         math::TerrainModel,
         plugin::TerrainPlugin,
         preprocess::{
             preprocessor::Preprocessor,
-            preprocessor::{PreprocessDataset, SphericalDataset},
+            preprocessor::{CubeMapDataset, CubeMapLayout, PreprocessDataset, SphericalDataset},
             TerrainPreprocessPlugin,
         },
         render::terrain_material::TerrainMaterialPlugin,
-        terrain::{TerrainBundle, TerrainConfig},
+        terrain::{
+            TerrainAtmosphere, TerrainBrushMode, TerrainBrushStroke, TerrainBundle, TerrainConfig,
+            TerrainConfigError, TerrainContourLines, TerrainDetailNoise, TerrainHeightOverlay,
+            TerrainShading,
+        },
         terrain_data::{
-            tile_atlas::TileAtlas, tile_tree::TileTree, AttachmentConfig, AttachmentFormat,
+            tile_atlas::{TerrainTileLoadFailed, TerrainTileLoaded, TileAtlas},
+            tile_tree::{TileTree, TileTreeState},
+            AttachmentConfig, AttachmentFormat,
         },
-        terrain_view::{TerrainViewComponents, TerrainViewConfig},
+        terrain_view::{TerrainViewComponents, TerrainViewConfig, TerrainViewConfigError},
     };
 }
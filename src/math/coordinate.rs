@@ -1,6 +1,6 @@
-use crate::math::{TerrainModel, C_SQR};
+use crate::math::{terrain_model::ST_EPSILON, TerrainModel, C_SQR};
 use bevy::{
-    math::{DVec2, DVec3, IVec2},
+    math::{DVec2, DVec3, IVec2, Vec2},
     render::render_resource::ShaderType,
 };
 use bincode::{Decode, Encode};
@@ -66,7 +66,7 @@ impl Coordinate {
     }
 
     /// Calculates the coordinate for for the local position on the unit cube sphere.
-    pub(crate) fn from_world_position(world_position: DVec3, model: &TerrainModel) -> Self {
+    pub fn from_world_position(world_position: DVec3, model: &TerrainModel) -> Self {
         let local_position = model.position_world_to_local(world_position);
 
         let (side, uv) = if model.is_spherical() {
@@ -107,7 +107,27 @@ impl Coordinate {
         Self { side, uv }
     }
 
-    pub(crate) fn world_position(self, model: &TerrainModel, height: f32) -> DVec3 {
+    /// Identifies the tile of the lod, in which the coordinate lies, e.g. to edit or paint the terrain at a world position.
+    ///
+    /// Returns the side, the xy index of the tile and the fractional position within the tile.
+    /// The st coordinate is clamped by the same epsilon away from the edges of the side as for the origin tile of the
+    /// [`TerrainModelApproximation`](crate::math::TerrainModelApproximation). The origin tile matches this tile,
+    /// except while the view lies within the hysteresis around the previous origin tile
+    /// (see [`TerrainViewConfig::origin_hysteresis`](crate::terrain_view::TerrainViewConfig::origin_hysteresis)).
+    pub fn tile_coords(self, lod: u32) -> (u32, IVec2, Vec2) {
+        let tile_count = TileCoordinate::count(lod) as f64;
+        let uv = self
+            .uv
+            .clamp(DVec2::splat(ST_EPSILON), DVec2::splat(1.0 - ST_EPSILON));
+
+        let xy = (uv * tile_count).as_ivec2();
+        let frac = (uv * tile_count).fract().as_vec2();
+
+        (self.side, xy, frac)
+    }
+
+    /// Calculates the world position of the coordinate, displaced by the height along the surface normal.
+    pub fn world_position(self, model: &TerrainModel, height: f32) -> DVec3 {
         let local_position = if model.is_spherical() {
             let w = (self.uv - 0.5) / 0.5;
             let uv = w / (1.0 + C_SQR - C_SQR * w * w).powf(0.5);
@@ -131,7 +151,27 @@ impl Coordinate {
 
     /// Projects the coordinate onto one of the six cube faces.
     /// Thereby it chooses the closest location on this face to the original coordinate.
-    pub(crate) fn project_to_side(self, side: u32, model: &TerrainModel) -> Self {
+    ///
+    /// The sides are indexed like on the GPU: 0 = -x, 1 = +z, 2 = +y, 3 = +x, 4 = -z and 5 = -y of the local terrain space.
+    /// The resulting uv is the warped side coordinate (st) in the range of [0, 1], as used to address the tiles of the side.
+    ///
+    /// For the side itself the uv is kept and for the opposite side its two components are swapped,
+    /// while for the four adjacent sides one component is clamped to the shared edge.
+    /// This is used to approximate the distance between the view and the tiles of every side (see [`TerrainModelApproximation`](crate::math::TerrainModelApproximation)).
+    /// Planar coordinates are returned unchanged.
+    ///
+    /// ```
+    /// # use bevy::math::{DVec2, DVec3};
+    /// # use bevy_terrain::math::{Coordinate, TerrainModel};
+    /// let model = TerrainModel::sphere(DVec3::ZERO, 1.0, 0.0, 1.0);
+    ///
+    /// // side 0 (-x) and side 1 (+z) share the edge at the start of the first component of side 1
+    /// let coordinate = Coordinate::new(0, DVec2::new(0.25, 0.5)).project_to_side(1, &model);
+    ///
+    /// assert_eq!(coordinate.side, 1);
+    /// assert_eq!(coordinate.uv, DVec2::new(0.0, 0.5));
+    /// ```
+    pub fn project_to_side(self, side: u32, model: &TerrainModel) -> Self {
         if model.is_spherical() {
             let info = SideInfo::project_to_side(self.side, side);
 
@@ -177,23 +217,37 @@ impl TileCoordinate {
         Self { side, lod, x, y }
     }
 
+    /// The count of tiles along each axis of a side at the lod.
     pub fn count(lod: u32) -> u32 {
         1 << lod
     }
 
+    /// Hashes the tile coordinate together with the seed, identical to the `tile_hash` shader function.
+    ///
+    /// The result is deterministic across runs and platforms.
+    pub fn hash(self, seed: u32) -> u32 {
+        [self.lod, self.side, self.x, self.y]
+            .into_iter()
+            .fold(pcg_hash(seed), |hash, value| pcg_hash(hash ^ value))
+    }
+
     pub fn path(self, path: &str, extension: &str) -> String {
         format!("{path}/{self}.{extension}")
     }
 
-    pub fn parent(self) -> Self {
-        Self {
+    /// The tile of the next coarser lod, which contains this tile.
+    ///
+    /// Returns `None` for the tiles of lod zero, which are the roots of the quadtree.
+    pub fn parent(self) -> Option<Self> {
+        Some(Self {
             side: self.side,
-            lod: self.lod.wrapping_sub(1),
+            lod: self.lod.checked_sub(1)?,
             x: self.x >> 1,
             y: self.y >> 1,
-        }
+        })
     }
 
+    /// The four tiles of the next finer lod, which subdivide this tile, in the order top left, top right, bottom left and bottom right.
     pub fn children(self) -> impl Iterator<Item = Self> {
         (0..4).map(move |index| {
             TileCoordinate::new(
@@ -284,3 +338,45 @@ impl fmt::Display for TileCoordinate {
         write!(f, "{}_{}_{}_{}", self.side, self.lod, self.x, self.y)
     }
 }
+
+/// The PCG hash, see "Hash Functions for GPU Rendering" by Jarzynski and Olano.
+fn pcg_hash(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_hash_is_deterministic() {
+        let tile = TileCoordinate::new(3, 5, 17, 30);
+
+        // The reference values pin the hash across runs and platforms.
+        assert_eq!(TileCoordinate::new(0, 0, 0, 0).hash(0), 920646579);
+        assert_eq!(tile.hash(1234), 2414750560);
+        assert_eq!(tile.hash(1235), 548061628);
+
+        assert_ne!(
+            tile.hash(1234),
+            TileCoordinate::new(5, 3, 17, 30).hash(1234)
+        );
+        assert_ne!(
+            tile.hash(1234),
+            TileCoordinate::new(3, 5, 30, 17).hash(1234)
+        );
+    }
+
+    #[test]
+    fn parent_is_none_at_the_root() {
+        let tile = TileCoordinate::new(2, 2, 3, 1);
+        let parent = tile.parent().unwrap();
+
+        assert_eq!(parent, TileCoordinate::new(2, 1, 1, 0));
+        assert!(parent.children().any(|child| child == tile));
+        assert_eq!(parent.parent(), Some(TileCoordinate::new(2, 0, 0, 0)));
+        assert_eq!(TileCoordinate::new(2, 0, 0, 0).parent(), None);
+    }
+}
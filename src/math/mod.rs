@@ -5,9 +5,16 @@ mod terrain_model;
 pub use crate::math::{
     coordinate::{Coordinate, TileCoordinate},
     terrain_model::{
-        generate_terrain_model_approximation, TerrainModel, TerrainModelApproximation,
+        apply_target_pixel_error, compute_tile_screen_error, compute_view_coordinates,
+        generate_terrain_model_approximation, PlanarOrigin, SideParameter, TerrainModel,
+        TerrainModelApproximation, TerrainModelError, TileScreenError, SIDE_MATRICES,
     },
 };
 
 /// The square of the parameter c of the algebraic sigmoid function, used to convert between uv and st coordinates.
-const C_SQR: f64 = 0.87 * 0.87;
+///
+/// The st coordinate `s` of a side maps to the cube coordinate `u = (2s - 1) / sqrt(1 - 4 c s (s - 1))` and back
+/// with `s = 0.5 + 0.5 u sqrt((1 + c) / (1 + c u²))`, where `c` is this constant.
+/// This distributes the tiles more evenly across the sphere than a plain cube projection.
+/// External tools have to use the same value, to match the projection of the shaders exactly.
+pub const C_SQR: f64 = 0.87 * 0.87;
@@ -1,17 +1,26 @@
 use crate::{
     math::{coordinate::Coordinate, ellipsoid::project_point_ellipsoid, TileCoordinate, C_SQR},
-    terrain_data::tile_atlas::TileAtlas,
-    terrain_data::tile_tree::TileTree,
+    terrain_data::{sample_resident_height, tile_atlas::TileAtlas, tile_tree::TileTree},
     terrain_view::TerrainViewComponents,
 };
 use bevy::{
-    math::{DMat3, DMat4, DQuat, DVec2, DVec3, IVec2},
+    math::{DAffine3, DMat3, DMat4, DQuat, DVec2, DVec3, IVec2},
     prelude::*,
-    render::render_resource::ShaderType,
+    render::{primitives::Aabb, render_resource::ShaderType},
+    tasks::ComputeTaskPool,
 };
+use itertools::iproduct;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+use std::{error::Error, f64::consts::PI, fmt};
 
 /// One matrix per side, which shuffles the a, b, and c component to their corresponding position.
-const SIDE_MATRICES: [DMat3; 6] = [
+///
+/// For the st coordinate of a side, the unit cube position is `(a, b, c) = (1, u(s), v(t))`, where `u` and `v`
+/// are given by the algebraic sigmoid with the parameter [`C_SQR`](super::C_SQR).
+/// The matrix of the side maps this position into the local space of the unit sphere, which is then normalized.
+/// The sides are indexed like in the shaders: 0 is -x, 1 is +z, 2 is +y, 3 is +x, 4 is -z and 5 is -y.
+pub const SIDE_MATRICES: [DMat3; 6] = [
     DMat3::from_cols_array(&[-1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0]),
     DMat3::from_cols_array(&[0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0, 0.0]),
     DMat3::from_cols_array(&[0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]),
@@ -20,7 +29,13 @@ const SIDE_MATRICES: [DMat3; 6] = [
     DMat3::from_cols_array(&[0.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0]),
 ];
 
-#[derive(Clone)]
+/// The minimal distance of the view st coordinate to the edges of a cube side.
+/// At the exact edges and corners of the cube sphere (e.g. directly above a pole) the derivatives
+/// of the projection are degenerate, which would result in NaN coefficients of the Taylor series.
+pub(crate) const ST_EPSILON: f64 = 1e-9;
+
+#[derive(Clone, Reflect)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum TerrainKind {
     PLANAR {
         side_length: f64,
@@ -29,19 +44,76 @@ pub enum TerrainKind {
         radius: f64,
     },
     ELLIPSOIDAL {
+        /// Derived from the translation and rotation of the model, so it is rebuilt on deserialization.
+        #[cfg_attr(feature = "serialize", serde(skip))]
         ellipsoid_from_world: DMat4,
         major_axis: f64,
         minor_axis: f64,
     },
 }
 
-// Todo: keep in sync with terrain transform, make this authoritative?
+// The ellipsoid transform is derived from the translation and rotation of the model.
+impl PartialEq for TerrainKind {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                TerrainKind::PLANAR { side_length },
+                TerrainKind::PLANAR {
+                    side_length: other_side_length,
+                },
+            ) => side_length == other_side_length,
+            (
+                TerrainKind::SPHERICAL { radius },
+                TerrainKind::SPHERICAL {
+                    radius: other_radius,
+                },
+            ) => radius == other_radius,
+            (
+                TerrainKind::ELLIPSOIDAL {
+                    major_axis,
+                    minor_axis,
+                    ..
+                },
+                TerrainKind::ELLIPSOIDAL {
+                    major_axis: other_major_axis,
+                    minor_axis: other_minor_axis,
+                    ..
+                },
+            ) => major_axis == other_major_axis && minor_axis == other_minor_axis,
+            _ => false,
+        }
+    }
+}
+
+/// The point of a planar terrain, which is placed at the position of the model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlanarOrigin {
+    /// The center of the terrain, with the uv coordinate (0.5, 0.5).
+    #[default]
+    Center,
+    /// The corner of the terrain with the minimal x and z coordinate, with the uv coordinate (0.0, 0.0).
+    Corner,
+}
 
-#[derive(Clone)]
+/// The placement and shape of a terrain.
+///
+/// With the `serialize` feature, the model can be serialized (e.g. as part of a scene), where the matrices derived
+/// from its transform are omitted and rebuilt on deserialization.
+#[derive(Clone, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(from = "SerializedTerrainModel", into = "SerializedTerrainModel"),
+    reflect(Serialize, Deserialize)
+)]
 pub struct TerrainModel {
     pub(crate) kind: TerrainKind,
     pub(crate) min_height: f32,
     pub(crate) max_height: f32,
+    /// The curvature of planar models, see [`TerrainModel::with_curvature`].
+    pub(crate) curvature: f64,
+    /// One bit per side, which is set if the side is refined and drawn, see [`TerrainModel::with_sides`].
+    pub(crate) side_mask: u32,
     translation: DVec3,
     scale: DVec3,
     rotation: DQuat,
@@ -49,8 +121,119 @@ pub struct TerrainModel {
     local_from_world: DMat4,
 }
 
+/// Compares the kind, height range, curvature, translation, scale and rotation of the models.
+///
+/// The matrices derived from them are excluded, which makes this a cheap test for detecting changes of the model.
+impl PartialEq for TerrainModel {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.min_height == other.min_height
+            && self.max_height == other.max_height
+            && self.curvature == other.curvature
+            && self.side_mask == other.side_mask
+            && self.translation == other.translation
+            && self.scale == other.scale
+            && self.rotation == other.rotation
+    }
+}
+
+/// The serialized representation of a [`TerrainModel`], which excludes the derived matrices.
+#[cfg(feature = "serialize")]
+#[derive(Serialize, Deserialize)]
+struct SerializedTerrainModel {
+    kind: TerrainKind,
+    min_height: f32,
+    max_height: f32,
+    curvature: f64,
+    side_mask: u32,
+    translation: DVec3,
+    scale: DVec3,
+    rotation: DQuat,
+}
+
+#[cfg(feature = "serialize")]
+impl From<TerrainModel> for SerializedTerrainModel {
+    fn from(model: TerrainModel) -> Self {
+        Self {
+            kind: model.kind,
+            min_height: model.min_height,
+            max_height: model.max_height,
+            curvature: model.curvature,
+            side_mask: model.side_mask,
+            translation: model.translation,
+            scale: model.scale,
+            rotation: model.rotation,
+        }
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl From<SerializedTerrainModel> for TerrainModel {
+    fn from(model: SerializedTerrainModel) -> Self {
+        let kind = match model.kind {
+            TerrainKind::ELLIPSOIDAL {
+                major_axis,
+                minor_axis,
+                ..
+            } => TerrainKind::ELLIPSOIDAL {
+                ellipsoid_from_world: DMat4::from_rotation_translation(
+                    model.rotation,
+                    model.translation,
+                )
+                .inverse(),
+                major_axis,
+                minor_axis,
+            },
+            kind => kind,
+        };
+
+        Self::from_scale_rotation_translation(
+            model.scale,
+            model.rotation,
+            model.translation,
+            model.min_height,
+            model.max_height,
+            kind,
+        )
+        .with_curvature(model.curvature)
+        .with_side_mask(model.side_mask)
+    }
+}
+
+/// The reason, why a [`TerrainModel`] could not be derived, see [`TerrainModel::with_sides`] and [`TerrainModel::lerp`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TerrainModelError {
+    /// The sides of a planar model were restricted.
+    PlanarSides,
+    /// The sides are empty or contain an index larger than five.
+    InvalidSides { sides: Vec<u32> },
+    /// Only one of the interpolated models is spherical.
+    MismatchedKinds,
+}
+
+impl fmt::Display for TerrainModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PlanarSides => write!(f, "only the sides of spherical models can be restricted"),
+            Self::InvalidSides { sides } => write!(
+                f,
+                "the sides {sides:?} have to be a non-empty subset of the six sides"
+            ),
+            Self::MismatchedKinds => write!(
+                f,
+                "only terrain models, which are both planar or both spherical, can be interpolated"
+            ),
+        }
+    }
+}
+
+impl Error for TerrainModelError {}
+
 impl TerrainModel {
-    pub(crate) fn is_spherical(&self) -> bool {
+    /// Whether the model is a sphere or an ellipsoid, which selects the `SPHERICAL` shader def of its terrain.
+    ///
+    /// The shader def is part of the pipeline keys, so planar and spherical terrains can be rendered side by side.
+    pub fn is_spherical(&self) -> bool {
         match self.kind {
             TerrainKind::PLANAR { .. } => false,
             TerrainKind::SPHERICAL { .. } => true,
@@ -68,11 +251,17 @@ impl TerrainModel {
     ) -> Self {
         let world_from_local = DMat4::from_scale_rotation_translation(scale, rotation, translation);
         let local_from_world = world_from_local.inverse();
+        let side_mask = match kind {
+            TerrainKind::PLANAR { .. } => 0b1,
+            _ => 0b111111,
+        };
 
         Self {
             kind,
             min_height,
             max_height,
+            curvature: 0.0,
+            side_mask,
             translation,
             scale,
             rotation,
@@ -81,17 +270,75 @@ impl TerrainModel {
         }
     }
 
+    /// Creates a planar model, which is centered at the position.
+    ///
+    /// The uv coordinate (0.5, 0.5) corresponds to the position, while u and v increase along the x and z axis.
     pub fn planar(position: DVec3, side_length: f64, min_height: f32, max_height: f32) -> Self {
+        Self::planar_with_origin(
+            position,
+            PlanarOrigin::Center,
+            side_length,
+            min_height,
+            max_height,
+        )
+    }
+
+    /// Creates a planar model, whose origin is placed at the position.
+    pub fn planar_with_origin(
+        position: DVec3,
+        origin: PlanarOrigin,
+        side_length: f64,
+        min_height: f32,
+        max_height: f32,
+    ) -> Self {
+        let center = match origin {
+            PlanarOrigin::Center => position,
+            PlanarOrigin::Corner => position + DVec3::new(0.5, 0.0, 0.5) * side_length,
+        };
+
         Self::from_scale_rotation_translation(
             DVec3::splat(side_length),
             DQuat::IDENTITY,
-            position,
+            center,
             min_height,
             max_height,
             TerrainKind::PLANAR { side_length },
         )
     }
 
+    /// Bends a planar model downwards around its center, to transition from a flat local terrain to a curved planet.
+    ///
+    /// A curvature of zero keeps the model planar, while a curvature of one approximates the curvature
+    /// of a sphere with the side length as its radius. The local position `(x, z)` is lowered by `curvature * (x² + z²) / 2`.
+    /// This displacement is quadratic in the st coordinate, so the Taylor series of the [`TerrainModelApproximation`]
+    /// stays exact and continuous for any curvature. Spherical models ignore the curvature.
+    pub fn with_curvature(mut self, curvature: f64) -> Self {
+        self.curvature = curvature;
+        self
+    }
+
+    /// Restricts a spherical model to a subset of its sides, e.g. for a map covering a single continent.
+    ///
+    /// Only the active sides are refined, drawn and streamed, while the others are skipped by
+    /// the tiling prepass, the tile trees and the [`TerrainModelApproximation`].
+    /// The side indices are the ones of [`SIDE_MATRICES`] and stay unchanged, so the data of all sides can still be preprocessed.
+    ///
+    /// # Errors
+    /// Fails, if the model is planar, no side is given or a side index is larger than five.
+    pub fn with_sides(mut self, sides: &[u32]) -> Result<Self, TerrainModelError> {
+        if !self.is_spherical() {
+            return Err(TerrainModelError::PlanarSides);
+        }
+        if sides.is_empty() || sides.iter().any(|&side| side >= 6) {
+            return Err(TerrainModelError::InvalidSides {
+                sides: sides.to_vec(),
+            });
+        }
+
+        self.side_mask = sides.iter().fold(0, |mask, side| mask | 1 << side);
+        Ok(self)
+    }
+
     pub fn sphere(position: DVec3, radius: f64, min_height: f32, max_height: f32) -> Self {
         Self::from_scale_rotation_translation(
             DVec3::splat(radius),
@@ -103,6 +350,53 @@ impl TerrainModel {
         )
     }
 
+    /// Creates a planar or spherical model from an arbitrary affine transform.
+    ///
+    /// The transform is decomposed into its scale, rotation and translation.
+    /// Since terrain models only support uniform scaling, non-uniform scales are normalized
+    /// to their average and shears are discarded, both with a warning.
+    pub fn from_affine(
+        affine: DAffine3,
+        min_height: f32,
+        max_height: f32,
+        spherical: bool,
+    ) -> Self {
+        let (scale, rotation, translation) = affine.to_scale_rotation_translation();
+
+        let uniform_scale = (scale.x + scale.y + scale.z) / 3.0;
+
+        if !scale.abs_diff_eq(DVec3::splat(uniform_scale), 1e-6 * uniform_scale) {
+            warn!("The terrain model only supports uniform scales, {scale} was normalized to {uniform_scale}.");
+        }
+
+        let decomposed = DAffine3::from_scale_rotation_translation(scale, rotation, translation);
+
+        if !decomposed.abs_diff_eq(affine, 1e-6 * uniform_scale) {
+            warn!(
+                "The terrain model does not support sheared transforms, the shear was discarded."
+            );
+        }
+
+        let kind = if spherical {
+            TerrainKind::SPHERICAL {
+                radius: uniform_scale,
+            }
+        } else {
+            TerrainKind::PLANAR {
+                side_length: uniform_scale,
+            }
+        };
+
+        Self::from_scale_rotation_translation(
+            DVec3::splat(uniform_scale),
+            rotation,
+            translation,
+            min_height,
+            max_height,
+            kind,
+        )
+    }
+
     pub fn ellipsoid(
         position: DVec3,
         major_axis: f64,
@@ -127,20 +421,167 @@ impl TerrainModel {
         )
     }
 
+    /// Interpolates between the two models, e.g. for animated transitions.
+    ///
+    /// The translation, scale, height range, curvature and the dimensions of the kind are interpolated linearly,
+    /// while the rotation is interpolated spherically. The matrices are rebuilt from the interpolated values.
+    /// Spheres and ellipsoids can be interpolated with each other, which results in an ellipsoid.
+    ///
+    /// # Errors
+    /// Fails, if only one of the models is spherical.
+    pub fn lerp(&self, other: &TerrainModel, t: f32) -> Result<TerrainModel, TerrainModelError> {
+        if self.is_spherical() != other.is_spherical() {
+            return Err(TerrainModelError::MismatchedKinds);
+        }
+
+        let t_f64 = t as f64;
+        let translation = self.translation.lerp(other.translation, t_f64);
+        let rotation = self.rotation.slerp(other.rotation, t_f64);
+
+        let axes = |kind: &TerrainKind| match *kind {
+            TerrainKind::PLANAR { side_length } => (side_length, side_length),
+            TerrainKind::SPHERICAL { radius } => (radius, radius),
+            TerrainKind::ELLIPSOIDAL {
+                major_axis,
+                minor_axis,
+                ..
+            } => (major_axis, minor_axis),
+        };
+        let (major_axis, minor_axis) = axes(&self.kind);
+        let (other_major_axis, other_minor_axis) = axes(&other.kind);
+        let major_axis = major_axis.lerp(other_major_axis, t_f64);
+        let minor_axis = minor_axis.lerp(other_minor_axis, t_f64);
+
+        let kind = match (&self.kind, &other.kind) {
+            (TerrainKind::PLANAR { .. }, _) => TerrainKind::PLANAR {
+                side_length: major_axis,
+            },
+            (TerrainKind::SPHERICAL { .. }, TerrainKind::SPHERICAL { .. }) => {
+                TerrainKind::SPHERICAL { radius: major_axis }
+            }
+            _ => TerrainKind::ELLIPSOIDAL {
+                ellipsoid_from_world: DMat4::from_rotation_translation(rotation, translation)
+                    .inverse(),
+                major_axis,
+                minor_axis,
+            },
+        };
+
+        Ok(Self::from_scale_rotation_translation(
+            self.scale.lerp(other.scale, t_f64),
+            rotation,
+            translation,
+            self.min_height.lerp(other.min_height, t),
+            self.max_height.lerp(other.max_height, t),
+            kind,
+        )
+        .with_curvature(self.curvature.lerp(other.curvature, t_f64))
+        .with_side_mask(self.side_mask | other.side_mask))
+    }
+
+    fn with_side_mask(mut self, side_mask: u32) -> Self {
+        self.side_mask = side_mask;
+        self
+    }
+
+    /// Moves the model to the translation, rotation and scale of the terrain entity and rebuilds its matrices.
+    ///
+    /// Planar and spherical models only support uniform scales, so non-uniform scales are mapped
+    /// onto their average side length or radius with a warning. Ellipsoids take their major axis
+    /// from the average of the x and z scale and their minor axis from the y scale.
+    pub(crate) fn set_transform(&mut self, translation: DVec3, rotation: DQuat, scale: DVec3) {
+        let uniform_scale = (scale.x + scale.y + scale.z) / 3.0;
+
+        let (scale, kind) = match self.kind {
+            TerrainKind::PLANAR { .. } | TerrainKind::SPHERICAL { .. } => {
+                if !scale.abs_diff_eq(DVec3::splat(uniform_scale), 1e-6 * uniform_scale) {
+                    warn!("The terrain model only supports uniform scales, {scale} was normalized to {uniform_scale}.");
+                }
+
+                let kind = if self.is_spherical() {
+                    TerrainKind::SPHERICAL {
+                        radius: uniform_scale,
+                    }
+                } else {
+                    TerrainKind::PLANAR {
+                        side_length: uniform_scale,
+                    }
+                };
+
+                (DVec3::splat(uniform_scale), kind)
+            }
+            TerrainKind::ELLIPSOIDAL { .. } => {
+                let major_axis = (scale.x + scale.z) / 2.0;
+                let minor_axis = scale.y;
+
+                if (scale.x - scale.z).abs() > 1e-6 * major_axis {
+                    warn!("The ellipsoid only supports equal x and z scales, {scale} was normalized to a major axis of {major_axis}.");
+                }
+
+                let kind = TerrainKind::ELLIPSOIDAL {
+                    ellipsoid_from_world: DMat4::from_rotation_translation(rotation, translation)
+                        .inverse(),
+                    major_axis,
+                    minor_axis,
+                };
+
+                (DVec3::new(major_axis, minor_axis, major_axis), kind)
+            }
+        };
+
+        *self = Self::from_scale_rotation_translation(
+            scale,
+            rotation,
+            translation,
+            self.min_height,
+            self.max_height,
+            kind,
+        )
+        .with_curvature(self.curvature)
+        .with_side_mask(self.side_mask);
+    }
+
     pub(crate) fn position_local_to_world(&self, local_position: DVec3, height: f64) -> DVec3 {
+        let world_normal = self.normal_local_to_world(local_position);
+
+        let local_position = if self.is_spherical() {
+            local_position
+        } else {
+            let k = self.curvature;
+            let DVec3 { x, z, .. } = local_position;
+
+            DVec3::new(x, -0.5 * k * (x * x + z * z), z)
+        };
+
         let world_position = self.world_from_local.transform_point3(local_position);
-        let world_normal = self
-            .world_from_local
-            .transform_vector3(if self.is_spherical() {
-                local_position
-            } else {
-                DVec3::Y
-            })
-            .normalize();
 
         world_position + height * world_normal
     }
 
+    /// Computes the world normal of the surface at the local position, like `normal_local_to_world` in the shader.
+    ///
+    /// The local normal is the gradient of the implicit equation of the unit sphere or the curved plane.
+    /// It is transformed with the inverse transpose of the model matrix, which keeps it perpendicular to the surface,
+    /// even if the model is scaled non-uniformly, like ellipsoids are.
+    pub(crate) fn normal_local_to_world(&self, local_position: DVec3) -> DVec3 {
+        let local_normal = if self.is_spherical() {
+            local_position
+        } else {
+            let k = self.curvature;
+
+            DVec3::new(k * local_position.x, 1.0, k * local_position.z)
+        };
+
+        self.local_from_world
+            .transpose()
+            .transform_vector3(local_normal)
+            .normalize()
+    }
+
+    /// Projects the world position onto the reference surface and returns its local position.
+    ///
+    /// Planar positions are projected along the y axis and spherical ones radially. Ellipsoidal positions are projected
+    /// onto the closest point of the ellipsoid, so that [`Self::normal_local_to_world`] at the result points back towards them.
     pub(crate) fn position_world_to_local(&self, world_position: DVec3) -> DVec3 {
         match self.kind {
             TerrainKind::PLANAR { .. } => {
@@ -161,9 +602,9 @@ impl TerrainModel {
                     DVec3::new(major_axis, major_axis, minor_axis),
                     ellipsoid_position,
                 );
-                self.local_from_world
-                    .transform_point3(surface_position)
-                    .normalize()
+
+                // the surface position is relative to the center of the ellipsoid, so only its scale remains
+                (surface_position / self.scale).normalize()
             }
         }
     }
@@ -172,6 +613,192 @@ impl TerrainModel {
         self.position_local_to_world(self.position_world_to_local(world_position), height)
     }
 
+    /// Tests whether the world position lies inside the shell of the terrain, spanned by its minimum and maximum height.
+    ///
+    /// For planar terrains the position additionally has to lie above or below the extent of the plane.
+    /// This is a cheap conservative test, which ignores the height scale of the terrain.
+    pub fn contains(&self, world_position: DVec3) -> bool {
+        let local_position = self.position_world_to_local(world_position);
+
+        if let TerrainKind::PLANAR { .. } = self.kind {
+            let st = local_position.xz() + 0.5;
+
+            if st.cmplt(DVec2::ZERO).any() || st.cmpgt(DVec2::ONE).any() {
+                return false;
+            }
+        }
+
+        let surface_position = self.position_local_to_world(local_position, 0.0);
+        let surface_normal = self.position_local_to_world(local_position, 1.0) - surface_position;
+        let height = (world_position - surface_position).dot(surface_normal);
+
+        (self.min_height as f64..=self.max_height as f64).contains(&height)
+    }
+
+    /// The horizontal distance of the world position to the boundary of the terrain, which is negative outside of it.
+    ///
+    /// Planar terrains are bounded by the edges of their square, while spherical terrains cover every direction,
+    /// so their distance is infinite.
+    pub fn boundary_distance(&self, world_position: DVec3) -> f64 {
+        match self.kind {
+            TerrainKind::PLANAR { .. } => {
+                let local_position = self.position_world_to_local(world_position);
+                let distance = (0.5 - local_position.xz().abs()) * self.scale.xz();
+
+                distance.min_element()
+            }
+            TerrainKind::SPHERICAL { .. } | TerrainKind::ELLIPSOIDAL { .. } => f64::INFINITY,
+        }
+    }
+
+    /// Determines the sides of the terrain, which the ray may hit first, to prune a raycast to them instead of all six.
+    ///
+    /// The ray is intersected with the spheres bounding the shell of the terrain, spanned by its minimum and maximum height.
+    /// The candidates are the side, where the ray enters the outer sphere, and the side, where it hits the inner sphere
+    /// (or passes it most closely), in the order the ray reaches them and without duplicates.
+    /// Close to the corners of the cube, the ray may cross a third side between both points,
+    /// so a raycast, which misses the candidates, should fall back to the remaining sides.
+    /// A ray, which misses the terrain, has no candidates, while planar terrains only consist of side zero.
+    pub fn ray_sides(&self, ray_origin: DVec3, ray_direction: DVec3) -> Vec<u32> {
+        let (outer_radius, inner_radius) = match self.kind {
+            TerrainKind::PLANAR { .. } => return vec![0],
+            TerrainKind::SPHERICAL { radius } => (radius, radius),
+            TerrainKind::ELLIPSOIDAL {
+                major_axis,
+                minor_axis,
+                ..
+            } => (major_axis, minor_axis),
+        };
+        let outer_radius = outer_radius + self.max_height as f64;
+        let inner_radius = inner_radius + self.min_height as f64;
+
+        let direction = ray_direction.normalize();
+        let offset = ray_origin - self.translation;
+        let b = offset.dot(direction);
+
+        // the distances along the ray, at which it enters and exits the sphere
+        let intersect = |radius: f64| {
+            let discriminant = b * b - (offset.length_squared() - radius * radius);
+            (discriminant >= 0.0).then(|| {
+                let root = discriminant.sqrt();
+                (-b - root, -b + root)
+            })
+        };
+
+        let Some((outer_entry, outer_exit)) = intersect(outer_radius) else {
+            return Vec::new();
+        };
+
+        if outer_exit < 0.0 {
+            return Vec::new();
+        }
+
+        let entry = outer_entry.max(0.0);
+        let hit = match intersect(inner_radius) {
+            Some((inner_entry, _)) if inner_entry >= 0.0 => inner_entry,
+            _ => (-b).clamp(entry, outer_exit),
+        };
+
+        let side = |t: f64| Coordinate::from_world_position(ray_origin + t * direction, self).side;
+
+        let mut sides = vec![side(entry)];
+
+        if side(hit) != sides[0] {
+            sides.push(side(hit));
+        }
+
+        sides
+    }
+
+    /// Computes the world space bounding box of the surface of the tile, displaced by any height in the height range of the model.
+    ///
+    /// Returns the minimum and maximum corner, e.g. to place objects per tile in editors or to draw streaming overlays.
+    /// The surface is sampled on a grid at the minimum and maximum height, which is padded by the distance,
+    /// by which the curved surface can bulge out between adjacent samples, so that the bounds are conservative.
+    pub fn tile_aabb(&self, tile: TileCoordinate) -> (DVec3, DVec3) {
+        const SAMPLE_COUNT: u32 = 8;
+
+        let tile_count = TileCoordinate::count(tile.lod) as f64;
+        let tile_xy = UVec2::new(tile.x, tile.y).as_dvec2();
+        let vertices_per_row = SAMPLE_COUNT as usize + 1;
+
+        let (mut min, mut max) = (DVec3::splat(f64::MAX), DVec3::splat(f64::MIN));
+        let mut max_spacing: f64 = 0.0;
+
+        for height in [self.min_height, self.max_height] {
+            let positions = iproduct!(0..=SAMPLE_COUNT, 0..=SAMPLE_COUNT)
+                .map(|(y, x)| {
+                    let uv = UVec2::new(x, y).as_dvec2() / SAMPLE_COUNT as f64;
+                    Coordinate::new(tile.side, (tile_xy + uv) / tile_count)
+                        .world_position(self, height)
+                })
+                .collect::<Vec<_>>();
+
+            for (index, &position) in positions.iter().enumerate() {
+                min = min.min(position);
+                max = max.max(position);
+
+                // the distances to the right and lower neighbours
+                if index % vertices_per_row + 1 < vertices_per_row {
+                    max_spacing = max_spacing.max(position.distance(positions[index + 1]));
+                }
+                if index + vertices_per_row < positions.len() {
+                    max_spacing =
+                        max_spacing.max(position.distance(positions[index + vertices_per_row]));
+                }
+            }
+        }
+
+        // the smallest radius of curvature of the surface
+        let scale = self.scale_vec();
+        let radius = if self.is_spherical() {
+            scale.min_element().powi(2) / scale.max_element() + self.min_height.min(0.0) as f64
+        } else {
+            scale.x / self.curvature.abs()
+        };
+
+        // the sagitta of an arc with the length of the sample spacing
+        let padding = if radius > 0.0 && radius.is_finite() {
+            max_spacing * max_spacing / (8.0 * radius)
+        } else {
+            0.0
+        };
+
+        (min - padding, max + padding)
+    }
+
+    /// Computes the bounding box of the terrain in the local space of the terrain transform,
+    /// which encloses the surface displaced by any height in the height range.
+    pub(crate) fn aabb(&self, min_height: f32, max_height: f32) -> Aabb {
+        // the heights are measured in world units, while the local space is scaled by the model along each axis
+        let min_height = min_height as f64 / self.scale;
+        let max_height = max_height as f64 / self.scale;
+
+        let (min, max) = if self.is_spherical() {
+            let radius = DVec3::ONE + max_height.max(DVec3::ZERO);
+
+            (-radius, radius)
+        } else {
+            // the corners are lowered the most by the curvature
+            let curvature = self.curvature.max(0.0);
+            let curvature_drop = 0.25 * curvature;
+            // the curved normals tilt outwards, so the heights displace the edges beyond the reference surface
+            let spread = 0.5 * curvature * min_height.abs().max(max_height.abs());
+
+            (
+                DVec3::new(
+                    -0.5 - spread.x,
+                    min_height.y.min(0.0) - curvature_drop,
+                    -0.5 - spread.z,
+                ),
+                DVec3::new(0.5 + spread.x, max_height.y.max(0.0), 0.5 + spread.z),
+            )
+        };
+
+        Aabb::from_min_max(min.as_vec3(), max.as_vec3())
+    }
+
+    /// The count of side indices of the model, including the inactive sides, see [`TerrainModel::with_sides`].
     pub(crate) fn side_count(&self) -> u32 {
         if self.is_spherical() {
             6
@@ -180,6 +807,72 @@ impl TerrainModel {
         }
     }
 
+    /// Whether the side is refined and drawn, see [`TerrainModel::with_sides`].
+    pub fn is_side_active(&self, side: u32) -> bool {
+        self.side_mask & (1 << side) != 0
+    }
+
+    /// Iterates over the indices of the active sides, see [`TerrainModel::with_sides`].
+    pub fn active_sides(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.side_count()).filter(|&side| self.is_side_active(side))
+    }
+
+    /// The per-axis scale of the model transform, e.g. the major axis along x and z and the minor axis along y for ellipsoids.
+    ///
+    /// Use this instead of the scalar scale of the model, whenever the extent along a specific axis is required.
+    pub fn scale_vec(&self) -> DVec3 {
+        self.scale
+    }
+
+    /// The translation of the model transform, i.e. the center of spherical models.
+    pub fn translation(&self) -> DVec3 {
+        self.translation
+    }
+
+    /// The rotation of the model transform.
+    pub fn rotation(&self) -> DQuat {
+        self.rotation
+    }
+
+    /// The minimum and maximum height of the model.
+    pub fn height_range(&self) -> (f32, f32) {
+        (self.min_height, self.max_height)
+    }
+
+    /// The area of the reference surface of the model, i.e. the square of planar models or the surface of spherical ones.
+    ///
+    /// The area is computed analytically from the scale of the model, so it ignores the heights,
+    /// the curvature of planar models and the active sides (see [`TerrainModel::with_sides`]).
+    /// Ellipsoids are treated as spheroids with the major axis along x and z and the minor axis along y.
+    pub fn surface_area(&self) -> f64 {
+        if !self.is_spherical() {
+            return self.scale.x * self.scale.z;
+        }
+
+        let (a, c) = (self.scale.x, self.scale.y);
+
+        if a == c {
+            4.0 * PI * a * a
+        } else if c < a {
+            // oblate spheroid
+            let e = (1.0 - (c * c) / (a * a)).sqrt();
+            2.0 * PI * a * a * (1.0 + (1.0 - e * e) / e * e.atanh())
+        } else {
+            // prolate spheroid
+            let e = (1.0 - (a * a) / (c * c)).sqrt();
+            2.0 * PI * a * a * (1.0 + c / (a * e) * e.asin())
+        }
+    }
+
+    /// The volume enclosed by the reference surface of spherical models, or `None` for planar ones.
+    ///
+    /// Like [`TerrainModel::surface_area`], this ignores the heights of the terrain.
+    pub fn volume(&self) -> Option<f64> {
+        self.is_spherical()
+            .then(|| 4.0 / 3.0 * PI * self.scale.x * self.scale.y * self.scale.z)
+    }
+
+    /// The average scale of the model, which is used to scale distances independent of the direction.
     pub(crate) fn scale(&self) -> f64 {
         match self.kind {
             TerrainKind::PLANAR { side_length } => side_length / 2.0,
@@ -201,6 +894,12 @@ impl TerrainModel {
         }
     }
 
+    /// Converts the model transform into a grid cell and a single precision transform relative to it.
+    ///
+    /// Only the translation has to be split, since the grid cell is selected by the model origin (e.g. the planet center).
+    /// The scale and rotation are independent of the position and thus keep their relative single precision.
+    /// Surface positions close to the view do not depend on this transform, but are computed
+    /// using the double precision [`TerrainModelApproximation`] instead.
     #[cfg(feature = "high_precision")]
     pub(crate) fn grid_transform(
         &self,
@@ -225,27 +924,43 @@ impl TerrainModel {
 /// The idea behind the approximation, is to map from st coordinates relative to the view, to world positions relative to the view.
 /// Therefore, we identify a origin tile with sufficiently high lod (origin LOD), that serves as a reference, to which we can compute our relative coordinate using partly integer math.
 #[derive(Copy, Clone, Debug, Default, ShaderType)]
-pub(crate) struct SideParameter {
+pub struct SideParameter {
     /// The tile index of the origin tile projected to this side.
-    pub(crate) origin_xy: IVec2,
+    pub origin_xy: IVec2,
     /// The offset between the view st coordinate and the origin st coordinate.
     /// This can be used to translate from st coordinates relative to the origin tile to st coordinates relative to the view coordinate in the shader.
-    pub(crate) origin_uv: Vec2,
+    pub origin_uv: Vec2,
     /// The constant coefficient of the series.
     /// Describes the offset between the location vertically under view and the view position.
-    pub(crate) c: Vec3,
+    pub c: Vec3,
     /// The linear coefficient of the series with respect to s.
-    pub(crate) c_s: Vec3,
+    pub c_s: Vec3,
     /// The linear coefficient of the series with respect to t.
-    pub(crate) c_t: Vec3,
+    pub c_t: Vec3,
     /// The quadratic coefficient of the series with respect to s and s.
     /// This value is pre-multiplied with 0.5.
-    pub(crate) c_ss: Vec3,
+    pub c_ss: Vec3,
     /// The quadratic coefficient of the series with respect to s and t.
-    pub(crate) c_st: Vec3,
+    pub c_st: Vec3,
     /// The quadratic coefficient of the series with respect to t and t.
     /// This value is pre-multiplied with 0.5.
-    pub(crate) c_tt: Vec3,
+    pub c_tt: Vec3,
+}
+
+impl SideParameter {
+    /// Evaluates the second order Taylor series at the st coordinate relative to the view.
+    ///
+    /// This matches `evaluate_taylor_series` in the shader operation by operation, so both yield the same f32 result.
+    pub fn evaluate(&self, relative_st: Vec2) -> Vec3 {
+        let Vec2 { x: s, y: t } = relative_st;
+
+        self.c
+            + self.c_s * s
+            + self.c_t * t
+            + self.c_ss * s * s
+            + self.c_st * s * t
+            + self.c_tt * t * t
+    }
 }
 
 #[derive(Clone, Debug, Default, ShaderType)]
@@ -259,12 +974,46 @@ pub struct TerrainModelApproximation {
 }
 
 impl TerrainModelApproximation {
+    /// The lod of the origin tile, relative to which the st coordinates are measured.
+    pub fn origin_lod(&self) -> u32 {
+        self.origin_lod
+    }
+
+    /// The approximate height of the terrain below the view.
+    pub fn approximate_height(&self) -> f32 {
+        self.approximate_height
+    }
+
+    /// The parameters of the six cube sphere faces.
+    pub fn sides(&self) -> &[SideParameter; 6] {
+        &self.sides
+    }
+
+    /// Evaluates the Taylor series of the side at the st coordinate relative to the view.
+    /// The result is the world position relative to the view, identical to `compute_relative_position` in the shader.
+    pub fn reconstruct_position(&self, side: u32, relative_st: Vec2) -> Vec3 {
+        self.sides[side as usize].evaluate(relative_st)
+    }
+
     /// Computes the view parameters based on the it's world position.
+    ///
+    /// The st coordinate of the view on each side is clamped by [`ST_EPSILON`] away from the edges of the side.
+    /// Thus, views positioned directly over a pole or cube corner use the location marginally inside the side as their origin.
+    ///
+    /// The origin tiles of the previous approximation are kept within the origin hysteresis of the tile tree.
     pub(crate) fn compute(
         tile_tree: &TileTree,
         tile_atlas: &TileAtlas,
+        previous: Option<&TerrainModelApproximation>,
     ) -> TerrainModelApproximation {
-        let origin_count = TileCoordinate::count(tile_tree.origin_lod) as f64;
+        // Sample the resident tile under the view directly, so that the height does not lag behind the view.
+        // Fall back to the cached height of the tile tree, while no tile has been loaded yet.
+        let approximate_height = tile_tree
+            .pinned_approximate_height
+            .or_else(|| {
+                sample_resident_height(tile_tree, tile_atlas, tile_tree.view_world_position)
+            })
+            .unwrap_or(tile_tree.approximate_height);
 
         // Coordinate of the location vertically below the view.
         let view_coordinate =
@@ -284,10 +1033,58 @@ impl TerrainModelApproximation {
 
         let mut sides = [SideParameter::default(); 6];
 
+        if !tile_atlas.model.is_spherical() {
+            // The planar position p(s,t) = (s - 0.5, -k/2 ((s - 0.5)^2 + (t - 0.5)^2), t - 0.5) is quadratic,
+            // thus its Taylor series is exact.
+            // Without curvature, c_s and c_t are the (scaled and rotated) x and z axes of the model,
+            // which are constant across the plane, and the quadratic coefficients vanish.
+            let (view_xy, view_uv) =
+                Self::origin_tile_coords(view_coordinate, tile_tree, previous.map(|p| &p.sides[0]));
+
+            let k = tile_atlas.model.curvature;
+            let DVec2 { x: s, y: t } = view_coordinate.uv - 0.5;
+
+            let m = tile_atlas.model.world_from_local;
+            let p = m.transform_point3(DVec3::new(s, -0.5 * k * (s * s + t * t), t));
+            let p_ds = m.transform_vector3(DVec3::new(1.0, -k * s, 0.0));
+            let p_dt = m.transform_vector3(DVec3::new(0.0, -k * t, 1.0));
+            let p_dss = m.transform_vector3(DVec3::new(0.0, -k, 0.0));
+            let p_dtt = m.transform_vector3(DVec3::new(0.0, -k, 0.0));
+
+            sides[0] = SideParameter {
+                origin_xy: view_xy,
+                origin_uv: view_uv,
+                c: (p - tile_tree.view_world_position).as_vec3(),
+                c_s: p_ds.as_vec3(),
+                c_t: p_dt.as_vec3(),
+                c_ss: (p_dss / 2.0).as_vec3(),
+                c_st: Vec3::ZERO,
+                c_tt: (p_dtt / 2.0).as_vec3(),
+            };
+
+            return TerrainModelApproximation {
+                origin_lod: tile_tree.origin_lod,
+                approximate_height,
+                sides,
+            };
+        }
+
         for (side, &sm) in SIDE_MATRICES.iter().enumerate() {
-            let view_coordinate = view_coordinate.project_to_side(side as u32, &tile_atlas.model);
-            let view_xy = (view_coordinate.uv * origin_count).as_ivec2();
-            let view_uv = (view_coordinate.uv * origin_count).fract().as_vec2();
+            if !tile_atlas.model.is_side_active(side as u32) {
+                continue;
+            }
+
+            let mut view_coordinate =
+                view_coordinate.project_to_side(side as u32, &tile_atlas.model);
+            view_coordinate.uv = view_coordinate
+                .uv
+                .clamp(DVec2::splat(ST_EPSILON), DVec2::splat(1.0 - ST_EPSILON));
+
+            let (view_xy, view_uv) = Self::origin_tile_coords(
+                view_coordinate,
+                tile_tree,
+                previous.map(|p| &p.sides[side]),
+            );
 
             let DVec2 { x: s, y: t } = view_coordinate.uv;
 
@@ -343,6 +1140,8 @@ impl TerrainModelApproximation {
             sides[side] = SideParameter {
                 origin_xy: view_xy,
                 origin_uv: view_uv,
+                // Both positions may be huge for planets far from the origin, so they are subtracted
+                // in double precision and only the small relative offset is cast to single precision.
                 c: (p - tile_tree.view_world_position).as_vec3(),
                 c_s: p_ds.as_vec3(),
                 c_t: p_dt.as_vec3(),
@@ -354,23 +1153,636 @@ impl TerrainModelApproximation {
 
         TerrainModelApproximation {
             origin_lod: tile_tree.origin_lod,
-            approximate_height: tile_tree.approximate_height,
+            approximate_height,
             sides,
         }
     }
+
+    /// Computes the origin tile under the view coordinate and the uv of the view inside it.
+    ///
+    /// The previous origin tile is kept, while the view lies within the hysteresis around it,
+    /// in which case the uv lies slightly outside of the unit square.
+    fn origin_tile_coords(
+        view_coordinate: Coordinate,
+        tile_tree: &TileTree,
+        previous: Option<&SideParameter>,
+    ) -> (IVec2, Vec2) {
+        let (_, view_xy, view_uv) = view_coordinate.tile_coords(tile_tree.origin_lod);
+
+        if let Some(previous) = previous {
+            let uv = (view_xy - previous.origin_xy).as_dvec2() + view_uv.as_dvec2();
+            let hysteresis = tile_tree.origin_hysteresis;
+
+            if uv.cmpge(DVec2::splat(-hysteresis)).all()
+                && uv.cmple(DVec2::splat(1.0 + hysteresis)).all()
+            {
+                return (previous.origin_xy, uv.as_vec2());
+            }
+        }
+
+        (view_xy, view_uv)
+    }
 }
 
 pub fn generate_terrain_model_approximation(
     tile_trees: Res<TerrainViewComponents<TileTree>>,
     tile_atlases: Query<&TileAtlas>,
     mut terrain_model_approximations: ResMut<TerrainViewComponents<TerrainModelApproximation>>,
+) {
+    // The approximations of all terrain view pairs are independent, so we compute them in parallel.
+    let approximations = ComputeTaskPool::get().scope(|scope| {
+        for (&(terrain, view), tile_tree) in tile_trees.iter() {
+            let tile_atlas = tile_atlases.get(terrain).unwrap();
+            let previous = terrain_model_approximations
+                .get(&(terrain, view))
+                .filter(|previous| previous.origin_lod == tile_tree.origin_lod);
+
+            scope.spawn(async move {
+                (
+                    (terrain, view),
+                    TerrainModelApproximation::compute(tile_tree, tile_atlas, previous),
+                )
+            });
+        }
+    });
+
+    terrain_model_approximations.extend(approximations);
+}
+
+/// Updates the [`Coordinate`] vertically below each view, e.g. to show the st position in a coordinate HUD or minimap.
+///
+/// The coordinates are stored in the [`TerrainViewComponents<Coordinate>`] resource, keyed by the terrain and the view.
+pub fn compute_view_coordinates(
+    tile_trees: Res<TerrainViewComponents<TileTree>>,
+    tile_atlases: Query<&TileAtlas>,
+    mut view_coordinates: ResMut<TerrainViewComponents<Coordinate>>,
 ) {
     for (&(terrain, view), tile_tree) in tile_trees.iter() {
         let tile_atlas = tile_atlases.get(terrain).unwrap();
 
-        terrain_model_approximations.insert(
+        view_coordinates.insert(
             (terrain, view),
-            TerrainModelApproximation::compute(tile_tree, tile_atlas),
+            Coordinate::from_world_position(tile_tree.view_world_position, &tile_atlas.model),
         );
     }
 }
+
+/// The approximate projected edge length in pixels of the grid cells of the tiles selected by the refinement.
+///
+/// The refinement happens on the GPU, so this is estimated from the [`TerrainModelApproximation`]
+/// and the subdivision distance of the view instead. Use it to choose the view config empirically.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TileScreenError {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+/// The projected edge length of the grid cells, relative to the subdivision distance of the view.
+///
+/// A tile is not subdivided, if its view distance lies in [D / 2^(lod + 1), D / 2^lod),
+/// where D is the subdivision distance. Its grid cells have an edge length of side_size / (2^lod * grid_size).
+/// Thus, the ratio between edge length and view distance is independent of the lod.
+fn screen_error_distance_product(
+    tile_tree: &TileTree,
+    tile_atlas: &TileAtlas,
+    approximation: &TerrainModelApproximation,
+    camera: &Camera,
+    projection: &PerspectiveProjection,
+) -> Option<f32> {
+    let viewport_size = camera.physical_viewport_size()?;
+    let focal_length = viewport_size.y as f32 / (2.0 * (projection.fov / 2.0).tan());
+
+    // The size of the side below the view, approximated by the derivatives of the Taylor series.
+    let side =
+        Coordinate::from_world_position(tile_tree.view_world_position, &tile_atlas.model).side;
+    let params = &approximation.sides[side as usize];
+    let side_size = (params.c_s.length() + params.c_t.length()) / 2.0;
+
+    Some(focal_length * side_size / tile_tree.grid_size as f32)
+}
+
+/// Derives the morph and subdivision distances of the views with a target pixel error,
+/// so that the largest grid cells of the selected tiles project to the target edge length.
+pub fn apply_target_pixel_error(
+    mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
+    tile_atlases: Query<&TileAtlas>,
+    terrain_model_approximations: Res<TerrainViewComponents<TerrainModelApproximation>>,
+    views: Query<(&Camera, &Projection)>,
+) {
+    for (&(terrain, view), tile_tree) in tile_trees.iter_mut() {
+        let Some(target_pixel_error) = tile_tree.target_pixel_error else {
+            continue;
+        };
+        let tile_atlas = tile_atlases.get(terrain).unwrap();
+
+        let Some(approximation) = terrain_model_approximations.get(&(terrain, view)) else {
+            continue;
+        };
+        // Todo: support orthographic projections
+        let Ok((camera, Projection::Perspective(projection))) = views.get(view) else {
+            continue;
+        };
+        let Some(product) =
+            screen_error_distance_product(tile_tree, tile_atlas, approximation, camera, projection)
+        else {
+            continue;
+        };
+
+        // the maximum error is twice the minimum error, see `compute_tile_screen_error`
+        let subdivision_distance = 2.0 * product as f64
+            / target_pixel_error.max(f32::EPSILON) as f64
+            / tile_tree.lod_bias_scale();
+
+        // keep the subdivision tolerance, which is the ratio of the subdivision and the morph distance
+        let tolerance = tile_tree.subdivision_distance / tile_tree.morph_distance;
+        tile_tree.morph_distance = subdivision_distance / tolerance;
+        tile_tree.subdivision_distance = subdivision_distance;
+    }
+}
+
+pub fn compute_tile_screen_error(
+    tile_trees: Res<TerrainViewComponents<TileTree>>,
+    tile_atlases: Query<&TileAtlas>,
+    terrain_model_approximations: Res<TerrainViewComponents<TerrainModelApproximation>>,
+    views: Query<(&Camera, &Projection)>,
+    mut tile_screen_errors: ResMut<TerrainViewComponents<TileScreenError>>,
+) {
+    for (&(terrain, view), tile_tree) in tile_trees.iter() {
+        let tile_atlas = tile_atlases.get(terrain).unwrap();
+
+        let Some(approximation) = terrain_model_approximations.get(&(terrain, view)) else {
+            continue;
+        };
+        // Todo: support orthographic projections
+        let Ok((camera, Projection::Perspective(projection))) = views.get(view) else {
+            continue;
+        };
+        let Some(product) =
+            screen_error_distance_product(tile_tree, tile_atlas, approximation, camera, projection)
+        else {
+            continue;
+        };
+
+        let min = product / (tile_tree.subdivision_distance * tile_tree.lod_bias_scale()) as f32;
+
+        tile_screen_errors.insert(
+            (terrain, view),
+            TileScreenError {
+                min,
+                max: 2.0 * min,
+                // the mean of 1 / d for d uniformly distributed in [a, 2a] is ln(2) / a
+                mean: 2.0 * std::f32::consts::LN_2 * min,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{terrain::TerrainConfig, terrain_view::TerrainViewConfig};
+
+    /// Computes the approximation of a single view at the world position, before any tile has been loaded.
+    fn approximate(model: TerrainModel, view_world_position: DVec3) -> TerrainModelApproximation {
+        let tile_atlas = TileAtlas::new(&TerrainConfig { model, ..default() }).unwrap();
+        let mut tile_tree = TileTree::new(&tile_atlas, &TerrainViewConfig::default()).unwrap();
+        tile_tree.view_world_position = view_world_position;
+
+        TerrainModelApproximation::compute(&tile_tree, &tile_atlas, None)
+    }
+
+    #[cfg(feature = "high_precision")]
+    #[test]
+    fn grid_transform_splits_translation() {
+        use crate::big_space::{GridCell, ReferenceFrame};
+
+        let radius = 6_371_000.0;
+        let position = DVec3::new(1.496e10 + 123.25, -4.2e8 + 7.5, 3.0e9 - 0.125);
+        let model = TerrainModel::sphere(position, radius, 0.0, 1.0);
+
+        let frame = ReferenceFrame::new(2000.0, 100.0);
+        let grid_transform = model.grid_transform(&frame);
+
+        assert_eq!(
+            grid_transform.cell,
+            GridCell::new(7_480_000, -210_000, 1_500_000)
+        );
+        assert!(grid_transform
+            .transform
+            .translation
+            .abs_diff_eq(Vec3::new(123.25, 7.5, -0.125), 1e-3));
+        assert_eq!(grid_transform.transform.scale, Vec3::splat(radius as f32));
+        assert_eq!(grid_transform.transform.rotation, Quat::IDENTITY);
+
+        // the planet center is reconstructed within single precision of the residual translation
+        let reconstructed =
+            frame.grid_position_double(&grid_transform.cell, &grid_transform.transform);
+
+        assert!((reconstructed - position).abs().max_element() < 1e-3);
+    }
+
+    #[test]
+    fn approximation_is_precise_far_from_origin() {
+        let radius = 6_371_000.0;
+        let position = DVec3::new(1.0e7, -1.0e7, 1.0e7);
+        let model = TerrainModel::sphere(position, radius, 0.0, 1.0);
+
+        // the view hovers 1234.5 meters above the side two, where the single precision spacing of its position is about one meter
+        let direction = DVec3::new(0.1, 1.0, 0.2).normalize();
+        let view_world_position = position + direction * (radius + 1234.5);
+        let approximation = approximate(model, view_world_position);
+
+        let expected = position + direction * radius - view_world_position;
+        let c = approximation.sides()[2].c;
+
+        assert!(
+            (c.as_dvec3() - expected).abs().max_element()
+                <= expected.length() * f32::EPSILON as f64,
+            "the constant coefficient {c} deviates from the double precision offset {expected}"
+        );
+    }
+
+    #[test]
+    fn approximation_is_finite_above_poles_and_corners() {
+        let radius = 6_371_000.0;
+        let model = TerrainModel::sphere(DVec3::ZERO, radius, 0.0, 1.0);
+
+        // the poles project onto the edges of the four equatorial sides, the cube corners onto the corners of three sides
+        for direction in [
+            DVec3::Y,
+            DVec3::NEG_Y,
+            DVec3::ONE.normalize(),
+            DVec3::new(-1.0, -1.0, 1.0).normalize(),
+        ] {
+            let view_world_position = direction * (radius + 100.0);
+            let approximation = approximate(model.clone(), view_world_position);
+
+            for (side, parameter) in approximation.sides().iter().enumerate() {
+                let SideParameter {
+                    c,
+                    c_s,
+                    c_t,
+                    c_ss,
+                    c_st,
+                    c_tt,
+                    ..
+                } = *parameter;
+
+                assert!(
+                    [c, c_s, c_t, c_ss, c_st, c_tt]
+                        .iter()
+                        .all(|coefficient| coefficient.is_finite()),
+                    "the coefficients of side {side} are not finite above {direction}"
+                );
+            }
+
+            // the series of the side below the view still reproduces the surface vertically under the view
+            let side = Coordinate::from_world_position(view_world_position, &model).side;
+            let relative_position = approximation.reconstruct_position(side, Vec2::ZERO);
+
+            assert!(
+                relative_position.abs_diff_eq(-100.0 * direction.as_vec3(), 1e-2),
+                "the surface below the view is reconstructed at {relative_position} above {direction}"
+            );
+        }
+    }
+
+    #[test]
+    fn planar_approximation_is_linear() {
+        let side_length = 1000.0;
+        let position = DVec3::new(10.0, 0.0, -20.0);
+        let model = TerrainModel::planar(position, side_length, 0.0, 100.0);
+        let scale = model.scale_vec().as_vec3();
+
+        // the view hovers 50 meters above the uv coordinate (0.6, 0.53)
+        let view_offset = DVec3::new(100.0, 50.0, 30.0);
+        let approximation = approximate(model.clone(), position + view_offset);
+        let side = approximation.sides()[0];
+
+        assert_eq!(side.c_s, Vec3::new(scale.x, 0.0, 0.0));
+        assert_eq!(side.c_t, Vec3::new(0.0, 0.0, scale.z));
+        assert_eq!(side.c_ss, Vec3::ZERO);
+        assert_eq!(side.c_st, Vec3::ZERO);
+        assert_eq!(side.c_tt, Vec3::ZERO);
+        assert!(
+            side.c.abs_diff_eq(Vec3::new(0.0, -50.0, 0.0), 1e-4),
+            "the constant coefficient {} is not the offset from the view to the surface below it",
+            side.c
+        );
+
+        // the series is exact, so it reproduces the surface position relative to the view
+        let relative_st = Vec2::new(0.125, -0.25);
+        let expected = (Coordinate::new(0, DVec2::new(0.725, 0.28)).world_position(&model, 0.0)
+            - (position + view_offset))
+            .as_vec3();
+
+        assert!(approximation
+            .reconstruct_position(0, relative_st)
+            .abs_diff_eq(expected, 1e-3));
+    }
+
+    #[test]
+    fn planar_origins_place_the_uv_coordinates() {
+        let side_length = 1000.0;
+        let position = DVec3::new(10.0, 5.0, -20.0);
+        let half = DVec3::new(0.5, 0.0, 0.5) * side_length;
+
+        for (origin, center) in [
+            (PlanarOrigin::Center, position),
+            (PlanarOrigin::Corner, position + half),
+        ] {
+            let model = TerrainModel::planar_with_origin(position, origin, side_length, 0.0, 100.0);
+
+            for (uv, expected) in [
+                (DVec2::splat(0.5), center),
+                (DVec2::ZERO, center - half),
+                (DVec2::ONE, center + half),
+            ] {
+                let world_position = Coordinate::new(0, uv).world_position(&model, 0.0);
+
+                assert!(
+                    (world_position - expected).abs().max_element() < 1e-9,
+                    "{origin:?}: the uv coordinate {uv} is placed at {world_position} instead of {expected}"
+                );
+
+                let coordinate = Coordinate::from_world_position(expected, &model);
+                assert!((coordinate.uv - uv).abs().max_element() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn aabb_encloses_the_corners() {
+        let (min_height, max_height) = (-20.0, 150.0);
+
+        for model in [
+            TerrainModel::planar(DVec3::new(10.0, 5.0, -20.0), 1000.0, min_height, max_height)
+                .with_curvature(0.1),
+            TerrainModel::sphere(DVec3::new(0.0, -50.0, 0.0), 500.0, min_height, max_height),
+            TerrainModel::ellipsoid(DVec3::ZERO, 600.0, 400.0, min_height, max_height),
+        ] {
+            let aabb = model.aabb(min_height, max_height);
+            let local_from_world = model.transform().compute_matrix().inverse();
+
+            for (side, uv, height) in iproduct!(
+                model.active_sides(),
+                [
+                    DVec2::new(0.0, 0.0),
+                    DVec2::new(1.0, 0.0),
+                    DVec2::new(0.0, 1.0),
+                    DVec2::new(1.0, 1.0),
+                    DVec2::new(0.5, 0.5),
+                ],
+                [min_height, max_height]
+            ) {
+                let world_position = Coordinate::new(side, uv).world_position(&model, height);
+                let local_position = local_from_world.transform_point3(world_position.as_vec3());
+                let offset = (bevy::math::Vec3A::from(local_position) - aabb.center).abs();
+
+                assert!(
+                    offset.cmple(aabb.half_extents + 1e-4).all(),
+                    "the corner {uv} of side {side} at the height {height} lies outside of {aabb:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ellipsoid_normal_is_geodetic() {
+        let (major_axis, minor_axis) = (6_378_137.0, 6_356_752.314245);
+        let model = TerrainModel::ellipsoid(DVec3::ZERO, major_axis, minor_axis, 0.0, 1.0);
+
+        // the surface point at a geodetic latitude of 45 degrees, whose normal is the geodetic up direction
+        let latitude = 45.0_f64.to_radians();
+        let e_sqr = 1.0 - (minor_axis / major_axis).powi(2);
+        let n = major_axis / (1.0 - e_sqr * latitude.sin().powi(2)).sqrt();
+        let surface_position =
+            DVec3::new(n * latitude.cos(), n * (1.0 - e_sqr) * latitude.sin(), 0.0);
+        let up = DVec3::new(latitude.cos(), latitude.sin(), 0.0);
+
+        let local_position = model.position_world_to_local(surface_position + 1000.0 * up);
+        let projected_position = model.position_local_to_world(local_position, 0.0);
+
+        let DVec3 { x, y, z } = projected_position;
+        assert!(
+            ((x * x + z * z) / (major_axis * major_axis) + y * y / (minor_axis * minor_axis) - 1.0)
+                .abs()
+                < 1e-12,
+            "the projected position {projected_position} does not lie on the ellipsoid"
+        );
+        assert!((projected_position - surface_position).length() < 1e-3);
+
+        let normal = model.normal_local_to_world(local_position);
+
+        assert!(normal.angle_between(up) < 1e-7);
+
+        // the geodetic normal tilts away from the geocentric radial direction by about 0.19 degrees at 45 degrees latitude
+        let deviation = normal
+            .angle_between(projected_position.normalize())
+            .to_degrees();
+
+        assert!(
+            (deviation - 0.1924).abs() < 1e-3,
+            "the normal deviates by {deviation} degrees from the radial direction"
+        );
+    }
+
+    #[test]
+    fn ellipsoid_normal_matches_the_gradient() {
+        let (major_axis, minor_axis) = (6_378_137.0, 6_356_752.314245);
+        let position = DVec3::new(1000.0, -2000.0, 500.0);
+        let model = TerrainModel::ellipsoid(position, major_axis, minor_axis, 0.0, 1.0);
+        let e_sqr = 1.0 - (minor_axis / major_axis).powi(2);
+
+        for (latitude, longitude) in [
+            (0.0_f64, 0.0_f64),
+            (10.0, 75.0),
+            (-30.0, -120.0),
+            (60.0, 180.0),
+            (-80.0, 45.0),
+            (89.0, -10.0),
+        ] {
+            let (latitude, longitude) = (latitude.to_radians(), longitude.to_radians());
+            let n = major_axis / (1.0 - e_sqr * latitude.sin().powi(2)).sqrt();
+            let surface_position = DVec3::new(
+                n * latitude.cos() * longitude.cos(),
+                n * (1.0 - e_sqr) * latitude.sin(),
+                n * latitude.cos() * longitude.sin(),
+            );
+
+            // the gradient of the implicit equation x² / a² + y² / b² + z² / a² = 1
+            let gradient = (surface_position
+                / DVec3::new(major_axis, minor_axis, major_axis).powf(2.0))
+            .normalize();
+
+            for height in [0.0, 5000.0] {
+                let local_position =
+                    model.position_world_to_local(position + surface_position + height * gradient);
+                let normal = model.normal_local_to_world(local_position);
+
+                assert!(
+                    normal.angle_between(gradient) < 1e-7,
+                    "the normal {normal} deviates from the gradient {gradient} at {surface_position}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lerp_matches_the_endpoints() {
+        let start = TerrainModel::sphere(DVec3::new(1.0, 2.0, 3.0), 10.0, -1.0, 2.0)
+            .with_sides(&[0, 1])
+            .unwrap();
+        let end = TerrainModel::ellipsoid(DVec3::new(-5.0, 0.0, 7.0), 20.0, 15.0, -3.0, 4.0)
+            .with_sides(&[1, 2])
+            .unwrap();
+
+        for (t, endpoint) in [(0.0, &start), (1.0, &end)] {
+            let model = start.lerp(&end, t).unwrap();
+
+            assert!(model.translation.abs_diff_eq(endpoint.translation, 1e-12));
+            assert!(model.rotation.abs_diff_eq(endpoint.rotation, 1e-12));
+            assert!(model.scale.abs_diff_eq(endpoint.scale, 1e-12));
+            assert_eq!(model.min_height, endpoint.min_height);
+            assert_eq!(model.max_height, endpoint.max_height);
+            assert!(model
+                .world_from_local
+                .abs_diff_eq(endpoint.world_from_local, 1e-9));
+            // a sphere and an ellipsoid interpolate into an ellipsoid, which keeps the sides of both models
+            assert!(matches!(
+                model.kind,
+                TerrainKind::ELLIPSOIDAL { major_axis, minor_axis, .. }
+                    if major_axis == endpoint.scale.x && minor_axis == endpoint.scale.y
+            ));
+            assert_eq!(model.side_mask, 0b111);
+        }
+
+        let planar = TerrainModel::planar(DVec3::ZERO, 10.0, 0.0, 1.0);
+        let moved = TerrainModel::planar(DVec3::X, 30.0, 0.0, 1.0);
+
+        assert!(start.lerp(&planar, 0.5) == Err(TerrainModelError::MismatchedKinds));
+        assert!(planar.lerp(&moved, 1.0).unwrap() == moved);
+    }
+
+    #[test]
+    fn with_sides_rejects_invalid_sides() {
+        let sphere = TerrainModel::sphere(DVec3::ZERO, 1.0, 0.0, 1.0);
+
+        assert_eq!(
+            sphere.clone().with_sides(&[2, 5]).unwrap().side_mask,
+            0b100100
+        );
+        assert!(
+            sphere.clone().with_sides(&[])
+                == Err(TerrainModelError::InvalidSides { sides: vec![] })
+        );
+        assert!(
+            sphere.with_sides(&[1, 6])
+                == Err(TerrainModelError::InvalidSides { sides: vec![1, 6] })
+        );
+        assert!(
+            TerrainModel::planar(DVec3::ZERO, 1.0, 0.0, 1.0).with_sides(&[0])
+                == Err(TerrainModelError::PlanarSides)
+        );
+    }
+
+    #[test]
+    fn origin_tile_is_stable_at_tile_boundaries() {
+        let tile_atlas = TileAtlas::new(&TerrainConfig {
+            model: TerrainModel::planar(DVec3::ZERO, 1000.0, 0.0, 10.0),
+            ..default()
+        })
+        .unwrap();
+        let view_config = TerrainViewConfig::default();
+        let mut tile_tree = TileTree::new(&tile_atlas, &view_config).unwrap();
+
+        // The view moves slowly across the tile boundary at x = 0 and jitters back and forth by less than the hysteresis.
+        let tile_size = 1000.0 / (1 << view_config.origin_lod) as f64;
+        let jitter = 0.3 * view_config.origin_hysteresis * tile_size;
+
+        let mut previous: Option<TerrainModelApproximation> = None;
+        let mut origins = Vec::new();
+
+        for step in -40..=80 {
+            let x = 0.0002 * step as f64 + if step % 2 == 0 { jitter } else { -jitter };
+            tile_tree.view_world_position = DVec3::new(x, 5.0, 0.25);
+
+            let approximation =
+                TerrainModelApproximation::compute(&tile_tree, &tile_atlas, previous.as_ref());
+            let origin_xy = approximation.sides[0].origin_xy;
+
+            if origins.last() != Some(&origin_xy) {
+                origins.push(origin_xy);
+            }
+
+            previous = Some(approximation);
+        }
+
+        // the origin tile switches once, after the view has left the first tile by more than the hysteresis
+        assert_eq!(origins.len(), 2, "the origin tile oscillates: {origins:?}");
+        assert_eq!(origins[1].x, origins[0].x + 1);
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn scene_round_trip_rebuilds_the_matrices() {
+        use bevy::{
+            ecs::entity::EntityHashMap,
+            scene::{ron, serde::SceneDeserializer, DynamicScene},
+        };
+        use serde::de::DeserializeSeed;
+
+        #[derive(Component, Reflect)]
+        #[reflect(Component)]
+        struct Placement(TerrainModel);
+
+        let model = TerrainModel::ellipsoid(
+            DVec3::new(10.0, -20.0, 5.0),
+            6_378_137.0,
+            6_356_752.314245,
+            -100.0,
+            8000.0,
+        );
+
+        let type_registry = AppTypeRegistry::default();
+        type_registry.write().register::<Placement>();
+
+        let mut world = World::new();
+        world.insert_resource(type_registry.clone());
+        world.spawn(Placement(model.clone()));
+
+        let serialized = DynamicScene::from_world(&world)
+            .serialize(&type_registry.read())
+            .unwrap();
+
+        // the matrices are derived from the transform, so they are not stored in the scene
+        assert!(!serialized.contains("world_from_local"));
+
+        let mut deserializer = ron::de::Deserializer::from_str(&serialized).unwrap();
+        let scene = SceneDeserializer {
+            type_registry: &type_registry.read(),
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+
+        let mut world = World::new();
+        world.insert_resource(type_registry);
+        scene
+            .write_to_world(&mut world, &mut EntityHashMap::default())
+            .unwrap();
+
+        let deserialized = &world.query::<&Placement>().single(&world).0;
+
+        assert!(*deserialized == model);
+        assert_eq!(deserialized.world_from_local, model.world_from_local);
+        assert_eq!(deserialized.local_from_world, model.local_from_world);
+        assert!(matches!(
+            (&deserialized.kind, &model.kind),
+            (
+                TerrainKind::ELLIPSOIDAL { ellipsoid_from_world: a, .. },
+                TerrainKind::ELLIPSOIDAL { ellipsoid_from_world: b, .. },
+            ) if a == b
+        ));
+    }
+}
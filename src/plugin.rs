@@ -1,8 +1,15 @@
 use crate::{
-    math::{generate_terrain_model_approximation, TerrainModelApproximation},
+    math::{
+        apply_target_pixel_error, compute_tile_screen_error, compute_view_coordinates,
+        generate_terrain_model_approximation, Coordinate, TerrainModelApproximation,
+        TileScreenError,
+    },
     render::{
         culling_bind_group::CullingBindGroup,
         terrain_bind_group::TerrainData,
+        terrain_brush::{
+            TerrainBrushItem, TerrainBrushLabel, TerrainBrushNode, TerrainBrushPipeline,
+        },
         terrain_view_bind_group::TerrainViewData,
         tiling_prepass::{
             queue_tiling_prepass, TilingPrepassItem, TilingPrepassLabel, TilingPrepassNode,
@@ -12,12 +19,15 @@ use crate::{
     shaders::{load_terrain_shaders, InternalShaders},
     terrain::TerrainComponents,
     terrain_data::{
-        gpu_tile_atlas::GpuTileAtlas, gpu_tile_tree::GpuTileTree, tile_atlas::TileAtlas,
+        gpu_tile_atlas::GpuTileAtlas,
+        gpu_tile_tree::GpuTileTree,
+        tile_atlas::{TerrainTileLoadFailed, TerrainTileLoaded, TileAtlas},
         tile_tree::TileTree,
     },
     terrain_view::TerrainViewComponents,
 };
 use bevy::{
+    asset::AssetEvents,
     prelude::*,
     render::{
         graph::CameraDriverLabel,
@@ -39,18 +49,28 @@ impl Plugin for TerrainPlugin {
         app.init_resource::<InternalShaders>()
             .init_resource::<TerrainViewComponents<TileTree>>()
             .init_resource::<TerrainViewComponents<TerrainModelApproximation>>()
+            .init_resource::<TerrainViewComponents<TileScreenError>>()
+            .init_resource::<TerrainViewComponents<Coordinate>>()
+            .add_event::<TerrainTileLoaded>()
+            .add_event::<TerrainTileLoadFailed>()
             .add_systems(
                 PostUpdate,
                 check_visibility::<With<TileAtlas>>.in_set(VisibilitySystems::CheckVisibility),
             )
+            .add_systems(Last, TileAtlas::discard_brush_edits.after(AssetEvents))
             .add_systems(
                 Last,
                 (
+                    TileAtlas::sync_model_transform,
+                    TileTree::compute_visibility,
                     TileTree::compute_requests,
                     TileAtlas::update,
                     TileTree::adjust_to_tile_atlas,
                     TileTree::approximate_height,
                     generate_terrain_model_approximation,
+                    compute_view_coordinates,
+                    apply_target_pixel_error,
+                    compute_tile_screen_error,
                 )
                     .chain(),
             );
@@ -62,6 +82,7 @@ impl Plugin for TerrainPlugin {
             .init_resource::<TerrainViewComponents<TerrainViewData>>()
             .init_resource::<TerrainViewComponents<CullingBindGroup>>()
             .init_resource::<TerrainViewComponents<TilingPrepassItem>>()
+            .init_resource::<TerrainComponents<TerrainBrushItem>>()
             .add_systems(
                 ExtractSchedule,
                 (
@@ -73,6 +94,7 @@ impl Plugin for TerrainPlugin {
                     TerrainData::extract.after(TerrainData::initialize),
                     TerrainViewData::initialize.after(GpuTileTree::initialize),
                     TerrainViewData::extract.after(TerrainViewData::initialize),
+                    TerrainViewData::extract_readbacks.after(TerrainViewData::initialize),
                 ),
             )
             .add_systems(
@@ -87,9 +109,13 @@ impl Plugin for TerrainPlugin {
                     )
                         .in_set(RenderSet::Prepare),
                     queue_tiling_prepass.in_set(RenderSet::Queue),
+                    TerrainBrushItem::prepare.in_set(RenderSet::PrepareBindGroups),
                     GpuTileAtlas::cleanup
                         .before(World::clear_entities)
                         .in_set(RenderSet::Cleanup),
+                    TerrainViewData::cleanup
+                        .before(World::clear_entities)
+                        .in_set(RenderSet::Cleanup),
                 ),
             );
     }
@@ -100,10 +126,13 @@ impl Plugin for TerrainPlugin {
         let render_app = app
             .sub_app_mut(RenderApp)
             .init_resource::<TilingPrepassPipelines>()
-            .init_resource::<SpecializedComputePipelines<TilingPrepassPipelines>>();
+            .init_resource::<SpecializedComputePipelines<TilingPrepassPipelines>>()
+            .init_resource::<TerrainBrushPipeline>();
 
         let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
         render_graph.add_node(TilingPrepassLabel, TilingPrepassNode);
         render_graph.add_node_edge(TilingPrepassLabel, CameraDriverLabel);
+        render_graph.add_node(TerrainBrushLabel, TerrainBrushNode);
+        render_graph.add_node_edge(TerrainBrushLabel, CameraDriverLabel);
     }
 }
@@ -32,6 +32,8 @@ pub(crate) struct SplitData {
     tile: AtlasTile,
     top_left: Vec2,
     bottom_right: Vec2,
+    source_top_left: Vec2,
+    source_bottom_right: Vec2,
     tile_index: u32,
 }
 
@@ -148,6 +150,8 @@ impl GpuPreprocessor {
                             tile_data,
                             top_left,
                             bottom_right,
+                            source_top_left,
+                            source_bottom_right,
                         } => {
                             let tile_data = images.get(tile_data).unwrap();
 
@@ -158,6 +162,8 @@ impl GpuPreprocessor {
                                     tile: task.tile.into(),
                                     top_left: *top_left,
                                     bottom_right: *bottom_right,
+                                    source_top_left: *source_top_left,
+                                    source_bottom_right: *source_bottom_right,
                                     tile_index: section_index,
                                 },
                                 BufferUsages::UNIFORM,
@@ -1,14 +1,25 @@
+#[cfg(feature = "tiff_loader")]
+use crate::formats::tiff::TiffLoader;
 use crate::{
-    formats::tiff::TiffLoader,
+    formats::tiff::GeoTiffMetadata,
     preprocess::{
         gpu_preprocessor::{
             create_downsample_layout, create_split_layout, create_stitch_layout, GpuPreprocessor,
         },
-        preprocessor::{preprocessor_load_tile, select_ready_tasks, PreprocessTaskType},
+        preprocessor::{
+            preprocessor_load_tile, reprocess_modified_sources, select_ready_tasks,
+            PreprocessTaskType,
+        },
+    },
+    shaders::{
+        load_preprocess_shaders, DOWNSAMPLE_SHADER, PREPARE_DOWNSAMPLE_SHADER, SPLIT_SHADER,
+        STITCH_SHADER,
     },
-    shaders::{load_preprocess_shaders, DOWNSAMPLE_SHADER, SPLIT_SHADER, STITCH_SHADER},
     terrain::TerrainComponents,
-    terrain_data::gpu_tile_atlas::{create_attachment_layout, GpuTileAtlas},
+    terrain_data::gpu_tile_atlas::{
+        create_attachment_layout, create_prepare_downsample_layout, GpuTileAtlas,
+    },
+    util::report_pipeline_error,
 };
 use bevy::{
     prelude::*,
@@ -19,6 +30,7 @@ use bevy::{
         renderer::{RenderContext, RenderDevice},
         Render, RenderApp, RenderSet,
     },
+    utils::HashSet,
 };
 
 pub mod gpu_preprocessor;
@@ -35,6 +47,7 @@ bitflags::bitflags! {
         const SPLIT      = 1 << 1;
         const STITCH     = 1 << 2;
         const DOWNSAMPLE = 1 << 3;
+        const PREPARE_DOWNSAMPLE = 1 << 4;
     }
 }
 
@@ -42,17 +55,24 @@ pub(crate) struct TerrainPreprocessItem {
     split_pipeline: CachedComputePipelineId,
     stitch_pipeline: CachedComputePipelineId,
     downsample_pipeline: CachedComputePipelineId,
+    prepare_downsample_pipeline: CachedComputePipelineId,
 }
 
 impl TerrainPreprocessItem {
     fn pipelines<'a>(
         &'a self,
         pipeline_cache: &'a PipelineCache,
-    ) -> Option<(&ComputePipeline, &ComputePipeline, &ComputePipeline)> {
+    ) -> Option<(
+        &ComputePipeline,
+        &ComputePipeline,
+        &ComputePipeline,
+        &ComputePipeline,
+    )> {
         Some((
             pipeline_cache.get_compute_pipeline(self.split_pipeline)?,
             pipeline_cache.get_compute_pipeline(self.stitch_pipeline)?,
             pipeline_cache.get_compute_pipeline(self.downsample_pipeline)?,
+            pipeline_cache.get_compute_pipeline(self.prepare_downsample_pipeline)?,
         ))
     }
 
@@ -67,9 +87,11 @@ pub struct TerrainPreprocessPipelines {
     split_layout: BindGroupLayout,
     stitch_layout: BindGroupLayout,
     downsample_layout: BindGroupLayout,
+    prepare_downsample_layout: BindGroupLayout,
     split_shader: Handle<Shader>,
     stitch_shader: Handle<Shader>,
     downsample_shader: Handle<Shader>,
+    prepare_downsample_shader: Handle<Shader>,
 }
 
 impl FromWorld for TerrainPreprocessPipelines {
@@ -81,19 +103,23 @@ impl FromWorld for TerrainPreprocessPipelines {
         let split_layout = create_split_layout(device);
         let stitch_layout = create_stitch_layout(device);
         let downsample_layout = create_downsample_layout(device);
+        let prepare_downsample_layout = create_prepare_downsample_layout(device);
 
         let split_shader = asset_server.load(SPLIT_SHADER);
         let stitch_shader = asset_server.load(STITCH_SHADER);
         let downsample_shader = asset_server.load(DOWNSAMPLE_SHADER);
+        let prepare_downsample_shader = asset_server.load(PREPARE_DOWNSAMPLE_SHADER);
 
         Self {
             attachment_layout,
             split_layout,
             stitch_layout,
             downsample_layout,
+            prepare_downsample_layout,
             split_shader,
             stitch_shader,
             downsample_shader,
+            prepare_downsample_shader,
         }
     }
 }
@@ -126,6 +152,14 @@ impl SpecializedComputePipeline for TerrainPreprocessPipelines {
             shader = self.downsample_shader.clone();
             entry_point = "downsample".into();
         }
+        if key.contains(TerrainPreprocessPipelineKey::PREPARE_DOWNSAMPLE) {
+            layout = vec![
+                self.attachment_layout.clone(),
+                self.prepare_downsample_layout.clone(),
+            ];
+            shader = self.prepare_downsample_shader.clone();
+            entry_point = "prepare_downsample".into();
+        }
 
         ComputePipelineDescriptor {
             label: Some("terrain_preprocess_pipeline".into()),
@@ -158,8 +192,12 @@ impl render_graph::Node for TerrainPreprocessNode {
                 device.create_command_encoder(&CommandEncoderDescriptor::default());
 
             for (&terrain, preprocess_item) in preprocess_items.iter() {
-                let Some((split_pipeline, stitch_pipeline, downsample_pipeline)) =
-                    preprocess_item.pipelines(pipeline_cache)
+                let Some((
+                    split_pipeline,
+                    stitch_pipeline,
+                    downsample_pipeline,
+                    prepare_downsample_pipeline,
+                )) = preprocess_item.pipelines(pipeline_cache)
                 else {
                     continue;
                 };
@@ -199,6 +237,8 @@ impl render_graph::Node for TerrainPreprocessNode {
 
                 for attachment in &gpu_tile_atlas.attachments {
                     attachment.copy_tiles_from_write_section(&mut command_encoder);
+                    attachment
+                        .prepare_downsample(&mut command_encoder, prepare_downsample_pipeline);
 
                     attachment.download_tiles(&mut command_encoder);
 
@@ -224,6 +264,7 @@ pub(crate) fn queue_terrain_preprocess(
     mut pipelines: ResMut<SpecializedComputePipelines<TerrainPreprocessPipelines>>,
     mut preprocess_items: ResMut<TerrainComponents<TerrainPreprocessItem>>,
     gpu_tile_atlas: Res<TerrainComponents<GpuTileAtlas>>,
+    mut reported_errors: Local<HashSet<CachedComputePipelineId>>,
 ) {
     for &terrain in gpu_tile_atlas.keys() {
         let split_pipeline = pipelines.specialize(
@@ -241,6 +282,20 @@ pub(crate) fn queue_terrain_preprocess(
             &preprocess_pipelines,
             TerrainPreprocessPipelineKey::DOWNSAMPLE,
         );
+        let prepare_downsample_pipeline = pipelines.specialize(
+            &pipeline_cache,
+            &preprocess_pipelines,
+            TerrainPreprocessPipelineKey::PREPARE_DOWNSAMPLE,
+        );
+
+        for (pipeline, name) in [
+            (split_pipeline, "split"),
+            (stitch_pipeline, "stitch"),
+            (downsample_pipeline, "downsample"),
+            (prepare_downsample_pipeline, "prepare downsample"),
+        ] {
+            report_pipeline_error(&pipeline_cache, &mut reported_errors, pipeline, name);
+        }
 
         preprocess_items.insert(
             terrain,
@@ -248,17 +303,55 @@ pub(crate) fn queue_terrain_preprocess(
                 split_pipeline,
                 stitch_pipeline,
                 downsample_pipeline,
+                prepare_downsample_pipeline,
             },
         );
     }
 }
 
-pub struct TerrainPreprocessPlugin;
+/// Preprocesses the source data of the terrains into the tiles of their attachments.
+///
+/// By default, this also registers the [`TiffLoader`] for `.tif` and `.tiff` files, which loads the source heightmaps,
+/// if the `tiff_loader` feature is enabled.
+/// Disable this with [`TerrainPreprocessPlugin::without_tiff_loader`], if another loader should claim these extensions.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainPreprocessPlugin {
+    /// Whether the [`TiffLoader`] is registered.
+    pub register_tiff_loader: bool,
+}
+
+impl Default for TerrainPreprocessPlugin {
+    fn default() -> Self {
+        Self {
+            register_tiff_loader: true,
+        }
+    }
+}
+
+impl TerrainPreprocessPlugin {
+    /// Skips the registration of the [`TiffLoader`], e.g. because the app already registers its own tiff loader.
+    pub fn without_tiff_loader(mut self) -> Self {
+        self.register_tiff_loader = false;
+        self
+    }
+}
 
 impl Plugin for TerrainPreprocessPlugin {
     fn build(&self, app: &mut App) {
-        app.init_asset_loader::<TiffLoader>()
-            .add_systems(Update, (select_ready_tasks, preprocessor_load_tile));
+        if self.register_tiff_loader {
+            app.init_asset::<GeoTiffMetadata>();
+            #[cfg(feature = "tiff_loader")]
+            app.init_asset_loader::<TiffLoader>();
+        }
+
+        app.add_systems(
+            Update,
+            (
+                reprocess_modified_sources.before(preprocessor_load_tile),
+                select_ready_tasks,
+                preprocessor_load_tile,
+            ),
+        );
 
         app.sub_app_mut(RenderApp)
             .init_resource::<TerrainComponents<GpuPreprocessor>>()
@@ -6,11 +6,11 @@ use crate::{
     },
     util::CollectArray,
 };
-use bevy::{prelude::*, image::ImageSampler};
+use bevy::{prelude::*, image::ImageSampler, render::render_resource::TextureFormat};
 use itertools::{iproduct, Itertools};
 use std::{
-    collections::VecDeque,
-    fs,
+    collections::{HashSet, VecDeque},
+    fs, iter,
     ops::{DerefMut, Range},
     time::Instant,
 };
@@ -26,18 +26,87 @@ pub(crate) struct LoadingTile {
     format: AttachmentFormat,
 }
 
+/// A source image of the preprocessor, which is watched for hot-reloads.
+pub(crate) struct PreprocessSource {
+    handle: Handle<Image>,
+    dataset: PreprocessDataset,
+}
+
+/// A spherical dataset, which consists of one independent image per cube face (e.g. a cube map DEM).
+///
+/// Each image covers the whole st range of its side and is preprocessed into the tiles of this side only,
+/// since the tiles are identified by their side, lod and xy. The sides are indexed like the
+/// [`SIDE_MATRICES`](crate::math::SIDE_MATRICES). Faces can also be streamed individually with a
+/// [`SideTileSource`](crate::terrain_data::tile_source::SideTileSource), that selects the source by the side of the tile.
 pub struct SphericalDataset {
     pub attachment_index: u32,
+    /// The paths of the six face images, ordered by their side index.
     pub paths: Vec<String>,
     pub lod_range: Range<u32>,
 }
 
+/// The arrangement of the six cube faces, which are packed into a single image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CubeMapLayout {
+    /// A 4×3 grid with four faces in the middle row and one face above and below its second column.
+    /// The faces are ordered from top to bottom and left to right.
+    #[default]
+    HorizontalCross,
+    /// A 1×6 grid with the faces ordered from top to bottom.
+    VerticalStrip,
+    /// A 6×1 grid with the faces ordered from left to right.
+    HorizontalStrip,
+}
+
+impl CubeMapLayout {
+    /// Returns the uv region (top left and bottom right) of the face with the index in the order of the layout.
+    pub fn face_region(self, face: usize) -> (Vec2, Vec2) {
+        let (grid_size, cells) = match self {
+            CubeMapLayout::HorizontalCross => (
+                UVec2::new(4, 3),
+                [(1, 0), (0, 1), (1, 1), (2, 1), (3, 1), (1, 2)],
+            ),
+            CubeMapLayout::VerticalStrip => (
+                UVec2::new(1, 6),
+                [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5)],
+            ),
+            CubeMapLayout::HorizontalStrip => (
+                UVec2::new(6, 1),
+                [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0)],
+            ),
+        };
+
+        let cell_size = 1.0 / grid_size.as_vec2();
+        let top_left = UVec2::from(cells[face]).as_vec2() * cell_size;
+
+        (top_left, top_left + cell_size)
+    }
+}
+
+/// A spherical dataset, which packs all six cube faces into a single image (e.g. pre-baked cube-sphere data).
+///
+/// The faces are sliced out of the image according to the layout and are preprocessed like the images of a [`SphericalDataset`].
+/// They are not rotated, so each face has to be oriented like the side it is mapped to.
+pub struct CubeMapDataset {
+    pub attachment_index: u32,
+    /// The path of the image containing all faces.
+    pub path: String,
+    pub layout: CubeMapLayout,
+    /// The side index of each face, in the order of the layout.
+    pub sides: [u32; 6],
+    pub lod_range: Range<u32>,
+}
+
+#[derive(Clone)]
 pub struct PreprocessDataset {
     pub attachment_index: u32,
     pub path: String,
     pub side: u32,
     pub top_left: Vec2,
     pub bottom_right: Vec2,
+    /// The uv region of the image, which contains the dataset (e.g. a face of a [`CubeMapDataset`]).
+    pub source_top_left: Vec2,
+    pub source_bottom_right: Vec2,
     pub lod_range: Range<u32>,
 }
 
@@ -49,6 +118,8 @@ impl Default for PreprocessDataset {
             side: 0,
             top_left: Vec2::splat(0.0),
             bottom_right: Vec2::splat(1.0),
+            source_top_left: Vec2::splat(0.0),
+            source_bottom_right: Vec2::splat(1.0),
             lod_range: 0..1,
         }
     }
@@ -72,6 +143,8 @@ pub(crate) enum PreprocessTaskType {
         tile_data: Handle<Image>,
         top_left: Vec2,
         bottom_right: Vec2,
+        source_top_left: Vec2,
+        source_bottom_right: Vec2,
     },
     Stitch {
         neighbour_tiles: [AtlasTile; 8],
@@ -163,6 +236,8 @@ impl PreprocessTask {
                 tile_data,
                 top_left: dataset.top_left,
                 bottom_right: dataset.bottom_right,
+                source_top_left: dataset.source_top_left,
+                source_bottom_right: dataset.source_bottom_right,
             },
         }
     }
@@ -215,6 +290,10 @@ pub struct Preprocessor {
     pub(crate) loading_tiles: Vec<LoadingTile>,
     pub(crate) task_queue: VecDeque<PreprocessTask>,
     pub(crate) ready_tasks: Vec<PreprocessTask>,
+    /// The source images, which are reprocessed, once they are modified (e.g. by hot-reloading).
+    pub(crate) sources: Vec<PreprocessSource>,
+    /// The source images, whose modification was caused by adjusting their format and has to be ignored.
+    pub(crate) adjusted_sources: HashSet<AssetId<Image>>,
 
     pub(crate) start_time: Option<Instant>,
     loaded: bool,
@@ -226,6 +305,8 @@ impl Preprocessor {
             loading_tiles: default(),
             task_queue: default(),
             ready_tasks: default(),
+            sources: default(),
+            adjusted_sources: default(),
             start_time: None,
             loaded: false,
         }
@@ -239,11 +320,32 @@ impl Preprocessor {
     ) {
         let tile_handle = asset_server.load(&dataset.path);
 
-        self.loading_tiles.push(LoadingTile {
-            id: tile_handle.id(),
-            format: tile_atlas.attachments[dataset.attachment_index as usize].format,
+        self.sources.push(PreprocessSource {
+            handle: tile_handle.clone(),
+            dataset: dataset.clone(),
         });
 
+        self.queue_split_and_downsample(dataset, tile_handle, tile_atlas);
+    }
+
+    fn queue_split_and_downsample(
+        &mut self,
+        dataset: &PreprocessDataset,
+        tile_handle: Handle<Image>,
+        tile_atlas: &mut TileAtlas,
+    ) {
+        // the faces of a cube map share their source image, which is only adjusted once
+        if !self
+            .loading_tiles
+            .iter()
+            .any(|tile| tile.id == tile_handle.id())
+        {
+            self.loading_tiles.push(LoadingTile {
+                id: tile_handle.id(),
+                format: tile_atlas.attachments[dataset.attachment_index as usize].format,
+            });
+        }
+
         let mut lods = dataset.lod_range.clone().rev();
 
         for tile_coordinate in dataset.overlapping_tiles(lods.next().unwrap()) {
@@ -317,6 +419,12 @@ impl Preprocessor {
         asset_server: &AssetServer,
         tile_atlas: &mut TileAtlas,
     ) -> Self {
+        assert_eq!(
+            dataset.paths.len(),
+            6,
+            "A spherical dataset requires one image per cube face."
+        );
+
         let side_datasets = (0..6)
             .map(|side| PreprocessDataset {
                 attachment_index: dataset.attachment_index,
@@ -327,19 +435,107 @@ impl Preprocessor {
             })
             .collect_vec();
 
-        for dataset in &side_datasets {
+        self.preprocess_sides(&side_datasets, dataset.lod_range, asset_server, tile_atlas);
+
+        self
+    }
+
+    pub fn preprocess_cube_map(
+        mut self,
+        dataset: CubeMapDataset,
+        asset_server: &AssetServer,
+        tile_atlas: &mut TileAtlas,
+    ) -> Self {
+        let side_datasets = iter::zip(0..6, dataset.sides)
+            .map(|(face, side)| {
+                let (source_top_left, source_bottom_right) = dataset.layout.face_region(face);
+
+                PreprocessDataset {
+                    attachment_index: dataset.attachment_index,
+                    path: dataset.path.clone(),
+                    side,
+                    source_top_left,
+                    source_bottom_right,
+                    lod_range: dataset.lod_range.clone(),
+                    ..default()
+                }
+            })
+            .collect_vec();
+
+        self.preprocess_sides(&side_datasets, dataset.lod_range, asset_server, tile_atlas);
+
+        self
+    }
+
+    /// Preprocesses the datasets of all sides, stitching the tiles across the sides once all of them are split.
+    fn preprocess_sides(
+        &mut self,
+        side_datasets: &[PreprocessDataset],
+        lod_range: Range<u32>,
+        asset_server: &AssetServer,
+        tile_atlas: &mut TileAtlas,
+    ) {
+        for dataset in side_datasets {
             self.split_and_downsample(dataset, asset_server, tile_atlas);
         }
 
         self.task_queue.push_back(PreprocessTask::barrier());
 
-        for lod in dataset.lod_range {
-            for dataset in &side_datasets {
+        for lod in lod_range {
+            for dataset in side_datasets {
                 self.stitch_and_save_layer(dataset, tile_atlas, lod);
             }
         }
+    }
+}
 
-        self
+/// Reprocesses the tiles overlapped by the source images, which have been modified since they were preprocessed.
+///
+/// Only the tiles of the modified datasets are split, downsampled, stitched and saved again.
+/// Since the tiles are processed directly inside the [`TileAtlas`], they are updated live if the terrain is rendered as well.
+pub(crate) fn reprocess_modified_sources(
+    mut asset_events: EventReader<AssetEvent<Image>>,
+    mut terrains: Query<(&mut Preprocessor, &mut TileAtlas)>,
+) {
+    let modified = asset_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    if modified.is_empty() {
+        return;
+    }
+
+    for (mut preprocessor, mut tile_atlas) in terrains.iter_mut() {
+        // the adjustment is ignored for all datasets sharing the source (e.g. the faces of a cube map)
+        let datasets = preprocessor
+            .sources
+            .iter()
+            .filter(|source| modified.contains(&source.handle.id()))
+            .filter(|source| !preprocessor.adjusted_sources.contains(&source.handle.id()))
+            .map(|source| (source.handle.clone(), source.dataset.clone()))
+            .collect_vec();
+
+        preprocessor
+            .adjusted_sources
+            .retain(|id| !modified.contains(id));
+
+        for (handle, dataset) in datasets {
+            info!("Reprocessing the modified source {}.", dataset.path);
+
+            preprocessor.queue_split_and_downsample(&dataset, handle, &mut tile_atlas);
+            preprocessor.task_queue.push_back(PreprocessTask::barrier());
+
+            for lod in dataset.lod_range.clone() {
+                preprocessor.stitch_and_save_layer(&dataset, &mut tile_atlas, lod);
+            }
+
+            // restart the preprocessing, once the reloaded image has been adjusted
+            preprocessor.loaded = false;
+        }
     }
 }
 
@@ -403,10 +599,32 @@ pub(crate) fn preprocessor_load_tile(
     mut images: ResMut<Assets<Image>>,
 ) {
     for mut preprocessor in preprocessors.iter_mut() {
-        preprocessor.loading_tiles.retain_mut(|tile| {
+        let Preprocessor {
+            loading_tiles,
+            adjusted_sources,
+            ..
+        } = preprocessor.deref_mut();
+
+        loading_tiles.retain_mut(|tile| {
             if let Some(image) = images.get_mut(tile.id) {
-                image.texture_descriptor.format = tile.format.processing_format();
+                let format = tile.format.processing_format();
+
+                // 8 bit heights (e.g. of grayscale PNG or TIFF files) are widened onto the full 16 bit range,
+                // since their texels are reinterpreted in the processing format
+                if image.texture_descriptor.format == TextureFormat::R8Unorm
+                    && format == TextureFormat::R16Unorm
+                {
+                    image.data = image
+                        .data
+                        .iter()
+                        .flat_map(|&height| (height as u16 * 257).to_le_bytes())
+                        .collect();
+                }
+
+                image.texture_descriptor.format = format;
                 image.sampler = ImageSampler::linear();
+                // mutating the image emits a modified event, which must not trigger reprocessing
+                adjusted_sources.insert(tile.id);
                 false
             } else {
                 true
@@ -22,6 +22,9 @@ pub(crate) fn create_culling_layout(device: &RenderDevice) -> BindGroupLayout {
     )
 }
 
+/// Extracts the left, right, bottom, top and near planes of the view frustum.
+/// The far plane is omitted, since bevy uses an infinite reversed z projection.
+/// A point lies inside the frustum, if its dot product with all planes is positive.
 pub fn planes(view_projection: &Mat4) -> [Vec4; 5] {
     let row3 = view_projection.row(3);
     let mut planes = [default(); 5];
@@ -42,14 +45,18 @@ pub struct CullingUniform {
     world_position: Vec3,
     view_proj: Mat4,
     planes: [Vec4; 5],
+    culling_margin: f32,
 }
 
-impl From<&ExtractedView> for CullingUniform {
-    fn from(view: &ExtractedView) -> Self {
+impl CullingUniform {
+    fn new(view: &ExtractedView, culling_margin: f32) -> Self {
+        let view_proj = view.clip_from_view * view.world_from_view.compute_matrix().inverse();
+
         Self {
             world_position: view.world_from_view.translation(),
-            view_proj: view.world_from_view.compute_matrix().inverse(),
-            planes: default(),
+            view_proj,
+            planes: planes(&view_proj),
+            culling_margin,
         }
     }
 }
@@ -90,12 +97,13 @@ impl CullingBindGroup {
         extracted_views: Query<&ExtractedView>,
         mut culling_bind_groups: ResMut<TerrainViewComponents<CullingBindGroup>>,
     ) {
-        for &(terrain, view) in gpu_tile_trees.keys() {
+        for (&(terrain, view), gpu_tile_tree) in gpu_tile_trees.iter() {
             let extracted_view = extracted_views.get(view).unwrap();
+            let culling_uniform = CullingUniform::new(extracted_view, gpu_tile_tree.culling_margin);
 
             culling_bind_groups.insert(
                 (terrain, view),
-                CullingBindGroup::new(&device, extracted_view.into()),
+                CullingBindGroup::new(&device, culling_uniform),
             );
         }
     }
@@ -7,6 +7,7 @@
 
 pub mod culling_bind_group;
 pub mod terrain_bind_group;
+pub mod terrain_brush;
 pub mod terrain_material;
 pub mod terrain_view_bind_group;
 pub mod tiling_prepass;
@@ -10,10 +10,11 @@ use bevy::{
     pbr::{MeshTransforms, MeshUniform, PreviousGlobalTransform},
     prelude::*,
     render::{
+        render_asset::RenderAssets,
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
         render_resource::{binding_types::*, *},
-        renderer::{RenderDevice, RenderQueue},
-        texture::FallbackImage,
+        renderer::{RenderAdapter, RenderDevice, RenderQueue},
+        texture::{FallbackImage, GpuImage},
         Extract,
     },
 };
@@ -38,6 +39,11 @@ pub(crate) fn create_terrain_layout(device: &RenderDevice) -> BindGroupLayout {
                 texture_2d_array(TextureSampleType::Float { filterable: true }), // attachment 6
                 texture_2d_array(TextureSampleType::Float { filterable: true }), // attachment 7
                 texture_2d_array(TextureSampleType::Float { filterable: true }), // attachment 8
+                texture_2d_array(TextureSampleType::Float { filterable: true }), // hole mask
+                texture_2d(TextureSampleType::Float { filterable: true }), // detail noise
+                sampler(SamplerBindingType::Filtering), // detail noise sampler
+                texture_2d_array(TextureSampleType::Float { filterable: true }), // height overlay
+                uniform_buffer::<TerrainTimeUniform>(false), // time
             ),
         ),
     )
@@ -72,6 +78,17 @@ impl AttachmentUniform {
     }
 }
 
+/// The time, which is available in shaders to animate the terrain surface (e.g. tides or procedural waves).
+///
+/// Animated displacements are not accounted for by the culling, unless they stay within the height range of the terrain.
+#[derive(Default, ShaderType)]
+struct TerrainTimeUniform {
+    /// The elapsed time in seconds, which wraps around every hour like the `globals.time` of bevy, to preserve precision.
+    time: f32,
+    /// The time since the previous frame in seconds.
+    delta: f32,
+}
+
 /// The terrain config data that is available in shaders.
 #[derive(Default, ShaderType)]
 struct TerrainConfigUniform {
@@ -79,22 +96,89 @@ struct TerrainConfigUniform {
     min_height: f32,
     max_height: f32,
     scale: f32,
+    height_gamma: f32,
+    curvature: f32,
+    atmosphere_color: Vec4,
+    atmosphere_density: f32,
+    planet_radius: f32,
+    sea_level: f32,
+    sea_color: Vec4,
+    seed: u32,
+    height_channel: u32,
+    detail_amplitude: f32,
+    detail_frequency: f32,
+    detail_fade_lod_count: f32,
+    max_lod: u32,
+    mip_bias: f32,
+    side_mask: u32,
+    skirt_depth: f32,
+    height_floor: f32,
+    overlay_min_height: f32,
+    overlay_max_height: f32,
+    contour_interval: f32,
+    contour_width: f32,
+    contour_color: Vec4,
 }
 
 impl TerrainConfigUniform {
     fn from_tile_atlas(tile_atlas: &TileAtlas) -> Self {
+        let atmosphere = tile_atlas.atmosphere.unwrap_or_default();
+        let detail_noise = tile_atlas.detail_noise.clone().unwrap_or_default();
+        let contour_lines = tile_atlas.contour_lines.unwrap_or_default();
+        let overlay_height_range = tile_atlas.overlay_height_range();
+
         Self {
             lod_count: tile_atlas.lod_count,
-            min_height: tile_atlas.model.min_height,
-            max_height: tile_atlas.model.max_height,
+            // The height scale is applied to the height range, which scales all sampled heights.
+            min_height: tile_atlas.min_height(),
+            max_height: tile_atlas.max_height(),
             scale: tile_atlas.model.scale() as f32,
+            height_gamma: tile_atlas.height_gamma,
+            curvature: tile_atlas.model.curvature as f32,
+            atmosphere_color: LinearRgba::from(atmosphere.color).to_vec4(),
+            atmosphere_density: atmosphere.density,
+            planet_radius: atmosphere.planet_radius,
+            sea_level: tile_atlas.sea_level.unwrap_or(f32::MIN),
+            sea_color: LinearRgba::from(tile_atlas.sea_color).to_vec4(),
+            seed: tile_atlas.seed,
+            height_channel: tile_atlas.height_channel,
+            // the amplitude bounds the displacement of the culled tiles, which is zero without detail noise
+            detail_amplitude: tile_atlas
+                .detail_noise
+                .as_ref()
+                .map_or(0.0, |detail_noise| detail_noise.amplitude),
+            detail_frequency: detail_noise.frequency,
+            detail_fade_lod_count: detail_noise.fade_lod_count.max(1) as f32,
+            max_lod: tile_atlas.max_lod.unwrap_or(u32::MAX),
+            mip_bias: tile_atlas.mip_bias,
+            side_mask: tile_atlas.model.side_mask,
+            skirt_depth: tile_atlas.skirt_depth.unwrap_or(0.0),
+            height_floor: tile_atlas.height_floor.unwrap_or(f32::MIN),
+            // the bounds of the overlay are added to the culled tiles, which are zero without an enabled overlay
+            overlay_min_height: overlay_height_range.x,
+            overlay_max_height: overlay_height_range.y,
+            contour_interval: contour_lines.interval,
+            contour_width: contour_lines.width,
+            contour_color: LinearRgba::from(contour_lines.color).to_vec4(),
         }
     }
 }
 
 pub struct TerrainData {
     mesh_buffer: StaticBuffer<MeshUniform>,
+    /// The time, which is updated every frame.
+    time_buffer: StaticBuffer<TerrainTimeUniform>,
+    /// The terrain config, which is rewritten once the scale of the terrain model or the overlay height range changes.
+    terrain_config_buffer: StaticBuffer<TerrainConfigUniform>,
+    /// The scale of the terrain model, which was last written into the terrain config.
+    scale: f32,
+    /// The height range of the overlay, which was last written into the terrain config.
+    overlay_height_range: Vec2,
+    /// Whether the terrain config has to be written in the next prepare.
+    config_changed: bool,
     pub(crate) terrain_bind_group: BindGroup,
+    /// The texture of the height overlay, which is bound in the terrain bind group.
+    height_overlay: Option<TextureId>,
 }
 
 impl TerrainData {
@@ -103,6 +187,10 @@ impl TerrainData {
         fallback_image: &FallbackImage,
         tile_atlas: &TileAtlas,
         gpu_tile_atlas: &GpuTileAtlas,
+        hole_mask: Option<&GpuImage>,
+        detail_noise: Option<&GpuImage>,
+        height_overlay: Option<&GpuImage>,
+        anisotropy: u16,
     ) -> Self {
         let mesh_buffer = StaticBuffer::empty_sized(
             None,
@@ -110,18 +198,25 @@ impl TerrainData {
             MeshUniform::SHADER_SIZE.get(),
             BufferUsages::STORAGE | BufferUsages::COPY_DST,
         );
+        let time_buffer =
+            StaticBuffer::empty(None, device, BufferUsages::UNIFORM | BufferUsages::COPY_DST);
         let terrain_config_buffer = StaticBuffer::create(
             None,
             device,
             &TerrainConfigUniform::from_tile_atlas(tile_atlas),
-            BufferUsages::UNIFORM,
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         );
 
         let atlas_sampler = device.create_sampler(&SamplerDescriptor {
             mag_filter: FilterMode::Linear,
             min_filter: FilterMode::Linear,
             mipmap_filter: FilterMode::Linear,
-            anisotropy_clamp: 16, // Todo: make this customisable
+            // the tile borders already contain the data of the adjacent tiles
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            anisotropy_clamp: anisotropy,
+            // the bias is applied in the shaders, since samplers do not support one
+            lod_max_clamp: tile_atlas.max_mip_level.map_or(32.0, |level| level as f32),
             ..default()
         });
 
@@ -136,6 +231,55 @@ impl TerrainData {
             })
             .collect_vec();
 
+        // The fallback image is white, which does not mask anything.
+        let hole_mask = hole_mask.map_or(fallback_image.d2_array.texture_view.clone(), |image| {
+            image.texture.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2Array),
+                ..default()
+            })
+        });
+
+        let detail_noise = detail_noise.map_or(fallback_image.d2.texture_view.clone(), |image| {
+            image.texture_view.clone()
+        });
+
+        // Unlike the white fallback image, the zeroed texture adds nothing, until the overlay is loaded.
+        let height_overlay_view = height_overlay.map_or_else(
+            || {
+                device
+                    .create_texture(&TextureDescriptor {
+                        label: None,
+                        size: Extent3d::default(),
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: TextureFormat::R8Unorm,
+                        usage: TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    })
+                    .create_view(&TextureViewDescriptor {
+                        dimension: Some(TextureViewDimension::D2Array),
+                        ..default()
+                    })
+            },
+            |image| {
+                image.texture.create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2Array),
+                    ..default()
+                })
+            },
+        );
+
+        // the noise is tileable and repeated across the sides
+        let detail_sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            ..default()
+        });
+
         let attachment_uniform = AttachmentUniform::new(gpu_tile_atlas);
         let attachment_buffer =
             StaticBuffer::create(None, device, &attachment_uniform, BufferUsages::UNIFORM);
@@ -156,28 +300,103 @@ impl TerrainData {
                 &attachments[5],
                 &attachments[6],
                 &attachments[7],
+                &hole_mask,
+                &detail_noise,
+                &detail_sampler,
+                &height_overlay_view,
+                &time_buffer,
             )),
         );
 
         Self {
             mesh_buffer,
+            time_buffer,
+            terrain_config_buffer,
+            scale: tile_atlas.model.scale() as f32,
+            overlay_height_range: tile_atlas.overlay_height_range(),
+            config_changed: false,
             terrain_bind_group,
+            height_overlay: height_overlay.map(|image| image.texture.id()),
         }
     }
 
     pub(crate) fn initialize(
         device: Res<RenderDevice>,
+        adapter: Res<RenderAdapter>,
         fallback_image: Res<FallbackImage>,
+        images: Res<RenderAssets<GpuImage>>,
         mut terrain_data: ResMut<TerrainComponents<TerrainData>>,
         gpu_tile_atlases: Res<TerrainComponents<GpuTileAtlas>>,
-        tile_atlases: Extract<Query<(Entity, &TileAtlas), Added<TileAtlas>>>,
+        tile_atlases: Extract<Query<(Entity, Ref<TileAtlas>)>>,
     ) {
         for (terrain, tile_atlas) in &tile_atlases {
+            let height_overlay = tile_atlas
+                .height_overlay
+                .as_ref()
+                .and_then(|height_overlay| images.get(&height_overlay.texture));
+
+            // The bind group is recreated, once the height overlay is modified and thus uploaded into a new texture.
+            if !tile_atlas.is_added()
+                && terrain_data.get(&terrain).unwrap().height_overlay
+                    == height_overlay.map(|image| image.texture.id())
+            {
+                continue;
+            }
+
             let gpu_tile_atlas = gpu_tile_atlases.get(&terrain).unwrap();
 
+            let hole_mask = tile_atlas.hole_mask.as_ref().and_then(|hole_mask| {
+                let image = images.get(hole_mask);
+
+                if image.is_none() {
+                    warn!("The hole mask of the terrain is not loaded yet and will be ignored.");
+                }
+
+                image
+            });
+
+            let detail_noise = tile_atlas.detail_noise.as_ref().and_then(|detail_noise| {
+                let image = images.get(&detail_noise.texture);
+
+                if image.is_none() {
+                    warn!("The detail noise of the terrain is not loaded yet and will be ignored.");
+                }
+
+                image
+            });
+
+            if tile_atlas.height_overlay.is_some() && height_overlay.is_none() {
+                warn!("The height overlay of the terrain is not loaded yet.");
+            }
+
+            let anisotropy = if adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(DownlevelFlags::ANISOTROPIC_FILTERING)
+            {
+                tile_atlas.anisotropy.clamp(1, 16) as u16
+            } else {
+                if tile_atlas.anisotropy > 1 {
+                    warn_once!(
+                        "Anisotropic filtering is not supported by the GPU and thus disabled."
+                    );
+                }
+
+                1
+            };
+
             terrain_data.insert(
                 terrain,
-                TerrainData::new(&device, &fallback_image, tile_atlas.into(), gpu_tile_atlas),
+                TerrainData::new(
+                    &device,
+                    &fallback_image,
+                    &tile_atlas,
+                    gpu_tile_atlas,
+                    hole_mask,
+                    detail_noise,
+                    height_overlay,
+                    anisotropy,
+                ),
             );
         }
     }
@@ -185,10 +404,16 @@ impl TerrainData {
     pub(crate) fn extract(
         mut terrain_data: ResMut<TerrainComponents<TerrainData>>,
         terrains: Extract<
-            Query<(Entity, &GlobalTransform, Option<&PreviousGlobalTransform>), With<TileAtlas>>,
+            Query<(
+                Entity,
+                &TileAtlas,
+                &GlobalTransform,
+                Option<&PreviousGlobalTransform>,
+            )>,
         >,
+        time: Extract<Res<Time>>,
     ) {
-        for (terrain, transform, previous_transform) in terrains.iter() {
+        for (terrain, tile_atlas, transform, previous_transform) in terrains.iter() {
             let mesh_transforms = MeshTransforms {
                 world_from_local: (&transform.affine()).into(),
                 flags: 0,
@@ -201,6 +426,24 @@ impl TerrainData {
 
             let terrain_data = terrain_data.get_mut(&terrain).unwrap();
             terrain_data.mesh_buffer.set_value(mesh_uniform);
+            terrain_data.time_buffer.set_value(TerrainTimeUniform {
+                time: time.elapsed_secs_wrapped(),
+                delta: time.delta_secs(),
+            });
+
+            let scale = tile_atlas.model.scale() as f32;
+            let overlay_height_range = tile_atlas.overlay_height_range();
+
+            if scale != terrain_data.scale
+                || overlay_height_range != terrain_data.overlay_height_range
+            {
+                terrain_data
+                    .terrain_config_buffer
+                    .set_value(TerrainConfigUniform::from_tile_atlas(tile_atlas));
+                terrain_data.scale = scale;
+                terrain_data.overlay_height_range = overlay_height_range;
+                terrain_data.config_changed = true;
+            }
         }
     }
 
@@ -210,6 +453,12 @@ impl TerrainData {
     ) {
         for terrain_data in &mut terrain_data.values_mut() {
             terrain_data.mesh_buffer.update(&queue);
+            terrain_data.time_buffer.update(&queue);
+
+            if terrain_data.config_changed {
+                terrain_data.terrain_config_buffer.update(&queue);
+                terrain_data.config_changed = false;
+            }
         }
     }
 }
@@ -235,3 +484,312 @@ impl<const I: usize, P: PhaseItem> RenderCommand<P> for SetTerrainBindGroup<I> {
         RenderCommandResult::Success
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{SideParameter, TileCoordinate},
+        terrain::TerrainConfig,
+    };
+    use bevy::{render::render_resource::encase::UniformBuffer, tasks::block_on};
+    use bytemuck::Pod;
+    use wgpu::util::DeviceExt;
+
+    /// Copies the item of the shader source, which starts with the declaration, up to its closing brace.
+    ///
+    /// The other items of the shader modules depend on the view bindings of bevy, so only the tested ones are compiled.
+    fn wgsl_item<'a>(source: &'a str, declaration: &str) -> &'a str {
+        let start = source.find(declaration).unwrap();
+        let end = start + source[start..].find("\n}").unwrap() + 2;
+
+        &source[start..end]
+    }
+
+    /// Runs the `main` function of the compute shader once per invocation, which reads and writes the `values` in place.
+    ///
+    /// The config of the tile atlas is bound as `config`. Returns `None`, if no GPU adapter is available.
+    fn run_compute<T: Pod>(
+        tile_atlas: &TileAtlas,
+        source: &str,
+        invocations: u32,
+        values: &[T],
+    ) -> Option<Vec<T>> {
+        let instance = wgpu::Instance::default();
+        let Some(adapter) =
+            block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        else {
+            eprintln!("skipped, since no GPU adapter is available");
+            return None;
+        };
+        let (device, queue) =
+            block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+
+        let mut config = UniformBuffer::new(Vec::new());
+        config
+            .write(&TerrainConfigUniform::from_tile_atlas(tile_atlas))
+            .unwrap();
+
+        let shader = format!(
+            "{}\n@group(0) @binding(0) var<uniform> config: TerrainConfig;\n{source}",
+            wgsl_item(
+                include_str!("../shaders/types.wgsl"),
+                "struct TerrainConfig"
+            ),
+        );
+
+        let size = size_of_val(values) as u64;
+
+        let config_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: config.as_ref(),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let value_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(values),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(shader.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: default(),
+            cache: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: config_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: value_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&default());
+        {
+            let mut pass = encoder.begin_compute_pass(&default());
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(invocations, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&value_buffer, 0, &read_buffer, 0, size);
+        queue.submit([encoder.finish()]);
+
+        read_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::Maintain::Wait);
+
+        let values = bytemuck::pod_collect_to_vec(&read_buffer.slice(..).get_mapped_range());
+
+        Some(values)
+    }
+
+    #[test]
+    fn remap_height_matches_shader() {
+        let tile_atlas = TileAtlas::new(&TerrainConfig {
+            height_scale: 2.0,
+            height_offset: -50.0,
+            height_gamma: 2.2,
+            height_floor: Some(-49.5),
+            ..default()
+        })
+        .unwrap();
+
+        let source = format!(
+            "{}
+            @group(0) @binding(1) var<storage, read_write> heights: array<f32>;
+
+            @compute @workgroup_size(1)
+            fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+                heights[id.x] = remap_height(heights[id.x]);
+            }}",
+            wgsl_item(include_str!("../shaders/functions.wgsl"), "fn remap_height"),
+        );
+
+        let normalized_heights = (-4..=68)
+            .map(|index| index as f32 / 64.0)
+            .collect::<Vec<_>>();
+
+        let Some(heights) = run_compute(
+            &tile_atlas,
+            &source,
+            normalized_heights.len() as u32,
+            &normalized_heights,
+        ) else {
+            return;
+        };
+
+        for (normalized_height, height) in normalized_heights.into_iter().zip(heights) {
+            let expected = tile_atlas.remap_height(normalized_height);
+
+            assert!(
+                (height - expected).abs() < 1e-3,
+                "the normalized height {normalized_height} is remapped to {height} on the GPU and {expected} on the CPU"
+            );
+        }
+    }
+
+    #[test]
+    fn tile_hash_matches_shader() {
+        let tile_atlas = TileAtlas::new(&TerrainConfig {
+            seed: 1234,
+            ..default()
+        })
+        .unwrap();
+
+        let source = format!(
+            "{}
+            {}
+            {}
+            @group(0) @binding(1) var<storage, read_write> tiles: array<vec4<u32>>;
+
+            @compute @workgroup_size(1)
+            fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+                let tile = tiles[id.x];
+                tiles[id.x] = vec4<u32>(tile_hash(TileCoordinate(tile.x, tile.y, tile.zw)));
+            }}",
+            wgsl_item(
+                include_str!("../shaders/types.wgsl"),
+                "struct TileCoordinate"
+            ),
+            wgsl_item(include_str!("../shaders/functions.wgsl"), "fn pcg_hash"),
+            wgsl_item(include_str!("../shaders/functions.wgsl"), "fn tile_hash"),
+        );
+
+        let coordinates = [
+            TileCoordinate::new(0, 0, 0, 0),
+            TileCoordinate::new(3, 5, 17, 30),
+            TileCoordinate::new(5, 20, 1 << 19, (1 << 20) - 1),
+        ];
+        let tiles = coordinates
+            .iter()
+            .map(|tile| [tile.side, tile.lod, tile.x, tile.y])
+            .collect::<Vec<_>>();
+
+        let Some(hashes) = run_compute(&tile_atlas, &source, tiles.len() as u32, &tiles) else {
+            return;
+        };
+
+        for (tile, hash) in coordinates.into_iter().zip(hashes) {
+            assert_eq!(hash[0], tile.hash(1234), "{tile}");
+        }
+    }
+
+    #[test]
+    fn taylor_series_matches_shader() {
+        let tile_atlas = TileAtlas::new(&TerrainConfig::default()).unwrap();
+
+        let source = format!(
+            "{}
+            {}
+            @group(0) @binding(1) var<storage, read_write> values: array<f32>;
+
+            fn coefficient(base: u32, index: u32) -> vec3<f32> {{
+                return vec3<f32>(values[base + 3u * index], values[base + 3u * index + 1u], values[base + 3u * index + 2u]);
+            }}
+
+            @compute @workgroup_size(1)
+            fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+                // the config is unused, but has to be bound
+                _ = config;
+
+                let base = 20u * id.x;
+                let params = SideParameter(vec2<i32>(0), vec2<f32>(0.0), coefficient(base, 0u), coefficient(base, 1u),
+                    coefficient(base, 2u), coefficient(base, 3u), coefficient(base, 4u), coefficient(base, 5u));
+                let position = evaluate_taylor_series(params, vec2<f32>(values[base + 18u], values[base + 19u]));
+
+                values[base] = position.x;
+                values[base + 1u] = position.y;
+                values[base + 2u] = position.z;
+            }}",
+            wgsl_item(
+                include_str!("../shaders/types.wgsl"),
+                "struct SideParameter"
+            ),
+            wgsl_item(
+                include_str!("../shaders/functions.wgsl"),
+                "fn evaluate_taylor_series"
+            ),
+        );
+
+        // coefficients of the magnitudes of earth sized terrains, evaluated close to and far from the view
+        let side = |scale: f32| SideParameter {
+            c: Vec3::new(-12.5, -1520.25, 3.75) * scale,
+            c_s: Vec3::new(6.4e6, -1.3e5, 2.1e4) * scale,
+            c_t: Vec3::new(-3.2e4, 2.2e5, 6.3e6) * scale,
+            c_ss: Vec3::new(-1.1e5, -3.2e6, 4.5e3) * scale,
+            c_st: Vec3::new(2.5e4, -7.5e3, -1.5e5) * scale,
+            c_tt: Vec3::new(3.3e3, -3.1e6, -9.8e4) * scale,
+            ..default()
+        };
+        let inputs = [
+            (side(1.0), Vec2::ZERO),
+            (side(1.0), Vec2::new(1.5e-4, -2.5e-4)),
+            (side(1.0), Vec2::new(-0.0123, 0.0456)),
+            (side(0.001), Vec2::new(0.25, -0.125)),
+            (side(-3.7), Vec2::new(-0.4, 0.3)),
+        ];
+        let values = inputs
+            .iter()
+            .flat_map(|(params, relative_st)| {
+                [
+                    params.c,
+                    params.c_s,
+                    params.c_t,
+                    params.c_ss,
+                    params.c_st,
+                    params.c_tt,
+                ]
+                .into_iter()
+                .flat_map(|coefficient| coefficient.to_array())
+                .chain(relative_st.to_array())
+            })
+            .collect::<Vec<_>>();
+
+        let Some(values) = run_compute(&tile_atlas, &source, inputs.len() as u32, &values) else {
+            return;
+        };
+
+        for ((params, relative_st), values) in inputs.into_iter().zip(values.chunks(20)) {
+            let position = Vec3::from_slice(values);
+            let expected = params.evaluate(relative_st);
+
+            // The GPU may fuse the multiplications and additions, which rounds less often.
+            let Vec2 { x: s, y: t } = relative_st.abs();
+            let magnitude = params.c.abs()
+                + params.c_s.abs() * s
+                + params.c_t.abs() * t
+                + params.c_ss.abs() * s * s
+                + params.c_st.abs() * s * t
+                + params.c_tt.abs() * t * t;
+
+            assert!(
+                (position - expected)
+                    .abs()
+                    .cmple(4.0 * f32::EPSILON * magnitude)
+                    .all(),
+                "the series is evaluated to {position} on the GPU and {expected} on the CPU at {relative_st}"
+            );
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    shaders::BRUSH_SHADER,
+    terrain::{TerrainBrushMode, TerrainBrushStroke, TerrainComponents},
+    terrain_data::gpu_tile_atlas::GpuTileAtlas,
+    util::{report_pipeline_error, StaticBuffer},
+};
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{self, RenderLabel},
+        render_resource::{binding_types::*, *},
+        renderer::{RenderContext, RenderDevice},
+        texture::GpuImage,
+    },
+    utils::HashSet,
+};
+use std::mem;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct TerrainBrushLabel;
+
+pub(crate) fn create_brush_layout(device: &RenderDevice) -> BindGroupLayout {
+    device.create_bind_group_layout(
+        None,
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                texture_storage_2d_array(TextureFormat::R32Float, StorageTextureAccess::ReadWrite), // height overlay
+                texture_storage_2d_array(TextureFormat::R32Float, StorageTextureAccess::ReadWrite), // source heights
+                uniform_buffer::<GpuBrushStroke>(false), // brush stroke
+            ),
+        ),
+    )
+}
+
+#[derive(Clone, Copy, Default, ShaderType)]
+struct GpuBrushStroke {
+    side: u32,
+    mode: u32,
+    uv: Vec2,
+    radius: f32,
+    strength: f32,
+}
+
+impl From<&TerrainBrushStroke> for GpuBrushStroke {
+    fn from(stroke: &TerrainBrushStroke) -> Self {
+        Self {
+            side: stroke.coordinate.side,
+            mode: match stroke.mode {
+                TerrainBrushMode::Raise => 0,
+                TerrainBrushMode::Lower => 1,
+                TerrainBrushMode::Smooth => 2,
+            },
+            uv: stroke.coordinate.uv.as_vec2(),
+            radius: stroke.radius,
+            strength: stroke.strength,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct TerrainBrushPipeline {
+    brush_layout: BindGroupLayout,
+    copy_pipeline: CachedComputePipelineId,
+    brush_pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for TerrainBrushPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let asset_server = world.resource::<AssetServer>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let brush_layout = create_brush_layout(device);
+        let brush_shader = asset_server.load(BRUSH_SHADER);
+
+        let copy_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("terrain_brush_copy_pipeline".into()),
+            layout: vec![brush_layout.clone()],
+            push_constant_ranges: default(),
+            shader: brush_shader.clone(),
+            shader_defs: default(),
+            entry_point: "copy_heights".into(),
+            zero_initialize_workgroup_memory: false,
+        });
+        let brush_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("terrain_brush_pipeline".into()),
+            layout: vec![brush_layout.clone()],
+            push_constant_ranges: default(),
+            shader: brush_shader,
+            shader_defs: default(),
+            entry_point: "brush".into(),
+            zero_initialize_workgroup_memory: false,
+        });
+
+        Self {
+            brush_layout,
+            copy_pipeline,
+            brush_pipeline,
+        }
+    }
+}
+
+/// The brush strokes of a terrain, which are applied to its height overlay in this frame.
+///
+/// Each stroke first copies the overlay into the source heights and then writes the overlay,
+/// so that the smoothing reads the heights before the stroke, like on the CPU (see [`TerrainBrushStroke::apply`]).
+pub(crate) struct TerrainBrushItem {
+    /// The bind groups of the strokes, in the order they were enqueued.
+    stroke_bind_groups: Vec<BindGroup>,
+    workgroup_count: UVec3,
+}
+
+impl TerrainBrushItem {
+    /// Prepares the bind groups of all terrains with pending brush strokes.
+    ///
+    /// The strokes are kept until the pipeline is compiled, but are discarded, if they can not be applied
+    /// (e.g. if the overlay is still loading), so that they do not accumulate.
+    pub(crate) fn prepare(
+        device: Res<RenderDevice>,
+        brush_pipeline: Res<TerrainBrushPipeline>,
+        pipeline_cache: Res<PipelineCache>,
+        images: Res<RenderAssets<GpuImage>>,
+        mut gpu_tile_atlases: ResMut<TerrainComponents<GpuTileAtlas>>,
+        mut brush_items: ResMut<TerrainComponents<TerrainBrushItem>>,
+        mut reported_errors: Local<HashSet<CachedComputePipelineId>>,
+        mut reported_terrains: Local<HashSet<Entity>>,
+    ) {
+        brush_items.clear();
+
+        let pipelines = [
+            (brush_pipeline.copy_pipeline, "brush copy"),
+            (brush_pipeline.brush_pipeline, "brush"),
+        ];
+
+        for (pipeline, name) in pipelines {
+            report_pipeline_error(&pipeline_cache, &mut reported_errors, pipeline, name);
+        }
+
+        if pipelines
+            .iter()
+            .any(|&(pipeline, _)| pipeline_cache.get_compute_pipeline(pipeline).is_none())
+        {
+            return;
+        }
+
+        for (&terrain, gpu_tile_atlas) in gpu_tile_atlases.iter_mut() {
+            if gpu_tile_atlas.brush_strokes.is_empty() {
+                continue;
+            }
+
+            let strokes = mem::take(&mut gpu_tile_atlas.brush_strokes);
+
+            let Some(image) = gpu_tile_atlas
+                .height_overlay_image
+                .and_then(|image| images.get(image))
+            else {
+                continue;
+            };
+
+            if image.texture_format != TextureFormat::R32Float
+                || !image
+                    .texture
+                    .usage()
+                    .contains(TextureUsages::STORAGE_BINDING)
+            {
+                if reported_terrains.insert(terrain) {
+                    warn!("The height overlay of the terrain can not be edited by the brush, which requires the `R32Float` format and the `STORAGE_BINDING` usage.");
+                }
+                continue;
+            }
+
+            let size = image.texture.size();
+
+            let overlay_view = image.texture.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2Array),
+                mip_level_count: Some(1),
+                ..default()
+            });
+            let source_view = device
+                .create_texture(&TextureDescriptor {
+                    label: Some("terrain_brush_source_heights"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::R32Float,
+                    usage: TextureUsages::STORAGE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2Array),
+                    ..default()
+                });
+
+            let stroke_bind_groups = strokes
+                .iter()
+                .map(|stroke| {
+                    let stroke_buffer = StaticBuffer::create(
+                        None,
+                        &device,
+                        &GpuBrushStroke::from(stroke),
+                        BufferUsages::UNIFORM,
+                    );
+
+                    device.create_bind_group(
+                        "terrain_brush_bind_group",
+                        &brush_pipeline.brush_layout,
+                        &BindGroupEntries::sequential((
+                            &overlay_view,
+                            &source_view,
+                            &stroke_buffer,
+                        )),
+                    )
+                })
+                .collect();
+
+            brush_items.insert(
+                terrain,
+                TerrainBrushItem {
+                    stroke_bind_groups,
+                    workgroup_count: UVec3::new(
+                        size.width.div_ceil(WORKGROUP_SIZE),
+                        size.height.div_ceil(WORKGROUP_SIZE),
+                        size.depth_or_array_layers,
+                    ),
+                },
+            );
+        }
+    }
+}
+
+/// Applies the pending brush strokes to the height overlays.
+///
+/// This is dispatched only in frames with brush strokes and is independent of the tiling prepass.
+pub struct TerrainBrushNode;
+
+impl render_graph::Node for TerrainBrushNode {
+    fn run<'w>(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        context: &mut RenderContext<'w>,
+        world: &'w World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let brush_items = world.resource::<TerrainComponents<TerrainBrushItem>>();
+        let brush_pipeline = world.resource::<TerrainBrushPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        if brush_items.is_empty() {
+            return Ok(());
+        }
+
+        let (Some(copy_pipeline), Some(pipeline)) = (
+            pipeline_cache.get_compute_pipeline(brush_pipeline.copy_pipeline),
+            pipeline_cache.get_compute_pipeline(brush_pipeline.brush_pipeline),
+        ) else {
+            return Ok(());
+        };
+
+        let mut compute_pass = context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor::default());
+
+        // The dispatches are synchronized, so each stroke reads the heights written by the previous one.
+        for brush_item in brush_items.values() {
+            let UVec3 { x, y, z } = brush_item.workgroup_count;
+
+            for bind_group in &brush_item.stroke_bind_groups {
+                compute_pass.set_bind_group(0, bind_group, &[]);
+
+                compute_pass.set_pipeline(copy_pipeline);
+                compute_pass.dispatch_workgroups(x, y, z);
+
+                compute_pass.set_pipeline(pipeline);
+                compute_pass.dispatch_workgroups(x, y, z);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -35,6 +35,8 @@ use std::{hash::Hash, marker::PhantomData};
 
 pub struct TerrainPipelineKey<M: Material> {
     pub flags: TerrainPipelineFlags,
+    /// The depth bias of the terrain, see [`TerrainConfig::depth_bias`](crate::terrain::TerrainConfig::depth_bias).
+    pub depth_bias: DepthBiasState,
     pub bind_group_data: M::Data,
 }
 
@@ -45,7 +47,9 @@ where
     M::Data: PartialEq,
 {
     fn eq(&self, other: &Self) -> bool {
-        self.flags == other.flags && self.bind_group_data == other.bind_group_data
+        self.flags == other.flags
+            && self.depth_bias == other.depth_bias
+            && self.bind_group_data == other.bind_group_data
     }
 }
 
@@ -56,6 +60,7 @@ where
     fn clone(&self) -> Self {
         Self {
             flags: self.flags,
+            depth_bias: self.depth_bias,
             bind_group_data: self.bind_group_data.clone(),
         }
     }
@@ -67,6 +72,7 @@ where
 {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.flags.hash(state);
+        self.depth_bias.hash(state);
         self.bind_group_data.hash(state);
     }
 }
@@ -74,7 +80,7 @@ where
 bitflags::bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
     #[repr(transparent)]
-    pub struct TerrainPipelineFlags: u32 {
+    pub struct TerrainPipelineFlags: u64 {
         const NONE               = 0;
         const SPHERICAL          = 1 <<  0;
         const WIREFRAME          = 1 <<  1;
@@ -90,19 +96,36 @@ bitflags::bitflags! {
         const LIGHTING           = 1 << 11;
         const SAMPLE_GRAD        = 1 << 12;
         const HIGH_PRECISION     = 1 << 13;
-        const TEST1              = 1 << 14;
-        const TEST2              = 1 << 15;
-        const TEST3              = 1 << 16;
+        const SHOW_LOD           = 1 << 14;
+        const TEST1              = 1 << 15;
+        const TEST2              = 1 << 16;
+        const TEST3              = 1 << 17;
+        const BICUBIC            = 1 << 18;
+        const ATMOSPHERE         = 1 << 19;
+        const SEA_LEVEL          = 1 << 20;
+        const HOLE_MASK          = 1 << 21;
+        const DETAIL_NOISE       = 1 << 22;
+        const DOUBLE_SIDED       = 1 << 23;
+        const SKIRTS             = 1 << 24;
+        const POINTS             = 1 << 25;
+        const FLAT_SHADING       = 1 << 26;
+        const HEIGHT_OVERLAY     = 1 << 27;
+        const CONTOUR_LINES      = 1 << 28;
+        const SHOW_SCREEN_ERROR  = 1 << 29;
+        const TILE_FADE          = 1 << 30;
+        const TILE_PICKING       = 1 << 31;
+        const SHOW_CULLED_TILES  = 1 << 32;
         const MSAA_RESERVED_BITS = TerrainPipelineFlags::MSAA_MASK_BITS << TerrainPipelineFlags::MSAA_SHIFT_BITS;
     }
 }
 
 impl TerrainPipelineFlags {
-    const MSAA_MASK_BITS: u32 = 0b111111;
-    const MSAA_SHIFT_BITS: u32 = 32 - 6;
+    // at most 8 samples are supported, which fit into three bits
+    const MSAA_MASK_BITS: u64 = 0b111;
+    const MSAA_SHIFT_BITS: u64 = 64 - 3;
 
     pub fn from_msaa_samples(msaa_samples: u32) -> Self {
-        let msaa_bits = ((msaa_samples - 1) & Self::MSAA_MASK_BITS) << Self::MSAA_SHIFT_BITS;
+        let msaa_bits = ((msaa_samples as u64 - 1) & Self::MSAA_MASK_BITS) << Self::MSAA_SHIFT_BITS;
         TerrainPipelineFlags::from_bits(msaa_bits).unwrap()
     }
 
@@ -130,6 +153,18 @@ impl TerrainPipelineFlags {
         if debug.show_normals {
             key |= TerrainPipelineFlags::SHOW_NORMALS;
         }
+        if debug.show_lod {
+            key |= TerrainPipelineFlags::SHOW_LOD;
+        }
+        if debug.show_screen_error {
+            key |= TerrainPipelineFlags::SHOW_SCREEN_ERROR;
+        }
+        if debug.show_culled_tiles {
+            key |= TerrainPipelineFlags::SHOW_CULLED_TILES;
+        }
+        if debug.tile_picking {
+            key |= TerrainPipelineFlags::TILE_PICKING;
+        }
         if debug.morph {
             key |= TerrainPipelineFlags::MORPH;
         }
@@ -148,6 +183,9 @@ impl TerrainPipelineFlags {
         if debug.high_precision {
             key |= TerrainPipelineFlags::HIGH_PRECISION;
         }
+        if debug.show_points {
+            key |= TerrainPipelineFlags::POINTS;
+        }
         if debug.test1 {
             key |= TerrainPipelineFlags::TEST1;
         }
@@ -162,22 +200,43 @@ impl TerrainPipelineFlags {
     }
 
     pub fn msaa_samples(&self) -> u32 {
-        ((self.bits() >> Self::MSAA_SHIFT_BITS) & Self::MSAA_MASK_BITS) + 1
+        ((self.bits() >> Self::MSAA_SHIFT_BITS) & Self::MSAA_MASK_BITS) as u32 + 1
     }
 
-    pub fn polygon_mode(&self) -> PolygonMode {
-        match self.contains(TerrainPipelineFlags::WIREFRAME) {
+    /// The line polygon mode is only used if it is supported by the device.
+    /// Otherwise, the wireframe is outlined in the fragment shader only.
+    pub fn polygon_mode(&self, polygon_mode_line: bool) -> PolygonMode {
+        match self.contains(TerrainPipelineFlags::WIREFRAME) && polygon_mode_line {
             true => PolygonMode::Line,
             false => PolygonMode::Fill,
         }
     }
 
+    /// The points view draws each vertex of the tiles as a point, instead of filling the triangles between them.
+    pub fn topology(&self) -> PrimitiveTopology {
+        match self.contains(TerrainPipelineFlags::POINTS) {
+            true => PrimitiveTopology::PointList,
+            false => PrimitiveTopology::TriangleStrip,
+        }
+    }
+
+    /// Double-sided terrains do not cull their back faces.
+    pub fn cull_mode(&self) -> Option<Face> {
+        match self.contains(TerrainPipelineFlags::DOUBLE_SIDED) {
+            true => None,
+            false => Some(Face::Back),
+        }
+    }
+
     pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
         let mut shader_defs = Vec::new();
 
         if self.contains(TerrainPipelineFlags::SPHERICAL) {
             shader_defs.push("SPHERICAL".into());
         }
+        if self.contains(TerrainPipelineFlags::WIREFRAME) {
+            shader_defs.push("WIREFRAME".into());
+        }
         if self.contains(TerrainPipelineFlags::SHOW_DATA_LOD) {
             shader_defs.push("SHOW_DATA_LOD".into());
         }
@@ -196,6 +255,15 @@ impl TerrainPipelineFlags {
         if self.contains(TerrainPipelineFlags::SHOW_NORMALS) {
             shader_defs.push("SHOW_NORMALS".into())
         }
+        if self.contains(TerrainPipelineFlags::SHOW_LOD) {
+            shader_defs.push("SHOW_LOD".into())
+        }
+        if self.contains(TerrainPipelineFlags::SHOW_SCREEN_ERROR) {
+            shader_defs.push("SHOW_SCREEN_ERROR".into())
+        }
+        if self.contains(TerrainPipelineFlags::SHOW_CULLED_TILES) {
+            shader_defs.push("SHOW_CULLED_TILES".into())
+        }
         if self.contains(TerrainPipelineFlags::MORPH) {
             shader_defs.push("MORPH".into());
         }
@@ -214,6 +282,42 @@ impl TerrainPipelineFlags {
         if self.contains(TerrainPipelineFlags::HIGH_PRECISION) {
             shader_defs.push("HIGH_PRECISION".into());
         }
+        if self.contains(TerrainPipelineFlags::BICUBIC) {
+            shader_defs.push("BICUBIC".into());
+        }
+        if self.contains(TerrainPipelineFlags::ATMOSPHERE) {
+            shader_defs.push("ATMOSPHERE".into());
+        }
+        if self.contains(TerrainPipelineFlags::SEA_LEVEL) {
+            shader_defs.push("SEA_LEVEL".into());
+        }
+        if self.contains(TerrainPipelineFlags::HOLE_MASK) {
+            shader_defs.push("HOLE_MASK".into());
+        }
+        if self.contains(TerrainPipelineFlags::DETAIL_NOISE) {
+            shader_defs.push("DETAIL_NOISE".into());
+        }
+        if self.contains(TerrainPipelineFlags::DOUBLE_SIDED) {
+            shader_defs.push("DOUBLE_SIDED".into());
+        }
+        if self.contains(TerrainPipelineFlags::SKIRTS) {
+            shader_defs.push("SKIRTS".into());
+        }
+        if self.contains(TerrainPipelineFlags::FLAT_SHADING) {
+            shader_defs.push("FLAT_SHADING".into());
+        }
+        if self.contains(TerrainPipelineFlags::HEIGHT_OVERLAY) {
+            shader_defs.push("HEIGHT_OVERLAY".into());
+        }
+        if self.contains(TerrainPipelineFlags::CONTOUR_LINES) {
+            shader_defs.push("CONTOUR_LINES".into());
+        }
+        if self.contains(TerrainPipelineFlags::TILE_FADE) {
+            shader_defs.push("TILE_FADE".into());
+        }
+        if self.contains(TerrainPipelineFlags::TILE_PICKING) {
+            shader_defs.push("TILE_PICKING".into());
+        }
         if self.contains(TerrainPipelineFlags::TEST1) {
             shader_defs.push("TEST1".into());
         }
@@ -238,6 +342,8 @@ pub struct TerrainRenderPipeline<M: Material> {
     pub(crate) material_layout: BindGroupLayout,
     pub vertex_shader: Handle<Shader>,
     pub fragment_shader: Handle<Shader>,
+    /// Whether the device supports the line polygon mode used by the wireframe view.
+    pub(crate) polygon_mode_line: bool,
     marker: PhantomData<M>,
 }
 
@@ -256,6 +362,7 @@ impl<M: Material> FromWorld for TerrainRenderPipeline<M> {
         let terrain_layout = create_terrain_layout(device);
         let terrain_view_layout = create_terrain_view_layout(device);
         let material_layout = M::bind_group_layout(device);
+        let polygon_mode_line = device.features().contains(WgpuFeatures::POLYGON_MODE_LINE);
 
         let vertex_shader = match M::vertex_shader() {
             ShaderRef::Default => asset_server.load(DEFAULT_VERTEX_SHADER),
@@ -277,6 +384,7 @@ impl<M: Material> FromWorld for TerrainRenderPipeline<M> {
             material_layout,
             vertex_shader,
             fragment_shader,
+            polygon_mode_line,
             marker: PhantomData,
         }
     }
@@ -319,11 +427,11 @@ where
             },
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
+                cull_mode: key.flags.cull_mode(),
                 unclipped_depth: false,
-                polygon_mode: key.flags.polygon_mode(),
+                polygon_mode: key.flags.polygon_mode(self.polygon_mode_line),
                 conservative: false,
-                topology: PrimitiveTopology::TriangleStrip,
+                topology: key.flags.topology(),
                 strip_index_format: None,
             },
             fragment: Some(FragmentState {
@@ -346,11 +454,7 @@ where
                     read_mask: 0,
                     write_mask: 0,
                 },
-                bias: DepthBiasState {
-                    constant: 0,
-                    slope_scale: 0.0,
-                    clamp: 0.0,
-                },
+                bias: key.depth_bias,
             }),
             multisample: MultisampleState {
                 count: key.flags.msaa_samples(),
@@ -395,11 +499,8 @@ pub(crate) fn queue_terrain<M: Material>(
         for (&terrain, &material_id) in render_material_instances.iter() {
             let gpu_tile_atlas = gpu_tile_atlases.get(&terrain).unwrap();
             if let Some(material) = render_materials.get(material_id) {
-                let mut flags = TerrainPipelineFlags::from_msaa_samples(msaa.samples());
-
-                if gpu_tile_atlas.is_spherical {
-                    flags |= TerrainPipelineFlags::SPHERICAL;
-                }
+                let mut flags = TerrainPipelineFlags::from_msaa_samples(msaa.samples())
+                    | gpu_tile_atlas.pipeline_flags();
 
                 if let Some(debug) = &debug {
                     flags |= TerrainPipelineFlags::from_debug(debug);
@@ -412,6 +513,7 @@ pub(crate) fn queue_terrain<M: Material>(
 
                 let key = TerrainPipelineKey {
                     flags,
+                    depth_bias: gpu_tile_atlas.depth_bias,
                     bind_group_data: material.key.clone(),
                 };
 
@@ -471,3 +573,25 @@ where
             .init_resource::<MaterialPipeline<M>>(); // prepare assets depends on this to access the material layout
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wireframe_flag_selects_the_line_polygon_mode() {
+        let wireframe = TerrainPipelineFlags::from_debug(&DebugTerrain {
+            wireframe: true,
+            ..default()
+        });
+        let solid = TerrainPipelineFlags::from_debug(&DebugTerrain::default());
+
+        assert_eq!(wireframe.polygon_mode(true), PolygonMode::Line);
+        assert_eq!(solid.polygon_mode(true), PolygonMode::Fill);
+
+        // without device support, the wireframe is only outlined in the fragment shader
+        assert_eq!(wireframe.polygon_mode(false), PolygonMode::Fill);
+        assert!(wireframe.shader_defs().contains(&"WIREFRAME".into()));
+        assert!(!solid.shader_defs().contains(&"WIREFRAME".into()));
+    }
+}
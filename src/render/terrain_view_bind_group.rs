@@ -1,6 +1,12 @@
 use crate::{
+    debug::DebugTerrain,
     math::{TerrainModelApproximation, TileCoordinate},
-    terrain_data::{gpu_tile_tree::GpuTileTree, tile_tree::TileTree},
+    terrain_data::{
+        gpu_tile_tree::GpuTileTree,
+        tile_tree::{
+            ScreenLods, TileCounts, TileTree, MAX_TILE_COUNT_ITERATIONS, SCREEN_LOD_GRID_SIZE,
+        },
+    },
     terrain_view::TerrainViewComponents,
     util::StaticBuffer,
 };
@@ -14,9 +20,17 @@ use bevy::{
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
         render_resource::{binding_types::*, *},
         renderer::{RenderDevice, RenderQueue},
-        Extract,
+        Extract, MainWorld,
     },
+    tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
 };
+use std::array;
+
+/// The size of the buffer storing the lod of each screen cell.
+const SCREEN_LOD_BUFFER_SIZE: BufferAddress =
+    (SCREEN_LOD_GRID_SIZE * SCREEN_LOD_GRID_SIZE) as BufferAddress * 4;
+/// The size of the buffer storing the depth, coordinate and pixel of the picked tile.
+const PICKED_TILE_BUFFER_SIZE: BufferAddress = 7 * 4;
 
 pub(crate) fn create_prepare_indirect_layout(device: &RenderDevice) -> BindGroupLayout {
     device.create_bind_group_layout(
@@ -41,6 +55,7 @@ pub(crate) fn create_refine_tiles_layout(device: &RenderDevice) -> BindGroupLayo
                 storage_buffer_sized(false, None),                 // final tiles
                 storage_buffer_sized(false, None),                 // temporary tiles
                 storage_buffer::<Parameters>(false),               // parameters
+                storage_buffer_sized(false, None),                 // screen lods
             ),
         ),
     )
@@ -57,6 +72,7 @@ pub(crate) fn create_terrain_view_layout(device: &RenderDevice) -> BindGroupLayo
                 storage_buffer_read_only_sized(false, None),       // tile_tree
                 storage_buffer_read_only_sized(false, None),       // origins
                 storage_buffer_read_only_sized(false, None),       // tiles
+                storage_buffer_sized(false, None).visibility(ShaderStages::FRAGMENT), // picked tile
             ),
         ),
     )
@@ -71,11 +87,15 @@ pub(crate) struct Indirect {
 }
 
 #[derive(Default, ShaderType)]
-struct Parameters {
+pub(super) struct Parameters {
     tile_count: u32,
     counter: i32,
     child_index: i32,
     final_index: i32,
+    /// The count of refinement iterations, which subdivided at least one tile.
+    used_refinement_count: u32,
+    /// The count of tiles, into which each refinement iteration subdivided.
+    iteration_tile_counts: [u32; MAX_TILE_COUNT_ITERATIONS],
 }
 
 #[derive(Default, ShaderType)]
@@ -93,24 +113,52 @@ struct TerrainViewConfigUniform {
     morph_range: f32,
     blend_range: f32,
     precision_threshold_distance: f32,
+    /// Tiles below this lod are always subdivided.
+    min_lod: u32,
+    /// The mip level forced by [`DebugTerrain::force_mip_level`], or a negative value if none is forced.
+    forced_mip_level: f32,
+    /// The lod distances of the view, packed into vectors to satisfy the uniform array stride.
+    lod_distances: [Vec4; 8],
+    /// The pixel, at which the drawn tile is picked, or `u32::MAX` if none is picked.
+    pick_position: UVec2,
 }
 
 impl TerrainViewConfigUniform {
-    fn from_tile_tree(tile_tree: &TileTree) -> Self {
+    fn from_tile_tree(tile_tree: &TileTree, debug: Option<&DebugTerrain>) -> Self {
+        let lod_bias_scale = tile_tree.lod_bias_scale();
+
+        let mut lod_distances = [0.0; 32];
+
+        for (lod_distance, &distance) in lod_distances
+            .iter_mut()
+            .zip(tile_tree.lod_distances.iter().flatten())
+        {
+            *lod_distance = (distance * lod_bias_scale) as f32;
+        }
+
+        // the skirts extend the grid by one ring of vertices, which is collapsed onto the tile edges
+        let grid_size = tile_tree.grid_size + if tile_tree.skirts { 2 } else { 0 };
+
         TerrainViewConfigUniform {
             tree_size: tile_tree.tree_size,
             geometry_tile_count: tile_tree.geometry_tile_count,
             refinement_count: tile_tree.refinement_count,
             grid_size: tile_tree.grid_size as f32,
-            vertices_per_row: 2 * (tile_tree.grid_size + 2),
-            vertices_per_tile: 2 * tile_tree.grid_size * (tile_tree.grid_size + 2),
-            morph_distance: tile_tree.morph_distance as f32,
+            vertices_per_row: 2 * (grid_size + 2),
+            vertices_per_tile: 2 * grid_size * (grid_size + 2),
+            morph_distance: (tile_tree.morph_distance * lod_bias_scale) as f32,
             blend_distance: tile_tree.blend_distance as f32,
             load_distance: tile_tree.load_distance as f32,
-            subdivision_distance: tile_tree.subdivision_distance as f32,
+            subdivision_distance: (tile_tree.subdivision_distance * lod_bias_scale) as f32,
             precision_threshold_distance: tile_tree.precision_threshold_distance as f32,
             morph_range: tile_tree.morph_range,
             blend_range: tile_tree.blend_range,
+            min_lod: tile_tree.force_lod.unwrap_or(0),
+            forced_mip_level: debug
+                .and_then(|debug| debug.force_mip_level)
+                .map_or(-1.0, |level| level as f32),
+            lod_distances: array::from_fn(|i| Vec4::from_slice(&lod_distances[4 * i..])),
+            pick_position: tile_tree.pick_position.unwrap_or(UVec2::MAX),
         }
     }
 }
@@ -119,9 +167,38 @@ pub struct TerrainViewData {
     view_config_buffer: StaticBuffer<TerrainViewConfigUniform>,
     terrain_model_approximation_buffer: StaticBuffer<TerrainModelApproximation>,
     pub(super) indirect_buffer: StaticBuffer<Indirect>,
+    pub(super) parameter_buffer: StaticBuffer<Parameters>,
+    /// The buffer, into which the parameters are copied after the tiling prepass, while debugging.
+    pub(super) parameter_readback_buffer: Option<StaticBuffer<()>>,
+    /// Reads back the tile counts and the count of refinement iterations used by the last tiling prepass.
+    parameter_readback: Option<Task<(TileCounts, u32)>>,
+    /// The used refinement count, which has been read back, but not moved into the [`TileTree`] yet.
+    used_refinement_count: Option<u32>,
+    /// Whether the tile counts are read back, see [`TileTree::tile_counts`].
+    tile_count_readback: bool,
+    /// The tile counts, which have been read back, but not moved into the [`TileTree`] yet.
+    tile_counts: Option<TileCounts>,
+    /// Whether the lods selected by the tiling prepass are read back, see [`TileTree::screen_lods`].
+    pub(super) screen_lod_readback: bool,
+    /// Stores the finest lod per screen cell, which the tiling prepass writes into.
+    pub(super) screen_lod_buffer: StaticBuffer<()>,
+    /// The buffer, into which the screen lods are copied after the tiling prepass.
+    pub(super) screen_lod_readback_buffer: Option<StaticBuffer<()>>,
+    /// Reads back the screen lods of the last tiling prepass.
+    screen_lod_task: Option<Task<ScreenLods>>,
+    /// Whether the tile drawn at the pick position is recorded, see [`TileTree::pick_tile`].
+    pub(super) tile_picking: bool,
+    /// Stores the tile closest to the view, which the fragment shader drew at the pick position.
+    pub(super) picked_tile_buffer: StaticBuffer<()>,
+    /// The buffer, into which the picked tile of the previous frame is copied before the tiling prepass.
+    pub(super) picked_tile_readback_buffer: Option<StaticBuffer<()>>,
+    /// Reads back the picked tile and the pixel it was picked at.
+    picked_tile_task: Option<Task<Option<(UVec2, TileCoordinate)>>>,
     pub(super) prepare_indirect_bind_group: BindGroup,
     pub(super) refine_tiles_bind_group: BindGroup,
     pub(super) terrain_view_bind_group: BindGroup,
+    /// The priority of the view in the tiling prepass.
+    pub(super) priority: i32,
 }
 
 impl TerrainViewData {
@@ -134,12 +211,27 @@ impl TerrainViewData {
             StaticBuffer::empty(None, device, BufferUsages::UNIFORM | BufferUsages::COPY_DST);
         let indirect_buffer =
             StaticBuffer::empty(None, device, BufferUsages::STORAGE | BufferUsages::INDIRECT);
-        let parameter_buffer =
-            StaticBuffer::<Parameters>::empty(None, device, BufferUsages::STORAGE);
+        let parameter_buffer = StaticBuffer::<Parameters>::empty(
+            None,
+            device,
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        );
         let temporary_tile_buffer =
             StaticBuffer::<()>::empty_sized(None, device, tile_buffer_size, BufferUsages::STORAGE);
         let final_tile_buffer =
             StaticBuffer::<()>::empty_sized(None, device, tile_buffer_size, BufferUsages::STORAGE);
+        let screen_lod_buffer = StaticBuffer::<()>::empty_sized(
+            None,
+            device,
+            SCREEN_LOD_BUFFER_SIZE,
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        );
+        let picked_tile_buffer = StaticBuffer::<()>::empty_sized(
+            None,
+            device,
+            PICKED_TILE_BUFFER_SIZE,
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        );
         let terrain_model_approximation_buffer = StaticBuffer::<TerrainModelApproximation>::empty(
             None,
             device,
@@ -162,6 +254,7 @@ impl TerrainViewData {
                 &final_tile_buffer,
                 &temporary_tile_buffer,
                 &parameter_buffer,
+                &screen_lod_buffer,
             )),
         );
         let terrain_view_bind_group = device.create_bind_group(
@@ -173,6 +266,7 @@ impl TerrainViewData {
                 &gpu_tile_tree.tile_tree_buffer,
                 &gpu_tile_tree.origins_buffer,
                 &final_tile_buffer,
+                &picked_tile_buffer,
             )),
         );
 
@@ -180,9 +274,24 @@ impl TerrainViewData {
             view_config_buffer,
             terrain_model_approximation_buffer,
             indirect_buffer,
+            parameter_buffer,
+            parameter_readback_buffer: None,
+            parameter_readback: None,
+            used_refinement_count: None,
+            tile_count_readback: tile_tree.tile_count_readback,
+            tile_counts: None,
+            screen_lod_readback: tile_tree.screen_lod_readback,
+            screen_lod_buffer,
+            screen_lod_readback_buffer: None,
+            screen_lod_task: None,
+            tile_picking: false,
+            picked_tile_buffer,
+            picked_tile_readback_buffer: None,
+            picked_tile_task: None,
             prepare_indirect_bind_group,
             refine_tiles_bind_group,
             terrain_view_bind_group,
+            priority: tile_tree.priority,
         }
     }
 
@@ -198,7 +307,7 @@ impl TerrainViewData {
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter() {
             if terrain_view_data.contains_key(&(terrain, view)) {
-                return;
+                continue;
             }
 
             let gpu_tile_tree = gpu_tile_trees.get(&(terrain, view)).unwrap();
@@ -216,13 +325,16 @@ impl TerrainViewData {
         terrain_model_approximations: Extract<
             Res<TerrainViewComponents<TerrainModelApproximation>>,
         >,
+        debug: Extract<Option<Res<DebugTerrain>>>,
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter() {
             let terrain_view_data = terrain_view_data.get_mut(&(terrain, view)).unwrap();
 
-            terrain_view_data
-                .view_config_buffer
-                .set_value(TerrainViewConfigUniform::from_tile_tree(tile_tree));
+            terrain_view_data.priority = tile_tree.priority;
+            terrain_view_data.tile_picking = debug.as_ref().is_some_and(|debug| debug.tile_picking);
+            terrain_view_data.view_config_buffer.set_value(
+                TerrainViewConfigUniform::from_tile_tree(tile_tree, debug.as_deref()),
+            );
 
             terrain_view_data
                 .terrain_model_approximation_buffer
@@ -236,12 +348,213 @@ impl TerrainViewData {
     }
 
     pub(crate) fn prepare(
+        device: Res<RenderDevice>,
         queue: Res<RenderQueue>,
+        debug: Option<Res<DebugTerrain>>,
         mut terrain_view_data: ResMut<TerrainViewComponents<TerrainViewData>>,
     ) {
         for data in &mut terrain_view_data.values_mut() {
             data.view_config_buffer.update(&queue);
             data.terrain_model_approximation_buffer.update(&queue);
+
+            data.check_parameters();
+
+            // Only a single readback is in flight at a time, so that the buffers are not reused while mapped.
+            if (debug.is_some() || data.tile_count_readback) && data.parameter_readback.is_none() {
+                data.parameter_readback_buffer = Some(StaticBuffer::empty_sized(
+                    "parameter_readback_buffer",
+                    &device,
+                    Parameters::min_size().get(),
+                    BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                ));
+            }
+
+            if data.screen_lod_readback && data.screen_lod_task.is_none() {
+                data.screen_lod_readback_buffer = Some(StaticBuffer::empty_sized(
+                    "screen_lod_readback_buffer",
+                    &device,
+                    SCREEN_LOD_BUFFER_SIZE,
+                    BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                ));
+            }
+
+            if data.tile_picking && data.picked_tile_task.is_none() {
+                data.picked_tile_readback_buffer = Some(StaticBuffer::empty_sized(
+                    "picked_tile_readback_buffer",
+                    &device,
+                    PICKED_TILE_BUFFER_SIZE,
+                    BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                ));
+            }
+        }
+    }
+
+    /// Moves the screen lods, tile counts, used refinement counts and picked tiles, which have finished reading back, into the corresponding [`TileTree`]s.
+    pub(crate) fn extract_readbacks(
+        mut main_world: ResMut<MainWorld>,
+        mut terrain_view_data: ResMut<TerrainViewComponents<TerrainViewData>>,
+    ) {
+        let mut tile_trees = main_world.resource_mut::<TerrainViewComponents<TileTree>>();
+
+        for (&(terrain, view), data) in terrain_view_data.iter_mut() {
+            let Some(tile_tree) = tile_trees.get_mut(&(terrain, view)) else {
+                continue;
+            };
+
+            if let Some(used_refinement_count) = data.used_refinement_count.take() {
+                tile_tree.used_refinement_count = Some(used_refinement_count);
+            }
+
+            if let Some(tile_counts) = data.tile_counts.take() {
+                tile_tree.tile_counts = Some(tile_counts);
+            }
+
+            if let Some(task) = &mut data.picked_tile_task {
+                if let Some(picked_tile) = future::block_on(future::poll_once(task)) {
+                    data.picked_tile_task = None;
+                    tile_tree.picked_tile = picked_tile;
+                }
+            }
+
+            let Some(task) = &mut data.screen_lod_task else {
+                continue;
+            };
+
+            let Some(screen_lods) = future::block_on(future::poll_once(task)) else {
+                continue;
+            };
+
+            data.screen_lod_task = None;
+            tile_tree.screen_lods = Some(screen_lods);
+        }
+    }
+
+    /// Warns, if the tiling prepass emitted nearly as many tiles as fit into the tile buffers.
+    /// Tiles beyond the geometry tile count are dropped, which leaves holes in the terrain.
+    /// Also stores the used refinement count and the tile counts, which are moved into the [`TileTree`] during the next extraction.
+    fn check_parameters(&mut self) {
+        let Some(task) = &mut self.parameter_readback else {
+            return;
+        };
+
+        let Some((tile_counts, used_refinement_count)) = future::block_on(future::poll_once(task))
+        else {
+            return;
+        };
+
+        self.parameter_readback = None;
+        self.used_refinement_count = Some(used_refinement_count);
+
+        let tile_count = tile_counts.final_tile_count;
+
+        if self.tile_count_readback {
+            self.tile_counts = Some(tile_counts);
+        }
+
+        let capacity = self.view_config_buffer.value().geometry_tile_count;
+
+        if tile_count as u64 * 10 >= capacity as u64 * 9 {
+            warn_once!(
+                "The tiling prepass emitted {tile_count} tiles, which is close to or exceeds the geometry tile count of {capacity}. \
+                 Tiles beyond it are dropped, so increase the geometry tile count of the terrain view config."
+            );
+        }
+    }
+
+    /// Starts reading back the parameters, screen lods and picked tiles, which have been copied during the tiling prepass.
+    pub(crate) fn cleanup(mut terrain_view_data: ResMut<TerrainViewComponents<TerrainViewData>>) {
+        for data in &mut terrain_view_data.values_mut() {
+            let Some(readback_buffer) = data.parameter_readback_buffer.take() else {
+                continue;
+            };
+
+            data.parameter_readback = Some(AsyncComputeTaskPool::get().spawn(async move {
+                let (tx, rx) = async_channel::bounded(1);
+
+                let buffer_slice = readback_buffer.slice(..);
+
+                buffer_slice.map_async(MapMode::Read, move |_| {
+                    tx.try_send(()).unwrap();
+                });
+
+                rx.recv().await.unwrap();
+
+                let parameters: Vec<i32> =
+                    bytemuck::pod_collect_to_vec(&buffer_slice.get_mapped_range());
+
+                readback_buffer.unmap();
+
+                let used_refinement_count = parameters[4] as u32;
+                let recorded_count =
+                    (used_refinement_count as usize).min(MAX_TILE_COUNT_ITERATIONS);
+
+                let tile_counts = TileCounts {
+                    // the final index counts all emitted tiles, including the dropped ones
+                    final_tile_count: parameters[3].max(0) as u32,
+                    iteration_tile_counts: parameters[5..5 + recorded_count]
+                        .iter()
+                        .map(|&count| count as u32)
+                        .collect(),
+                };
+
+                (tile_counts, used_refinement_count)
+            }));
+        }
+
+        for data in &mut terrain_view_data.values_mut() {
+            let Some(readback_buffer) = data.screen_lod_readback_buffer.take() else {
+                continue;
+            };
+
+            data.screen_lod_task = Some(AsyncComputeTaskPool::get().spawn(async move {
+                let (tx, rx) = async_channel::bounded(1);
+
+                let buffer_slice = readback_buffer.slice(..);
+
+                buffer_slice.map_async(MapMode::Read, move |_| {
+                    tx.try_send(()).unwrap();
+                });
+
+                rx.recv().await.unwrap();
+
+                let screen_lods =
+                    ScreenLods::from_cells(bytemuck::cast_slice(&buffer_slice.get_mapped_range()));
+
+                readback_buffer.unmap();
+
+                screen_lods
+            }));
+        }
+
+        for data in &mut terrain_view_data.values_mut() {
+            let Some(readback_buffer) = data.picked_tile_readback_buffer.take() else {
+                continue;
+            };
+
+            data.picked_tile_task = Some(AsyncComputeTaskPool::get().spawn(async move {
+                let (tx, rx) = async_channel::bounded(1);
+
+                let buffer_slice = readback_buffer.slice(..);
+
+                buffer_slice.map_async(MapMode::Read, move |_| {
+                    tx.try_send(()).unwrap();
+                });
+
+                rx.recv().await.unwrap();
+
+                let [depth, side, lod, x, y, pixel_x, pixel_y]: [u32; 7] =
+                    bytemuck::pod_read_unaligned(&buffer_slice.get_mapped_range());
+
+                readback_buffer.unmap();
+
+                // fragments have a positive depth (reverse z), so a depth of zero means no tile was drawn at the pixel
+                (depth != 0).then(|| {
+                    (
+                        UVec2::new(pixel_x, pixel_y),
+                        TileCoordinate::new(side, lod, x, y),
+                    )
+                })
+            }));
         }
     }
 }
@@ -12,15 +12,26 @@ use crate::{
     terrain::TerrainComponents,
     terrain_data::gpu_tile_atlas::GpuTileAtlas,
     terrain_view::TerrainViewComponents,
+    util::report_pipeline_error,
 };
 use bevy::{
     prelude::*,
     render::{
+        diagnostic::RecordDiagnostics,
         render_graph::{self, RenderLabel},
         render_resource::*,
         renderer::{RenderContext, RenderDevice},
     },
+    utils::HashSet,
 };
+use std::cmp::Reverse;
+
+/// The name of the diagnostic span of the tiling prepass.
+///
+/// The span is recorded into the [`DiagnosticsStore`](bevy::diagnostic::DiagnosticsStore), while [`DebugTerrain::gpu_timings`] is enabled,
+/// next to the `main_opaque_pass_3d` span containing the terrain draws. Both are summarized in the [`TerrainGpuTimings`](crate::debug::TerrainGpuTimings).
+/// The GPU time is only recorded if the device supports timestamp queries.
+pub const TILING_PREPASS_DIAGNOSTIC: &str = "tiling_prepass";
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 pub struct TilingPrepassLabel;
@@ -35,49 +46,91 @@ bitflags::bitflags! {
         const PREPARE_NEXT   = 1 << 2;
         const PREPARE_RENDER = 1 << 3;
         const SPHERICAL      = 1 << 4;
-        const TEST1          = 1 << 5;
-        const TEST2          = 1 << 6;
-        const TEST3          = 1 << 7;
+        const CULLING        = 1 << 5;
+        const TEST1          = 1 << 6;
+        const TEST2          = 1 << 7;
+        const TEST3          = 1 << 8;
+        const HOLE_MASK      = 1 << 9;
+        const LOD_DISTANCES  = 1 << 10;
+        const SCREEN_LODS    = 1 << 11;
+        const SHOW_CULLED    = 1 << 12;
+        // the upper bits are reserved for custom flags
+        const _              = !0;
     }
 }
 
 impl TilingPrepassPipelineKey {
-    pub fn from_debug(debug: &DebugTerrain) -> Self {
-        let mut key = TilingPrepassPipelineKey::NONE;
+    /// The index of the first bit, that is reserved for custom flags.
+    pub const CUSTOM_BIT_OFFSET: u32 = 16;
+
+    /// The built-in flags and their corresponding shader defs.
+    const SHADER_DEFS: [(Self, &'static str); 9] = [
+        (Self::SPHERICAL, "SPHERICAL"),
+        (Self::CULLING, "CULLING"),
+        (Self::HOLE_MASK, "HOLE_MASK"),
+        (Self::LOD_DISTANCES, "LOD_DISTANCES"),
+        (Self::SCREEN_LODS, "SCREEN_LODS"),
+        (Self::SHOW_CULLED, "SHOW_CULLED_TILES"),
+        (Self::TEST1, "TEST1"),
+        (Self::TEST2, "TEST2"),
+        (Self::TEST3, "TEST3"),
+    ];
+
+    /// Returns the custom flag with the given index.
+    /// Use [`TilingPrepassPipelines::register_shader_def`] to map it to a shader def.
+    pub const fn custom(index: u32) -> Self {
+        assert!(index < 32 - Self::CUSTOM_BIT_OFFSET);
+        Self::from_bits_retain(1 << (Self::CUSTOM_BIT_OFFSET + index))
+    }
 
-        if debug.test1 {
-            key |= TilingPrepassPipelineKey::TEST1;
-        }
-        if debug.test2 {
-            key |= TilingPrepassPipelineKey::TEST2;
-        }
-        if debug.test3 {
-            key |= TilingPrepassPipelineKey::TEST3;
-        }
+    /// Enables or disables the given flags.
+    pub fn with(mut self, flags: Self, enabled: bool) -> Self {
+        self.set(flags, enabled);
+        self
+    }
 
-        key
+    pub fn spherical(self, enabled: bool) -> Self {
+        self.with(Self::SPHERICAL, enabled)
     }
 
-    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
-        let mut shader_defs = Vec::new();
+    pub fn culling(self, enabled: bool) -> Self {
+        self.with(Self::CULLING, enabled)
+    }
 
-        if self.contains(TilingPrepassPipelineKey::SPHERICAL) {
-            shader_defs.push("SPHERICAL".into());
-        }
-        if self.contains(TilingPrepassPipelineKey::TEST1) {
-            shader_defs.push("TEST1".into());
-        }
-        if self.contains(TilingPrepassPipelineKey::TEST2) {
-            shader_defs.push("TEST2".into());
-        }
-        if self.contains(TilingPrepassPipelineKey::TEST3) {
-            shader_defs.push("TEST3".into());
-        }
+    pub fn from_debug(debug: &DebugTerrain) -> Self {
+        TilingPrepassPipelineKey::NONE
+            .culling(debug.frustum_culling)
+            .with(
+                TilingPrepassPipelineKey::SHOW_CULLED,
+                debug.show_culled_tiles,
+            )
+            .with(TilingPrepassPipelineKey::TEST1, debug.test1)
+            .with(TilingPrepassPipelineKey::TEST2, debug.test2)
+            .with(TilingPrepassPipelineKey::TEST3, debug.test3)
+    }
 
-        shader_defs
+    /// Returns the shader defs of the built-in flags.
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        Self::SHADER_DEFS
+            .iter()
+            .filter(|&&(flag, _)| self.contains(flag))
+            .map(|&(_, shader_def)| shader_def.into())
+            .collect()
     }
 }
 
+/// The specialization key of the tiling prepass pipelines.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TilingPrepassKey {
+    pub flags: TilingPrepassPipelineKey,
+    /// The additional shader defs of the terrain.
+    pub shader_defs: Vec<ShaderDefVal>,
+    /// The shader replacing the built-in refinement shader, if any.
+    pub refine_tiles_shader: Option<Handle<Shader>>,
+    /// The shader replacing the built-in prepare prepass shader, if any.
+    pub prepare_prepass_shader: Option<Handle<Shader>>,
+}
+
 pub(crate) struct TilingPrepassItem {
     refine_tiles_pipeline: CachedComputePipelineId,
     prepare_root_pipeline: CachedComputePipelineId,
@@ -112,6 +165,18 @@ pub struct TilingPrepassPipelines {
     terrain_layout: BindGroupLayout,
     prepare_prepass_shader: Handle<Shader>,
     refine_tiles_shader: Handle<Shader>,
+    custom_shader_defs: Vec<(TilingPrepassPipelineKey, ShaderDefVal)>,
+}
+
+impl TilingPrepassPipelines {
+    /// Registers a shader def, that is added to the prepass shaders whenever all of the flags are set.
+    pub fn register_shader_def(
+        &mut self,
+        flags: TilingPrepassPipelineKey,
+        shader_def: impl Into<ShaderDefVal>,
+    ) {
+        self.custom_shader_defs.push((flags, shader_def.into()));
+    }
 }
 
 impl FromWorld for TilingPrepassPipelines {
@@ -134,19 +199,36 @@ impl FromWorld for TilingPrepassPipelines {
             terrain_layout,
             prepare_prepass_shader,
             refine_tiles_shader,
+            custom_shader_defs: default(),
         }
     }
 }
 
 impl SpecializedComputePipeline for TilingPrepassPipelines {
-    type Key = TilingPrepassPipelineKey;
+    type Key = TilingPrepassKey;
 
     fn specialize(&self, key: Self::Key) -> ComputePipelineDescriptor {
         let mut layout = default();
         let mut shader = default();
         let mut entry_point = default();
 
-        let shader_defs = key.shader_defs();
+        let mut shader_defs = key.flags.shader_defs();
+        shader_defs.extend(
+            self.custom_shader_defs
+                .iter()
+                .filter(|(flags, _)| key.flags.contains(*flags))
+                .map(|(_, shader_def)| shader_def.clone()),
+        );
+        shader_defs.extend(key.shader_defs);
+
+        let refine_tiles_shader = key
+            .refine_tiles_shader
+            .unwrap_or_else(|| self.refine_tiles_shader.clone());
+        let prepare_prepass_shader = key
+            .prepare_prepass_shader
+            .unwrap_or_else(|| self.prepare_prepass_shader.clone());
+
+        let key = key.flags;
 
         if key.contains(TilingPrepassPipelineKey::REFINE_TILES) {
             layout = vec![
@@ -154,7 +236,7 @@ impl SpecializedComputePipeline for TilingPrepassPipelines {
                 self.terrain_layout.clone(),
                 self.refine_tiles_layout.clone(),
             ];
-            shader = self.refine_tiles_shader.clone();
+            shader = refine_tiles_shader.clone();
             entry_point = "refine_tiles".into();
         }
         if key.contains(TilingPrepassPipelineKey::PREPARE_ROOT) {
@@ -164,7 +246,7 @@ impl SpecializedComputePipeline for TilingPrepassPipelines {
                 self.refine_tiles_layout.clone(),
                 self.prepare_indirect_layout.clone(),
             ];
-            shader = self.prepare_prepass_shader.clone();
+            shader = prepare_prepass_shader.clone();
             entry_point = "prepare_root".into();
         }
         if key.contains(TilingPrepassPipelineKey::PREPARE_NEXT) {
@@ -174,7 +256,7 @@ impl SpecializedComputePipeline for TilingPrepassPipelines {
                 self.refine_tiles_layout.clone(),
                 self.prepare_indirect_layout.clone(),
             ];
-            shader = self.prepare_prepass_shader.clone();
+            shader = prepare_prepass_shader.clone();
             entry_point = "prepare_next".into();
         }
         if key.contains(TilingPrepassPipelineKey::PREPARE_RENDER) {
@@ -184,7 +266,7 @@ impl SpecializedComputePipeline for TilingPrepassPipelines {
                 self.refine_tiles_layout.clone(),
                 self.prepare_indirect_layout.clone(),
             ];
-            shader = self.prepare_prepass_shader.clone();
+            shader = prepare_prepass_shader.clone();
             entry_point = "prepare_render".into();
         }
 
@@ -214,19 +296,58 @@ impl render_graph::Node for TilingPrepassNode {
         let terrain_data = world.resource::<TerrainComponents<TerrainData>>();
         let terrain_view_data = world.resource::<TerrainViewComponents<TerrainViewData>>();
         let culling_bind_groups = world.resource::<TerrainViewComponents<CullingBindGroup>>();
+        let gpu_tile_atlases = world.resource::<TerrainComponents<GpuTileAtlas>>();
         let debug = world.get_resource::<DebugTerrain>();
 
         if debug.map(|debug| debug.freeze).unwrap_or(false) {
             return Ok(());
         }
 
+        // process the views with the highest priority first and views of equal priority in a deterministic order
+        let mut prepass_items = prepass_items.iter().collect::<Vec<_>>();
+        prepass_items.sort_by_key(|&(&(terrain, view), _)| {
+            let priority = terrain_view_data.get(&(terrain, view)).unwrap().priority;
+            (Reverse(priority), terrain, view)
+        });
+
+        let gpu_timings = debug.is_some_and(|debug| debug.gpu_timings);
+        let diagnostics = context.diagnostic_recorder();
+
         context.add_command_buffer_generation_task(move |device| {
             let mut command_encoder =
                 device.create_command_encoder(&CommandEncoderDescriptor::default());
+
+            for view_data in terrain_view_data.values() {
+                if view_data.screen_lod_readback {
+                    command_encoder.clear_buffer(&view_data.screen_lod_buffer, 0, None);
+                }
+
+                // the tile is picked while drawing the terrain, so the buffer still holds the one of the previous frame
+                if let Some(readback_buffer) = &view_data.picked_tile_readback_buffer {
+                    command_encoder.copy_buffer_to_buffer(
+                        &view_data.picked_tile_buffer,
+                        0,
+                        readback_buffer,
+                        0,
+                        readback_buffer.size(),
+                    );
+                }
+
+                if view_data.tile_picking {
+                    command_encoder.clear_buffer(&view_data.picked_tile_buffer, 0, None);
+                }
+            }
+
             let mut compute_pass =
                 command_encoder.begin_compute_pass(&ComputePassDescriptor::default());
+            let pass_span = gpu_timings
+                .then(|| diagnostics.pass_span(&mut compute_pass, TILING_PREPASS_DIAGNOSTIC));
+
+            for (&(terrain, view), prepass_item) in prepass_items {
+                if gpu_tile_atlases.get(&terrain).unwrap().frozen {
+                    continue;
+                }
 
-            for (&(terrain, view), prepass_item) in prepass_items.iter() {
                 let Some((
                     refine_tiles_pipeline,
                     prepare_root_pipeline,
@@ -241,6 +362,9 @@ impl render_graph::Node for TilingPrepassNode {
                 let terrain_data = terrain_data.get(&terrain).unwrap();
                 let view_data = terrain_view_data.get(&(terrain, view)).unwrap();
 
+                // label the dispatches of each terrain and view, which makes GPU captures (e.g. RenderDoc) readable
+                compute_pass.push_debug_group(&format!("terrain {terrain} view {view}"));
+
                 compute_pass.set_bind_group(0, culling_bind_group, &[]);
                 compute_pass.set_bind_group(1, &terrain_data.terrain_bind_group, &[]);
                 compute_pass.set_bind_group(2, &view_data.refine_tiles_bind_group, &[]);
@@ -262,9 +386,37 @@ impl render_graph::Node for TilingPrepassNode {
 
                 compute_pass.set_pipeline(prepare_render_pipeline);
                 compute_pass.dispatch_workgroups(1, 1, 1);
+
+                compute_pass.pop_debug_group();
             }
 
+            if let Some(pass_span) = pass_span {
+                pass_span.end(&mut compute_pass);
+            }
             drop(compute_pass);
+
+            for view_data in terrain_view_data.values() {
+                if let Some(readback_buffer) = &view_data.parameter_readback_buffer {
+                    command_encoder.copy_buffer_to_buffer(
+                        &view_data.parameter_buffer,
+                        0,
+                        readback_buffer,
+                        0,
+                        readback_buffer.size(),
+                    );
+                }
+
+                if let Some(readback_buffer) = &view_data.screen_lod_readback_buffer {
+                    command_encoder.copy_buffer_to_buffer(
+                        &view_data.screen_lod_buffer,
+                        0,
+                        readback_buffer,
+                        0,
+                        readback_buffer.size(),
+                    );
+                }
+            }
+
             command_encoder.finish()
         });
 
@@ -280,41 +432,84 @@ pub(crate) fn queue_tiling_prepass(
     mut prepass_items: ResMut<TerrainViewComponents<TilingPrepassItem>>,
     gpu_tile_trees: Res<TerrainViewComponents<GpuTileTree>>,
     gpu_tile_atlases: Res<TerrainComponents<GpuTileAtlas>>,
+    mut reported_errors: Local<HashSet<CachedComputePipelineId>>,
 ) {
-    for &(terrain, view) in gpu_tile_trees.keys() {
-        let gpu_tile_atlas = gpu_tile_atlases.get(&terrain).unwrap();
+    for (&(terrain, view), gpu_tile_tree) in gpu_tile_trees.iter() {
+        // skip the prepass of terrains outside of the view, which keeps their previous tiles
+        if !gpu_tile_tree.visible {
+            prepass_items.remove(&(terrain, view));
+            continue;
+        }
 
-        let mut key = TilingPrepassPipelineKey::NONE;
+        let gpu_tile_atlas = gpu_tile_atlases.get(&terrain).unwrap();
 
-        if gpu_tile_atlas.is_spherical {
-            key |= TilingPrepassPipelineKey::SPHERICAL;
-        }
+        let mut key = gpu_tile_atlas
+            .prepass_pipeline_flags()
+            .with(
+                TilingPrepassPipelineKey::LOD_DISTANCES,
+                gpu_tile_tree.lod_distances,
+            )
+            .with(
+                TilingPrepassPipelineKey::SCREEN_LODS,
+                gpu_tile_tree.screen_lod_readback,
+            );
 
         if let Some(debug) = &debug {
             key |= TilingPrepassPipelineKey::from_debug(debug);
+        } else {
+            key |= TilingPrepassPipelineKey::CULLING;
         }
 
         let refine_tiles_pipeline = pipelines.specialize(
             &pipeline_cache,
             &prepass_pipelines,
-            key | TilingPrepassPipelineKey::REFINE_TILES,
+            TilingPrepassKey {
+                flags: key | TilingPrepassPipelineKey::REFINE_TILES,
+                shader_defs: gpu_tile_atlas.prepass_shader_defs.clone(),
+                refine_tiles_shader: gpu_tile_atlas.refine_tiles_shader.clone(),
+                prepare_prepass_shader: gpu_tile_atlas.prepare_prepass_shader.clone(),
+            },
         );
         let prepare_root_pipeline = pipelines.specialize(
             &pipeline_cache,
             &prepass_pipelines,
-            key | TilingPrepassPipelineKey::PREPARE_ROOT,
+            TilingPrepassKey {
+                flags: key | TilingPrepassPipelineKey::PREPARE_ROOT,
+                shader_defs: gpu_tile_atlas.prepass_shader_defs.clone(),
+                refine_tiles_shader: gpu_tile_atlas.refine_tiles_shader.clone(),
+                prepare_prepass_shader: gpu_tile_atlas.prepare_prepass_shader.clone(),
+            },
         );
         let prepare_next_pipeline = pipelines.specialize(
             &pipeline_cache,
             &prepass_pipelines,
-            key | TilingPrepassPipelineKey::PREPARE_NEXT,
+            TilingPrepassKey {
+                flags: key | TilingPrepassPipelineKey::PREPARE_NEXT,
+                shader_defs: gpu_tile_atlas.prepass_shader_defs.clone(),
+                refine_tiles_shader: gpu_tile_atlas.refine_tiles_shader.clone(),
+                prepare_prepass_shader: gpu_tile_atlas.prepare_prepass_shader.clone(),
+            },
         );
         let prepare_render_pipeline = pipelines.specialize(
             &pipeline_cache,
             &prepass_pipelines,
-            key | TilingPrepassPipelineKey::PREPARE_RENDER,
+            TilingPrepassKey {
+                flags: key | TilingPrepassPipelineKey::PREPARE_RENDER,
+                shader_defs: gpu_tile_atlas.prepass_shader_defs.clone(),
+                refine_tiles_shader: gpu_tile_atlas.refine_tiles_shader.clone(),
+                prepare_prepass_shader: gpu_tile_atlas.prepare_prepass_shader.clone(),
+            },
         );
 
+        for (pipeline, name) in [
+            (refine_tiles_pipeline, "refine tiles"),
+            (prepare_root_pipeline, "prepare root"),
+            (prepare_next_pipeline, "prepare next"),
+            (prepare_render_pipeline, "prepare render"),
+        ] {
+            report_pipeline_error(&pipeline_cache, &mut reported_errors, pipeline, name);
+        }
+
         prepass_items.insert(
             (terrain, view),
             TilingPrepassItem {
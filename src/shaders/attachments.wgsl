@@ -1,25 +1,91 @@
 #define_import_path bevy_terrain::attachments
 
 #import bevy_terrain::types::AtlasTile
-#import bevy_terrain::bindings::{config, atlas_sampler, attachments, attachment0_atlas, attachment1_atlas, attachment2_atlas}
-#import bevy_terrain::functions::tile_count
+#import bevy_terrain::bindings::{config, view_config, atlas_sampler, attachments, attachment0_atlas, attachment1_atlas, attachment2_atlas, height_overlay}
+#import bevy_terrain::functions::{tile_count, remap_height}
 
 fn attachment_uv(uv: vec2<f32>, attachment_index: u32) -> vec2<f32> {
     let attachment = attachments[attachment_index];
     return uv * attachment.scale + attachment.offset;
 }
 
+// Offsets the mip level by the mip bias of the terrain, or replaces it with the level forced for debugging.
+fn biased_mip_level(level: f32) -> f32 {
+    if (view_config.forced_mip_level >= 0.0) { return view_config.forced_mip_level; }
+
+    return level + config.mip_bias;
+}
+
+// The mip level sampled by the vertices of a geometry tile.
+// If the attachment has a higher resolution than the geometry tile, the vertices would otherwise alias the height data.
+fn vertex_mip_level(attachment_index: u32) -> f32 {
+    let attachment = attachments[attachment_index];
+    return biased_mip_level(max(log2(attachment.size * attachment.scale / view_config.grid_size), 0.0));
+}
+
+#ifdef FRAGMENT
+#ifdef SAMPLE_GRAD
+// Samples the atlas with the screen space gradients of the tile, which are scaled to apply the mip bias of the terrain.
+fn sample_atlas_grad(atlas: texture_2d_array<f32>, uv: vec2<f32>, tile: AtlasTile) -> vec4<f32> {
+    if (view_config.forced_mip_level >= 0.0) {
+        return textureSampleLevel(atlas, atlas_sampler, uv, tile.index, view_config.forced_mip_level);
+    }
+
+    let scale = exp2(config.mip_bias);
+    return textureSampleGrad(atlas, atlas_sampler, uv, tile.index, scale * tile.coordinate.uv_dx, scale * tile.coordinate.uv_dy);
+}
+#endif
+#endif
+
+// The Catmull-Rom weights of the four texels surrounding the sample position in each dimension.
+fn catmull_rom_weights(t: vec2<f32>) -> array<vec2<f32>, 4> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    return array(
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    );
+}
+
+// Samples the 4x4 texel neighborhood of the attachment with a bicubic Catmull-Rom filter.
+// The neighborhood reaches two texels beyond the center, so the attachment should have a border size of at least two.
+fn sample_attachment0_bicubic(tile: AtlasTile) -> vec4<f32> {
+    let level   = clamp(i32(vertex_mip_level(0u)), 0, i32(textureNumLevels(attachment0_atlas)) - 1);
+    let size    = vec2<i32>(textureDimensions(attachment0_atlas, level));
+    let texel   = attachment_uv(tile.coordinate.uv, 0u) * vec2<f32>(size) - 0.5;
+    let origin  = vec2<i32>(floor(texel)) - 1;
+    var weights = catmull_rom_weights(fract(texel));
+
+    var value = vec4<f32>(0.0);
+
+    for (var y: i32 = 0; y < 4; y = y + 1) {
+        for (var x: i32 = 0; x < 4; x = x + 1) {
+            let coords = clamp(origin + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            value += weights[x].x * weights[y].y * textureLoad(attachment0_atlas, coords, tile.index, level);
+        }
+    }
+
+    return value;
+}
+
 fn sample_attachment0(tile: AtlasTile) -> vec4<f32> {
     let uv = attachment_uv(tile.coordinate.uv, 0u);
 
 #ifdef FRAGMENT
 #ifdef SAMPLE_GRAD
-    return textureSampleGrad(attachment0_atlas, atlas_sampler, uv, tile.index, tile.coordinate.uv_dx, tile.coordinate.uv_dy);
+    return sample_atlas_grad(attachment0_atlas, uv, tile);
 #else
-    return textureSampleLevel(attachment0_atlas, atlas_sampler, uv, tile.index, 0.0);
+    return textureSampleLevel(attachment0_atlas, atlas_sampler, uv, tile.index, biased_mip_level(0.0));
 #endif
 #else
-    return textureSampleLevel(attachment0_atlas, atlas_sampler, uv, tile.index, 0.0);
+#ifdef BICUBIC
+    return sample_attachment0_bicubic(tile);
+#else
+    return textureSampleLevel(attachment0_atlas, atlas_sampler, uv, tile.index, vertex_mip_level(0u));
+#endif
 #endif
 }
 
@@ -28,12 +94,12 @@ fn sample_attachment1(tile: AtlasTile) -> vec4<f32> {
 
 #ifdef FRAGMENT
 #ifdef SAMPLE_GRAD
-    return textureSampleGrad(attachment1_atlas, atlas_sampler, uv, tile.index, tile.coordinate.uv_dx, tile.coordinate.uv_dy);
+    return sample_atlas_grad(attachment1_atlas, uv, tile);
 #else
-    return textureSampleLevel(attachment1_atlas, atlas_sampler, uv, tile.index, 0.0);
+    return textureSampleLevel(attachment1_atlas, atlas_sampler, uv, tile.index, biased_mip_level(0.0));
 #endif
 #else
-    return textureSampleLevel(attachment1_atlas, atlas_sampler, uv, tile.index, 0.0);
+    return textureSampleLevel(attachment1_atlas, atlas_sampler, uv, tile.index, vertex_mip_level(1u));
 #endif
 }
 
@@ -42,10 +108,24 @@ fn sample_attachment1_gather0(tile: AtlasTile) -> vec4<f32> {
     return textureGather(0, attachment1_atlas, atlas_sampler, uv, tile.index);
 }
 
+// Samples the height offset of the overlay, which is stored with one layer per side and sampled with the side uv.
+// The offset is added to the uv of the tile, e.g. for the taps of the normal.
+fn sample_height_overlay(tile: AtlasTile, uv_offset: vec2<f32>) -> f32 {
+    let coordinate = tile.coordinate;
+    let side_uv    = (vec2<f32>(coordinate.xy) + coordinate.uv + uv_offset) / tile_count(coordinate.lod);
+    let layer      = min(coordinate.side, textureNumLayers(height_overlay) - 1u);
+
+    return textureSampleLevel(height_overlay, atlas_sampler, side_uv, layer, 0.0).x;
+}
+
 fn sample_height(tile: AtlasTile) -> f32 {
-    let height = sample_attachment0(tile).x;
+    let height = sample_attachment0(tile)[config.height_channel];
 
-    return mix(config.min_height, config.max_height, height);
+#ifdef HEIGHT_OVERLAY
+    return remap_height(height) + sample_height_overlay(tile, vec2<f32>(0.0));
+#else
+    return remap_height(height);
+#endif
 }
 
 fn sample_normal(tile: AtlasTile, vertex_normal: vec3<f32>) -> vec3<f32> {
@@ -84,30 +164,41 @@ fn sample_normal(tile: AtlasTile, vertex_normal: vec3<f32>) -> vec3<f32> {
 
 #ifdef FRAGMENT
 #ifdef SAMPLE_GRAD
-    let left  = mix(config.min_height, config.max_height, textureSampleGrad(attachment0_atlas, atlas_sampler, uv + vec2<f32>(-offset,     0.0), tile.index, tile.coordinate.uv_dx, tile.coordinate.uv_dy).x);
-    let up    = mix(config.min_height, config.max_height, textureSampleGrad(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0, -offset), tile.index, tile.coordinate.uv_dx, tile.coordinate.uv_dy).x);
-    let right = mix(config.min_height, config.max_height, textureSampleGrad(attachment0_atlas, atlas_sampler, uv + vec2<f32>( offset,     0.0), tile.index, tile.coordinate.uv_dx, tile.coordinate.uv_dy).x);
-    let down  = mix(config.min_height, config.max_height, textureSampleGrad(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0,  offset), tile.index, tile.coordinate.uv_dx, tile.coordinate.uv_dy).x);
+    let left  = remap_height(sample_atlas_grad(attachment0_atlas, uv + vec2<f32>(-offset,     0.0), tile)[config.height_channel]);
+    let up    = remap_height(sample_atlas_grad(attachment0_atlas, uv + vec2<f32>(    0.0, -offset), tile)[config.height_channel]);
+    let right = remap_height(sample_atlas_grad(attachment0_atlas, uv + vec2<f32>( offset,     0.0), tile)[config.height_channel]);
+    let down  = remap_height(sample_atlas_grad(attachment0_atlas, uv + vec2<f32>(    0.0,  offset), tile)[config.height_channel]);
 #else
-    let left  = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(-offset,     0.0), tile.index, 0.0).x);
-    let up    = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0, -offset), tile.index, 0.0).x);
-    let right = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>( offset,     0.0), tile.index, 0.0).x);
-    let down  = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0,  offset), tile.index, 0.0).x);
+    let left  = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(-offset,     0.0), tile.index, biased_mip_level(0.0))[config.height_channel]);
+    let up    = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0, -offset), tile.index, biased_mip_level(0.0))[config.height_channel]);
+    let right = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>( offset,     0.0), tile.index, biased_mip_level(0.0))[config.height_channel]);
+    let down  = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0,  offset), tile.index, biased_mip_level(0.0))[config.height_channel]);
 #endif
 #else
-    let left  = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(-offset,     0.0), tile.index, 0.0).x);
-    let up    = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0, -offset), tile.index, 0.0).x);
-    let right = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>( offset,     0.0), tile.index, 0.0).x);
-    let down  = mix(config.min_height, config.max_height, textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0,  offset), tile.index, 0.0).x);
+    let level = vertex_mip_level(0u);
+    let left  = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(-offset,     0.0), tile.index, level)[config.height_channel]);
+    let up    = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0, -offset), tile.index, level)[config.height_channel]);
+    let right = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>( offset,     0.0), tile.index, level)[config.height_channel]);
+    let down  = remap_height(textureSampleLevel(attachment0_atlas, atlas_sampler, uv + vec2<f32>(    0.0,  offset), tile.index, level)[config.height_channel]);
+#endif
+
+#ifdef HEIGHT_OVERLAY
+    // the taps are offset in the atlas uv, which is scaled down from the tile uv by the attachment scale
+    let overlay_offset = offset / attachments[0u].scale;
+    let overlay_dx = sample_height_overlay(tile, vec2<f32>(-overlay_offset, 0.0)) - sample_height_overlay(tile, vec2<f32>(overlay_offset, 0.0));
+    let overlay_dy = sample_height_overlay(tile, vec2<f32>(0.0, overlay_offset)) - sample_height_overlay(tile, vec2<f32>(0.0, -overlay_offset));
+#else
+    let overlay_dx = 0.0;
+    let overlay_dy = 0.0;
 #endif
 
-    let surface_normal = normalize(vec3<f32>(left - right, down - up, distance_between_samples));
+    let surface_normal = normalize(vec3<f32>(left - right + overlay_dx, down - up + overlay_dy, distance_between_samples));
 
     return normalize(TBN * surface_normal);
 }
 
 fn sample_color(tile: AtlasTile) -> vec4<f32> {
-    let height = sample_attachment0(tile).x;
+    let height = sample_attachment0(tile)[config.height_channel];
 
     return vec4<f32>(height * 0.5);
 }
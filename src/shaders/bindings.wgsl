@@ -1,6 +1,6 @@
 #define_import_path bevy_terrain::bindings
 
-#import bevy_terrain::types::{TerrainViewConfig, TerrainConfig, TileTreeEntry, TileCoordinate, AttachmentConfig, TerrainModelApproximation, CullingData, IndirectBuffer, Parameters}
+#import bevy_terrain::types::{TerrainViewConfig, TerrainConfig, TerrainTime, TileTreeEntry, TileCoordinate, AttachmentConfig, TerrainModelApproximation, CullingData, IndirectBuffer, Parameters, PickedTile}
 #import bevy_pbr::mesh_types::Mesh
 
 // terrain bindings
@@ -28,6 +28,17 @@ var attachment5_atlas: texture_2d_array<f32>;
 var attachment6_atlas: texture_2d_array<f32>;
 @group(1) @binding(11)
 var attachment7_atlas: texture_2d_array<f32>;
+@group(1) @binding(12)
+var hole_mask: texture_2d_array<f32>;
+@group(1) @binding(13)
+var detail_noise: texture_2d<f32>;
+@group(1) @binding(14)
+var detail_sampler: sampler;
+@group(1) @binding(15)
+var height_overlay: texture_2d_array<f32>;
+// animated displacements are not accounted for by the culling, unless they stay within the height range of the terrain
+@group(1) @binding(16)
+var<uniform> terrain_time: TerrainTime;
 
 // terrain view bindings
 @group(2) @binding(0)
@@ -40,6 +51,10 @@ var<storage> tile_tree: array<TileTreeEntry>;
 var<storage> origins: array<vec2<u32>>;
 @group(2) @binding(4)
 var<storage> geometry_tiles: array<TileCoordinate>;
+#ifdef TILE_PICKING
+@group(2) @binding(5)
+var<storage, read_write> picked_tile: PickedTile;
+#endif
 
 // refine geometry_tiles bindings
 @group(2) @binding(4)
@@ -48,6 +63,8 @@ var<storage, read_write> final_tiles: array<TileCoordinate>;
 var<storage, read_write> temporary_tiles: array<TileCoordinate>;
 @group(2) @binding(6)
 var<storage, read_write> parameters: Parameters;
+@group(2) @binding(7)
+var<storage, read_write> screen_lods: array<atomic<u32>>;
 
 @group(3) @binding(0)
 var<storage, read_write> indirect_buffer: IndirectBuffer;
@@ -2,7 +2,7 @@
 
 #import bevy_terrain::types::{Coordinate, AtlasTile, Blend}
 #import bevy_terrain::bindings::{config, tile_tree, view_config, geometry_tiles, attachments, origins, terrain_model_approximation}
-#import bevy_terrain::functions::{inverse_mix, compute_coordinate, lookup_best, approximate_view_distance, compute_blend, tree_lod, inside_square, tile_coordinate, coordinate_from_local_position, compute_subdivision_coordinate}
+#import bevy_terrain::functions::{inverse_mix, compute_coordinate, lookup_best, approximate_view_distance, compute_blend, tree_lod, inside_square, tile_coordinate, coordinate_from_local_position, compute_subdivision_coordinate, tile_count}
 #import bevy_pbr::mesh_view_bindings::view
 
 fn index_color(index: u32) -> vec4<f32> {
@@ -24,6 +24,20 @@ fn tile_tree_outlines(uv: vec2<f32>) -> f32 {
     return 1.0 - inside_square(uv, vec2<f32>(thickness), 1.0 - 2.0 * thickness);
 }
 
+// Outlines the tile edges and the cells of the geometry grid, independent of the polygon mode.
+fn wireframe_outlines(uv: vec2<f32>) -> f32 {
+    let grid_uv = uv * view_config.grid_size;
+
+    // distance to the closest line in pixels
+    let tile_distance = abs(fract(uv      - 0.5) - 0.5) / fwidth(uv);
+    let grid_distance = abs(fract(grid_uv - 0.5) - 0.5) / fwidth(grid_uv);
+
+    let tile_edge = 1.0 - min(min(tile_distance.x, tile_distance.y) / 2.0, 1.0);
+    let grid_edge = 1.0 - min(min(grid_distance.x, grid_distance.y),       1.0);
+
+    return max(tile_edge, 0.5 * grid_edge);
+}
+
 fn checker_color(coordinate: Coordinate, ratio: f32) -> vec4<f32> {
     var color        = index_color(coordinate.lod);
     var parent_color = index_color(coordinate.lod - 1);
@@ -92,6 +106,17 @@ fn show_geometry_lod(coordinate: Coordinate) -> vec4<f32> {
 
     return color;
 }
+// Mirrors the subdivision criterion of the refinement, which splits tiles closer than the subdivision distance of their lod.
+fn show_screen_error(coordinate: Coordinate) -> vec4<f32> {
+    let subdivision_coordinate = compute_subdivision_coordinate(coordinate);
+    let view_distance          = approximate_view_distance(subdivision_coordinate, view.world_position);
+    let error                  = view_config.subdivision_distance / tile_count(coordinate.lod) / view_distance;
+
+    if (error >= 1.0) { return vec4<f32>(1.0, 0.0, 0.0, 1.0); }
+
+    return mix(vec4<f32>(0.0, 1.0, 0.0, 1.0), vec4<f32>(1.0, 1.0, 0.0, 1.0), error);
+}
+
 fn show_tile_tree(coordinate: Coordinate) -> vec4<f32> {
     let view_distance  = approximate_view_distance(coordinate, view.world_position);
     let target_lod     = log2(view_config.load_distance / view_distance);
@@ -108,6 +133,16 @@ fn show_tile_tree(coordinate: Coordinate) -> vec4<f32> {
     return color;
 }
 
+// Tints the tile by its lod, using a color ramp from blue (lowest lod) to red (highest lod).
+fn show_lod(coordinate: Coordinate) -> vec4<f32> {
+    let ratio = f32(coordinate.lod) / f32(max(config.lod_count, 2u) - 1u);
+
+    let color = mix(mix(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0), saturate(2.0 * ratio)),
+                    vec3<f32>(1.0, 0.0, 0.0), saturate(2.0 * ratio - 1.0));
+
+    return vec4<f32>(color, 1.0);
+}
+
 fn show_pixels(tile: AtlasTile) -> vec4<f32> {
     let pixel_size = 4.0;
     let pixel_coordinate = tile.coordinate.uv * f32(attachments[0].size) / pixel_size;
@@ -0,0 +1,75 @@
+struct BrushStroke {
+    side: u32,
+    mode: u32,
+    uv: vec2<f32>,
+    radius: f32,
+    strength: f32,
+}
+
+@group(0) @binding(0)
+var height_overlay: texture_storage_2d_array<r32float, read_write>;
+// The heights of the overlay before the stroke, which are read while the overlay is written.
+@group(0) @binding(1)
+var source_heights: texture_storage_2d_array<r32float, read_write>;
+@group(0) @binding(2)
+var<uniform> stroke: BrushStroke;
+
+const RAISE: u32 = 0u;
+const LOWER: u32 = 1u;
+
+fn load_height(coords: vec2<i32>, layer: u32) -> f32 {
+    let size = vec2<i32>(textureDimensions(source_heights));
+
+    return textureLoad(source_heights, clamp(coords, vec2<i32>(0), size - 1), layer).x;
+}
+
+fn smoothed_height(coords: vec2<i32>, layer: u32) -> f32 {
+    return (load_height(coords + vec2<i32>(1, 0), layer) +
+            load_height(coords - vec2<i32>(1, 0), layer) +
+            load_height(coords + vec2<i32>(0, 1), layer) +
+            load_height(coords - vec2<i32>(0, 1), layer)) / 4.0;
+}
+
+// Copies the heights of the overlay before each stroke, so that the smoothing does not read texels,
+// which are written by other invocations of the same stroke.
+@compute @workgroup_size(8, 8, 1)
+fn copy_heights(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = invocation_id.xy;
+    let layer  = invocation_id.z;
+
+    if (any(coords >= textureDimensions(height_overlay)) || layer >= textureNumLayers(height_overlay)) { return; }
+
+    textureStore(source_heights, coords, layer, textureLoad(height_overlay, coords, layer));
+}
+
+// This matches `TerrainBrushStroke::apply` on the CPU, keep both in sync.
+@compute @workgroup_size(8, 8, 1)
+fn brush(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size   = textureDimensions(height_overlay);
+    let coords = invocation_id.xy;
+    let layer  = invocation_id.z;
+
+    if (any(coords >= size) || layer >= textureNumLayers(height_overlay)) { return; }
+
+    // overlays with a single layer are shared by all sides, like in the CPU sampling
+    if (min(stroke.side, textureNumLayers(height_overlay) - 1u) != layer) { return; }
+
+    let uv = (vec2<f32>(coords) + 0.5) / vec2<f32>(size);
+
+    // the strength falls off smoothly from the center to the radius of the brush
+    let falloff = 1.0 - smoothstep(0.0, stroke.radius, distance(uv, stroke.uv));
+
+    if (falloff <= 0.0) { return; }
+
+    var height = load_height(vec2<i32>(coords), layer);
+
+    if (stroke.mode == RAISE) {
+        height += falloff * stroke.strength;
+    } else if (stroke.mode == LOWER) {
+        height -= falloff * stroke.strength;
+    } else {
+        height = mix(height, smoothed_height(vec2<i32>(coords), layer), saturate(falloff * stroke.strength));
+    }
+
+    textureStore(height_overlay, coords, layer, vec4<f32>(height));
+}
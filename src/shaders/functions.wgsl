@@ -1,7 +1,7 @@
 #define_import_path bevy_terrain::functions
 
-#import bevy_terrain::bindings::{mesh, config, origins, view_config, geometry_tiles, tile_tree, terrain_model_approximation}
-#import bevy_terrain::types::{TileCoordinate, TileTree, TileTreeEntry, AtlasTile, Blend, BestLookup, Coordinate, Morph}
+#import bevy_terrain::bindings::{mesh, config, origins, view_config, geometry_tiles, tile_tree, terrain_model_approximation, atlas_sampler, hole_mask}
+#import bevy_terrain::types::{TileCoordinate, TileTree, TileTreeEntry, AtlasTile, Blend, BestLookup, Coordinate, Morph, SideParameter}
 #import bevy_pbr::mesh_view_bindings::view
 #import bevy_render::maths::{affine3_to_square, mat2x4_f32_to_mat3x3_unpack}
 
@@ -11,23 +11,113 @@ const PS = 2u;
 const PT = 3u;
 const C_SQR = 0.87 * 0.87;
 
+// Marks the side of the tiles, which the refinement culled, but still emitted for the culled tiles debug view.
+const CULLED_TILE = 1u << 31u;
+
 fn normal_local_to_world(local_position: vec3<f32>) -> vec3<f32> {
 #ifdef SPHERICAL
     let local_normal = local_position;
 #else
-    let local_normal = vec3<f32>(0.0, 1.0, 0.0);
+    // the gradient of the curved plane, see `compute_local_position`
+    let local_normal = vec3<f32>(config.curvature * local_position.x, 1.0, config.curvature * local_position.z);
 #endif
 
+    // Normals are transformed with the inverse transpose of the model matrix, which yields the gradient of the
+    // implicit surface equation in world space, so they stay correct for non-uniformly scaled ellipsoids.
     let world_from_local = mat2x4_f32_to_mat3x3_unpack(mesh[0].local_from_world_transpose_a,
                                                        mesh[0].local_from_world_transpose_b);
     return normalize(world_from_local * local_normal);
 }
 
+fn tangent_local_to_world(local_tangent: vec3<f32>) -> vec3<f32> {
+    let world_from_local = affine3_to_square(mesh[0].world_from_local);
+    return normalize((world_from_local * vec4<f32>(local_tangent, 0.0)).xyz);
+}
+
+// Computes the directions in which the side uv (s, t) increases at the local position.
+// Each component of the side uv only affects a single axis of the unprojected cube position,
+// thus the tangents are these axes projected onto the plane orthogonal to the normal.
+// The tangent frame is continuous within each side and aligned with the side uv,
+// which detail normal maps are sampled with.
+fn compute_local_tangents(side: u32, local_position: vec3<f32>) -> mat2x3<f32> {
+#ifdef SPHERICAL
+    var axis_s: vec3<f32>; var axis_t: vec3<f32>;
+
+    switch (side) {
+        case 0u:      { axis_s = vec3( 0.0,  0.0, 1.0); axis_t = vec3(0.0, -1.0, 0.0); }
+        case 1u:      { axis_s = vec3( 1.0,  0.0, 0.0); axis_t = vec3(0.0, -1.0, 0.0); }
+        case 2u:      { axis_s = vec3( 1.0,  0.0, 0.0); axis_t = vec3(0.0,  0.0, 1.0); }
+        case 3u:      { axis_s = vec3( 0.0, -1.0, 0.0); axis_t = vec3(0.0,  0.0, 1.0); }
+        case 4u:      { axis_s = vec3( 0.0, -1.0, 0.0); axis_t = vec3(1.0,  0.0, 0.0); }
+        case 5u:      { axis_s = vec3( 0.0,  0.0, 1.0); axis_t = vec3(1.0,  0.0, 0.0); }
+        case default: {}
+    }
+
+    let tangent   = normalize(axis_s - local_position * dot(local_position, axis_s));
+    let bitangent = normalize(axis_t - local_position * dot(local_position, axis_t));
+
+    return mat2x3<f32>(tangent, bitangent);
+#else
+    let k = config.curvature;
+    return mat2x3<f32>(normalize(vec3<f32>(1.0, -k * local_position.x, 0.0)), normalize(vec3<f32>(0.0, -k * local_position.z, 1.0)));
+#endif
+}
+
 fn position_local_to_world(local_position: vec3<f32>) -> vec3<f32> {
     let world_from_local = affine3_to_square(mesh[0].world_from_local);
     return (world_from_local * vec4<f32>(local_position, 1.0)).xyz;
 }
 
+// Maps the normalized height stored in the atlas onto the height range of the terrain, applying the height gamma.
+// The PCG hash, see "Hash Functions for GPU Rendering" by Jarzynski and Olano.
+fn pcg_hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Hashes the tile together with the seed of the terrain, identical to `TileCoordinate::hash` on the CPU.
+// Only integer operations are used, so the result is deterministic across platforms.
+fn tile_hash(tile: TileCoordinate) -> u32 {
+    var hash = pcg_hash(config.seed);
+    hash = pcg_hash(hash ^ tile.lod);
+    hash = pcg_hash(hash ^ tile.side);
+    hash = pcg_hash(hash ^ tile.xy.x);
+    hash = pcg_hash(hash ^ tile.xy.y);
+    return hash;
+}
+
+// Hashes the tile of the given lod, which contains the coordinate.
+// The value is uniformly distributed in [0, 1) and identical for all coordinates inside the same tile.
+fn coordinate_hash(coordinate: Coordinate, lod: u32) -> f32 {
+    let xy = vec2<u32>(floor((vec2<f32>(coordinate.xy) + coordinate.uv) * exp2(f32(lod) - f32(coordinate.lod))));
+
+    return f32(tile_hash(TileCoordinate(coordinate.side, lod, xy)) >> 8u) / 16777216.0;
+}
+
+fn remap_height(normalized_height: f32) -> f32 {
+    // heights outside of zero to one (e.g. of float attachments) extrapolate the height range without the gamma
+    let inside = normalized_height >= 0.0 && normalized_height <= 1.0;
+    let remapped_height = select(normalized_height, pow(normalized_height, config.height_gamma), inside);
+    let height = mix(config.min_height, config.max_height, remapped_height);
+
+    // clamp the heights below the floor, which is the minimum float if none is set
+    return max(height, config.height_floor);
+}
+
+// Tests whether the tile lies completely inside a hole of the hole mask.
+// The mip level, at which a single texel covers the tile, contains the average mask value of the tile.
+// Without a complete mip chain the tiles of low lods can not be tested and are never culled.
+fn inside_hole(tile: TileCoordinate) -> bool {
+    let level = log2(f32(textureDimensions(hole_mask).x)) - f32(tile.lod);
+
+    if (level > f32(textureNumLevels(hole_mask) - 1u)) { return false; }
+
+    let uv = (vec2<f32>(tile.xy) + 0.5) / tile_count(tile.lod);
+
+    return textureSampleLevel(hole_mask, atlas_sampler, uv, tile.side, max(level, 0.0)).x == 0.0;
+}
+
 fn inverse_mix(a: f32, b: f32, value: f32) -> f32 {
     return saturate((value - a) / (b - a));
 }
@@ -61,13 +151,36 @@ fn compute_blend(view_distance: f32) -> Blend {
 #endif
 }
 
-fn compute_tile_uv(vertex_index: u32) -> vec2<f32>{
+fn compute_grid_position(vertex_index: u32) -> vec2<u32> {
     // use first and last indices of the rows twice, to form degenerate triangles
     let grid_index   = vertex_index % view_config.vertices_per_tile;
     let row_index    = clamp(grid_index % view_config.vertices_per_row, 1u, view_config.vertices_per_row - 2u) - 1u;
     let column_index = grid_index / view_config.vertices_per_row;
 
-    return vec2<f32>(f32(column_index + (row_index & 1u)), f32(row_index >> 1u)) / view_config.grid_size;
+    return vec2<u32>(column_index + (row_index & 1u), row_index >> 1u);
+}
+
+fn compute_tile_uv(vertex_index: u32) -> vec2<f32>{
+    let grid_position = vec2<f32>(compute_grid_position(vertex_index));
+
+#ifdef SKIRTS
+    // the outer ring of the grid is collapsed onto the tile edges, where it is extruded downwards to form the skirts
+    return clamp(grid_position - 1.0, vec2<f32>(0.0), vec2<f32>(view_config.grid_size)) / view_config.grid_size;
+#else
+    return grid_position / view_config.grid_size;
+#endif
+}
+
+// Whether the vertex belongs to the outer ring of the grid, which forms the skirts of the tile.
+fn is_skirt_vertex(vertex_index: u32) -> bool {
+#ifdef SKIRTS
+    let grid_position = compute_grid_position(vertex_index);
+    let edge          = u32(view_config.grid_size) + 2u;
+
+    return any(grid_position == vec2<u32>(0u)) || any(grid_position == vec2<u32>(edge));
+#else
+    return false;
+#endif
 }
 
 fn compute_local_position(coordinate: Coordinate) -> vec3<f32> {
@@ -91,7 +204,9 @@ fn compute_local_position(coordinate: Coordinate) -> vec3<f32> {
 
     return normalize(local_position);
 #else
-    return vec3<f32>(uv.x - 0.5, 0.0, uv.y - 0.5);
+    // planar terrains are bent downwards around their center by the curvature
+    let xz = uv - 0.5;
+    return vec3<f32>(xz.x, -0.5 * config.curvature * dot(xz, xz), xz.y);
 #endif
 }
 
@@ -102,16 +217,17 @@ fn compute_relative_position(coord: Coordinate) -> vec3<f32> {
     let params = terrain_model_approximation.sides[coordinate.side];
     let relative_st = (vec2<f32>(vec2<i32>(coordinate.xy) - params.view_xy) + coordinate.uv - params.view_uv) / tile_count(terrain_model_approximation.origin_lod);
 
+    return evaluate_taylor_series(params, relative_st);
+}
+
+// Evaluates the second order Taylor series of the side at the st coordinate relative to the view,
+// which approximates the world position relative to the view.
+// This matches `SideParameter::evaluate` on the CPU, keep both in sync.
+fn evaluate_taylor_series(params: SideParameter, relative_st: vec2<f32>) -> vec3<f32> {
     let s = relative_st.x;
     let t = relative_st.y;
-    let c = params.c;
-    let c_s = params.c_s;
-    let c_t = params.c_t;
-    let c_ss = params.c_ss;
-    let c_st = params.c_st;
-    let c_tt = params.c_tt;
 
-    return c + c_s * s + c_t * t + c_ss * s * s + c_st * s * t + c_tt * t * t;
+    return params.c + params.c_s * s + params.c_t * t + params.c_ss * s * s + params.c_st * s * t + params.c_tt * t * t;
 }
 
 fn approximate_view_distance(coordinate: Coordinate, view_world_position: vec3<f32>) -> f32 {
@@ -133,10 +249,15 @@ fn approximate_view_distance(coordinate: Coordinate, view_world_position: vec3<f
 fn compute_subdivision_coordinate(coordinate: Coordinate) -> Coordinate {
     let params  = terrain_model_approximation.sides[coordinate.side];
 
+    // the origin tile is kept within a hysteresis, so the view uv may lie slightly outside of it
+    let view_offset = vec2<i32>(floor(params.view_uv));
+    let view_xy     = vec2<u32>(params.view_xy + view_offset);
+    let view_uv     = params.view_uv - vec2<f32>(view_offset);
+
 #ifdef FRAGMENT
-    var view_coordinate = Coordinate(coordinate.side, terrain_model_approximation.origin_lod, vec2<u32>(params.view_xy), params.view_uv, vec2<f32>(0.0), vec2<f32>(0.0));
+    var view_coordinate = Coordinate(coordinate.side, terrain_model_approximation.origin_lod, view_xy, view_uv, vec2<f32>(0.0), vec2<f32>(0.0));
 #else
-    var view_coordinate = Coordinate(coordinate.side, terrain_model_approximation.origin_lod, vec2<u32>(params.view_xy), params.view_uv);
+    var view_coordinate = Coordinate(coordinate.side, terrain_model_approximation.origin_lod, view_xy, view_uv);
 #endif
 
     coordinate_change_lod(&view_coordinate, coordinate.lod);
@@ -229,6 +350,24 @@ fn lookup_best(lookup_coordinate: Coordinate) -> BestLookup {
     return BestLookup(AtlasTile(tile_tree_entry.atlas_index, coordinate), tile_tree_uv);
 }
 
+// The progress of the fade-in of the tile, which increases from zero to one over the first frames after it was loaded.
+fn lookup_tile_fade(tile: AtlasTile) -> f32 {
+    return lookup_tile_tree_entry(tile.coordinate).fade;
+}
+
+// Looks up the best loaded tile of the parent of the tile, which the tile fades in from.
+fn lookup_parent_tile(tile: AtlasTile) -> AtlasTile {
+    var coordinate = tile.coordinate;
+
+    coordinate_change_lod(&coordinate, max(coordinate.lod, 1u) - 1u);
+
+    let tile_tree_entry = lookup_tile_tree_entry(coordinate);
+
+    coordinate_change_lod(&coordinate, tile_tree_entry.atlas_lod);
+
+    return AtlasTile(tile_tree_entry.atlas_index, coordinate);
+}
+
 fn lookup_tile(lookup_coordinate: Coordinate, blend: Blend, lod_offset: u32) -> AtlasTile {
 #ifdef TILE_TREE_LOD
     return lookup_best(lookup_coordinate).tile;
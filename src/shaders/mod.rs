@@ -7,10 +7,13 @@ pub const PREPARE_PREPASS_SHADER: &str =
     "embedded://bevy_terrain/shaders/tiling_prepass/prepare_prepass.wgsl";
 pub const REFINE_TILES_SHADER: &str =
     "embedded://bevy_terrain/shaders/tiling_prepass/refine_tiles.wgsl";
+pub(crate) const BRUSH_SHADER: &str = "embedded://bevy_terrain/shaders/editing/brush.wgsl";
 pub(crate) const SPLIT_SHADER: &str = "embedded://bevy_terrain/shaders/preprocess/split.wgsl";
 pub(crate) const STITCH_SHADER: &str = "embedded://bevy_terrain/shaders/preprocess/stitch.wgsl";
 pub(crate) const DOWNSAMPLE_SHADER: &str =
     "embedded://bevy_terrain/shaders/preprocess/downsample.wgsl";
+pub(crate) const PREPARE_DOWNSAMPLE_SHADER: &str =
+    "embedded://bevy_terrain/shaders/preprocess/prepare_downsample.wgsl";
 
 #[derive(Default, Resource)]
 pub(crate) struct InternalShaders(Vec<Handle<Shader>>);
@@ -37,6 +40,7 @@ pub(crate) fn load_terrain_shaders(app: &mut App) {
     embedded_asset!(app, "render/fragment.wgsl");
     embedded_asset!(app, "tiling_prepass/prepare_prepass.wgsl");
     embedded_asset!(app, "tiling_prepass/refine_tiles.wgsl");
+    embedded_asset!(app, "editing/brush.wgsl");
 
     InternalShaders::load(
         app,
@@ -57,6 +61,7 @@ pub(crate) fn load_preprocess_shaders(app: &mut App) {
     embedded_asset!(app, "preprocess/split.wgsl");
     embedded_asset!(app, "preprocess/stitch.wgsl");
     embedded_asset!(app, "preprocess/downsample.wgsl");
+    embedded_asset!(app, "preprocess/prepare_downsample.wgsl");
 
     InternalShaders::load(
         app,
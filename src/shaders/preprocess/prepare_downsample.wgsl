@@ -0,0 +1,90 @@
+#import bevy_terrain::preprocessing::{atlas, attachment, pixel_value, process_entry, FORMAT_R16, FORMAT_R32F}
+
+struct PrepareDownsampleData {
+    mip_level: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> prepare_downsample_data: PrepareDownsampleData;
+@group(1) @binding(1)
+var<storage> atlas_indices: array<u32>;
+@group(1) @binding(2)
+var<storage, read_write> height_ranges: array<atomic<u32>>;
+
+// The write slot of the tile, which is processed by this invocation.
+var<private> slot: u32;
+
+// Maps the float onto an unsigned integer of the same order, so that the heights can be compared with atomics.
+fn ordered_bits(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+    return select(bits | 0x80000000u, ~bits, (bits & 0x80000000u) != 0u);
+}
+
+// The minimum is stored inverted, so that both bounds start at zero and only grow.
+fn include_height(height: f32) {
+    let bits = ordered_bits(height);
+    atomicMax(&height_ranges[2u * slot], ~bits);
+    atomicMax(&height_ranges[2u * slot + 1u], bits);
+}
+
+// Averages the four texels of the previous mip level, exactly like `AttachmentData::generate_mipmaps` on the CPU.
+override fn pixel_value(coords: vec2<u32>) -> vec4<f32> {
+    let mip_level = prepare_downsample_data.mip_level;
+
+    if (any(coords >= vec2<u32>(attachment.texture_size >> mip_level))) {
+        return vec4<f32>(0.0);
+    }
+
+    var OFFSETS = array(vec2(0u, 0u), vec2(0u, 1u), vec2(1u, 0u), vec2(1u, 1u));
+
+    var value = vec4<f32>(0.0);
+    var count = 0.0;
+
+    for (var index = 0u; index < 4u; index += 1u) {
+        let parent_value = textureLoad(atlas, 2u * coords + OFFSETS[index], atlas_indices[slot], i32(mip_level) - 1);
+
+        // The texels of the first level are each read by exactly one invocation of the first pass.
+        if (mip_level == 1u && (attachment.format_id == FORMAT_R16 || attachment.format_id == FORMAT_R32F)) {
+            include_height(parent_value.x);
+        }
+
+        if (attachment.format_id == FORMAT_R32F) {
+            value += parent_value;
+            count += 1.0;
+        } else if (attachment.format_id == FORMAT_R16) {
+            // zero marks missing data in 16 bit attachments
+            if (parent_value.x != 0.0) {
+                value += round(parent_value * 65535.0);
+                count += 1.0;
+            }
+        } else {
+            value += round(parent_value * 255.0);
+            count += 1.0;
+        }
+    }
+
+    if (count == 0.0) {
+        return vec4<f32>(0.0);
+    }
+
+    // The integer averages of the CPU are truncated.
+    if (attachment.format_id == FORMAT_R32F) {
+        return value / count;
+    } else if (attachment.format_id == FORMAT_R16) {
+        return floor(value / count) / 65535.0;
+    } else {
+        return floor(value / count) / 255.0;
+    }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn prepare_downsample(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = attachment.texture_size >> prepare_downsample_data.mip_level;
+
+    if (invocation_id.x * attachment.pixels_per_entry >= size || invocation_id.y >= size) {
+        return;
+    }
+
+    slot = invocation_id.z;
+    process_entry(invocation_id);
+}
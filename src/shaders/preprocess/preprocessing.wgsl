@@ -3,6 +3,7 @@
 const FORMAT_R8: u32 = 2u;
 const FORMAT_RGBA8: u32 = 0u;
 const FORMAT_R16: u32 = 1u;
+const FORMAT_R32F: u32 = 4u;
 
 const INVALID_ATLAS_INDEX: u32 = 4294967295u;
 
@@ -87,4 +88,8 @@ fn process_entry(entry_coords: vec3<u32>) {
                                               pixel_value(pixel_coords(entry_coords, 1u)).x));
         store_entry(entry_coords, entry_value);
     }
+    if (attachment.format_id == FORMAT_R32F) {
+        let entry_value = bitcast<u32>(pixel_value(pixel_coords(entry_coords, 0u)).x);
+        store_entry(entry_coords, entry_value);
+    }
 }
@@ -5,6 +5,8 @@ struct SplitData {
     tile: AtlasTile,
     top_left: vec2<f32>,
     bottom_right: vec2<f32>,
+    source_top_left: vec2<f32>,
+    source_bottom_right: vec2<f32>,
     tile_index: u32,
 }
 
@@ -29,6 +31,11 @@ override fn pixel_value(coords: vec2<u32>) -> vec4<f32> {
 
     source_coords = inverse_mix(split_data.top_left, split_data.bottom_right, source_coords);
 
+    // map onto the region of the source image containing the dataset, without bleeding into the adjacent regions (e.g. of a cube map)
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(source_tile));
+    source_coords  = clamp(mix(split_data.source_top_left, split_data.source_bottom_right, source_coords),
+                           split_data.source_top_left + half_texel, split_data.source_bottom_right - half_texel);
+
     let value = textureSampleLevel(source_tile, source_tile_sampler, source_coords, 0.0);
 
     let is_valid  = all(textureGather(0u, source_tile, source_tile_sampler, source_coords) != vec4<f32>(0.0));
@@ -1,20 +1,38 @@
 #define_import_path bevy_terrain::fragment
 
-#import bevy_terrain::types::{Blend, AtlasTile, Coordinate}
-#import bevy_terrain::bindings::{config, view_config, geometry_tiles}
-#import bevy_terrain::functions::{compute_blend, lookup_tile}
-#import bevy_terrain::attachments::{sample_normal, sample_color}
-#import bevy_terrain::debug::{show_data_lod, show_geometry_lod, show_tile_tree, show_pixels}
+#import bevy_terrain::types::{Blend, AtlasTile, Coordinate, TileCoordinate}
+#import bevy_terrain::bindings::{config, view_config, geometry_tiles, atlas_sampler, hole_mask}
+#ifdef TILE_PICKING
+#import bevy_terrain::bindings::picked_tile
+#endif
+#import bevy_terrain::functions::{compute_blend, lookup_tile, lookup_tile_fade, lookup_parent_tile, CULLED_TILE}
+#import bevy_terrain::attachments::{sample_height, sample_normal, sample_color}
+#import bevy_terrain::debug::{show_data_lod, show_geometry_lod, show_tile_tree, show_screen_error, show_pixels, show_lod, wireframe_outlines}
 #import bevy_pbr::mesh_view_bindings::view
 #import bevy_pbr::pbr_types::{PbrInput, pbr_input_new}
 #import bevy_pbr::pbr_functions::{calculate_view, apply_pbr_lighting}
 
+// The varyings passed from the terrain vertex shader.
+// The side is the index of the cube sphere face (always zero for planar terrains)
+// and the side uv is the location of the fragment within this face, both in the range [0, 1].
+// The world tangent and bitangent point in the direction of increasing side uv, which forms the tangent frame
+// for normal mapping textures sampled with the side uv.
+// The side uv has only f32 precision, so use the coordinate of the fragment info for high lods instead.
+// Near the view, `compute_relative_position` reconstructs the position relative to the view from the coordinate,
+// using the Taylor series of the terrain model approximation (see `SideParameter`).
 struct FragmentInput {
     @builtin(position)     clip_position: vec4<f32>,
     @location(0)           tile_index: u32,
     @location(1)           coordinate_uv: vec2<f32>,
     @location(2)           world_position: vec4<f32>,
     @location(3)           world_normal: vec3<f32>,
+    @location(4)           side: u32,
+    @location(5)           side_uv: vec2<f32>,
+    @location(6)           world_tangent: vec3<f32>,
+    @location(7)           world_bitangent: vec3<f32>,
+#ifdef DOUBLE_SIDED
+    @builtin(front_facing) front_facing: bool,
+#endif
 }
 
 struct FragmentOutput {
@@ -23,32 +41,173 @@ struct FragmentOutput {
 
 struct FragmentInfo {
     coordinate: Coordinate,
+    side: u32,
+    side_uv: vec2<f32>,
     view_distance: f32,
     blend: Blend,
     clip_position: vec4<f32>,
     world_normal: vec3<f32>,
+    world_tangent: vec3<f32>,
+    world_bitangent: vec3<f32>,
     world_position: vec4<f32>,
     color: vec4<f32>,
     normal: vec3<f32>,
+#ifdef DOUBLE_SIDED
+    front_facing: bool,
+#endif
+#ifdef SHOW_CULLED_TILES
+    culled: bool,
+#endif
+}
+
+#ifdef TILE_PICKING
+// Records the tile of the fragment, if it is drawn at the pick position and closer to the view (reverse z) than the previous ones.
+// Fragments of different tiles at the same pixel race each other, so the recorded tile is approximate where they overlap.
+fn record_picked_tile(tile: TileCoordinate, clip_position: vec4<f32>) {
+    if (any(vec2<u32>(clip_position.xy) != view_config.pick_position)) { return; }
+
+    let depth = bitcast<u32>(clip_position.z);
+
+    if (atomicMax(&picked_tile.depth, depth) <= depth) {
+        picked_tile.side          = tile.side;
+        picked_tile.lod           = tile.lod;
+        picked_tile.xy            = tile.xy;
+        picked_tile.pick_position = view_config.pick_position;
+    }
 }
+#endif
 
 fn fragment_info(input: FragmentInput) -> FragmentInfo{
-    let tile          = geometry_tiles[input.tile_index];
+#ifdef HOLE_MASK
+    // Discard the fragments inside the holes of the terrain.
+    if (textureSample(hole_mask, atlas_sampler, input.side_uv, input.side).x < 0.5) { discard; }
+#endif
+
+    var tile          = geometry_tiles[input.tile_index];
     let uv            = input.coordinate_uv;
     let view_distance = distance(input.world_position.xyz, view.world_position);
 
+#ifdef SHOW_CULLED_TILES
+    let culled        = (tile.side & CULLED_TILE) != 0u;
+    tile.side         = tile.side & ~CULLED_TILE;
+#endif
+
+#ifdef TILE_PICKING
+    record_picked_tile(tile, input.clip_position);
+#endif
+
     var info: FragmentInfo;
-    info.coordinate     = Coordinate(tile.side, tile.lod, tile.xy, uv, dpdx(uv), dpdy(uv));
-    info.view_distance  = view_distance;
-    info.blend          = compute_blend(view_distance);
-    info.clip_position  = input.clip_position;
-    info.world_normal   = input.world_normal;
-    info.world_position = input.world_position;
+    info.coordinate      = Coordinate(tile.side, tile.lod, tile.xy, uv, dpdx(uv), dpdy(uv));
+    info.side            = input.side;
+    info.side_uv         = input.side_uv;
+    info.view_distance   = view_distance;
+    info.blend           = compute_blend(view_distance);
+    info.clip_position   = input.clip_position;
+    info.world_normal    = input.world_normal;
+    info.world_tangent   = normalize(input.world_tangent);
+    info.world_bitangent = normalize(input.world_bitangent);
+    info.world_position  = input.world_position;
+#ifdef DOUBLE_SIDED
+    info.front_facing    = input.front_facing;
+#endif
+#ifdef SHOW_CULLED_TILES
+    info.culled          = culled;
+#endif
 
     return info;
 }
 
-fn fragment_output(info: ptr<function, FragmentInfo>, output: ptr<function, FragmentOutput>, color: vec4<f32>, normal: vec3<f32>) {
+// Samples the elevation of the fragment above the reference surface of the terrain model in world units
+// (e.g. for snow lines or contour bands in custom terrain materials), blending the two lods like the fragment shader.
+// The normalized height h stored in the atlas is mapped as `mix(min_height, max_height, pow(h, height_gamma))`,
+// where `min_height = model.min_height * height_scale + height_offset` (and likewise for the maximum height).
+// Heights are independent of the horizontal scale of the model, so no further scaling is applied.
+// The elevation excludes the detail noise and the sea level, compare it with `config.sea_level` to detect the sea.
+fn sample_elevation(info: ptr<function, FragmentInfo>) -> f32 {
+    let tile      = lookup_tile((*info).coordinate, (*info).blend, 0u);
+    var elevation = sample_height(tile);
+
+    if ((*info).blend.ratio > 0.0) {
+        let tile2 = lookup_tile((*info).coordinate, (*info).blend, 1u);
+        elevation = mix(elevation, sample_height(tile2), (*info).blend.ratio);
+    }
+
+    return elevation;
+}
+
+// Cross-fades the color of freshly loaded tiles with the one of their parent, so that finer tiles do not pop in.
+fn sample_faded_color(tile: AtlasTile) -> vec4<f32> {
+    var color = sample_color(tile);
+
+#ifdef TILE_FADE
+    let fade = lookup_tile_fade(tile);
+    if (fade < 1.0) { color = mix(sample_color(lookup_parent_tile(tile)), color, fade); }
+#endif
+
+    return color;
+}
+
+// Cross-fades the normal of freshly loaded tiles with the one of their parent, like `sample_faded_color`.
+fn sample_faded_normal(tile: AtlasTile, vertex_normal: vec3<f32>) -> vec3<f32> {
+    var normal = sample_normal(tile, vertex_normal);
+
+#ifdef TILE_FADE
+    let fade = lookup_tile_fade(tile);
+    if (fade < 1.0) { normal = mix(sample_normal(lookup_parent_tile(tile), vertex_normal), normal, fade); }
+#endif
+
+    return normal;
+}
+
+// Projects the fragment onto a decal, which is draped onto the terrain (e.g. a selection ring or a territory border).
+// The world position of the fragment info lies on the displaced surface, so the decal follows the terrain heights.
+// Its world normal is the one of the surface without heights, while `sample_normal` includes the slope of the terrain.
+// The decal is centered at its world position and spans the right and forward axes, whose lengths are its half extents.
+// Both axes should be perpendicular to the surface normal at the center, so the fragments are projected along this normal.
+// The returned uv lies in the range [0, 1] inside the decal. Pass the decals (e.g. an array of instances) in the
+// bind group of a custom terrain material and blend them into the color before calling `fragment_output`:
+//     let uv = decal_uv(&info, decal.center, decal.right, decal.forward);
+//     if (all(uv >= vec2(0.0)) && all(uv <= vec2(1.0))) { color = mix(color, decal.color, sample_decal(uv)); }
+fn decal_uv(info: ptr<function, FragmentInfo>, center: vec3<f32>, right: vec3<f32>, forward: vec3<f32>) -> vec2<f32> {
+    let offset = (*info).world_position.xyz - center;
+
+    return 0.5 * vec2<f32>(dot(offset, right) / dot(right, right), dot(offset, forward) / dot(forward, forward)) + 0.5;
+}
+
+fn fragment_output(info: ptr<function, FragmentInfo>, output: ptr<function, FragmentOutput>, terrain_color: vec4<f32>, terrain_normal: vec3<f32>) {
+    var color        = terrain_color;
+    var normal       = terrain_normal;
+    var world_normal = (*info).world_normal;
+
+#ifdef FLAT_SHADING
+    // Use the normal of the triangle, derived from the screen space derivatives of the world position.
+    let position    = (*info).world_position.xyz;
+    let face_normal = normalize(cross(dpdy(position), dpdx(position)));
+    normal          = face_normal * sign(dot(face_normal, world_normal));
+#endif
+
+#ifdef SEA_LEVEL
+    // The terrain below the sea level has been raised to the flat sea surface.
+    let tile = lookup_tile((*info).coordinate, (*info).blend, 0u);
+
+    if (sample_height(tile) <= config.sea_level) {
+        color  = config.sea_color;
+        normal = (*info).world_normal;
+    }
+#endif
+
+#ifdef CONTOUR_LINES
+    color = apply_contour_lines(info, color);
+#endif
+
+#ifdef DOUBLE_SIDED
+    // Light the back faces from below the surface.
+    if (!(*info).front_facing) {
+        normal       = -normal;
+        world_normal = -world_normal;
+    }
+#endif
+
 #ifdef LIGHTING
     var pbr_input: PbrInput                 = pbr_input_new();
     pbr_input.material.base_color           = color;
@@ -56,7 +215,7 @@ fn fragment_output(info: ptr<function, FragmentInfo>, output: ptr<function, Frag
     pbr_input.material.reflectance          = 0.0;
     pbr_input.frag_coord                    = (*info).clip_position;
     pbr_input.world_position                = (*info).world_position;
-    pbr_input.world_normal                  = (*info).world_normal;
+    pbr_input.world_normal                  = world_normal;
     pbr_input.N                             = normal;
     pbr_input.V                             = calculate_view((*info).world_position, pbr_input.is_orthographic);
 
@@ -64,6 +223,28 @@ fn fragment_output(info: ptr<function, FragmentInfo>, output: ptr<function, Frag
 #else
     (*output).color = color;
 #endif
+
+#ifdef ATMOSPHERE
+    (*output).color = apply_atmosphere(info, (*output).color);
+#endif
+}
+
+// Blends the contour lines into the color, which are placed at the multiples of the contour interval of the elevation.
+// The distance to the closest line is divided by the screen space derivative of the elevation, to measure it in pixels.
+fn apply_contour_lines(info: ptr<function, FragmentInfo>, color: vec4<f32>) -> vec4<f32> {
+    let elevation = sample_elevation(info) / config.contour_interval;
+    let distance  = abs(fract(elevation + 0.5) - 0.5) / max(fwidth(elevation), 0.000001);
+    let coverage  = 1.0 - smoothstep(0.5 * config.contour_width - 0.5, 0.5 * config.contour_width + 0.5, distance);
+
+    return vec4<f32>(mix(color.rgb, config.contour_color.rgb, coverage * config.contour_color.a), color.a);
+}
+
+// Blends the color towards the horizon color of the atmosphere, based on the distance to the view.
+fn apply_atmosphere(info: ptr<function, FragmentInfo>, color: vec4<f32>) -> vec4<f32> {
+    let optical_depth = config.atmosphere_density * (*info).view_distance / config.planet_radius;
+    let transmittance = exp(-optical_depth);
+
+    return vec4<f32>(mix(config.atmosphere_color.rgb, color.rgb, transmittance), color.a);
 }
 
 fn fragment_debug(info: ptr<function, FragmentInfo>, output: ptr<function, FragmentOutput>, tile: AtlasTile, normal: vec3<f32>) {
@@ -76,6 +257,9 @@ fn fragment_debug(info: ptr<function, FragmentInfo>, output: ptr<function, Fragm
 #ifdef SHOW_TILE_TREE
     (*output).color = show_tile_tree((*info).coordinate);
 #endif
+#ifdef SHOW_SCREEN_ERROR
+    (*output).color = show_screen_error((*info).coordinate);
+#endif
 #ifdef SHOW_PIXELS
     (*output).color = mix((*output).color, show_pixels(tile), 0.5);
 #endif
@@ -85,6 +269,19 @@ fn fragment_debug(info: ptr<function, FragmentInfo>, output: ptr<function, Fragm
 #ifdef SHOW_NORMALS
     (*output).color = vec4<f32>(normal, 1.0);
 #endif
+#ifdef SHOW_LOD
+    (*output).color = mix((*output).color, show_lod((*info).coordinate), 0.5);
+#endif
+#ifdef WIREFRAME
+    (*output).color = mix((*output).color, vec4<f32>(0.0, 0.0, 0.0, 1.0), wireframe_outlines((*info).coordinate.uv));
+#endif
+#ifdef SHOW_CULLED_TILES
+    // Tint the tiles culled by the refinement and outline them, even without the wireframe view.
+    if ((*info).culled) {
+        (*output).color = mix((*output).color, vec4<f32>(1.0, 0.0, 0.0, 1.0), 0.5);
+        (*output).color = mix((*output).color, vec4<f32>(1.0, 0.0, 0.0, 1.0), wireframe_outlines((*info).coordinate.uv));
+    }
+#endif
 
     // Todo: move this somewhere else
     if ((*info).view_distance < view_config.precision_threshold_distance) {
@@ -97,13 +294,13 @@ fn fragment(input: FragmentInput) -> FragmentOutput {
     var info = fragment_info(input);
 
     let tile   = lookup_tile(info.coordinate, info.blend, 0u);
-    var color  = sample_color(tile);
-    var normal = sample_normal(tile, info.world_normal);
+    var color  = sample_faded_color(tile);
+    var normal = sample_faded_normal(tile, info.world_normal);
 
     if (info.blend.ratio > 0.0) {
         let tile2 = lookup_tile(info.coordinate, info.blend, 1u);
-        color     = mix(color,  sample_color(tile2),                     info.blend.ratio);
-        normal    = mix(normal, sample_normal(tile2, info.world_normal), info.blend.ratio);
+        color     = mix(color,  sample_faded_color(tile2),                     info.blend.ratio);
+        normal    = mix(normal, sample_faded_normal(tile2, info.world_normal), info.blend.ratio);
     }
 
     var output: FragmentOutput;
@@ -1,8 +1,8 @@
 #define_import_path bevy_terrain::vertex
 
 #import bevy_terrain::types::{Blend, AtlasTile, Coordinate}
-#import bevy_terrain::bindings::{config, view_config, geometry_tiles, terrain_model_approximation}
-#import bevy_terrain::functions::{lookup_tile, compute_tile_uv, compute_local_position, compute_relative_position, compute_morph, compute_blend, normal_local_to_world, position_local_to_world}
+#import bevy_terrain::bindings::{config, view_config, geometry_tiles, terrain_model_approximation, detail_noise, detail_sampler}
+#import bevy_terrain::functions::{lookup_tile, compute_tile_uv, compute_local_position, compute_relative_position, compute_morph, compute_blend, lookup_tile_fade, lookup_parent_tile, is_skirt_vertex, compute_local_tangents, normal_local_to_world, tangent_local_to_world, position_local_to_world, tile_count, CULLED_TILE}
 #import bevy_terrain::attachments::{sample_height}
 #import bevy_pbr::mesh_view_bindings::view
 #import bevy_pbr::view_transformations::position_world_to_clip
@@ -17,6 +17,10 @@ struct VertexOutput {
     @location(1)       coordinate_uv: vec2<f32>,
     @location(2)       world_position: vec4<f32>,
     @location(3)       world_normal: vec3<f32>,
+    @location(4)       side: u32,
+    @location(5)       side_uv: vec2<f32>,
+    @location(6)       world_tangent: vec3<f32>,
+    @location(7)       world_bitangent: vec3<f32>,
 }
 
 struct VertexInfo {
@@ -24,12 +28,18 @@ struct VertexInfo {
     coordinate: Coordinate,
     world_position: vec3<f32>,
     world_normal: vec3<f32>,
+    world_tangent: vec3<f32>,
+    world_bitangent: vec3<f32>,
     blend: Blend,
+    skirt: bool,
 }
 
 fn vertex_info(input: VertexInput) -> VertexInfo {
     let tile_index                 = input.vertex_index / view_config.vertices_per_tile;
-    let tile                       = geometry_tiles[tile_index];
+    var tile                       = geometry_tiles[tile_index];
+#ifdef SHOW_CULLED_TILES
+    tile.side                      = tile.side & ~CULLED_TILE;
+#endif
     let tile_uv                    = compute_tile_uv(input.vertex_index);
     let approximate_coordinate     = Coordinate(tile.side, tile.lod, tile.xy, tile_uv);
     let approximate_local_position = compute_local_position(approximate_coordinate);
@@ -43,7 +53,7 @@ fn vertex_info(input: VertexInput) -> VertexInfo {
     let high_precision = false;
 #endif
 
-    var coordinate: Coordinate; var world_position: vec3<f32>; var world_normal: vec3<f32>;
+    var coordinate: Coordinate; var world_position: vec3<f32>; var world_normal: vec3<f32>; var local_tangents: mat2x3<f32>;
 
     if (high_precision) {
         let approximate_relative_position = compute_relative_position(approximate_coordinate);
@@ -53,32 +63,83 @@ fn vertex_info(input: VertexInput) -> VertexInfo {
         let relative_position = compute_relative_position(coordinate);
         world_position        = view.world_position + relative_position;
         world_normal          = approximate_world_normal;
+        local_tangents        = compute_local_tangents(approximate_coordinate.side, approximate_local_position);
     } else {
         coordinate         = compute_morph(approximate_coordinate, approximate_view_distance);
         let local_position = compute_local_position(coordinate);
         world_position     = position_local_to_world(local_position);
         world_normal       = normal_local_to_world(local_position);
+        local_tangents     = compute_local_tangents(coordinate.side, local_position);
     }
 
     var info: VertexInfo;
-    info.tile_index     = tile_index;
-    info.coordinate     = coordinate;
-    info.world_position = world_position;
-    info.world_normal   = world_normal;
-    info.blend          = compute_blend(approximate_view_distance);
+    info.tile_index      = tile_index;
+    info.coordinate      = coordinate;
+    info.world_position  = world_position;
+    info.world_normal    = world_normal;
+    info.world_tangent   = tangent_local_to_world(local_tangents[0]);
+    info.world_bitangent = tangent_local_to_world(local_tangents[1]);
+    info.blend           = compute_blend(approximate_view_distance);
+    info.skirt           = is_skirt_vertex(input.vertex_index);
 
     return info;
 }
 
+// Samples the detail noise, which fades in over the finest lods, following the continuous blend lod of the vertex.
+fn sample_detail_noise(info: ptr<function, VertexInfo>, side_uv: vec2<f32>) -> f32 {
+    let blend_lod = f32((*info).blend.lod) - (*info).blend.ratio;
+    let fade      = saturate((blend_lod + 1.0 - f32(config.lod_count)) / config.detail_fade_lod_count + 1.0);
+    let noise     = textureSampleLevel(detail_noise, detail_sampler, side_uv * config.detail_frequency, 0.0).x;
+
+    return fade * config.detail_amplitude * (2.0 * noise - 1.0);
+}
+
+// Cross-fades the height of freshly loaded tiles with the one of their parent, so that finer tiles do not pop in.
+fn sample_faded_height(tile: AtlasTile) -> f32 {
+    var height = sample_height(tile);
+
+#ifdef TILE_FADE
+    let fade = lookup_tile_fade(tile);
+    if (fade < 1.0) { height = mix(sample_height(lookup_parent_tile(tile)), height, fade); }
+#endif
+
+    return height;
+}
+
 fn vertex_output(info: ptr<function, VertexInfo>, height: f32) -> VertexOutput {
-    let world_position = (*info).world_position + height * (*info).world_normal;
+    let side_uv = (vec2<f32>((*info).coordinate.xy) + (*info).coordinate.uv) / tile_count((*info).coordinate.lod);
+
+#ifdef DETAIL_NOISE
+    let detail_height = height + sample_detail_noise(info, side_uv);
+#else
+    let detail_height = height;
+#endif
+
+#ifdef SEA_LEVEL
+    let surface_height = max(detail_height, config.sea_level);
+#else
+    let surface_height = detail_height;
+#endif
+
+#ifdef SKIRTS
+    // extrude the skirt vertices below the surface, to hide the cracks between tiles of different lods
+    let skirt_height = select(0.0, config.skirt_depth, (*info).skirt);
+#else
+    let skirt_height = 0.0;
+#endif
+
+    let world_position = (*info).world_position + (surface_height - skirt_height) * (*info).world_normal;
 
     var output: VertexOutput;
-    output.clip_position  = position_world_to_clip(world_position);
-    output.tile_index     = (*info).tile_index;
-    output.coordinate_uv  = (*info).coordinate.uv;
-    output.world_position = vec4<f32>(world_position, 1.0);
-    output.world_normal   = (*info).world_normal;
+    output.clip_position   = position_world_to_clip(world_position);
+    output.tile_index      = (*info).tile_index;
+    output.coordinate_uv   = (*info).coordinate.uv;
+    output.world_position  = vec4<f32>(world_position, 1.0);
+    output.world_normal    = (*info).world_normal;
+    output.side            = (*info).coordinate.side;
+    output.side_uv         = side_uv;
+    output.world_tangent   = (*info).world_tangent;
+    output.world_bitangent = (*info).world_bitangent;
     return output;
 }
 
@@ -87,11 +148,11 @@ fn vertex(input: VertexInput) -> VertexOutput {
     var info = vertex_info(input);
 
     let tile   = lookup_tile(info.coordinate, info.blend, 0u);
-    var height = sample_height(tile);
+    var height = sample_faded_height(tile);
 
     if (info.blend.ratio > 0.0) {
         let tile2 = lookup_tile(info.coordinate, info.blend, 1u);
-        height    = mix(height, sample_height(tile2), info.blend.ratio);
+        height    = mix(height, sample_faded_height(tile2), info.blend.ratio);
     }
 
     return vertex_output(&info, height);
@@ -1,17 +1,26 @@
 #import bevy_terrain::types::TileCoordinate
-#import bevy_terrain::bindings::{view_config, temporary_tiles, parameters, indirect_buffer}
+#import bevy_terrain::bindings::{config, view_config, temporary_tiles, parameters, indirect_buffer}
 
 @compute @workgroup_size(1, 1, 1)
 fn prepare_root() {
     parameters.counter = -1;
     atomicStore(&parameters.child_index, i32(view_config.tile_count - 1u));
     atomicStore(&parameters.final_index, 0);
+    parameters.used_refinement_count = 0u;
+
+    for (var i: u32 = 0u; i < 32u; i = i + 1u) {
+        parameters.iteration_tile_counts[i] = 0u;
+    }
 
 #ifdef SPHERICAL
-    parameters.tile_count = 6u;
+    parameters.tile_count = 0u;
 
+    // only the active sides are refined
     for (var i: u32 = 0u; i < 6u; i = i + 1u) {
-        temporary_tiles[i] = TileCoordinate(i, 0u, vec2<u32>(0u));
+        if ((config.side_mask & (1u << i)) != 0u) {
+            temporary_tiles[parameters.tile_count] = TileCoordinate(i, 0u, vec2<u32>(0u));
+            parameters.tile_count += 1u;
+        }
     }
 #else
     parameters.tile_count = 1u;
@@ -31,13 +40,22 @@ fn prepare_next() {
         parameters.tile_count = view_config.tile_count - 1u - u32(atomicExchange(&parameters.child_index, 0));
     }
 
+    // the last refinement iteration subdivided at least one tile, if it emitted children
+    if (parameters.tile_count > 0u) {
+        if (parameters.used_refinement_count < 32u) {
+            parameters.iteration_tile_counts[parameters.used_refinement_count] = parameters.tile_count;
+        }
+
+        parameters.used_refinement_count += 1u;
+    }
+
     parameters.counter = -parameters.counter;
     indirect_buffer.workgroup_count.x = (parameters.tile_count + 63u) / 64u;
 }
 
 @compute @workgroup_size(1, 1, 1)
 fn prepare_render() {
-    let tile_count = u32(atomicLoad(&parameters.final_index));
+    let tile_count = min(u32(atomicLoad(&parameters.final_index)), view_config.tile_count);
     let vertex_count = view_config.vertices_per_tile * tile_count;
 
     indirect_buffer.workgroup_count = vec3<u32>(vertex_count, 1u, 0u);
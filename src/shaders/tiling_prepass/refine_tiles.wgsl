@@ -1,6 +1,10 @@
 #import bevy_terrain::types::{TileCoordinate, Coordinate}
-#import bevy_terrain::bindings::{config, culling_view, view_config, final_tiles, temporary_tiles, parameters, terrain_model_approximation}
-#import bevy_terrain::functions::{approximate_view_distance, compute_relative_position, position_local_to_world, normal_local_to_world, tile_count, compute_subdivision_coordinate}
+#import bevy_terrain::bindings::{config, culling_view, view_config, final_tiles, temporary_tiles, parameters, terrain_model_approximation, screen_lods}
+#import bevy_terrain::functions::{approximate_view_distance, compute_relative_position, compute_local_position, position_local_to_world, normal_local_to_world, tile_count, compute_subdivision_coordinate, compute_tile_tree_uv, lookup_tile_tree_entry, remap_height, inside_hole, CULLED_TILE}
+
+#ifdef PREPASS_EXTENSION
+#import bevy_terrain::prepass_extension::{discard_tile, tile_height_range}
+#endif
 
 fn child_index() -> i32 {
     return atomicAdd(&parameters.child_index, parameters.counter);
@@ -15,10 +19,121 @@ fn final_index() -> i32 {
 }
 
 fn should_be_divided(tile: TileCoordinate) -> bool {
+    if (tile.lod >= config.max_lod) { return false; }
+    if (tile.lod < view_config.min_lod) { return true; }
+
     let coordinate    = compute_subdivision_coordinate(Coordinate(tile.side, tile.lod, tile.xy, vec2<f32>(0.0)));
     let view_distance = approximate_view_distance(coordinate, culling_view.world_position);
 
+#ifdef LOD_DISTANCES
+    // Lods without a distance have a distance of zero and are never subdivided.
+    if (tile.lod >= 32u) { return false; }
+
+    return view_distance < view_config.lod_distances[tile.lod / 4u][tile.lod % 4u];
+#else
     return view_distance < view_config.subdivision_distance / tile_count(tile.lod);
+#endif
+}
+
+fn frustum_cull(tile: TileCoordinate) -> bool {
+#ifdef SPHERICAL
+    // The tiles of the first lods curve around the sphere and are not bounded by their corners.
+    if (tile.lod < 2u) { return false; }
+#endif
+
+    var min_height = config.min_height;
+    var max_height = config.max_height;
+
+    // Tighten the height bounds using the best loaded tile, if the tile is covered by the tile tree.
+    let center = Coordinate(tile.side, tile.lod, tile.xy, vec2<f32>(0.5));
+    let tile_tree_uv = compute_tile_tree_uv(center);
+
+    if (tile.lod < config.lod_count && all(tile_tree_uv > vec2<f32>(0.0)) && all(tile_tree_uv < vec2<f32>(1.0))) {
+        let entry = lookup_tile_tree_entry(center);
+        min_height = remap_height(entry.min_height);
+        max_height = remap_height(entry.max_height);
+    }
+
+    // The height overlay offsets the terrain by up to its bounds, which are zero without an enabled overlay.
+    min_height += config.overlay_min_height;
+    max_height += config.overlay_max_height;
+
+    // The detail noise displaces the terrain by up to its amplitude, which is zero without detail noise.
+    // The terrain below the sea level is flattened onto it, see `vertex_output`.
+    // Without a sea level, it is the lowest float and leaves the bounds unchanged.
+    // The skirts extend below the surface by their depth, which is zero without skirts.
+    min_height = max(min_height - config.detail_amplitude, config.sea_level) - config.skirt_depth;
+    max_height = max(max_height + config.detail_amplitude, config.sea_level);
+
+#ifdef PREPASS_EXTENSION
+    let height_range = tile_height_range(tile, vec2<f32>(min_height, max_height));
+    min_height = height_range.x;
+    max_height = height_range.y;
+#endif
+
+    // Bound the tile by its corners and center, each at the minimum and maximum tile height.
+    var UVS = array(vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0), vec2(0.5, 0.5));
+    var points: array<vec3<f32>, 10>;
+
+    for (var i: u32 = 0u; i < 5u; i = i + 1u) {
+        let local_position = compute_local_position(Coordinate(tile.side, tile.lod, tile.xy, UVS[i]));
+        let world_position = position_local_to_world(local_position);
+        let world_normal   = normal_local_to_world(local_position);
+
+        points[2u * i]      = world_position + min_height * world_normal;
+        points[2u * i + 1u] = world_position + max_height * world_normal;
+    }
+
+    // The tile is culled, if all of its points lie outside of any frustum plane.
+    // The planes are not normalized, so the culling margin is scaled by the length of their normal.
+    for (var p: u32 = 0u; p < 5u; p = p + 1u) {
+        let plane  = culling_view.planes[p];
+        let margin = culling_view.culling_margin * length(plane.xyz);
+        var outside = true;
+
+        for (var i: u32 = 0u; i < 10u; i = i + 1u) {
+            if (dot(plane.xyz, points[i]) + plane.w > -margin) { outside = false; }
+        }
+
+        if (outside) { return true; }
+    }
+
+    return false;
+}
+
+// The count of rows and columns of the screen cells, matching `SCREEN_LOD_GRID_SIZE` on the CPU.
+const SCREEN_LOD_GRID_SIZE: u32 = 16u;
+
+// Records the lod of the tile in all screen cells, which overlap the screen space bounds of its corners and center.
+// Each cell keeps the finest lod offset by one, so that zero marks cells without any tile.
+// Points behind the view are ignored, which only shrinks the bounds of tiles close to the view.
+fn record_screen_lod(tile: TileCoordinate) {
+    var UVS = array(vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0), vec2(0.5, 0.5));
+    var min_uv = vec2<f32>(1.0);
+    var max_uv = vec2<f32>(0.0);
+
+    for (var i: u32 = 0u; i < 5u; i = i + 1u) {
+        let local_position = compute_local_position(Coordinate(tile.side, tile.lod, tile.xy, UVS[i]));
+        let clip_position  = culling_view.view_proj * vec4<f32>(position_local_to_world(local_position), 1.0);
+
+        if (clip_position.w <= 0.0) { continue; }
+
+        let screen_uv = clip_position.xy / clip_position.w * vec2<f32>(0.5, -0.5) + 0.5;
+        min_uv = min(min_uv, screen_uv);
+        max_uv = max(max_uv, screen_uv);
+    }
+
+    if (any(min_uv > max_uv) || any(max_uv < vec2<f32>(0.0)) || any(min_uv > vec2<f32>(1.0))) { return; }
+
+    let last_cell = vec2<f32>(f32(SCREEN_LOD_GRID_SIZE - 1u));
+    let min_cell  = vec2<u32>(clamp(min_uv * f32(SCREEN_LOD_GRID_SIZE), vec2<f32>(0.0), last_cell));
+    let max_cell  = vec2<u32>(clamp(max_uv * f32(SCREEN_LOD_GRID_SIZE), vec2<f32>(0.0), last_cell));
+
+    for (var y: u32 = min_cell.y; y <= max_cell.y; y = y + 1u) {
+        for (var x: u32 = min_cell.x; x <= max_cell.x; x = x + 1u) {
+            atomicMax(&screen_lods[y * SCREEN_LOD_GRID_SIZE + x], tile.lod + 1u);
+        }
+    }
 }
 
 fn subdivide(tile: TileCoordinate) {
@@ -36,9 +151,34 @@ fn refine_tiles(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     let tile = temporary_tiles[parent_index(invocation_id.x)];
 
+#ifdef PREPASS_EXTENSION
+    if (discard_tile(tile)) { return; }
+#endif
+
+#ifdef HOLE_MASK
+    if (inside_hole(tile)) { return; }
+#endif
+
+#ifdef CULLING
+    if (frustum_cull(tile)) {
+#ifdef SHOW_CULLED_TILES
+        // Emit the culled tile without refining it any further, so that it can be drawn tinted.
+        let index = final_index();
+        if (index < i32(view_config.tile_count)) { final_tiles[index] = TileCoordinate(tile.side | CULLED_TILE, tile.lod, tile.xy); }
+#endif
+        return;
+    }
+#endif
+
     if (should_be_divided(tile)) {
         subdivide(tile);
     } else {
-        final_tiles[final_index()] = tile;
+        // Tiles beyond the capacity of the tile buffer are dropped, but still counted.
+        let index = final_index();
+        if (index < i32(view_config.tile_count)) { final_tiles[index] = tile; }
+
+#ifdef SCREEN_LODS
+        record_screen_lod(tile);
+#endif
     }
 }
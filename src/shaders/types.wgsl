@@ -5,6 +5,35 @@ struct TerrainConfig {
     min_height: f32,
     max_height: f32,
     scale: f32,
+    height_gamma: f32,
+    curvature: f32,
+    atmosphere_color: vec4<f32>,
+    atmosphere_density: f32,
+    planet_radius: f32,
+    sea_level: f32,
+    sea_color: vec4<f32>,
+    seed: u32,
+    height_channel: u32,
+    detail_amplitude: f32,
+    detail_frequency: f32,
+    detail_fade_lod_count: f32,
+    max_lod: u32,
+    mip_bias: f32,
+    side_mask: u32,
+    skirt_depth: f32,
+    height_floor: f32,
+    overlay_min_height: f32,
+    overlay_max_height: f32,
+    contour_interval: f32,
+    contour_width: f32,
+    contour_color: vec4<f32>,
+}
+
+// The time of the frame in seconds, which can be used to animate the terrain surface in custom shaders.
+// The elapsed time wraps around every hour, like `globals.time` of bevy.
+struct TerrainTime {
+    time: f32,
+    delta: f32,
 }
 
 struct TerrainViewConfig {
@@ -21,6 +50,19 @@ struct TerrainViewConfig {
     morph_range: f32,
     blend_range: f32,
     precision_threshold_distance: f32,
+    min_lod: u32,
+    forced_mip_level: f32,
+    lod_distances: array<vec4<f32>, 8>,
+    pick_position: vec2<u32>,
+}
+
+// The geometry tile closest to the view, which was drawn at the pick position of the view.
+struct PickedTile {
+    depth: atomic<u32>,
+    side: u32,
+    lod: u32,
+    xy: vec2<u32>,
+    pick_position: vec2<u32>,
 }
 
 struct TileCoordinate {
@@ -45,6 +87,9 @@ struct Parameters {
     counter: i32,
     child_index: atomic<i32>,
     final_index: atomic<i32>,
+    used_refinement_count: u32,
+    // the count of tiles, into which each refinement iteration subdivided (see `MAX_TILE_COUNT_ITERATIONS`)
+    iteration_tile_counts: array<u32, 32>,
 }
 
 struct Blend {
@@ -55,6 +100,9 @@ struct Blend {
 struct TileTreeEntry {
     atlas_index: u32,
     atlas_lod: u32,
+    min_height: f32,
+    max_height: f32,
+    fade: f32,
 }
 
 // A tile inside the tile atlas, looked up based on the view of a tile tree.
@@ -100,4 +148,5 @@ struct CullingData {
     world_position: vec3<f32>,
     view_proj: mat4x4<f32>,
     planes: array<vec4<f32>, 5>,
+    culling_margin: f32,
 }
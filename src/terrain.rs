@@ -4,10 +4,25 @@
 use crate::big_space::{GridCell, GridTransformOwned, ReferenceFrame};
 
 use crate::{
-    math::TerrainModel,
-    terrain_data::{tile_atlas::TileAtlas, AttachmentConfig},
+    math::{Coordinate, TerrainModel},
+    render::tiling_prepass::TilingPrepassPipelineKey,
+    terrain_data::{
+        tile_atlas::TileAtlas,
+        tile_source::{FileTileSource, TileSource},
+        AttachmentConfig,
+    },
 };
-use bevy::{ecs::entity::EntityHashMap, prelude::*, render::view::NoFrustumCulling};
+use bevy::{
+    ecs::entity::EntityHashMap,
+    math::{DVec2, DVec3},
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_resource::{DepthBiasState, ShaderDefVal, TextureFormat},
+        view::NoFrustumCulling,
+    },
+};
+use std::{error::Error, fmt, sync::Arc};
 
 /// Resource that stores components that are associated to a terrain entity.
 /// This is used to persist components in the render world.
@@ -20,6 +35,291 @@ impl<C> Default for TerrainComponents<C> {
     }
 }
 
+/// The atmospheric perspective of a terrain.
+///
+/// The color of each fragment is blended towards the horizon color by `1 - exp(-density * distance / planet_radius)`,
+/// where distance is the distance between the fragment and the view.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainAtmosphere {
+    /// The color distant terrain fades to.
+    pub color: Color,
+    /// The optical depth of the atmosphere along a path with the length of the planet radius.
+    pub density: f32,
+    /// The radius of the planet, which scales the distance at which the terrain fades.
+    pub planet_radius: f32,
+}
+
+impl Default for TerrainAtmosphere {
+    fn default() -> Self {
+        Self {
+            color: Color::srgb(0.6, 0.75, 0.9),
+            density: 1.0,
+            planet_radius: 1.0,
+        }
+    }
+}
+
+/// Contour lines, which are drawn at regular intervals of the elevation (e.g. for topographic maps).
+///
+/// The lines are drawn in the fragment shader at the multiples of the interval of the elevation (see `sample_elevation`).
+/// Their distance is measured with the screen space derivatives of the elevation, so they keep their width in pixels at any distance.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainContourLines {
+    /// The elevation difference between adjacent lines, specified like the heights of the model (including the height scale).
+    pub interval: f32,
+    /// The color of the lines, whose alpha blends them with the terrain color.
+    pub color: Color,
+    /// The width of the lines in pixels.
+    pub width: f32,
+}
+
+impl Default for TerrainContourLines {
+    fn default() -> Self {
+        Self {
+            interval: 1.0,
+            color: Color::srgba(0.1, 0.05, 0.0, 0.8),
+            width: 1.0,
+        }
+    }
+}
+
+/// High frequency noise, which displaces the finest tiles of the terrain beyond the resolution of the height data.
+///
+/// The first channel of the noise texture is sampled with the side uv, scaled by the frequency, and mapped from [0, 1] to
+/// [-amplitude, amplitude]. The noise fades in over the finest lods of the terrain, following the blend of the height data.
+/// The tiles are culled without accounting for the noise, so the culling margin of the views should be at least the amplitude.
+#[derive(Clone, Debug)]
+pub struct TerrainDetailNoise {
+    /// A tileable noise texture, which is sampled with a repeating sampler.
+    pub texture: Handle<Image>,
+    /// The maximum displacement in world units.
+    pub amplitude: f32,
+    /// The count of noise texture repetitions along a side of the terrain.
+    pub frequency: f32,
+    /// The count of lods, over which the noise fades in, ending at the finest lod of the terrain.
+    pub fade_lod_count: u32,
+}
+
+impl Default for TerrainDetailNoise {
+    fn default() -> Self {
+        Self {
+            texture: default(),
+            amplitude: 1.0,
+            frequency: 1024.0,
+            fade_lod_count: 2,
+        }
+    }
+}
+
+/// An additional heightmap, which is added onto the heights of the height attachment (e.g. for terrain editing).
+///
+/// The overlay keeps runtime edits separate from the source data, which stays untouched.
+/// Its first channel stores the height offset, specified like the heights of the model (including the height scale),
+/// and is sampled with the side uv, with one array layer per side of the terrain.
+/// It should use the `R32Float` format, since the CPU sampling reads texels of other formats as zero,
+/// and keep its data in the main world (see [`TerrainHeightOverlay::sample`]).
+/// Changes to the image are picked up by the render world, once they are uploaded.
+/// The tiles are culled with the bounds of the overlay heights (see [`TerrainHeightOverlay::min_height`]),
+/// while the normals include the slope of the overlay.
+///
+/// The overlay can also be edited on the GPU with brush strokes (see [`TileAtlas::apply_brush`]),
+/// which requires the `STORAGE_BINDING` texture usage.
+/// The strokes are mirrored on a CPU copy of the image, which the CPU sampling reads instead.
+/// The edits are lost on both, once the image is modified and thus uploaded again.
+#[derive(Clone, Debug)]
+pub struct TerrainHeightOverlay {
+    /// The overlay texture, which should be loaded before the terrain is spawned, like the hole mask.
+    pub texture: Handle<Image>,
+    /// Whether the overlay is added to the heights of the terrain.
+    pub enabled: bool,
+    /// The lower bound of the height offsets, which extends the height bounds of the tiles during culling.
+    ///
+    /// The overlay data is not read back to compute the bounds, so they have to include the edits
+    /// of the brush strokes. Otherwise, tiles raised or lowered beyond the bounds may be culled incorrectly.
+    pub min_height: f32,
+    /// The upper bound of the height offsets, see [`TerrainHeightOverlay::min_height`].
+    pub max_height: f32,
+    /// The copy of the image including the brush strokes, which mirrors the edits of the GPU.
+    pub(crate) edited_image: Option<Image>,
+}
+
+impl TerrainHeightOverlay {
+    pub fn new(texture: Handle<Image>, min_height: f32, max_height: f32) -> Self {
+        Self {
+            texture,
+            enabled: true,
+            min_height,
+            max_height,
+            edited_image: None,
+        }
+    }
+
+    /// Whether the overlay has been edited by brush strokes, which are mirrored on the CPU.
+    ///
+    /// The edits are only stored on the GPU and in this copy, not in the image asset.
+    pub fn is_edited(&self) -> bool {
+        self.edited_image.is_some()
+    }
+
+    /// Samples the height offset of the overlay at the coordinate on the CPU, matching the bilinear sampling of the shaders.
+    ///
+    /// The brush strokes are included, see [`TileAtlas::apply_brush`].
+    /// Returns zero, if the overlay is disabled or its image is not available in the main world.
+    pub fn sample(&self, coordinate: Coordinate, images: &Assets<Image>) -> f32 {
+        let image = self
+            .edited_image
+            .as_ref()
+            .or_else(|| images.get(&self.texture));
+
+        let Some(image) = image.filter(|_| self.enabled) else {
+            return 0.0;
+        };
+
+        if image.texture_descriptor.format != TextureFormat::R32Float {
+            return 0.0;
+        }
+
+        let size = image.size();
+        let layer = coordinate
+            .side
+            .min(image.texture_descriptor.array_layer_count() - 1);
+        let texel = (coordinate.uv * size.as_dvec2() - 0.5).max(DVec2::ZERO);
+        let remainder = texel.fract().as_vec2();
+        let texel = texel.as_uvec2();
+
+        let value = |x: u32, y: u32| {
+            let index = (layer * size.y + y.min(size.y - 1)) * size.x + x.min(size.x - 1);
+            let index = index as usize * size_of::<f32>();
+
+            image
+                .data
+                .get(index..index + size_of::<f32>())
+                .map_or(0.0, |bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        f32::lerp(
+            f32::lerp(
+                value(texel.x, texel.y),
+                value(texel.x + 1, texel.y),
+                remainder.x,
+            ),
+            f32::lerp(
+                value(texel.x, texel.y + 1),
+                value(texel.x + 1, texel.y + 1),
+                remainder.x,
+            ),
+            remainder.y,
+        )
+    }
+}
+
+/// The way a [`TerrainBrushStroke`] changes the height overlay.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TerrainBrushMode {
+    /// Adds the strength to the height offset.
+    #[default]
+    Raise,
+    /// Subtracts the strength from the height offset.
+    Lower,
+    /// Blends the height offset towards the average of its neighbours by the strength (between zero and one).
+    Smooth,
+}
+
+/// A single stroke of the brush, which edits the [`TerrainHeightOverlay`] on the GPU (and its copy on the CPU).
+///
+/// The strength falls off smoothly from the center of the brush to its radius.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainBrushStroke {
+    /// The center of the brush on the terrain.
+    pub coordinate: Coordinate,
+    /// The radius of the brush, in side uv.
+    pub radius: f32,
+    /// The strength of the brush at its center, specified like the height offset of the overlay.
+    pub strength: f32,
+    pub mode: TerrainBrushMode,
+}
+
+impl TerrainBrushStroke {
+    /// Applies the stroke to the overlay image on the CPU, exactly like the brush pass on the GPU.
+    ///
+    /// The smoothing reads the heights before the stroke. Images of other formats than `R32Float` are left unchanged.
+    pub fn apply(&self, image: &mut Image) {
+        if image.texture_descriptor.format != TextureFormat::R32Float {
+            return;
+        }
+
+        let size = image.size();
+        let layer = self
+            .coordinate
+            .side
+            .min(image.texture_descriptor.array_layer_count() - 1);
+        let source = image.data.clone();
+
+        let index =
+            |x: u32, y: u32| ((layer * size.y + y) * size.x + x) as usize * size_of::<f32>();
+        let load_height = |x: i32, y: i32| {
+            let index = index(
+                x.clamp(0, size.x as i32 - 1) as u32,
+                y.clamp(0, size.y as i32 - 1) as u32,
+            );
+
+            source
+                .get(index..index + size_of::<f32>())
+                .map_or(0.0, |bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let uv = (UVec2::new(x, y).as_vec2() + 0.5) / size.as_vec2();
+
+                // the strength falls off smoothly from the center to the radius of the brush, like `smoothstep`
+                let t = (uv.distance(self.coordinate.uv.as_vec2()) / self.radius).clamp(0.0, 1.0);
+                let falloff = 1.0 - t * t * (3.0 - 2.0 * t);
+
+                if falloff <= 0.0 {
+                    continue;
+                }
+
+                let (x, y) = (x as i32, y as i32);
+                let height = load_height(x, y);
+
+                let height = match self.mode {
+                    TerrainBrushMode::Raise => height + falloff * self.strength,
+                    TerrainBrushMode::Lower => height - falloff * self.strength,
+                    TerrainBrushMode::Smooth => {
+                        let smoothed_height = (load_height(x + 1, y)
+                            + load_height(x - 1, y)
+                            + load_height(x, y + 1)
+                            + load_height(x, y - 1))
+                            / 4.0;
+
+                        // the operations of `mix` in the shader
+                        let blend = (falloff * self.strength).clamp(0.0, 1.0);
+
+                        height * (1.0 - blend) + smoothed_height * blend
+                    }
+                };
+
+                let index = index(x as u32, y as u32);
+
+                if let Some(bytes) = image.data.get_mut(index..index + size_of::<f32>()) {
+                    bytes.copy_from_slice(&height.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// The shading of the terrain surface.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TerrainShading {
+    /// Uses the interpolated normals, which include the slope of the height attachment.
+    #[default]
+    Smooth,
+    /// Uses the normal of each triangle, which results in a faceted look (e.g. for stylized terrains).
+    Flat,
+}
+
 /// The configuration of a terrain.
 ///
 /// Here you can define all fundamental parameters of the terrain.
@@ -27,23 +327,199 @@ impl<C> Default for TerrainComponents<C> {
 pub struct TerrainConfig {
     /// The count of level of detail layers.
     pub lod_count: u32,
+    /// The optional finest lod, to which the tiles are refined and loaded, which bounds the detail and the streaming.
+    ///
+    /// The data only exists up to lod `lod_count - 1`, which corresponds to the native resolution of the heightmap.
+    /// Finer geometry tiles only upsample this data, so a maximum lod below the lod count also caps the resolution of the data,
+    /// while one above it only caps the geometric detail (e.g. once the camera is pressed against the surface).
+    pub max_lod: Option<u32>,
     pub model: TerrainModel,
+    /// The factor by which all terrain heights are exaggerated, independent of the horizontal scale of the model.
+    pub height_scale: f32,
+    /// The constant offset in world units, which is added to all terrain heights after the height scale.
+    ///
+    /// Use this to align datasets, which reference different vertical datums (e.g. a bathymetry and a land DEM).
+    pub height_offset: f32,
+    /// The exponent applied to the normalized heights, before they are mapped onto the height range of the model.
+    ///
+    /// Values above one flatten the lowlands and steepen the peaks, values below one do the opposite.
+    /// The default of one keeps the heights unchanged. Heights outside of zero to one, which only R32F attachments can store, are not affected.
+    pub height_gamma: f32,
     /// The amount of tiles the can be loaded simultaneously in the tile atlas.
     pub atlas_size: u32,
     /// The path to the terrain folder inside the assets directory.
     pub path: String,
     /// The attachments of the terrain.
     pub attachments: Vec<AttachmentConfig>,
+    /// An optional shader extending the tiling prepass.
+    ///
+    /// It has to declare `#define_import_path bevy_terrain::prepass_extension` and provide two hooks:
+    /// - `fn discard_tile(tile: TileCoordinate) -> bool`, which removes the tile and all its children from the refinement.
+    /// - `fn tile_height_range(tile: TileCoordinate, height_range: vec2<f32>) -> vec2<f32>`, which adjusts
+    ///   the minimum and maximum height used to cull the tile (e.g. to account for procedural displacement).
+    pub prepass_extension: Option<Handle<Shader>>,
+    /// Additional shader defs of the tiling prepass, available in the prepass extension.
+    pub prepass_shader_defs: Vec<ShaderDefVal>,
+    /// An optional replacement of the refinement shader of the tiling prepass, e.g. for custom lod metrics.
+    ///
+    /// It is bound to the same bind group layouts as the built-in `refine_tiles.wgsl` (culling data, terrain and
+    /// refinement) and has to provide the `refine_tiles` entry point with the same workgroup size.
+    pub refine_tiles_shader: Option<Handle<Shader>>,
+    /// An optional replacement of the shader of the tiling prepass, which prepares the indirect dispatches and draws.
+    ///
+    /// It is bound to the same bind group layouts as the built-in `prepare_prepass.wgsl` and has to provide
+    /// the `prepare_root`, `prepare_next` and `prepare_render` entry points.
+    pub prepare_prepass_shader: Option<Handle<Shader>>,
+    /// Additional flags of the tiling prepass pipelines, e.g. custom flags registered with
+    /// [`TilingPrepassPipelines::register_shader_def`](crate::render::tiling_prepass::TilingPrepassPipelines::register_shader_def).
+    pub prepass_flags: TilingPrepassPipelineKey,
+    /// Samples the heights in the vertex shader with a bicubic (Catmull-Rom) instead of a bilinear filter.
+    ///
+    /// This removes the creases of bilinear sampling on smooth terrain up close, at the cost of 16 texture loads per vertex.
+    /// The height attachment should have a border size of at least two, since the filter reaches across the tile edges.
+    /// The heights sampled on the CPU (e.g. [`sample_height`](crate::terrain_data::sample_height)) are filtered alike.
+    pub bicubic_height_sampling: bool,
+    /// Renders both sides of the terrain surface, instead of culling its back faces.
+    ///
+    /// Enable this for views below the surface (e.g. underwater or in caves), from which the terrain would disappear otherwise.
+    /// The normals of the back faces are flipped, so that they are lit from below.
+    pub double_sided: bool,
+    /// The depth bias (polygon offset) of the terrain render pipeline.
+    ///
+    /// Geometry placed on the surface (e.g. roads, rivers or decals) z-fights with the terrain.
+    /// Since the depth is reversed, a negative bias pushes the terrain slightly back, so that the overlaid geometry
+    /// wins consistently.
+    pub depth_bias: DepthBiasState,
+    /// The shading of the terrain, which switches between smooth and faceted normals.
+    pub shading: TerrainShading,
+    /// The optional depth of the skirts in world units, which hang down vertically from the edges of every tile.
+    ///
+    /// The skirts hide the cracks at the T-junctions between tiles of different lods, which can remain despite the morph.
+    /// The depth should exceed the largest height difference across such a crack, but each skirt costs an additional
+    /// ring of vertices per tile.
+    pub skirt_depth: Option<f32>,
+    /// The optional atmosphere, which blends distant terrain towards the horizon color.
+    pub atmosphere: Option<TerrainAtmosphere>,
+    /// The optional height of the sea surface.
+    ///
+    /// All terrain below it is raised to this height and shaded with the sea color, which results in a
+    /// flat sea following the terrain model (e.g. a spherical ocean shell).
+    /// The sea level is specified like the heights of the model (including the height scale).
+    pub sea_level: Option<f32>,
+    /// The color of the sea surface, if a sea level is set.
+    pub sea_color: Color,
+    /// The optional contour lines, which are drawn onto the terrain.
+    pub contour_lines: Option<TerrainContourLines>,
+    /// The optional minimum height of the terrain, to which all heights below it are clamped (e.g. for flat lake floors).
+    ///
+    /// Unlike the sea level, this modifies the terrain geometry itself, which keeps its regular shading.
+    /// The floor is specified like the heights of the model (including the height scale and offset).
+    pub height_floor: Option<f32>,
+    /// An optional single channel mask, where a value of zero cuts a hole into the terrain (e.g. for caves).
+    ///
+    /// The mask is sampled with the side uv and has one array layer per side of the terrain.
+    /// Tiles inside a hole are culled, if the mask has a complete mip chain, and fragments inside a hole are discarded.
+    /// The mask has to be loaded before the terrain is spawned (e.g. using [`LoadingImages`](crate::debug::LoadingImages)).
+    pub hole_mask: Option<Handle<Image>>,
+    /// The source, from which the tiles of the attachments are streamed.
+    pub tile_source: Arc<dyn TileSource>,
+    /// The seed of the hash functions available to procedural terrain shaders (e.g. `tile_hash` and `coordinate_hash`).
+    ///
+    /// The hashes use integer arithmetic only, so they are deterministic across runs and platforms for the same seed and lod.
+    /// They match [`TileCoordinate::hash`](crate::math::TileCoordinate::hash) on the CPU.
+    pub seed: u32,
+    /// The channel of the height attachment, which stores the height (0 = R, 1 = G, 2 = B, 3 = A).
+    ///
+    /// This allows using heightmaps packed into a multi-channel image, e.g. the green channel of an RGBA terrain pack.
+    /// Note that the height range used for culling is only computed for single channel height attachments.
+    pub height_channel: u32,
+    /// The maximum anisotropy of the sampler of the attachments, which reduces the blurring at grazing angles.
+    ///
+    /// It is clamped to the range of one (disabled) to 16, and disabled if the GPU does not support anisotropic filtering.
+    pub anisotropy: u8,
+    /// The bias added to the mip level, at which the attachments are sampled.
+    ///
+    /// Positive values select coarser mip levels, which trades sharpness for less shimmering
+    /// of tiles, that cover many texels per screen pixel, while negative values sharpen the attachments.
+    pub mip_bias: f32,
+    /// The coarsest mip level of the attachments, which the sampler may select (zero being the finest one).
+    pub max_mip_level: Option<u32>,
+    /// The optional maximum count of tiles per attachment, which are uploaded to the GPU each frame.
+    ///
+    /// Uploading many freshly loaded tiles at once (e.g. after a teleport) can cause a frame hitch.
+    /// With a budget, the uploads are spread across multiple frames, where coarser tiles are uploaded first,
+    /// since they cover the largest area. Tiles only become available for rendering, once they are uploaded.
+    pub max_uploads_per_frame: Option<u32>,
+    /// The optional count of frames, over which freshly loaded tiles fade in.
+    ///
+    /// Without fading, finer tiles snap in, once they become resident. While a tile fades in, its heights, colors and
+    /// normals are cross-faded with the ones of the best loaded tile of its parent, to hide the transition.
+    pub tile_fade_frames: Option<u32>,
+    /// The count of times loading a tile is retried, before the tile is marked as failed
+    /// and a [`TerrainTileLoadFailed`](crate::terrain_data::tile_atlas::TerrainTileLoadFailed) event is sent.
+    ///
+    /// This prevents permanent holes from transient failures of the [`TileSource`] (e.g. a dropped network request).
+    pub max_tile_load_retries: u32,
+    /// The count of frames, which are waited before the first retry of a failed tile load.
+    /// The delay doubles with every further retry.
+    pub tile_load_retry_frames: u32,
+    /// The optional detail noise, which is added to the heights in the vertex shader.
+    /// The texture has to be loaded before the terrain is spawned, like the hole mask.
+    pub detail_noise: Option<TerrainDetailNoise>,
+    /// The optional height overlay, which is added to the heights of the terrain (see [`TerrainHeightOverlay`]).
+    pub height_overlay: Option<TerrainHeightOverlay>,
+    /// The priority of the terrain, where it overlaps other terrains (e.g. a detailed local terrain on a global planet).
+    ///
+    /// [`sample_overlapping_surface`](crate::terrain_data::sample_overlapping_surface) prefers the surface of the
+    /// terrain with the highest priority, which covers the sample position.
+    pub priority: i32,
+    /// The width of the band along the boundary of the terrain, in which its surface is blended with the ones
+    /// of the lower priority terrains, which it overlaps.
+    pub priority_blend_width: f64,
 }
 
 impl Default for TerrainConfig {
     fn default() -> Self {
         Self {
             lod_count: 1,
+            max_lod: None,
             model: TerrainModel::sphere(default(), 1.0, 0.0, 1.0),
+            height_scale: 1.0,
+            height_offset: 0.0,
+            height_gamma: 1.0,
             atlas_size: 1024,
             path: default(),
             attachments: default(),
+            prepass_extension: None,
+            prepass_shader_defs: default(),
+            refine_tiles_shader: None,
+            prepare_prepass_shader: None,
+            prepass_flags: TilingPrepassPipelineKey::NONE,
+            bicubic_height_sampling: false,
+            double_sided: false,
+            depth_bias: default(),
+            shading: TerrainShading::Smooth,
+            skirt_depth: None,
+            atmosphere: None,
+            sea_level: None,
+            sea_color: Color::srgb(0.05, 0.2, 0.4),
+            contour_lines: None,
+            height_floor: None,
+            hole_mask: None,
+            tile_source: Arc::new(FileTileSource),
+            seed: 0,
+            height_channel: 0,
+            anisotropy: 16,
+            mip_bias: 0.0,
+            max_mip_level: None,
+            max_uploads_per_frame: None,
+            tile_fade_frames: None,
+            max_tile_load_retries: 3,
+            tile_load_retry_frames: 8,
+            detail_noise: None,
+            height_overlay: None,
+            priority: 0,
+            priority_blend_width: 0.0,
         }
     }
 }
@@ -53,8 +529,133 @@ impl TerrainConfig {
         self.attachments.push(attachment_config);
         self
     }
+
+    /// Checks the invariants of the config, which would otherwise surface as NaNs, panics or blank terrain later on.
+    ///
+    /// This is run by [`TileAtlas::new`], which refuses to create the tile atlas of an invalid config. The checked invariants are:
+    /// - the lod count is at least one,
+    /// - the minimum height of the model is finite and below its maximum height,
+    /// - the extent of the model (e.g. the radius of spherical models) is finite and positive along all axes,
+    /// - the height channel selects one of the four channels,
+    /// - the border of each attachment leaves a non-empty center of the tile,
+    /// - the mip level count of each attachment is at least one and the texture size is divisible by every mip level.
+    pub fn validate(&self) -> Result<(), TerrainConfigError> {
+        if self.lod_count == 0 {
+            return Err(TerrainConfigError::NoLods);
+        }
+
+        let (min_height, max_height) = (self.model.min_height, self.model.max_height);
+
+        if !(min_height.is_finite() && max_height.is_finite() && min_height < max_height) {
+            return Err(TerrainConfigError::InvalidHeightRange {
+                min_height,
+                max_height,
+            });
+        }
+
+        let scale = self.model.scale_vec();
+
+        if !(scale.is_finite() && scale.min_element() > 0.0) {
+            return Err(TerrainConfigError::InvalidModelSize { scale });
+        }
+
+        if self.height_channel > 3 {
+            return Err(TerrainConfigError::InvalidHeightChannel {
+                height_channel: self.height_channel,
+            });
+        }
+
+        for attachment in &self.attachments {
+            if 2 * attachment.border_size >= attachment.texture_size {
+                return Err(TerrainConfigError::InvalidBorderSize {
+                    attachment: attachment.name.clone(),
+                    texture_size: attachment.texture_size,
+                    border_size: attachment.border_size,
+                });
+            }
+
+            if attachment.mip_level_count == 0
+                || attachment.mip_level_count > attachment.texture_size.trailing_zeros() + 1
+            {
+                return Err(TerrainConfigError::InvalidMipLevelCount {
+                    attachment: attachment.name.clone(),
+                    texture_size: attachment.texture_size,
+                    mip_level_count: attachment.mip_level_count,
+                });
+            }
+        }
+
+        Ok(())
+    }
 }
 
+/// The violated invariant of an invalid [`TerrainConfig`], see [`TerrainConfig::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TerrainConfigError {
+    /// The lod count is zero.
+    NoLods,
+    /// The minimum height of the model is not below its maximum height.
+    InvalidHeightRange { min_height: f32, max_height: f32 },
+    /// The model has a non-positive extent, e.g. a zero radius.
+    InvalidModelSize { scale: DVec3 },
+    /// The height channel is not one of the four channels R, G, B and A.
+    InvalidHeightChannel { height_channel: u32 },
+    /// The border of the attachment covers the entire tile.
+    InvalidBorderSize {
+        attachment: String,
+        texture_size: u32,
+        border_size: u32,
+    },
+    /// The attachment has no mip levels, or more than the texture size can be halved into.
+    InvalidMipLevelCount {
+        attachment: String,
+        texture_size: u32,
+        mip_level_count: u32,
+    },
+}
+
+impl fmt::Display for TerrainConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoLods => write!(f, "the lod count has to be at least one"),
+            Self::InvalidHeightRange {
+                min_height,
+                max_height,
+            } => write!(
+                f,
+                "the minimum height {min_height} of the model has to be below its maximum height {max_height}"
+            ),
+            Self::InvalidModelSize { scale } => write!(
+                f,
+                "the extent {scale} of the model has to be positive, e.g. a spherical model needs a positive radius"
+            ),
+            Self::InvalidHeightChannel { height_channel } => write!(
+                f,
+                "the height channel {height_channel} has to be between zero (R) and three (A)"
+            ),
+            Self::InvalidBorderSize {
+                attachment,
+                texture_size,
+                border_size,
+            } => write!(
+                f,
+                "the border size {border_size} of the attachment {attachment} leaves no center of its texture size {texture_size}"
+            ),
+            Self::InvalidMipLevelCount {
+                attachment,
+                texture_size,
+                mip_level_count,
+            } => write!(
+                f,
+                "the mip level count {mip_level_count} of the attachment {attachment} has to be between one and {}, for the texture size {texture_size}",
+                texture_size.trailing_zeros() + 1
+            ),
+        }
+    }
+}
+
+impl Error for TerrainConfigError {}
+
 /// The components of a terrain.
 ///
 /// Does not include loader(s) and a material.
@@ -66,6 +667,9 @@ pub struct TerrainBundle {
     pub transform: Transform,
     pub global_transform: GlobalTransform,
     pub visibility_bundle: VisibilityBundle,
+    /// The bounds of the terrain, which enclose all heights of the model (e.g. for framing the terrain in editors).
+    pub aabb: Aabb,
+    /// The terrain culls its tiles itself, so it is not culled as a whole.
     pub no_frustum_culling: NoFrustumCulling,
 }
 
@@ -81,6 +685,10 @@ impl TerrainBundle {
         #[cfg(not(feature = "high_precision"))]
         let transform = tile_atlas.model.transform();
 
+        let aabb = tile_atlas
+            .model
+            .aabb(tile_atlas.min_height(), tile_atlas.max_height());
+
         Self {
             tile_atlas,
             transform,
@@ -92,6 +700,7 @@ impl TerrainBundle {
                 inherited_visibility: default(),
                 view_visibility: default(),
             },
+            aabb,
             no_frustum_culling: NoFrustumCulling,
         }
     }
@@ -1,5 +1,7 @@
 use crate::{
-    terrain::TerrainComponents,
+    debug::FreezeTerrain,
+    render::{terrain_material::TerrainPipelineFlags, tiling_prepass::TilingPrepassPipelineKey},
+    terrain::{TerrainBrushStroke, TerrainComponents, TerrainShading},
     terrain_data::{
         tile_atlas::{
             AtlasAttachment, AtlasTileAttachment, AtlasTileAttachmentWithData, TileAtlas,
@@ -17,11 +19,15 @@ use bevy::{
     },
     tasks::{AsyncComputeTaskPool, Task},
 };
+use bytemuck::cast_slice;
 use itertools::Itertools;
 use std::{iter, mem};
 
 const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
 
+/// The size of the height range of a tile in the write section, which is stored as two `u32`s.
+const HEIGHT_RANGE_SIZE: u32 = 2 * mem::size_of::<u32>() as u32;
+
 fn align_byte_size(value: u32) -> u32 {
     // only works for non zero values
     value - 1 - (value - 1) % COPY_BYTES_PER_ROW_ALIGNMENT + COPY_BYTES_PER_ROW_ALIGNMENT
@@ -42,6 +48,25 @@ pub(crate) fn create_attachment_layout(device: &RenderDevice) -> BindGroupLayout
     )
 }
 
+pub(crate) fn create_prepare_downsample_layout(device: &RenderDevice) -> BindGroupLayout {
+    device.create_bind_group_layout(
+        None,
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                uniform_buffer::<PrepareDownsampleData>(false), // prepare downsample data
+                storage_buffer_read_only::<u32>(false),         // atlas indices
+                storage_buffer::<u32>(false),                   // height ranges
+            ),
+        ),
+    )
+}
+
+#[derive(Default, ShaderType)]
+pub(crate) struct PrepareDownsampleData {
+    mip_level: u32,
+}
+
 #[derive(Default, ShaderType)]
 pub(crate) struct AttachmentMeta {
     pub(crate) format_id: u32,
@@ -184,6 +209,12 @@ pub(crate) struct GpuAtlasAttachment {
     pub(crate) atlas_write_section: StaticBuffer<()>,
     pub(crate) download_buffers: Vec<StaticBuffer<()>>,
     pub(crate) bind_group: BindGroup,
+    /// The atlas indices of the tiles in the write section, in the order of their write slots.
+    pub(crate) atlas_indices: StaticBuffer<()>,
+    /// The height ranges of the tiles in the write section, which are computed by the prepare downsample pass.
+    pub(crate) height_ranges: StaticBuffer<()>,
+    /// The bind groups of the prepare downsample pass, one for each mip level starting at the second one.
+    pub(crate) prepare_downsample_bind_groups: Vec<BindGroup>,
 
     pub(crate) max_atlas_write_slots: u32,
     pub(crate) atlas_write_slots: Vec<AtlasTileAttachment>,
@@ -203,6 +234,13 @@ impl GpuAtlasAttachment {
 
         let buffer_info = AtlasBufferInfo::new(attachment, tile_atlas.lod_count);
 
+        // the attachments are sampled with a filtering sampler
+        assert!(
+            !matches!(buffer_info.format, AttachmentFormat::R32F)
+                || device.features().contains(WgpuFeatures::FLOAT32_FILTERABLE),
+            "The attachment {name} has the R32F format, but the device does not support filtering 32 bit float textures."
+        );
+
         // dbg!(&buffer_info);
 
         let atlas_texture = device.create_texture(&TextureDescriptor {
@@ -231,6 +269,9 @@ impl GpuAtlasAttachment {
             mag_filter: FilterMode::Linear,
             min_filter: FilterMode::Linear,
             mipmap_filter: FilterMode::Linear,
+            // the tile borders already contain the data of the adjacent tiles
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
             ..default()
         });
 
@@ -259,6 +300,42 @@ impl GpuAtlasAttachment {
             )),
         );
 
+        let atlas_indices = StaticBuffer::empty_sized(
+            format!("{name}_atlas_indices").as_str(),
+            device,
+            (max_atlas_write_slots.max(1) * mem::size_of::<u32>() as u32) as BufferAddress,
+            BufferUsages::COPY_DST | BufferUsages::STORAGE,
+        );
+
+        let height_ranges = StaticBuffer::empty_sized(
+            format!("{name}_height_ranges").as_str(),
+            device,
+            (max_atlas_write_slots.max(1) * HEIGHT_RANGE_SIZE) as BufferAddress,
+            BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE,
+        );
+
+        // The first pass computes the height range and thus also runs for attachments without mips.
+        let prepare_downsample_bind_groups = (1..attachment.mip_level_count.max(2))
+            .map(|mip_level| {
+                let prepare_downsample_buffer = StaticBuffer::create(
+                    format!("{name}_prepare_downsample_buffer_{mip_level}").as_str(),
+                    device,
+                    &PrepareDownsampleData { mip_level },
+                    BufferUsages::UNIFORM,
+                );
+
+                device.create_bind_group(
+                    format!("{name}_prepare_downsample_bind_group_{mip_level}").as_str(),
+                    &create_prepare_downsample_layout(device),
+                    &BindGroupEntries::sequential((
+                        &prepare_downsample_buffer,
+                        &atlas_indices,
+                        &height_ranges,
+                    )),
+                )
+            })
+            .collect_vec();
+
         Self {
             name,
             buffer_info,
@@ -266,6 +343,9 @@ impl GpuAtlasAttachment {
             atlas_write_section,
             download_buffers: default(),
             bind_group,
+            atlas_indices,
+            height_ranges,
+            prepare_downsample_bind_groups,
             max_atlas_write_slots,
             atlas_write_slots,
             upload_tiles: default(),
@@ -306,6 +386,68 @@ impl GpuAtlasAttachment {
         }
     }
 
+    /// Generates the mip chain and computes the height range of the tiles in the write section,
+    /// after they have been copied into the first mip level of the atlas.
+    ///
+    /// The tiles written by the preprocessor never pass through [`AttachmentData::generate_mipmaps`] on the CPU.
+    /// Each level is downsampled from the previous one, so the levels are dispatched and copied one after another.
+    /// The write section is reused for the levels, since it is only refilled in the next frame.
+    pub(crate) fn prepare_downsample(
+        &self,
+        command_encoder: &mut CommandEncoder,
+        pipeline: &ComputePipeline,
+    ) {
+        if self.atlas_write_slots.is_empty() {
+            return;
+        }
+
+        command_encoder.clear_buffer(&self.height_ranges, 0, None);
+
+        for (mip_level, bind_group) in iter::zip(1.., &self.prepare_downsample_bind_groups) {
+            let size = self.buffer_info.texture_size >> mip_level;
+
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&ComputePassDescriptor::default());
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            compute_pass.set_bind_group(1, bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                size.div_ceil(self.buffer_info.pixels_per_entry).div_ceil(8),
+                size.div_ceil(8),
+                self.atlas_write_slots.len() as u32,
+            );
+            drop(compute_pass);
+
+            if mip_level < self.buffer_info.mip_level_count {
+                for (section_index, tile) in self.atlas_write_slots.iter().enumerate() {
+                    command_encoder.copy_buffer_to_texture(
+                        self.buffer_info
+                            .image_copy_buffer(&self.atlas_write_section, section_index as u32),
+                        self.buffer_info.image_copy_texture(
+                            &self.atlas_texture,
+                            tile.atlas_index,
+                            mip_level,
+                        ),
+                        self.buffer_info.image_copy_size(mip_level),
+                    );
+                }
+            }
+        }
+    }
+
+    fn upload_atlas_indices(&self, queue: &RenderQueue) {
+        let atlas_indices = self
+            .atlas_write_slots
+            .iter()
+            .map(|tile| tile.atlas_index)
+            .collect_vec();
+
+        if !atlas_indices.is_empty() {
+            self.atlas_indices
+                .update_bytes(queue, cast_slice(&atlas_indices));
+        }
+    }
+
     fn upload_tiles(&mut self, queue: &RenderQueue) {
         for tile in self.upload_tiles.drain(..) {
             let mut start = 0;
@@ -335,14 +477,24 @@ impl GpuAtlasAttachment {
         }
     }
 
+    /// Downloads the first mip level of the tiles in the write section, followed by their height range.
     pub(crate) fn download_tiles(&self, command_encoder: &mut CommandEncoder) {
-        for (tile, download_buffer) in iter::zip(&self.atlas_write_slots, &self.download_buffers) {
+        for (section_index, (tile, download_buffer)) in
+            iter::zip(&self.atlas_write_slots, &self.download_buffers).enumerate()
+        {
             command_encoder.copy_texture_to_buffer(
                 self.buffer_info
                     .image_copy_texture(&self.atlas_texture, tile.atlas_index, 0),
                 self.buffer_info.image_copy_buffer(download_buffer, 0),
                 self.buffer_info.image_copy_size(0),
             );
+            command_encoder.copy_buffer_to_buffer(
+                &self.height_ranges,
+                (section_index as u32 * HEIGHT_RANGE_SIZE) as BufferAddress,
+                download_buffer,
+                self.buffer_info.aligned_tile_size as BufferAddress,
+                HEIGHT_RANGE_SIZE as BufferAddress,
+            );
         }
     }
 
@@ -352,7 +504,7 @@ impl GpuAtlasAttachment {
                 StaticBuffer::empty_sized(
                     format!("{}_download_buffer_{i}", self.name).as_str(),
                     device,
-                    self.buffer_info.aligned_tile_size as BufferAddress,
+                    (self.buffer_info.aligned_tile_size + HEIGHT_RANGE_SIZE) as BufferAddress,
                     BufferUsages::COPY_DST | BufferUsages::MAP_READ,
                 )
             })
@@ -382,6 +534,8 @@ impl GpuAtlasAttachment {
                     download_buffer.unmap();
                     drop(download_buffer);
 
+                    let height_range = data.split_off(buffer_info.aligned_tile_size as usize);
+
                     if data.len() != buffer_info.actual_tile_size as usize {
                         let actual_side_size = buffer_info.actual_side_size as usize;
                         let aligned_side_size = buffer_info.aligned_side_size as usize;
@@ -401,10 +555,14 @@ impl GpuAtlasAttachment {
                         data.truncate(buffer_info.actual_tile_size as usize);
                     }
 
+                    let data = AttachmentData::from_bytes(&data, buffer_info.format);
+                    let height_range = decode_height_range(&height_range, buffer_info.format);
+
                     AtlasTileAttachmentWithData {
                         tile,
-                        data: AttachmentData::from_bytes(&data, buffer_info.format),
+                        data,
                         texture_size: buffer_info.texture_size,
+                        height_range,
                     }
                 })
             })
@@ -412,6 +570,28 @@ impl GpuAtlasAttachment {
     }
 }
 
+/// Decodes the height range computed by the prepare downsample pass (see `prepare_downsample.wgsl`).
+///
+/// The bounds are stored as floats mapped onto unsigned integers of the same order, where the minimum is inverted.
+/// Only single channel attachments have a meaningful range, all others cover the full range.
+fn decode_height_range(bytes: &[u8], format: AttachmentFormat) -> Vec2 {
+    if !matches!(format, AttachmentFormat::R16 | AttachmentFormat::R32F) {
+        return Vec2::new(0.0, 1.0);
+    }
+
+    let decode = |bits: u32| {
+        f32::from_bits(if bits & 0x8000_0000 != 0 {
+            bits & 0x7fff_ffff
+        } else {
+            !bits
+        })
+    };
+
+    let [min, max]: [u32; 2] = bytemuck::pod_read_unaligned(bytes);
+
+    Vec2::new(decode(!min), decode(max))
+}
+
 /// Stores the GPU representation of the [`TileAtlas`] (array textures)
 /// alongside the data to update it.
 ///
@@ -420,7 +600,34 @@ impl GpuAtlasAttachment {
 pub struct GpuTileAtlas {
     /// Stores the atlas attachments of the terrain.
     pub(crate) attachments: Vec<GpuAtlasAttachment>,
+    /// Whether the terrain model is spherical, which specializes the pipelines of this terrain.
     pub(crate) is_spherical: bool,
+    /// The shader defs of the tiling prepass, including the `PREPASS_EXTENSION` def if an extension is used.
+    pub(crate) prepass_shader_defs: Vec<ShaderDefVal>,
+    /// The replacements of the shaders of the tiling prepass, if any.
+    pub(crate) refine_tiles_shader: Option<Handle<Shader>>,
+    pub(crate) prepare_prepass_shader: Option<Handle<Shader>>,
+    /// The additional flags of the tiling prepass pipelines.
+    pub(crate) prepass_flags: TilingPrepassPipelineKey,
+    pub(crate) bicubic_height_sampling: bool,
+    pub(crate) double_sided: bool,
+    pub(crate) depth_bias: DepthBiasState,
+    pub(crate) skirts: bool,
+    pub(crate) flat_shading: bool,
+    pub(crate) atmosphere: bool,
+    pub(crate) sea_level: bool,
+    pub(crate) contour_lines: bool,
+    pub(crate) tile_fade: bool,
+    pub(crate) hole_mask: bool,
+    pub(crate) detail_noise: bool,
+    /// Whether the height overlay is enabled, which is updated every frame.
+    pub(crate) height_overlay: bool,
+    /// The image of the height overlay, which is edited by the brush strokes.
+    pub(crate) height_overlay_image: Option<AssetId<Image>>,
+    /// The brush strokes, which have not been applied to the height overlay yet.
+    pub(crate) brush_strokes: Vec<TerrainBrushStroke>,
+    /// Whether the refinement of the tile trees of this terrain is frozen (see [`FreezeTerrain`]).
+    pub(crate) frozen: bool,
 }
 
 impl GpuTileAtlas {
@@ -432,10 +639,86 @@ impl GpuTileAtlas {
             .map(|attachment| GpuAtlasAttachment::new(device, attachment, tile_atlas))
             .collect_vec();
 
+        let mut prepass_shader_defs = tile_atlas.prepass_shader_defs.clone();
+
+        if tile_atlas.prepass_extension.is_some() {
+            prepass_shader_defs.push("PREPASS_EXTENSION".into());
+        }
+
         Self {
             attachments,
             is_spherical: tile_atlas.model.is_spherical(),
+            prepass_shader_defs,
+            refine_tiles_shader: tile_atlas.refine_tiles_shader.clone(),
+            prepare_prepass_shader: tile_atlas.prepare_prepass_shader.clone(),
+            prepass_flags: tile_atlas.prepass_flags,
+            bicubic_height_sampling: tile_atlas.bicubic_height_sampling,
+            double_sided: tile_atlas.double_sided,
+            depth_bias: tile_atlas.depth_bias,
+            skirts: tile_atlas.skirt_depth.is_some(),
+            flat_shading: tile_atlas.shading == TerrainShading::Flat,
+            atmosphere: tile_atlas.atmosphere.is_some(),
+            sea_level: tile_atlas.sea_level.is_some(),
+            contour_lines: tile_atlas.contour_lines.is_some(),
+            tile_fade: tile_atlas.tile_fade_frames.is_some(),
+            hole_mask: tile_atlas.hole_mask.is_some(),
+            detail_noise: tile_atlas.detail_noise.is_some(),
+            height_overlay: false,
+            height_overlay_image: None,
+            brush_strokes: default(),
+            frozen: false,
+        }
+    }
+
+    /// The flags of the terrain pipeline, which are specialized per terrain (e.g. `SPHERICAL` for spherical models).
+    pub(crate) fn pipeline_flags(&self) -> TerrainPipelineFlags {
+        let mut flags = TerrainPipelineFlags::NONE;
+
+        if self.is_spherical {
+            flags |= TerrainPipelineFlags::SPHERICAL;
+        }
+        if self.bicubic_height_sampling {
+            flags |= TerrainPipelineFlags::BICUBIC;
+        }
+        if self.atmosphere {
+            flags |= TerrainPipelineFlags::ATMOSPHERE;
+        }
+        if self.sea_level {
+            flags |= TerrainPipelineFlags::SEA_LEVEL;
+        }
+        if self.hole_mask {
+            flags |= TerrainPipelineFlags::HOLE_MASK;
+        }
+        if self.detail_noise {
+            flags |= TerrainPipelineFlags::DETAIL_NOISE;
+        }
+        if self.double_sided {
+            flags |= TerrainPipelineFlags::DOUBLE_SIDED;
+        }
+        if self.skirts {
+            flags |= TerrainPipelineFlags::SKIRTS;
         }
+        if self.flat_shading {
+            flags |= TerrainPipelineFlags::FLAT_SHADING;
+        }
+        if self.height_overlay {
+            flags |= TerrainPipelineFlags::HEIGHT_OVERLAY;
+        }
+        if self.contour_lines {
+            flags |= TerrainPipelineFlags::CONTOUR_LINES;
+        }
+        if self.tile_fade {
+            flags |= TerrainPipelineFlags::TILE_FADE;
+        }
+
+        flags
+    }
+
+    /// The flags of the tiling prepass pipelines, which are specialized per terrain, excluding the flags of the views.
+    pub(crate) fn prepass_pipeline_flags(&self) -> TilingPrepassPipelineKey {
+        self.prepass_flags
+            .spherical(self.is_spherical)
+            .with(TilingPrepassPipelineKey::HOLE_MASK, self.hole_mask)
     }
 
     /// Initializes the [`GpuTileAtlas`] of newly created terrains.
@@ -455,10 +738,22 @@ impl GpuTileAtlas {
         mut main_world: ResMut<MainWorld>,
         mut gpu_tile_atlases: ResMut<TerrainComponents<GpuTileAtlas>>,
     ) {
-        let mut tile_atlases = main_world.query::<(Entity, &mut TileAtlas)>();
+        let mut tile_atlases = main_world.query::<(Entity, &mut TileAtlas, Has<FreezeTerrain>)>();
 
-        for (terrain, mut tile_atlas) in tile_atlases.iter_mut(&mut main_world) {
+        for (terrain, mut tile_atlas, frozen) in tile_atlases.iter_mut(&mut main_world) {
             let gpu_tile_atlas = gpu_tile_atlases.get_mut(&terrain).unwrap();
+            gpu_tile_atlas.frozen = frozen;
+            gpu_tile_atlas.height_overlay = tile_atlas
+                .height_overlay
+                .as_ref()
+                .is_some_and(|height_overlay| height_overlay.enabled);
+            gpu_tile_atlas.height_overlay_image = tile_atlas
+                .height_overlay
+                .as_ref()
+                .map(|height_overlay| height_overlay.texture.id());
+            gpu_tile_atlas
+                .brush_strokes
+                .extend(mem::take(&mut tile_atlas.brush_strokes));
 
             for (attachment, gpu_attachment) in
                 iter::zip(&mut tile_atlas.attachments, &mut gpu_tile_atlas.attachments)
@@ -485,6 +780,7 @@ impl GpuTileAtlas {
         for gpu_tile_atlas in gpu_tile_atlases.values_mut() {
             for attachment in &mut gpu_tile_atlas.attachments {
                 attachment.create_download_buffers(&device);
+                attachment.upload_atlas_indices(&queue);
                 attachment.upload_tiles(&queue);
             }
         }
@@ -498,3 +794,46 @@ impl GpuTileAtlas {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::TerrainModel, terrain::TerrainConfig};
+    use bevy::{math::DVec3, tasks::block_on};
+
+    #[test]
+    fn pipeline_flags_specialize_spherical_terrains() {
+        let instance = wgpu::Instance::default();
+        let Some(adapter) =
+            block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        else {
+            eprintln!("skipped, since no GPU adapter is available");
+            return;
+        };
+        let (device, _) =
+            block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+        let device = RenderDevice::from(device);
+
+        let gpu_tile_atlas = |model| {
+            let tile_atlas = TileAtlas::new(&TerrainConfig { model, ..default() }).unwrap();
+            GpuTileAtlas::new(&device, &tile_atlas)
+        };
+
+        // Both terrains are queued with the same view, so only their own flags may differ.
+        let planar = gpu_tile_atlas(TerrainModel::planar(DVec3::ZERO, 1000.0, 0.0, 100.0));
+        let spherical = gpu_tile_atlas(TerrainModel::sphere(DVec3::ZERO, 1000.0, 0.0, 100.0));
+
+        assert!(!planar
+            .pipeline_flags()
+            .contains(TerrainPipelineFlags::SPHERICAL));
+        assert!(spherical
+            .pipeline_flags()
+            .contains(TerrainPipelineFlags::SPHERICAL));
+        assert!(!planar
+            .prepass_pipeline_flags()
+            .contains(TilingPrepassPipelineKey::SPHERICAL));
+        assert!(spherical
+            .prepass_pipeline_flags()
+            .contains(TilingPrepassPipelineKey::SPHERICAL));
+    }
+}
@@ -26,6 +26,10 @@ pub struct GpuTileTree {
     /// The current cpu tile_tree data. This is synced each frame with the tile_tree data.
     data: Array4<TileTreeEntry>,
     origins: Array2<UVec2>,
+    pub(crate) culling_margin: f32,
+    pub(crate) lod_distances: bool,
+    pub(crate) screen_lod_readback: bool,
+    pub(crate) visible: bool,
 }
 
 impl GpuTileTree {
@@ -49,6 +53,10 @@ impl GpuTileTree {
             origins_buffer,
             data: default(),
             origins: default(),
+            culling_margin: tile_tree.culling_margin,
+            lod_distances: tile_tree.lod_distances.is_some(),
+            screen_lod_readback: tile_tree.screen_lod_readback,
+            visible: tile_tree.visible,
         }
     }
 
@@ -60,7 +68,7 @@ impl GpuTileTree {
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter() {
             if gpu_tile_trees.contains_key(&(terrain, view)) {
-                return;
+                continue;
             }
 
             gpu_tile_trees.insert((terrain, view), GpuTileTree::new(&device, tile_tree));
@@ -77,6 +85,7 @@ impl GpuTileTree {
 
             gpu_tile_tree.data = tile_tree.data.clone();
             gpu_tile_tree.origins = tile_tree.origins.clone();
+            gpu_tile_tree.visible = tile_tree.visible;
         }
     }
 
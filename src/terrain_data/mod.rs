@@ -16,10 +16,12 @@
 //! which can be used to access the terrain data in shaders.
 
 use crate::{
+    math::Coordinate,
     terrain_data::{tile_atlas::TileAtlas, tile_tree::TileTree},
+    terrain_view::TerrainViewComponents,
     util::CollectArray,
 };
-use bevy::{math::DVec3, prelude::*, render::render_resource::*};
+use bevy::{math::DVec3, prelude::*, render::render_resource::*, tasks::ComputeTaskPool};
 use bincode::{Decode, Encode};
 use bytemuck::cast_slice;
 use itertools::iproduct;
@@ -28,6 +30,7 @@ use std::iter;
 pub mod gpu_tile_atlas;
 pub mod gpu_tile_tree;
 pub mod tile_atlas;
+pub mod tile_source;
 pub mod tile_tree;
 
 pub const INVALID_ATLAS_INDEX: u32 = u32::MAX;
@@ -44,6 +47,13 @@ pub enum AttachmentFormat {
     R16,
     /// Two   channels 16 bit
     Rg16,
+    /// One   channel  32 bit float
+    ///
+    /// Stores the normalized heights without quantization, e.g. for terrains with a large height range.
+    /// Values outside of zero to one extrapolate the height range of the model, so heights in meters
+    /// can be stored as is, with a height range of zero to one.
+    /// Requires a device that supports filtering 32 bit float textures.
+    R32F,
 }
 
 impl AttachmentFormat {
@@ -53,6 +63,7 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => 0,
             AttachmentFormat::R16 => 1,
             AttachmentFormat::Rg16 => 3,
+            AttachmentFormat::R32F => 4,
         }
     }
     pub(crate) fn render_format(self) -> TextureFormat {
@@ -61,6 +72,7 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => TextureFormat::Rgba8UnormSrgb,
             AttachmentFormat::R16 => TextureFormat::R16Unorm,
             AttachmentFormat::Rg16 => TextureFormat::Rg16Unorm,
+            AttachmentFormat::R32F => TextureFormat::R32Float,
         }
     }
 
@@ -70,6 +82,7 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => TextureFormat::Rgba8Unorm,
             AttachmentFormat::R16 => TextureFormat::R16Unorm,
             AttachmentFormat::Rg16 => TextureFormat::Rg16Unorm,
+            AttachmentFormat::R32F => TextureFormat::R32Float,
         }
     }
 
@@ -79,6 +92,7 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => 4,
             AttachmentFormat::R16 => 2,
             AttachmentFormat::Rg16 => 4,
+            AttachmentFormat::R32F => 4,
         }
     }
 }
@@ -90,6 +104,10 @@ pub struct AttachmentConfig {
     pub name: String,
     pub texture_size: u32,
     /// The overlapping border size around the tile, used to prevent sampling artifacts.
+    ///
+    /// During preprocessing the border is stitched from the adjacent tiles, including the tiles of the adjacent
+    /// side across cube sphere side boundaries, so that filtering is continuous across tile and side edges.
+    /// Samples beyond the border are clamped to the edge of the tile.
     pub border_size: u32,
     pub mip_level_count: u32,
     /// The format of the attachment.
@@ -119,6 +137,8 @@ pub(crate) enum AttachmentData {
     R16(Vec<u16>),
     /// Two   channels 16 bit
     Rg16(Vec<[u16; 2]>),
+    /// One   channel  32 bit float
+    R32F(Vec<f32>),
 }
 
 impl AttachmentData {
@@ -128,6 +148,7 @@ impl AttachmentData {
             AttachmentFormat::Rgba8 => Self::Rgba8(cast_slice(data).to_vec()),
             AttachmentFormat::R16 => Self::R16(cast_slice(data).to_vec()),
             AttachmentFormat::Rg16 => Self::Rg16(cast_slice(data).to_vec()),
+            AttachmentFormat::R32F => Self::R32F(cast_slice(data).to_vec()),
         }
     }
 
@@ -136,11 +157,18 @@ impl AttachmentData {
             AttachmentData::Rgba8(data) => cast_slice(data),
             AttachmentData::R16(data) => cast_slice(data),
             AttachmentData::Rg16(data) => cast_slice(data),
+            AttachmentData::R32F(data) => cast_slice(data),
             AttachmentData::None => panic!("Attachment has no data."),
         }
     }
 
-    pub(crate) fn generate_mipmaps(&mut self, texture_size: u32, mip_level_count: u32) {
+    /// Appends the mip chain of the tile and returns its height range (see [`AttachmentData::height_range`]).
+    ///
+    /// The coarser levels average the texels of the first one, so they stay within its range.
+    /// Thus the range bounds the tile at every level, which the culling of the tiling prepass relies on.
+    pub(crate) fn generate_mipmaps(&mut self, texture_size: u32, mip_level_count: u32) -> Vec2 {
+        let height_range = self.height_range(texture_size);
+
         fn generate_mipmap_rgba8(
             data: &mut Vec<[u8; 4]>,
             parent_size: usize,
@@ -197,6 +225,50 @@ impl AttachmentData {
             }
         }
 
+        fn generate_mipmap_rg16(
+            data: &mut Vec<[u16; 2]>,
+            parent_size: usize,
+            child_size: usize,
+            start: usize,
+        ) {
+            for (child_y, child_x) in iproduct!(0..child_size, 0..child_size) {
+                let mut value = [0u64; 2];
+
+                for (parent_x, parent_y) in
+                    iproduct!(0..2, 0..2).map(|(x, y)| ((child_x << 1) + x, (child_y << 1) + y))
+                {
+                    let index = start + parent_y * parent_size + parent_x;
+
+                    iter::zip(&mut value, data[index]).for_each(|(value, v)| *value += v as u64);
+                }
+
+                let value = value.iter().map(|value| (value / 4) as u16).collect_array();
+
+                data.push(value);
+            }
+        }
+
+        fn generate_mipmap_r32f(
+            data: &mut Vec<f32>,
+            parent_size: usize,
+            child_size: usize,
+            start: usize,
+        ) {
+            for (child_y, child_x) in iproduct!(0..child_size, 0..child_size) {
+                let mut value = 0.0;
+
+                // zero is a valid height (e.g. sea level) for float heightmaps, so all four texels are averaged
+                for (parent_x, parent_y) in
+                    iproduct!(0..2, 0..2).map(|(x, y)| ((child_x << 1) + x, (child_y << 1) + y))
+                {
+                    let index = start + parent_y * parent_size + parent_x;
+                    value += data[index];
+                }
+
+                data.push(value / 4.0);
+            }
+        }
+
         let mut start = 0;
         let mut parent_size = texture_size as usize;
 
@@ -210,50 +282,134 @@ impl AttachmentData {
                 AttachmentData::R16(data) => {
                     generate_mipmap_r16(data, parent_size, child_size, start)
                 }
-                _ => {}
+                AttachmentData::Rg16(data) => {
+                    generate_mipmap_rg16(data, parent_size, child_size, start)
+                }
+                AttachmentData::R32F(data) => {
+                    generate_mipmap_r32f(data, parent_size, child_size, start)
+                }
+                AttachmentData::None => {}
             }
 
             start += parent_size * parent_size;
             parent_size = child_size;
         }
+
+        height_range
+    }
+
+    /// Computes the normalized minimum and maximum value of the first mip level.
+    /// Only single channel attachments have a meaningful range, all others cover the full range.
+    pub(crate) fn height_range(&self, texture_size: u32) -> Vec2 {
+        match self {
+            AttachmentData::R16(data) => {
+                let (min, max) = data[..(texture_size * texture_size) as usize]
+                    .iter()
+                    .fold((u16::MAX, u16::MIN), |(min, max), &value| {
+                        (min.min(value), max.max(value))
+                    });
+
+                Vec2::new(min as f32, max as f32) / u16::MAX as f32
+            }
+            AttachmentData::R32F(data) => data[..(texture_size * texture_size) as usize]
+                .iter()
+                .fold(Vec2::new(f32::MAX, f32::MIN), |range, &value| {
+                    Vec2::new(range.x.min(value), range.y.max(value))
+                }),
+            _ => Vec2::new(0.0, 1.0),
+        }
+    }
+
+    /// Computes the normal of each texel of the first mip level of a height attachment, using central differences.
+    ///
+    /// The height scale is the height difference between a normalized height of zero and one, measured in texels.
+    /// The slope of a texel is `acos(normal.y)`. All other attachments have no meaningful normals and return up vectors.
+    pub fn compute_normals(&self, texture_size: u32, height_scale: f32) -> Vec<Vec3> {
+        let size = texture_size as usize;
+
+        let heights = match self {
+            AttachmentData::R16(data) => data[..size * size]
+                .iter()
+                .map(|&value| value as f32 / u16::MAX as f32)
+                .collect(),
+            AttachmentData::R32F(data) => data[..size * size].to_vec(),
+            _ => return vec![Vec3::Y; size * size],
+        };
+        let height = |x: usize, y: usize| heights[y * size + x];
+
+        let mut normals = vec![Vec3::Y; size * size];
+
+        // Each task computes a disjoint block of rows, while reading the heights of the adjacent rows,
+        // which are shared immutably between all tasks.
+        let rows_per_task = size
+            .div_ceil(ComputeTaskPool::get().thread_num().max(1))
+            .max(1);
+
+        ComputeTaskPool::get().scope(|scope| {
+            for (block, normals) in normals.chunks_mut(rows_per_task * size).enumerate() {
+                scope.spawn(async move {
+                    for (index, normal) in normals.iter_mut().enumerate() {
+                        let x = index % size;
+                        let y = block * rows_per_task + index / size;
+
+                        // clamp to the edge, the borders of the tile already contain the adjacent heights
+                        let dx = height((x + 1).min(size - 1), y) - height(x.saturating_sub(1), y);
+                        let dy = height(x, (y + 1).min(size - 1)) - height(x, y.saturating_sub(1));
+
+                        *normal =
+                            Vec3::new(-dx * height_scale, 2.0, -dy * height_scale).normalize();
+                    }
+                });
+            }
+        });
+
+        normals
+    }
+
+    /// Reads the texel at the index of the first mip level as normalized values.
+    fn texel(&self, index: usize) -> Vec4 {
+        match self {
+            AttachmentData::None => Vec4::splat(0.0),
+            AttachmentData::Rgba8(data) => {
+                let value = data[index];
+                Vec4::new(
+                    value[0] as f32 / u8::MAX as f32,
+                    value[1] as f32 / u8::MAX as f32,
+                    value[2] as f32 / u8::MAX as f32,
+                    value[3] as f32 / u8::MAX as f32,
+                )
+            }
+            AttachmentData::R16(data) => {
+                let value = data[index];
+                Vec4::new(value as f32 / u16::MAX as f32, 0.0, 0.0, 0.0)
+            }
+            AttachmentData::Rg16(data) => {
+                let value = data[index];
+                Vec4::new(
+                    value[0] as f32 / u16::MAX as f32,
+                    value[1] as f32 / u16::MAX as f32,
+                    0.0,
+                    0.0,
+                )
+            }
+            AttachmentData::R32F(data) => Vec4::new(data[index], 0.0, 0.0, 0.0),
+        }
     }
 
     pub(crate) fn sample(&self, uv: Vec2, size: u32) -> Vec4 {
         let uv = uv * size as f32 - 0.5;
 
-        let remainder = uv % 1.0;
-        let uv = uv.as_ivec2();
+        let remainder = uv - uv.floor();
+        let uv = uv.floor().as_ivec2();
 
         let mut values = [[Vec4::ZERO; 2]; 2];
 
         for (x, y) in iproduct!(0..2, 0..2) {
-            let index = (uv.y + y) * size as i32 + (uv.x + x);
+            // clamp to the edge, instead of reading from the adjacent row or beyond the data
+            let texel = (uv + IVec2::new(x, y)).clamp(IVec2::ZERO, IVec2::splat(size as i32 - 1));
+            let index = texel.y * size as i32 + texel.x;
 
-            values[x as usize][y as usize] = match self {
-                AttachmentData::None => Vec4::splat(0.0),
-                AttachmentData::Rgba8(data) => {
-                    let value = data[index as usize];
-                    Vec4::new(
-                        value[0] as f32 / u8::MAX as f32,
-                        value[1] as f32 / u8::MAX as f32,
-                        value[2] as f32 / u8::MAX as f32,
-                        value[3] as f32 / u8::MAX as f32,
-                    )
-                }
-                AttachmentData::R16(data) => {
-                    let value = data[index as usize];
-                    Vec4::new(value as f32 / u16::MAX as f32, 0.0, 0.0, 0.0)
-                }
-                AttachmentData::Rg16(data) => {
-                    let value = data[index as usize];
-                    Vec4::new(
-                        value[0] as f32 / u16::MAX as f32,
-                        value[1] as f32 / u16::MAX as f32,
-                        0.0,
-                        0.0,
-                    )
-                }
-            };
+            values[x as usize][y as usize] = self.texel(index as usize);
         }
 
         Vec4::lerp(
@@ -262,6 +418,36 @@ impl AttachmentData {
             remainder.x,
         )
     }
+
+    /// Samples the 4x4 texel neighborhood with a bicubic Catmull-Rom filter, like `sample_attachment0_bicubic` in the shaders.
+    pub(crate) fn sample_bicubic(&self, uv: Vec2, size: u32) -> Vec4 {
+        let texel = uv * size as f32 - 0.5;
+        let origin = texel.floor().as_ivec2() - 1;
+        let weights = catmull_rom_weights(texel - texel.floor());
+
+        iproduct!(0..4, 0..4).fold(Vec4::ZERO, |value, (x, y)| {
+            let texel =
+                (origin + IVec2::new(x, y)).clamp(IVec2::ZERO, IVec2::splat(size as i32 - 1));
+            let index = texel.y * size as i32 + texel.x;
+
+            value + weights[x as usize].x * weights[y as usize].y * self.texel(index as usize)
+        })
+    }
+}
+
+/// The Catmull-Rom weights of the four texels surrounding the sample position in each dimension.
+///
+/// They sum up to one and reproduce the texels at their centers, like `catmull_rom_weights` in the shaders.
+fn catmull_rom_weights(t: Vec2) -> [Vec2; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
 }
 
 pub fn sample_attachment(
@@ -299,9 +485,311 @@ pub fn sample_height(
     tile_atlas: &TileAtlas,
     sample_world_position: DVec3,
 ) -> f32 {
-    f32::lerp(
-        tile_atlas.model.min_height,
-        tile_atlas.model.max_height,
-        sample_attachment(tile_tree, tile_atlas, 0, sample_world_position).x,
-    )
+    let height = sample_attachment(tile_tree, tile_atlas, 0, sample_world_position);
+
+    tile_atlas.remap_height(height[tile_atlas.height_channel as usize])
+}
+
+/// Samples the height like [`sample_height`], including the height overlay of the terrain, if it is enabled.
+pub fn sample_height_with_overlay(
+    tile_tree: &TileTree,
+    tile_atlas: &TileAtlas,
+    images: &Assets<Image>,
+    sample_world_position: DVec3,
+) -> f32 {
+    let height = sample_height(tile_tree, tile_atlas, sample_world_position);
+
+    let Some(height_overlay) = &tile_atlas.height_overlay else {
+        return height;
+    };
+
+    let model = &tile_atlas.model;
+
+    let surface_position =
+        model.surface_position(sample_world_position, tile_tree.approximate_height as f64);
+
+    let coordinate = Coordinate::from_world_position(surface_position, model);
+
+    height + height_overlay.sample(coordinate, images)
+}
+
+/// Samples the surface position of the overlapping terrains below the sample position, as seen from the view.
+///
+/// The terrain with the highest [`TerrainConfig::priority`](crate::terrain::TerrainConfig::priority), which covers
+/// the position, takes precedence. Within its blend width from the boundary, its surface is blended with the one of the
+/// lower priority terrains. The surfaces are blended in world space, since the heights of different terrain models
+/// are relative to different reference surfaces. Returns `None`, if no terrain covers the position.
+pub fn sample_overlapping_surface<'a>(
+    tile_trees: &TerrainViewComponents<TileTree>,
+    tile_atlases: impl IntoIterator<Item = (Entity, &'a TileAtlas)>,
+    view: Entity,
+    sample_world_position: DVec3,
+) -> Option<DVec3> {
+    let mut terrains = tile_atlases
+        .into_iter()
+        .filter_map(|(terrain, tile_atlas)| {
+            let tile_tree = tile_trees.get(&(terrain, view))?;
+            let boundary_distance = tile_atlas.model.boundary_distance(sample_world_position);
+
+            (boundary_distance >= 0.0).then_some((tile_tree, tile_atlas, boundary_distance))
+        })
+        .collect::<Vec<_>>();
+
+    terrains.sort_by_key(|&(_, tile_atlas, _)| tile_atlas.priority);
+
+    let mut surface_position = None;
+
+    for (tile_tree, tile_atlas, boundary_distance) in terrains {
+        let height = sample_height(tile_tree, tile_atlas, sample_world_position);
+        let position = tile_atlas
+            .model
+            .surface_position(sample_world_position, height as f64);
+
+        let weight = if tile_atlas.priority_blend_width > 0.0 {
+            (boundary_distance / tile_atlas.priority_blend_width).min(1.0)
+        } else {
+            1.0
+        };
+
+        surface_position = Some(surface_position.map_or(position, |lower_position: DVec3| {
+            lower_position.lerp(position, weight)
+        }));
+    }
+
+    surface_position
+}
+
+/// Samples the height of the best resident tile directly under the sample position.
+///
+/// Returns `None`, if no tile covering the position is loaded yet.
+pub fn sample_resident_height(
+    tile_tree: &TileTree,
+    tile_atlas: &TileAtlas,
+    sample_world_position: DVec3,
+) -> Option<f32> {
+    let model = &tile_atlas.model;
+
+    let surface_position =
+        model.surface_position(sample_world_position, tile_tree.approximate_height as f64);
+
+    let (lod, _) = tile_tree.compute_blend(surface_position);
+
+    let lookup = tile_tree.lookup_tile(surface_position, lod, model);
+
+    if lookup.atlas_index == INVALID_ATLAS_INDEX {
+        return None;
+    }
+
+    let height = tile_atlas.sample_attachment(lookup, 0);
+
+    Some(tile_atlas.remap_height(height[tile_atlas.height_channel as usize]))
+}
+
+/// Computes the normal of each texel of the square height image (e.g. a decoded tile), see `AttachmentData::compute_normals`.
+///
+/// Only `R16Unorm` and `R32Float` images hold heights, all other images return up vectors.
+pub fn compute_normals(image: &Image, height_scale: f32) -> Vec<Vec3> {
+    let format = match image.texture_descriptor.format {
+        TextureFormat::R16Unorm => AttachmentFormat::R16,
+        TextureFormat::R32Float => AttachmentFormat::R32F,
+        _ => return vec![Vec3::Y; (image.width() * image.height()) as usize],
+    };
+
+    AttachmentData::from_bytes(&image.data, format).compute_normals(image.width(), height_scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        formats::tiff::{decode_tiff, TiffLoaderSettings},
+        math::{TerrainModel, TileCoordinate},
+        terrain::TerrainConfig,
+    };
+    use bevy::render::mesh::VertexAttributeValues;
+    use std::io::Cursor;
+    use tiff::encoder::{colortype::Gray32Float, TiffEncoder};
+
+    /// Heights in meters, including sea level and depths below it.
+    const HEIGHTS: [f32; 16] = [
+        -10.0, 0.0, 0.0, 25.5, //
+        0.0, 0.0, 120.0, 80.0, //
+        -3.5, 7.0, 0.0, 0.0, //
+        1.0, 2.0, 3.0, 4.0,
+    ];
+    const SIZE: u32 = 4;
+
+    /// Bilinearly interpolates the heights like the attachment sampling, clamping to the edge.
+    fn expected_height(uv: Vec2) -> f32 {
+        let texel = (uv * SIZE as f32 - 0.5).clamp(Vec2::ZERO, Vec2::splat(SIZE as f32 - 1.0));
+        let remainder = texel.fract();
+        let texel = texel.as_uvec2();
+
+        let value = |x: u32, y: u32| HEIGHTS[(y.min(SIZE - 1) * SIZE + x.min(SIZE - 1)) as usize];
+
+        f32::lerp(
+            f32::lerp(
+                value(texel.x, texel.y),
+                value(texel.x + 1, texel.y),
+                remainder.x,
+            ),
+            f32::lerp(
+                value(texel.x, texel.y + 1),
+                value(texel.x + 1, texel.y + 1),
+                remainder.x,
+            ),
+            remainder.y,
+        )
+    }
+
+    fn encode_tiff() -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+
+        TiffEncoder::new(&mut bytes)
+            .unwrap()
+            .write_image::<Gray32Float>(SIZE, SIZE, &HEIGHTS)
+            .unwrap();
+
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn r32f_mipmaps_average_all_texels() {
+        let mut data = AttachmentData::R32F(vec![0.0, 4.0, 0.0, 0.0]);
+        data.generate_mipmaps(2, 2);
+
+        let AttachmentData::R32F(data) = data else {
+            unreachable!()
+        };
+
+        assert_eq!(data, [0.0, 4.0, 0.0, 0.0, 1.0]);
+
+        let mut data = AttachmentData::R32F(HEIGHTS.to_vec());
+        data.generate_mipmaps(SIZE, 3);
+
+        let AttachmentData::R32F(data) = data else {
+            unreachable!()
+        };
+
+        assert_eq!(data.len(), 16 + 4 + 1);
+        assert_eq!(data[16..20], [-2.5, 56.375, 1.625, 1.75]);
+        assert_eq!(data[20], HEIGHTS.iter().sum::<f32>() / 16.0);
+    }
+
+    #[test]
+    fn catmull_rom_weights_interpolate_the_texels() {
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            let sum = catmull_rom_weights(Vec2::splat(t)).iter().sum::<Vec2>();
+            assert!((sum - Vec2::ONE).abs().max_element() < 1e-6, "{t}: {sum}");
+        }
+
+        let weights = |t: f32| catmull_rom_weights(Vec2::splat(t)).map(|weight| weight.x);
+
+        assert_eq!(weights(0.0), [0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(weights(1.0), [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(
+            weights(0.5),
+            [-1.0, 9.0, 9.0, -1.0].map(|weight| weight / 16.0)
+        );
+
+        // the filter reproduces the texels at their centers and linear slopes between them
+        let data = AttachmentData::R32F(HEIGHTS.to_vec());
+
+        for (x, y) in iproduct!(0..SIZE, 0..SIZE) {
+            let uv = (UVec2::new(x, y).as_vec2() + 0.5) / SIZE as f32;
+            assert_eq!(data.sample_bicubic(uv, SIZE).x, expected_height(uv));
+        }
+
+        let ramp = AttachmentData::R32F((0..16).map(|index| index as f32).collect());
+        let uv = Vec2::new(0.4, 0.6);
+        let height = ramp.sample_bicubic(uv, SIZE).x;
+
+        assert!((height - ramp.sample(uv, SIZE).x).abs() < 1e-5, "{height}");
+    }
+
+    #[test]
+    fn height_range_bounds_all_mip_levels() {
+        let heights = (0..64u32)
+            .map(|index| ((index * 37) % 64 * 1000) as u16)
+            .collect::<Vec<_>>();
+        let mut data = AttachmentData::R16(heights);
+
+        let height_range = data.generate_mipmaps(8, 4);
+
+        let AttachmentData::R16(data) = data else {
+            unreachable!()
+        };
+
+        assert_eq!(data.len(), 64 + 16 + 4 + 1);
+        assert_eq!(height_range, Vec2::new(0.0, 63000.0) / u16::MAX as f32);
+
+        for &value in &data {
+            let value = value as f32 / u16::MAX as f32;
+
+            assert!(height_range.x <= value && value <= height_range.y);
+        }
+    }
+
+    #[test]
+    fn float_heightmap_displaces_vertices() {
+        let image = decode_tiff(encode_tiff(), TiffLoaderSettings::default()).unwrap();
+
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R32Float);
+        assert_eq!(image.width(), SIZE);
+
+        // the attachment keeps the heights in meters, which are used as is with a height range of zero to one
+        let mut attachment = AttachmentData::from_bytes(&image.data, AttachmentFormat::R32F);
+
+        assert_eq!(
+            attachment.generate_mipmaps(SIZE, 2),
+            Vec2::new(-10.0, 120.0)
+        );
+        assert_eq!(attachment.height_range(SIZE), Vec2::new(-10.0, 120.0));
+
+        let tile_atlas = TileAtlas::new(&TerrainConfig {
+            model: TerrainModel::planar(DVec3::ZERO, SIZE as f64, 0.0, 1.0),
+            ..default()
+        })
+        .unwrap();
+        let grid_size = 8;
+        let (mesh, center) = tile_atlas.generate_mesh(
+            &image,
+            &Assets::default(),
+            TileCoordinate::new(0, 0, 0, 0),
+            grid_size,
+        );
+
+        assert_eq!(center, DVec3::ZERO);
+
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("The mesh has no positions.");
+        };
+
+        assert_eq!(
+            positions.len(),
+            ((grid_size + 1) * (grid_size + 1)) as usize
+        );
+
+        for (index, position) in positions.iter().enumerate() {
+            let (x, y) = (
+                index as u32 % (grid_size + 1),
+                index as u32 / (grid_size + 1),
+            );
+            let uv = UVec2::new(x, y).as_vec2() / grid_size as f32;
+
+            let expected = expected_height(uv);
+
+            assert!(
+                (position[1] - expected).abs() < 1e-4,
+                "vertex {index} at {uv} has the height {}, instead of {expected}",
+                position[1]
+            );
+            assert!(
+                (attachment.sample(uv, SIZE).x - expected).abs() < 1e-4,
+                "the attachment sample at {uv} does not match the heightmap"
+            );
+        }
+    }
 }
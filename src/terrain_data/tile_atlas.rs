@@ -1,31 +1,42 @@
 use crate::{
     formats::TC,
-    math::{TerrainModel, TileCoordinate},
+    math::{Coordinate, TerrainModel, TileCoordinate},
     prelude::{AttachmentConfig, AttachmentFormat},
-    terrain::TerrainConfig,
+    render::tiling_prepass::TilingPrepassPipelineKey,
+    terrain::{
+        TerrainAtmosphere, TerrainBrushStroke, TerrainConfig, TerrainConfigError,
+        TerrainContourLines, TerrainDetailNoise, TerrainHeightOverlay, TerrainShading,
+    },
     terrain_data::{
+        tile_source::TileSource,
         tile_tree::{TileLookup, TileTree, TileTreeEntry},
-        AttachmentData, INVALID_ATLAS_INDEX, INVALID_LOD,
+        AttachmentData, INVALID_ATLAS_INDEX,
     },
     terrain_view::TerrainViewComponents,
 };
 use anyhow::Result;
 use bevy::{
+    math::{DVec2, DVec3},
     prelude::*,
-    render::render_resource::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::*,
+    },
     tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
     utils::{HashMap, HashSet},
 };
-use image::{io::Reader, DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
-use itertools::Itertools;
-use std::{collections::VecDeque, fs, mem, ops::DerefMut};
+use image::{DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
+use itertools::{iproduct, Itertools};
+use std::{collections::VecDeque, fs, mem, ops::DerefMut, sync::Arc};
 
 pub type Rgb8Image = ImageBuffer<Rgb<u8>, Vec<u8>>;
 pub type Rgba8Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
 pub type R16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
 pub type Rg16Image = ImageBuffer<LumaA<u16>, Vec<u16>>;
 
-const STORE_PNG: bool = false;
+pub(crate) const STORE_PNG: bool = false;
 
 #[derive(Copy, Clone, Debug, Default, ShaderType)]
 pub struct AtlasTile {
@@ -71,6 +82,8 @@ pub(crate) struct AtlasTileAttachmentWithData {
     pub(crate) tile: AtlasTileAttachment,
     pub(crate) data: AttachmentData,
     pub(crate) texture_size: u32,
+    /// The normalized minimum and maximum height of the first mip level, see [`AttachmentData::height_range`].
+    pub(crate) height_range: Vec2,
 }
 
 impl AtlasTileAttachmentWithData {
@@ -97,6 +110,9 @@ impl AtlasTileAttachmentWithData {
                                 .unwrap(),
                         )
                     }
+                    AttachmentData::R32F(_) => {
+                        panic!("Float attachments can not be stored as PNG.")
+                    }
                     AttachmentData::None => panic!("Attachment has not data."),
                 };
 
@@ -117,33 +133,24 @@ impl AtlasTileAttachmentWithData {
 
     pub(crate) fn start_loading(
         tile: AtlasTileAttachment,
+        source: Arc<dyn TileSource>,
         path: String,
         texture_size: u32,
         format: AttachmentFormat,
         mip_level_count: u32,
     ) -> Task<Result<Self>> {
         AsyncComputeTaskPool::get().spawn(async move {
-            let mut data = if STORE_PNG {
-                let path = tile.coordinate.path(&path, "png");
-
-                let mut reader = Reader::open(path)?;
-                reader.no_limits();
-                let image = reader.decode().unwrap();
-                AttachmentData::from_bytes(image.as_bytes(), format)
-            } else {
-                let path = tile.coordinate.path(&path, "bin");
+            let bytes = source.load_tile(&path, tile.coordinate, texture_size, format)?;
 
-                let bytes = fs::read(path)?;
+            let mut data = AttachmentData::from_bytes(&bytes, format);
 
-                AttachmentData::from_bytes(&bytes, format)
-            };
-
-            data.generate_mipmaps(texture_size, mip_level_count);
+            let height_range = data.generate_mipmaps(texture_size, mip_level_count);
 
             Ok(Self {
                 tile,
                 data,
                 texture_size: 0,
+                height_range,
             })
         })
     }
@@ -161,15 +168,26 @@ pub struct AtlasAttachment {
     pub(crate) mip_level_count: u32,
     pub(crate) format: AttachmentFormat,
     pub(crate) data: Vec<AttachmentData>,
+    source: Arc<dyn TileSource>,
 
     pub(crate) saving_tiles: Vec<Task<AtlasTileAttachment>>,
-    pub(crate) loading_tiles: Vec<Task<Result<AtlasTileAttachmentWithData>>>,
+    pub(crate) loading_tiles: Vec<(
+        AtlasTileAttachment,
+        Task<Result<AtlasTileAttachmentWithData>>,
+    )>,
+    /// The tiles, which finished loading, but still wait for the upload budget.
+    pending_tiles: Vec<AtlasTileAttachmentWithData>,
     pub(crate) uploading_tiles: Vec<AtlasTileAttachmentWithData>,
     pub(crate) downloading_tiles: Vec<Task<AtlasTileAttachmentWithData>>,
 }
 
 impl AtlasAttachment {
-    fn new(config: &AttachmentConfig, tile_atlas_size: u32, path: &str) -> Self {
+    fn new(
+        config: &AttachmentConfig,
+        tile_atlas_size: u32,
+        path: &str,
+        source: Arc<dyn TileSource>,
+    ) -> Self {
         let name = config.name.clone();
         let path = format!("assets/{path}/data/{name}");
         let center_size = config.texture_size - 2 * config.border_size;
@@ -185,31 +203,50 @@ impl AtlasAttachment {
             mip_level_count: config.mip_level_count,
             format: config.format,
             data: vec![AttachmentData::None; tile_atlas_size as usize],
+            source,
             saving_tiles: default(),
             loading_tiles: default(),
+            pending_tiles: default(),
             uploading_tiles: default(),
             downloading_tiles: default(),
         }
     }
 
     fn update(&mut self, atlas_state: &mut TileAtlasState) {
-        self.loading_tiles.retain_mut(|tile| {
-            future::block_on(future::poll_once(tile)).map_or(true, |tile| {
-                if let Ok(tile) = tile {
-                    atlas_state.loaded_tile_attachment(tile.tile);
-                    self.uploading_tiles.push(tile.clone());
-                    self.data[tile.tile.atlas_index as usize] = tile.data;
-                } else {
-                    atlas_state.load_slots += 1;
+        self.loading_tiles.retain_mut(|(tile, task)| {
+            future::block_on(future::poll_once(task)).map_or(true, |result| {
+                match result {
+                    Ok(tile) => self.pending_tiles.push(tile),
+                    Err(error) => atlas_state.failed_tile_attachment(*tile, &self.name, error),
                 }
 
                 false
             })
         });
 
+        let upload_count = atlas_state
+            .max_uploads_per_frame
+            .map_or(self.pending_tiles.len(), |max_uploads| {
+                self.pending_tiles.len().min(max_uploads as usize)
+            });
+
+        if upload_count < self.pending_tiles.len() {
+            // upload the coarsest tiles first, since they cover the largest area
+            self.pending_tiles
+                .sort_by_key(|tile| tile.tile.coordinate.lod);
+        }
+
+        for tile in self.pending_tiles.drain(..upload_count) {
+            atlas_state.loaded_tile_attachment(tile.tile);
+            atlas_state.update_height_range(tile.tile, tile.height_range);
+            self.uploading_tiles.push(tile.clone());
+            self.data[tile.tile.atlas_index as usize] = tile.data;
+        }
+
         self.downloading_tiles.retain_mut(|tile| {
             future::block_on(future::poll_once(tile)).map_or(true, |tile| {
                 atlas_state.downloaded_tile_attachment(tile.tile);
+                atlas_state.update_height_range(tile.tile, tile.height_range);
                 self.data[tile.tile.atlas_index as usize] = tile.data;
                 false
             })
@@ -224,15 +261,17 @@ impl AtlasAttachment {
     }
 
     fn load(&mut self, tile: AtlasTileAttachment) {
-        // Todo: build customizable loader abstraction
-        self.loading_tiles
-            .push(AtlasTileAttachmentWithData::start_loading(
+        self.loading_tiles.push((
+            tile,
+            AtlasTileAttachmentWithData::start_loading(
                 tile,
+                self.source.clone(),
                 self.path.clone(),
                 self.texture_size,
                 self.format,
                 self.mip_level_count,
-            ));
+            ),
+        ));
     }
 
     fn save(&mut self, tile: AtlasTileAttachment) {
@@ -241,12 +280,13 @@ impl AtlasAttachment {
                 tile: tile,
                 data: self.data[tile.atlas_index as usize].clone(),
                 texture_size: self.texture_size,
+                height_range: Vec2::new(0.0, 1.0),
             }
             .start_saving(self.path.clone()),
         );
     }
 
-    fn sample(&self, lookup: TileLookup) -> Vec4 {
+    fn sample(&self, lookup: TileLookup, bicubic: bool) -> Vec4 {
         if lookup.atlas_index == INVALID_ATLAS_INDEX {
             return Vec4::splat(0.0); // Todo: Handle this better
         }
@@ -254,10 +294,46 @@ impl AtlasAttachment {
         let data = &self.data[lookup.atlas_index as usize];
         let uv = lookup.atlas_uv * self.scale + self.offset;
 
-        data.sample(uv, self.texture_size)
+        if bicubic {
+            data.sample_bicubic(uv, self.texture_size)
+        } else {
+            data.sample(uv, self.texture_size)
+        }
     }
 }
 
+/// Sent in the main world, once all attachments of a tile have been loaded into the [`TileAtlas`] of the terrain.
+///
+/// The tiles of the atlas are shared by all views of the terrain, so the event is sent once per terrain.
+#[derive(Clone, Copy, Debug, Event)]
+pub struct TerrainTileLoaded {
+    pub terrain: Entity,
+    pub tile: TileCoordinate,
+}
+
+/// Sent in the main world, once loading an attachment of a tile has failed more often than
+/// [`TerrainConfig::max_tile_load_retries`] allows.
+///
+/// The tile is marked as failed and the best loaded ancestor of it is rendered instead.
+/// It is loaded again, once it has been evicted from the [`TileAtlas`] and is requested anew.
+#[derive(Clone, Copy, Debug, Event)]
+pub struct TerrainTileLoadFailed {
+    pub terrain: Entity,
+    pub tile: TileCoordinate,
+}
+
+/// A tile, which is loaded into a [`TileAtlas`].
+#[derive(Clone, Copy, Debug)]
+pub struct ResidentTile {
+    pub coordinate: TileCoordinate,
+    /// The minimum uv coordinate of the tile on its side.
+    pub uv_min: DVec2,
+    /// The maximum uv coordinate of the tile on its side.
+    pub uv_max: DVec2,
+    /// The index of the tile inside the atlas.
+    pub atlas_index: u32,
+}
+
 /// The current state of a tile of a [`TileAtlas`].
 ///
 /// This indicates, whether the tile is loading or loaded and ready to be used.
@@ -267,6 +343,8 @@ enum LoadingState {
     Loading(u32),
     /// The tile is loaded and can be used.
     Loaded,
+    /// Loading the tile has failed permanently, so it will never be used.
+    Failed,
 }
 
 /// The internal representation of a present tile in a [`TileAtlas`].
@@ -277,6 +355,8 @@ struct TileState {
     atlas_index: u32,
     /// The count of [`TileTrees`] that have requested this tile.
     requests: u32,
+    /// The count of failed load attempts of the attachments of this tile.
+    failed_attempts: u32,
 }
 
 pub(crate) struct TileAtlasState {
@@ -296,6 +376,27 @@ pub(crate) struct TileAtlasState {
     pub(crate) max_download_slots: u32,
 
     pub(crate) max_atlas_write_slots: u32,
+    /// The maximum count of tiles per attachment, which are uploaded each frame.
+    max_uploads_per_frame: Option<u32>,
+
+    /// The count of frames, over which freshly loaded tiles fade in.
+    tile_fade_frames: Option<u32>,
+
+    /// The normalized minimum and maximum height of each atlas tile, which includes the ranges of its resident descendants.
+    height_ranges: Vec<Vec2>,
+    /// The count of frames since each atlas tile finished loading.
+    loaded_frames: Vec<u32>,
+    /// The tiles, which finished loading since the last update.
+    loaded_tiles: Vec<TileCoordinate>,
+
+    /// The count of times a failed attachment is loaded again, before the tile is marked as failed.
+    max_tile_load_retries: u32,
+    /// The count of frames, which are waited before the first retry.
+    tile_load_retry_frames: u32,
+    /// The failed attachments, which are loaded again, once their count of remaining frames reaches zero.
+    retrying_tiles: Vec<(AtlasTileAttachment, u32)>,
+    /// The tiles, which failed loading permanently since the last update.
+    failed_tiles: Vec<TileCoordinate>,
 }
 
 impl TileAtlasState {
@@ -303,6 +404,10 @@ impl TileAtlasState {
         atlas_size: u32,
         attachment_count: u32,
         existing_tiles: HashSet<TileCoordinate>,
+        max_uploads_per_frame: Option<u32>,
+        tile_fade_frames: Option<u32>,
+        max_tile_load_retries: u32,
+        tile_load_retry_frames: u32,
     ) -> Self {
         let unused_tiles = (0..atlas_size)
             .map(|atlas_index| AtlasTile::new(TileCoordinate::INVALID, atlas_index))
@@ -321,10 +426,25 @@ impl TileAtlasState {
             download_slots: 128,
             max_download_slots: 128,
             max_atlas_write_slots: 32,
+            max_uploads_per_frame,
+            tile_fade_frames,
+            height_ranges: vec![Vec2::new(0.0, 1.0); atlas_size as usize],
+            loaded_frames: vec![u32::MAX; atlas_size as usize],
+            loaded_tiles: default(),
+            max_tile_load_retries,
+            tile_load_retry_frames,
+            retrying_tiles: default(),
+            failed_tiles: default(),
         }
     }
 
     fn update(&mut self, attachments: &mut [AtlasAttachment]) {
+        if self.tile_fade_frames.is_some() {
+            for frames in &mut self.loaded_frames {
+                *frames = frames.saturating_add(1);
+            }
+        }
+
         while self.save_slots > 0 {
             if let Some(tile) = self.to_save.pop_front() {
                 attachments[tile.attachment_index as usize].save(tile);
@@ -334,6 +454,22 @@ impl TileAtlasState {
             }
         }
 
+        let (to_load, tile_states) = (&mut self.to_load, &self.tile_states);
+        self.retrying_tiles.retain_mut(|(tile, frames)| {
+            *frames = frames.saturating_sub(1);
+
+            // skip the tiles, which have been evicted in the meantime
+            let present = tile_states
+                .get(&tile.coordinate)
+                .is_some_and(|tile_state| tile_state.atlas_index == tile.atlas_index);
+
+            if *frames == 0 && present {
+                to_load.push_front(*tile);
+            }
+
+            *frames > 0
+        });
+
         while self.load_slots > 0 {
             if let Some(tile) = self.to_load.pop_front() {
                 attachments[tile.attachment_index as usize].load(tile);
@@ -350,14 +486,98 @@ impl TileAtlasState {
         let tile_state = self.tile_states.get_mut(&tile.coordinate).unwrap();
 
         tile_state.state = match tile_state.state {
-            LoadingState::Loading(1) => LoadingState::Loaded,
+            LoadingState::Loading(1) => {
+                self.loaded_tiles.push(tile.coordinate);
+                self.loaded_frames[tile.atlas_index as usize] = 0;
+                LoadingState::Loaded
+            }
             LoadingState::Loading(n) => LoadingState::Loading(n - 1),
             LoadingState::Loaded => {
                 panic!("Loaded more attachments, than registered with the tile atlas.")
             }
+            LoadingState::Failed => LoadingState::Failed,
         };
     }
 
+    /// Schedules a retry of the failed attachment with an exponential backoff,
+    /// or marks the tile as failed, once it runs out of retries.
+    fn failed_tile_attachment(
+        &mut self,
+        tile: AtlasTileAttachment,
+        attachment_name: &str,
+        error: anyhow::Error,
+    ) {
+        self.load_slots += 1;
+
+        let Some(tile_state) = self
+            .tile_states
+            .get_mut(&tile.coordinate)
+            .filter(|tile_state| tile_state.atlas_index == tile.atlas_index)
+        else {
+            // the tile has been evicted in the meantime
+            return;
+        };
+
+        if matches!(tile_state.state, LoadingState::Failed) {
+            return;
+        }
+
+        tile_state.failed_attempts += 1;
+
+        if tile_state.failed_attempts <= self.max_tile_load_retries {
+            let backoff = 1u32 << (tile_state.failed_attempts - 1).min(16);
+            let frames = self.tile_load_retry_frames.max(1).saturating_mul(backoff);
+
+            self.retrying_tiles.push((tile, frames));
+        } else {
+            warn!(
+                "Failed to load the {attachment_name} attachment of the tile {:?}: {error}",
+                tile.coordinate
+            );
+
+            tile_state.state = LoadingState::Failed;
+            self.failed_tiles.push(tile.coordinate);
+        }
+    }
+
+    /// Updates the height range of the tile, once its height attachment is available.
+    ///
+    /// The range is reduced up the quadtree, so that the range of each tile also bounds its resident descendants.
+    /// These are drawn instead of the tile, where they are loaded, and may exceed the coarser data of the tile.
+    fn update_height_range(&mut self, tile: AtlasTileAttachment, height_range: Vec2) {
+        if tile.attachment_index != 0 {
+            return;
+        }
+
+        let merge = |a: Vec2, b: Vec2| Vec2::new(a.x.min(b.x), a.y.max(b.y));
+
+        // the descendants loaded while the tile was loading already widened its range
+        let mut height_range = merge(self.height_ranges[tile.atlas_index as usize], height_range);
+
+        // the descendants loaded before the tile cover their own descendants in turn
+        for child in tile.coordinate.children() {
+            if let Some(child) = self.tile_states.get(&child) {
+                if matches!(child.state, LoadingState::Loaded) {
+                    height_range =
+                        merge(height_range, self.height_ranges[child.atlas_index as usize]);
+                }
+            }
+        }
+
+        self.height_ranges[tile.atlas_index as usize] = height_range;
+
+        let mut coordinate = tile.coordinate;
+
+        while let Some(parent) = coordinate.parent() {
+            coordinate = parent;
+
+            if let Some(ancestor) = self.tile_states.get(&coordinate) {
+                let ancestor_range = &mut self.height_ranges[ancestor.atlas_index as usize];
+                *ancestor_range = merge(*ancestor_range, height_range);
+            }
+        }
+    }
+
     fn saved_tile_attachment(&mut self, _tile: AtlasTileAttachment) {
         self.save_slots += 1;
     }
@@ -384,6 +604,8 @@ impl TileAtlasState {
         let unused_tile = self.unused_tiles.pop_front().expect("Atlas out of indices");
 
         self.tile_states.remove(&unused_tile.coordinate);
+        // the range of the evicted tile must not leak into the new one, which widens an empty range once loaded
+        self.height_ranges[unused_tile.atlas_index as usize] = Vec2::new(f32::MAX, f32::MIN);
 
         unused_tile.atlas_index
     }
@@ -400,12 +622,16 @@ impl TileAtlasState {
         } else {
             let atlas_index = self.allocate_tile();
 
+            // the data is written on the GPU, so its height is unknown until it is downloaded
+            self.height_ranges[atlas_index as usize] = Vec2::new(0.0, 1.0);
+
             self.tile_states.insert(
                 tile_coordinate,
                 TileState {
                     requests: 1,
                     state: LoadingState::Loaded,
                     atlas_index,
+                    failed_attempts: 0,
                 },
             );
 
@@ -441,6 +667,7 @@ impl TileAtlasState {
                     requests: 1,
                     state: LoadingState::Loading(self.attachment_count),
                     atlas_index,
+                    failed_attempts: 0,
                 },
             );
 
@@ -474,31 +701,52 @@ impl TileAtlasState {
         }
     }
 
+    fn is_loaded(&self, tile_coordinate: TileCoordinate) -> bool {
+        // tiles that do not exist in the dataset or failed loading will never be loaded
+        !self.existing_tiles.contains(&tile_coordinate)
+            || self.tile_states.get(&tile_coordinate).is_some_and(|tile| {
+                matches!(tile.state, LoadingState::Loaded | LoadingState::Failed)
+            })
+    }
+
     fn get_best_tile(&self, tile_coordinate: TileCoordinate) -> TileTreeEntry {
         let mut best_tile_coordinate = tile_coordinate;
 
         loop {
-            if best_tile_coordinate == TileCoordinate::INVALID
-                || best_tile_coordinate.lod == INVALID_LOD
-            {
-                // highest lod is not loaded
-                return TileTreeEntry {
-                    atlas_index: INVALID_ATLAS_INDEX,
-                    atlas_lod: INVALID_LOD,
-                };
+            if best_tile_coordinate == TileCoordinate::INVALID {
+                return TileTreeEntry::default();
             }
 
             if let Some(atlas_tile) = self.tile_states.get(&best_tile_coordinate) {
                 if matches!(atlas_tile.state, LoadingState::Loaded) {
                     // found best loaded tile
+                    // The data of an ancestor is coarser than the one of the tile, so its range only bounds the
+                    // tile, if it is resident itself. Otherwise, the tile is bounded by the entire height range.
+                    let height_range = if best_tile_coordinate == tile_coordinate {
+                        self.height_ranges[atlas_tile.atlas_index as usize]
+                    } else {
+                        Vec2::new(0.0, 1.0)
+                    };
+                    let loaded_frames = self.loaded_frames[atlas_tile.atlas_index as usize];
+                    let fade = self.tile_fade_frames.map_or(1.0, |fade_frames| {
+                        (loaded_frames as f32 / fade_frames.max(1) as f32).min(1.0)
+                    });
+
                     return TileTreeEntry {
                         atlas_index: atlas_tile.atlas_index,
                         atlas_lod: best_tile_coordinate.lod,
+                        min_height: height_range.x,
+                        max_height: height_range.y,
+                        fade,
                     };
                 }
             }
 
-            best_tile_coordinate = best_tile_coordinate.parent();
+            match best_tile_coordinate.parent() {
+                Some(parent) => best_tile_coordinate = parent,
+                // highest lod is not loaded
+                None => return TileTreeEntry::default(),
+            }
         }
     }
 }
@@ -515,6 +763,10 @@ impl TileAtlasState {
 ///
 /// The [`u32`] can be used for accessing the attached data in systems by the CPU
 /// and in shaders by the GPU.
+///
+/// The atlas is shared by all views of the terrain. Each tile is only resident once, no matter how many
+/// [`TileTree`]s request it, and stays resident until the last of them releases it.
+/// Thus additional views (e.g. split-screen cameras) only add their per-view state, see [`TileTree`].
 #[derive(Component)]
 pub struct TileAtlas {
     pub(crate) attachments: Vec<AtlasAttachment>,
@@ -523,30 +775,521 @@ pub struct TileAtlas {
     pub(crate) path: String,
     pub(crate) atlas_size: u32,
     pub(crate) lod_count: u32,
+    pub(crate) max_lod: Option<u32>,
     pub(crate) model: TerrainModel,
+    pub(crate) height_scale: f32,
+    pub(crate) height_offset: f32,
+    pub(crate) height_gamma: f32,
+    /// Keeps the prepass extension shader loaded, so that it can be imported by the prepass.
+    pub(crate) prepass_extension: Option<Handle<Shader>>,
+    pub(crate) prepass_shader_defs: Vec<ShaderDefVal>,
+    pub(crate) refine_tiles_shader: Option<Handle<Shader>>,
+    pub(crate) prepare_prepass_shader: Option<Handle<Shader>>,
+    pub(crate) prepass_flags: TilingPrepassPipelineKey,
+    pub(crate) bicubic_height_sampling: bool,
+    pub(crate) double_sided: bool,
+    pub(crate) depth_bias: DepthBiasState,
+    pub(crate) shading: TerrainShading,
+    pub(crate) skirt_depth: Option<f32>,
+    pub(crate) atmosphere: Option<TerrainAtmosphere>,
+    pub(crate) sea_level: Option<f32>,
+    pub(crate) sea_color: Color,
+    pub(crate) contour_lines: Option<TerrainContourLines>,
+    pub(crate) height_floor: Option<f32>,
+    pub(crate) tile_fade_frames: Option<u32>,
+    /// Keeps the hole mask loaded, so that it can be bound in the render world.
+    pub(crate) hole_mask: Option<Handle<Image>>,
+    pub(crate) seed: u32,
+    pub(crate) height_channel: u32,
+    pub(crate) anisotropy: u8,
+    pub(crate) mip_bias: f32,
+    pub(crate) max_mip_level: Option<u32>,
+    /// Keeps the detail noise texture loaded, so that it can be bound in the render world.
+    pub(crate) detail_noise: Option<TerrainDetailNoise>,
+    /// Keeps the height overlay loaded, so that it can be bound in the render world.
+    pub(crate) height_overlay: Option<TerrainHeightOverlay>,
+    /// The brush strokes, which are applied to the height overlay on the GPU in the next frame.
+    pub(crate) brush_strokes: Vec<TerrainBrushStroke>,
+    pub(crate) priority: i32,
+    pub(crate) priority_blend_width: f64,
 }
 
 impl TileAtlas {
-    /// Creates a new tile_tree from a terrain config.
-    pub fn new(config: &TerrainConfig) -> Self {
+    /// Creates a new tile atlas from a terrain config.
+    ///
+    /// Returns the violated invariant, if the config is invalid (see [`TerrainConfig::validate`]),
+    /// so that the terrain is not spawned with a config, which would result in NaNs or blank terrain.
+    pub fn new(config: &TerrainConfig) -> Result<Self, TerrainConfigError> {
+        config.validate()?;
+
         let attachments = config
             .attachments
             .iter()
-            .map(|attachment| AtlasAttachment::new(attachment, config.atlas_size, &config.path))
+            .map(|attachment| {
+                AtlasAttachment::new(
+                    attachment,
+                    config.atlas_size,
+                    &config.path,
+                    config.tile_source.clone(),
+                )
+            })
             .collect_vec();
 
         let existing_tiles = Self::load_tile_config(&config.path);
 
-        let state =
-            TileAtlasState::new(config.atlas_size, attachments.len() as u32, existing_tiles);
+        let state = TileAtlasState::new(
+            config.atlas_size,
+            attachments.len() as u32,
+            existing_tiles,
+            config.max_uploads_per_frame,
+            config.tile_fade_frames,
+            config.max_tile_load_retries,
+            config.tile_load_retry_frames,
+        );
 
-        Self {
+        Ok(Self {
             model: config.model.clone(),
+            height_scale: config.height_scale,
+            height_offset: config.height_offset,
+            height_gamma: config.height_gamma,
+            prepass_extension: config.prepass_extension.clone(),
+            prepass_shader_defs: config.prepass_shader_defs.clone(),
+            refine_tiles_shader: config.refine_tiles_shader.clone(),
+            prepare_prepass_shader: config.prepare_prepass_shader.clone(),
+            prepass_flags: config.prepass_flags,
+            bicubic_height_sampling: config.bicubic_height_sampling,
+            double_sided: config.double_sided,
+            depth_bias: config.depth_bias,
+            shading: config.shading,
+            skirt_depth: config.skirt_depth,
+            atmosphere: config.atmosphere,
+            sea_level: config.sea_level,
+            sea_color: config.sea_color,
+            contour_lines: config.contour_lines,
+            height_floor: config.height_floor,
+            tile_fade_frames: config.tile_fade_frames,
+            hole_mask: config.hole_mask.clone(),
+            seed: config.seed,
+            height_channel: config.height_channel,
+            anisotropy: config.anisotropy,
+            mip_bias: config.mip_bias,
+            max_mip_level: config.max_mip_level,
+            detail_noise: config.detail_noise.clone(),
+            height_overlay: config.height_overlay.clone(),
+            brush_strokes: default(),
+            priority: config.priority,
+            priority_blend_width: config.priority_blend_width,
             attachments,
             state,
             path: config.path.to_string(),
             atlas_size: config.atlas_size,
             lod_count: config.lod_count,
+            max_lod: config.max_lod,
+        })
+    }
+
+    /// The minimum terrain height, including the height scale and offset.
+    pub(crate) fn min_height(&self) -> f32 {
+        self.model.min_height * self.height_scale + self.height_offset
+    }
+
+    /// The maximum terrain height, including the height scale and offset.
+    pub(crate) fn max_height(&self) -> f32 {
+        self.model.max_height * self.height_scale + self.height_offset
+    }
+
+    /// The bounds of the height offsets of the overlay, which are zero while the overlay is disabled or missing.
+    pub(crate) fn overlay_height_range(&self) -> Vec2 {
+        self.height_overlay
+            .as_ref()
+            .filter(|height_overlay| height_overlay.enabled)
+            .map_or(Vec2::ZERO, |height_overlay| {
+                Vec2::new(height_overlay.min_height, height_overlay.max_height)
+            })
+    }
+
+    /// Maps the normalized height stored in the atlas onto the height range, applying the height gamma and floor.
+    ///
+    /// Heights outside of zero to one (e.g. of float attachments) extrapolate the height range without the gamma.
+    pub(crate) fn remap_height(&self, normalized_height: f32) -> f32 {
+        let normalized_height = if (0.0..=1.0).contains(&normalized_height) {
+            normalized_height.powf(self.height_gamma)
+        } else {
+            normalized_height
+        };
+
+        let height = f32::lerp(self.min_height(), self.max_height(), normalized_height);
+
+        height.max(self.height_floor.unwrap_or(f32::MIN))
+    }
+
+    /// Samples the height of the terrain at the coordinate from a CPU-resident heightmap.
+    ///
+    /// Like the other sampling utilities of the atlas, this only requires the main world,
+    /// so it can be used in headless apps without a render world, e.g. for server-side height queries.
+    /// The heightmap has to cover the uv range of the side of the coordinate and store normalized heights in the height channel.
+    /// These are remapped like the heights of the atlas, including the height scale, offset, gamma and floor,
+    /// and are displaced by the height overlay, if it is enabled (see [`TerrainHeightOverlay::sample`]).
+    /// Texels, which can not be read (e.g. due to an unsupported texture format), are treated as zero.
+    pub fn sample_heightmap(
+        &self,
+        coordinate: Coordinate,
+        heightmap: &Image,
+        images: &Assets<Image>,
+    ) -> f32 {
+        let height = self.remap_height(sample_heightmap_channel(
+            heightmap,
+            coordinate.uv,
+            self.height_channel,
+        ));
+
+        let overlay = self.height_overlay.as_ref().map_or(0.0, |height_overlay| {
+            height_overlay.sample(coordinate, images)
+        });
+
+        height + overlay
+    }
+
+    /// Computes the world space surface normal at the coordinate, including the slope of the heightmap.
+    ///
+    /// The heightmap has to cover the uv range of the side of the coordinate, see [`TileAtlas::sample_heightmap`].
+    pub fn sample_normal(
+        &self,
+        coordinate: Coordinate,
+        heightmap: &Image,
+        images: &Assets<Image>,
+    ) -> DVec3 {
+        let size = heightmap.size().as_dvec2();
+
+        let position = |offset: DVec2| {
+            let uv = (coordinate.uv + offset / size).clamp(DVec2::ZERO, DVec2::ONE);
+            let coordinate = Coordinate::new(coordinate.side, uv);
+
+            coordinate.world_position(
+                &self.model,
+                self.sample_heightmap(coordinate, heightmap, images),
+            )
+        };
+
+        let tangent = position(DVec2::X) - position(DVec2::NEG_X);
+        let bitangent = position(DVec2::Y) - position(DVec2::NEG_Y);
+        let normal = tangent.cross(bitangent).normalize();
+
+        // orient the normal away from the surface, independent of the handedness of the side
+        let surface_normal = coordinate.world_position(&self.model, 1.0)
+            - coordinate.world_position(&self.model, 0.0);
+
+        if normal.dot(surface_normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
+
+    /// Finds the point on the surface of the terrain closest to the world position, e.g. to snap objects or cameras onto it.
+    ///
+    /// Spherical terrains project the position along the radial direction (onto the closest point for ellipsoids),
+    /// while planar terrains drop it onto the plane, clamped to its extent.
+    /// If a heightmap is given, the point is displaced by its height along the surface normal (see [`TileAtlas::sample_heightmap`]),
+    /// which lies exactly below the position, but is not necessarily the closest point on steep slopes.
+    pub fn closest_surface_point(
+        &self,
+        world_position: DVec3,
+        heightmap: Option<&Image>,
+        images: &Assets<Image>,
+    ) -> DVec3 {
+        let coordinate = Coordinate::from_world_position(world_position, &self.model);
+        let height = heightmap.map_or(0.0, |heightmap| {
+            self.sample_heightmap(coordinate, heightmap, images)
+        });
+
+        coordinate.world_position(&self.model, height)
+    }
+
+    /// Intersects the ray with the surface of the terrain, displaced by the heightmap, e.g. for click-to-place.
+    ///
+    /// The ray is marched towards the surface in steps of half its altitude above it, which stays conservative on slopes
+    /// of up to 60 degrees, and the hit is refined by bisection, once the ray passes below the surface.
+    /// Rays, which start below the surface, leave the shell of the terrain or hit a planar terrain outside of its extent, miss.
+    /// The heightmap has to cover the uv range of the sides, see [`TileAtlas::sample_heightmap`].
+    pub fn raycast(
+        &self,
+        ray_origin: DVec3,
+        ray_direction: DVec3,
+        heightmap: &Image,
+        images: &Assets<Image>,
+    ) -> Option<DVec3> {
+        const MAX_STEPS: u32 = 256;
+        const REFINEMENT_STEPS: u32 = 16;
+
+        let direction = ray_direction.normalize();
+        let epsilon = 1e-6 * self.model.scale();
+
+        // the signed distance of the point along the ray above the displaced surface below it
+        let altitude = |t: f64| {
+            let position = ray_origin + t * direction;
+            let coordinate = Coordinate::from_world_position(position, &self.model);
+            let height = self.sample_heightmap(coordinate, heightmap, images);
+            let surface_position = coordinate.world_position(&self.model, height);
+            let surface_normal = (coordinate.world_position(&self.model, height + 1.0)
+                - surface_position)
+                .normalize();
+
+            (
+                (position - surface_position).dot(surface_normal),
+                surface_normal,
+            )
+        };
+
+        let (mut previous_t, mut t) = (0.0, 0.0);
+
+        for step in 0..MAX_STEPS {
+            let (distance, surface_normal) = altitude(t);
+
+            if distance < 0.0 && step == 0 {
+                return None;
+            }
+
+            if distance < epsilon {
+                // the surface lies between the previous and the current step
+                let (mut above, mut below) = (previous_t, t);
+
+                for _ in 0..REFINEMENT_STEPS {
+                    let middle = (above + below) / 2.0;
+
+                    if altitude(middle).0 < 0.0 {
+                        below = middle;
+                    } else {
+                        above = middle;
+                    }
+                }
+
+                let hit = ray_origin + below * direction;
+
+                return (self.model.boundary_distance(hit) >= 0.0).then_some(hit);
+            }
+
+            // the ray moves away from the surface outside of the shell of the terrain
+            if distance > (self.max_height() - self.min_height()) as f64
+                && !self.model.contains(ray_origin + t * direction)
+                && direction.dot(surface_normal) >= 0.0
+            {
+                return None;
+            }
+
+            previous_t = t;
+            t += 0.5 * distance;
+        }
+
+        None
+    }
+
+    /// Samples a regular grid of heights covering the st range of the side, e.g. for a heightfield collider.
+    ///
+    /// The resolution is the count of samples per unit st, so the grid includes both borders of the range.
+    /// The heights are stored row by row (rows along t, columns along s) and measured in world units along the normal
+    /// at the center of the range, relative to the surface without any height.
+    /// For spherical and curved terrain models, the result is a local tangent-plane patch at this center,
+    /// which is only accurate for regions, that are small compared to the radius.
+    /// The heightmap has to cover the uv range of the side, see [`TileAtlas::sample_heightmap`].
+    pub fn extract_heightfield(
+        &self,
+        side: u32,
+        heightmap: &Image,
+        images: &Assets<Image>,
+        st_min: DVec2,
+        st_max: DVec2,
+        resolution: u32,
+    ) -> (Vec<f32>, u32, u32) {
+        let extent = st_max - st_min;
+        let cells = (extent * resolution as f64)
+            .ceil()
+            .as_uvec2()
+            .max(UVec2::ONE);
+        let (columns, rows) = (cells.x + 1, cells.y + 1);
+
+        let center = Coordinate::new(side, st_min + 0.5 * extent);
+        let origin = center.world_position(&self.model, 0.0);
+        let up = (center.world_position(&self.model, 1.0) - origin).normalize();
+
+        let heights = iproduct!(0..rows, 0..columns)
+            .map(|(row, column)| {
+                let st = st_min + extent * UVec2::new(column, row).as_dvec2() / cells.as_dvec2();
+                let coordinate = Coordinate::new(side, st);
+                let position = coordinate.world_position(
+                    &self.model,
+                    self.sample_heightmap(coordinate, heightmap, images),
+                );
+
+                (position - origin).dot(up) as f32
+            })
+            .collect();
+
+        (heights, columns, rows)
+    }
+
+    /// Tessellates the tile into a grid of `grid_size`×`grid_size` quads on the CPU, like the GPU tessellates each tile.
+    ///
+    /// This is an offline or editor utility (e.g. for baking static meshes or lightmaps), which is too slow to be used each frame.
+    /// The mesh contains positions, normals, the st coordinates of the side as uvs and triangle indices.
+    /// To preserve the precision on large terrains, the positions are relative to the returned center of the tile,
+    /// at which the mesh should be placed. The heightmap has to cover the uv range of the side, see [`TileAtlas::sample_heightmap`].
+    pub fn generate_mesh(
+        &self,
+        heightmap: &Image,
+        images: &Assets<Image>,
+        tile: TileCoordinate,
+        grid_size: u32,
+    ) -> (Mesh, DVec3) {
+        let grid_size = grid_size.max(1);
+        let vertices_per_row = grid_size + 1;
+        let tile_count = TileCoordinate::count(tile.lod) as f64;
+        let tile_xy = UVec2::new(tile.x, tile.y).as_dvec2();
+
+        let center = Coordinate::new(tile.side, (tile_xy + 0.5) / tile_count)
+            .world_position(&self.model, 0.0);
+
+        let coordinates = iproduct!(0..vertices_per_row, 0..vertices_per_row)
+            .map(|(y, x)| {
+                let uv = UVec2::new(x, y).as_dvec2() / grid_size as f64;
+                Coordinate::new(tile.side, (tile_xy + uv) / tile_count)
+            })
+            .collect::<Vec<_>>();
+
+        let positions = coordinates
+            .iter()
+            .map(|&coordinate| {
+                let height = self.sample_heightmap(coordinate, heightmap, images);
+
+                (coordinate.world_position(&self.model, height) - center)
+                    .as_vec3()
+                    .to_array()
+            })
+            .collect::<Vec<_>>();
+        let normals = coordinates
+            .iter()
+            .map(|&coordinate| {
+                self.sample_normal(coordinate, heightmap, images)
+                    .as_vec3()
+                    .to_array()
+            })
+            .collect::<Vec<_>>();
+        let uvs = coordinates
+            .iter()
+            .map(|coordinate| coordinate.uv.as_vec2().to_array())
+            .collect::<Vec<_>>();
+
+        // wind the triangles counter-clockwise, when viewed from above the surface
+        let first = Vec3::from(positions[0]);
+        let tangent = Vec3::from(positions[1]) - first;
+        let bitangent = Vec3::from(positions[vertices_per_row as usize]) - first;
+        let up = (Coordinate::new(tile.side, (tile_xy + 0.5) / tile_count)
+            .world_position(&self.model, 1.0)
+            - center)
+            .as_vec3();
+        let flip = tangent.cross(bitangent).dot(up) < 0.0;
+
+        let indices = iproduct!(0..grid_size, 0..grid_size)
+            .flat_map(|(y, x)| {
+                let index = y * vertices_per_row + x;
+                let (a, b, c, d) = (
+                    index,
+                    index + 1,
+                    index + vertices_per_row,
+                    index + vertices_per_row + 1,
+                );
+
+                if flip {
+                    [a, c, b, b, c, d]
+                } else {
+                    [a, b, c, b, d, c]
+                }
+            })
+            .collect();
+
+        let mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices));
+
+        (mesh, center)
+    }
+
+    /// The height overlay of the terrain, which can be enabled and disabled at runtime.
+    pub fn height_overlay_mut(&mut self) -> Option<&mut TerrainHeightOverlay> {
+        self.height_overlay.as_mut()
+    }
+
+    /// Enqueues a brush stroke, which edits the height overlay on the GPU in the next frame.
+    ///
+    /// The strokes are applied in the order they were enqueued, independent of the refinement of the tile trees.
+    /// They are discarded, if the terrain has no height overlay.
+    ///
+    /// The stroke is applied immediately to a copy of the overlay image on the CPU (see [`TerrainBrushStroke::apply`]),
+    /// so that the CPU sampling matches the GPU. The copy is only made, if the image is available in the main world.
+    pub fn apply_brush(&mut self, stroke: TerrainBrushStroke, images: &Assets<Image>) {
+        let Some(height_overlay) = &mut self.height_overlay else {
+            return;
+        };
+
+        if height_overlay.edited_image.is_none() {
+            height_overlay.edited_image = images.get(&height_overlay.texture).cloned();
+        }
+
+        if let Some(image) = &mut height_overlay.edited_image {
+            stroke.apply(image);
+        }
+
+        self.brush_strokes.push(stroke);
+    }
+
+    /// Discards the CPU copies of the height overlays, whose images have been modified.
+    ///
+    /// The modified image is uploaded again, which discards the GPU edits as well.
+    /// Only the pending strokes, which are applied to the uploaded image on the GPU, are applied to the new copy.
+    pub(crate) fn discard_brush_edits(
+        mut asset_events: EventReader<AssetEvent<Image>>,
+        mut tile_atlases: Query<&mut TileAtlas>,
+        images: Res<Assets<Image>>,
+    ) {
+        let modified = asset_events
+            .read()
+            .filter_map(|event| match event {
+                AssetEvent::Modified { id } => Some(*id),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        if modified.is_empty() {
+            return;
+        }
+
+        for mut tile_atlas in &mut tile_atlases {
+            let tile_atlas = &mut *tile_atlas;
+
+            let Some(height_overlay) = &mut tile_atlas.height_overlay else {
+                continue;
+            };
+
+            if !modified.contains(&height_overlay.texture.id()) {
+                continue;
+            }
+
+            height_overlay.edited_image = None;
+
+            if !tile_atlas.brush_strokes.is_empty() {
+                height_overlay.edited_image = images.get(&height_overlay.texture).cloned();
+            }
+
+            if let Some(image) = &mut height_overlay.edited_image {
+                for stroke in &tile_atlas.brush_strokes {
+                    stroke.apply(image);
+                }
+            }
         }
     }
 
@@ -554,6 +1297,43 @@ impl TileAtlas {
         self.state.get_tile(tile_coordinate)
     }
 
+    /// Checks whether all tiles of the lod, which cover the uv region of the side, are loaded.
+    ///
+    /// Tiles that do not exist in the dataset are ignored.
+    /// Note that tiles are only loaded, once they are requested by a [`TileTree`] (e.g. by moving the view nearby).
+    pub fn is_region_loaded(&self, side: u32, uv_min: DVec2, uv_max: DVec2, lod: u32) -> bool {
+        let tile_count = TileCoordinate::count(lod);
+
+        let xy_min = (uv_min.clamp(DVec2::ZERO, DVec2::ONE) * tile_count as f64)
+            .as_uvec2()
+            .min(UVec2::splat(tile_count - 1));
+        let xy_max = (uv_max.clamp(DVec2::ZERO, DVec2::ONE) * tile_count as f64)
+            .as_uvec2()
+            .min(UVec2::splat(tile_count - 1));
+
+        iproduct!(xy_min.x..=xy_max.x, xy_min.y..=xy_max.y)
+            .all(|(x, y)| self.state.is_loaded(TileCoordinate { side, lod, x, y }))
+    }
+
+    /// Iterates over all tiles, which are currently loaded and can be used, in no particular order.
+    pub fn resident_tiles(&self) -> impl Iterator<Item = ResidentTile> + '_ {
+        self.state
+            .tile_states
+            .iter()
+            .filter(|(_, tile)| matches!(tile.state, LoadingState::Loaded))
+            .map(|(&coordinate, tile)| {
+                let tile_count = TileCoordinate::count(coordinate.lod) as f64;
+                let xy = DVec2::new(coordinate.x as f64, coordinate.y as f64);
+
+                ResidentTile {
+                    coordinate,
+                    uv_min: xy / tile_count,
+                    uv_max: (xy + 1.0) / tile_count,
+                    atlas_index: tile.atlas_index,
+                }
+            })
+    }
+
     pub fn get_or_allocate_tile(&mut self, tile_coordinate: TileCoordinate) -> AtlasTile {
         self.state.get_or_allocate_tile(tile_coordinate)
     }
@@ -566,16 +1346,85 @@ impl TileAtlas {
         self.state.get_best_tile(tile_coordinate)
     }
 
+    pub(super) fn is_loaded(&self, tile_coordinate: TileCoordinate) -> bool {
+        self.state.is_loaded(tile_coordinate)
+    }
+
+    /// Samples the attachment like the vertex shader, which filters the heights bicubically, if enabled.
     pub(super) fn sample_attachment(&self, tile_lookup: TileLookup, attachment_index: u32) -> Vec4 {
-        self.attachments[attachment_index as usize].sample(tile_lookup)
+        let bicubic = attachment_index == 0 && self.bicubic_height_sampling;
+
+        self.attachments[attachment_index as usize].sample(tile_lookup, bicubic)
+    }
+
+    /// Moves the terrain models along with the transforms of their terrain entities.
+    ///
+    /// Only terrains, whose transform (or grid cell) changed, are visited and the matrices of the model
+    /// are only rebuilt, if the transform differs from the model, e.g. not for the transform set by the [`TerrainBundle`](crate::terrain::TerrainBundle).
+    /// The bounds are updated as well, since they depend on the scale of the model.
+    pub(crate) fn sync_model_transform(
+        #[cfg(feature = "high_precision")] frames: crate::big_space::ReferenceFrames,
+        #[cfg(feature = "high_precision")] mut terrains: Query<
+            (
+                Entity,
+                crate::big_space::GridTransformReadOnly,
+                &mut TileAtlas,
+                &mut Aabb,
+            ),
+            Or<(Changed<Transform>, Changed<crate::big_space::GridCell>)>,
+        >,
+        #[cfg(not(feature = "high_precision"))] mut terrains: Query<
+            (&Transform, &mut TileAtlas, &mut Aabb),
+            Changed<Transform>,
+        >,
+    ) {
+        #[cfg(feature = "high_precision")]
+        for (terrain, grid_transform, mut tile_atlas, mut aabb) in &mut terrains {
+            let frame = frames.parent_frame(terrain).unwrap();
+            let model_transform = tile_atlas.model.grid_transform(frame);
+
+            if model_transform.transform == *grid_transform.transform
+                && model_transform.cell == *grid_transform.cell
+            {
+                continue;
+            }
+
+            let transform = grid_transform.transform;
+            tile_atlas.model.set_transform(
+                grid_transform.position_double(frame),
+                transform.rotation.as_dquat(),
+                transform.scale.as_dvec3(),
+            );
+            *aabb = tile_atlas
+                .model
+                .aabb(tile_atlas.min_height(), tile_atlas.max_height());
+        }
+
+        #[cfg(not(feature = "high_precision"))]
+        for (transform, mut tile_atlas, mut aabb) in &mut terrains {
+            if tile_atlas.model.transform() == *transform {
+                continue;
+            }
+
+            tile_atlas.model.set_transform(
+                transform.translation.as_dvec3(),
+                transform.rotation.as_dquat(),
+                transform.scale.as_dvec3(),
+            );
+            *aabb = tile_atlas
+                .model
+                .aabb(tile_atlas.min_height(), tile_atlas.max_height());
+        }
     }
 
     /// Updates the tile atlas according to all corresponding tile_trees.
     pub(crate) fn update(
         mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
-        mut tile_atlases: Query<&mut TileAtlas>,
+        mut tile_atlases: Query<(Entity, &mut TileAtlas)>,
+        mut tile_loaded_events: EventWriter<TerrainTileLoaded>,
+        mut tile_load_failed_events: EventWriter<TerrainTileLoadFailed>,
     ) {
-        for mut tile_atlas in tile_atlases.iter_mut() {
+        for (terrain, mut tile_atlas) in tile_atlases.iter_mut() {
             let TileAtlas {
                 state, attachments, ..
             } = tile_atlas.deref_mut();
@@ -585,10 +1434,24 @@ impl TileAtlas {
             for attachment in attachments {
                 attachment.update(state);
             }
+
+            tile_loaded_events.send_batch(
+                state
+                    .loaded_tiles
+                    .drain(..)
+                    .map(|tile| TerrainTileLoaded { terrain, tile }),
+            );
+
+            tile_load_failed_events.send_batch(
+                state
+                    .failed_tiles
+                    .drain(..)
+                    .map(|tile| TerrainTileLoadFailed { terrain, tile }),
+            );
         }
 
         for (&(terrain, _view), tile_tree) in tile_trees.iter_mut() {
-            let mut tile_atlas = tile_atlases.get_mut(terrain).unwrap();
+            let (_, mut tile_atlas) = tile_atlases.get_mut(terrain).unwrap();
 
             for tile_coordinate in tile_tree.released_tiles.drain(..) {
                 tile_atlas.state.release_tile(tile_coordinate);
@@ -622,3 +1485,380 @@ impl TileAtlas {
         }
     }
 }
+
+/// Bilinearly samples the channel of the heightmap at the uv coordinate.
+fn sample_heightmap_channel(heightmap: &Image, uv: DVec2, channel: u32) -> f32 {
+    let size = heightmap.size();
+    let texel = (uv * size.as_dvec2() - 0.5).max(DVec2::ZERO);
+    let remainder = texel.fract().as_vec2();
+    let texel = texel.as_uvec2();
+
+    let value = |x: u32, y: u32| {
+        heightmap
+            .get_color_at(x.min(size.x - 1), y.min(size.y - 1))
+            .map_or(0.0, |color| {
+                color.to_linear().to_f32_array()[channel as usize]
+            })
+    };
+
+    f32::lerp(
+        f32::lerp(
+            value(texel.x, texel.y),
+            value(texel.x + 1, texel.y),
+            remainder.x,
+        ),
+        f32::lerp(
+            value(texel.x, texel.y + 1),
+            value(texel.x + 1, texel.y + 1),
+            remainder.x,
+        ),
+        remainder.y,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::TerrainBrushMode;
+
+    fn load(state: &mut TileAtlasState, coordinate: TileCoordinate, height_range: Vec2) {
+        let atlas_index = state.tile_states[&coordinate].atlas_index;
+        let tile = AtlasTileAttachment {
+            coordinate,
+            atlas_index,
+            attachment_index: 0,
+        };
+
+        state.loaded_tile_attachment(tile);
+        state.update_height_range(tile, height_range);
+    }
+
+    #[test]
+    fn views_share_resident_tiles() {
+        let tile = TileCoordinate::new(0, 1, 0, 0);
+        let mut state = TileAtlasState::new(4, 2, [tile].into_iter().collect(), None, None, 0, 0);
+
+        // the tile trees of two views request the same tile, which is only allocated and loaded once
+        state.request_tile(tile);
+        state.request_tile(tile);
+
+        assert_eq!(state.to_load.len(), 2);
+        assert_eq!(state.unused_tiles.len(), 3);
+
+        let atlas_index = state.tile_states[&tile].atlas_index;
+        let is_unused = |state: &TileAtlasState| {
+            state
+                .unused_tiles
+                .iter()
+                .any(|unused_tile| unused_tile.atlas_index == atlas_index)
+        };
+
+        // the tile stays resident, until the last view releases it
+        state.release_tile(tile);
+        assert!(!is_unused(&state));
+
+        state.release_tile(tile);
+        assert!(is_unused(&state));
+
+        // requested again before it is evicted, the tile is reused without loading it again
+        state.request_tile(tile);
+        assert!(!is_unused(&state));
+        assert_eq!(state.tile_states[&tile].atlas_index, atlas_index);
+        assert_eq!(state.to_load.len(), 2);
+    }
+
+    #[test]
+    fn height_ranges_bound_resident_descendants() {
+        let parent = TileCoordinate::new(0, 1, 0, 0);
+        let child = parent.children().next().unwrap();
+        let grandchild = child.children().next().unwrap();
+
+        let existing_tiles = [parent, child, grandchild].into_iter().collect();
+        let mut state = TileAtlasState::new(4, 1, existing_tiles, None, None, 0, 0);
+
+        state.request_tile(parent);
+        state.request_tile(child);
+        state.request_tile(grandchild);
+
+        // the children finish loading before and after their parent
+        load(&mut state, child, Vec2::new(0.2, 0.9));
+        load(&mut state, parent, Vec2::new(0.4, 0.5));
+        load(&mut state, grandchild, Vec2::new(0.1, 0.3));
+
+        let range = |entry: TileTreeEntry| Vec2::new(entry.min_height, entry.max_height);
+
+        assert_eq!(range(state.get_best_tile(parent)), Vec2::new(0.1, 0.9));
+        assert_eq!(range(state.get_best_tile(child)), Vec2::new(0.1, 0.9));
+        assert_eq!(range(state.get_best_tile(grandchild)), Vec2::new(0.1, 0.3));
+
+        // a tile drawn with the data of its parent is not bounded by the range of the parent
+        let sibling = parent.children().nth(1).unwrap();
+        assert_eq!(range(state.get_best_tile(sibling)), Vec2::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn samples_are_continuous_across_side_boundaries() {
+        let (texture_size, border_size) = (68, 2);
+        let model = TerrainModel::sphere(DVec3::ZERO, 1.0, 0.0, 1.0);
+        let config = TerrainConfig {
+            model: model.clone(),
+            ..default()
+        }
+        .add_attachment(AttachmentConfig {
+            name: "height".into(),
+            texture_size,
+            border_size,
+            mip_level_count: 1,
+            format: AttachmentFormat::R32F,
+        });
+        let mut tile_atlas = TileAtlas::new(&config).unwrap();
+
+        // a smooth height on the sphere, which is continuous across the side boundaries
+        let height = |world_position: DVec3| {
+            let direction = world_position.normalize().as_vec3();
+            direction.dot(Vec3::new(1.0, 2.0, 3.0))
+        };
+
+        // fill the tile of each side including its border, like the preprocessor stitches it from the adjacent sides
+        let attachment = &mut tile_atlas.attachments[0];
+        let (scale, offset) = (attachment.scale, attachment.offset);
+
+        for side in 0..6 {
+            let data = iproduct!(0..texture_size, 0..texture_size)
+                .map(|(y, x)| {
+                    let atlas_uv = (UVec2::new(x, y).as_vec2() + 0.5) / texture_size as f32;
+                    let uv = (atlas_uv - offset) / scale;
+
+                    height(Coordinate::new(side, uv.as_dvec2()).world_position(&model, 0.0))
+                })
+                .collect();
+
+            attachment.data[side as usize] = AttachmentData::R32F(data);
+        }
+
+        let sample = |world_position: DVec3| {
+            let coordinate = Coordinate::from_world_position(world_position, &model);
+            let lookup = TileLookup {
+                atlas_index: coordinate.side,
+                atlas_lod: 0,
+                atlas_uv: coordinate.uv.as_vec2(),
+            };
+
+            (coordinate.side, tile_atlas.sample_attachment(lookup, 0).x)
+        };
+
+        let epsilon = 1e-6;
+
+        for (side, t) in iproduct!(0..6, [0.1, 0.3, 0.5, 0.7, 0.9]) {
+            for (inside, outside) in [
+                (DVec2::new(epsilon, t), DVec2::new(-epsilon, t)),
+                (DVec2::new(1.0 - epsilon, t), DVec2::new(1.0 + epsilon, t)),
+                (DVec2::new(t, epsilon), DVec2::new(t, -epsilon)),
+                (DVec2::new(t, 1.0 - epsilon), DVec2::new(t, 1.0 + epsilon)),
+            ] {
+                let (inside_side, inside_height) =
+                    sample(Coordinate::new(side, inside).world_position(&model, 0.0));
+                let (outside_side, outside_height) =
+                    sample(Coordinate::new(side, outside).world_position(&model, 0.0));
+
+                assert_eq!(inside_side, side);
+                assert_ne!(outside_side, side);
+                assert!(
+                    (inside_height - outside_height).abs() < 1e-3,
+                    "the height jumps from {inside_height} to {outside_height} across the boundary of side {side} at {inside}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn float_heights_extrapolate_the_height_range() {
+        let tile_atlas = TileAtlas::new(&TerrainConfig {
+            model: TerrainModel::planar(DVec3::ZERO, 1.0, 0.0, 1.0),
+            height_gamma: 2.0,
+            ..default()
+        })
+        .unwrap();
+
+        // heights in meters are kept as is with a height range of zero to one, while the gamma only applies inside of it
+        assert_eq!(tile_atlas.remap_height(-10.0), -10.0);
+        assert_eq!(tile_atlas.remap_height(0.5), 0.25);
+        assert_eq!(tile_atlas.remap_height(120.0), 120.0);
+    }
+
+    #[test]
+    fn overlay_height_range_is_zero_while_disabled() {
+        let mut tile_atlas = TileAtlas::new(&TerrainConfig {
+            height_overlay: Some(TerrainHeightOverlay::new(Handle::default(), -2.0, 5.0)),
+            ..default()
+        })
+        .unwrap();
+
+        assert_eq!(tile_atlas.overlay_height_range(), Vec2::new(-2.0, 5.0));
+
+        tile_atlas.height_overlay_mut().unwrap().enabled = false;
+        assert_eq!(tile_atlas.overlay_height_range(), Vec2::ZERO);
+
+        tile_atlas.height_overlay = None;
+        assert_eq!(tile_atlas.overlay_height_range(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn brush_strokes_are_mirrored_for_sampling() {
+        let size = Extent3d {
+            width: 5,
+            height: 5,
+            depth_or_array_layers: 1,
+        };
+        let mut image = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            bytemuck::bytes_of(&0.0f32),
+            TextureFormat::R32Float,
+            default(),
+        );
+        // a spike in the center of the overlay
+        image.data[48..52].copy_from_slice(bytemuck::bytes_of(&4.0f32));
+
+        let mut images = Assets::<Image>::default();
+        let overlay = images.add(image);
+
+        let mut tile_atlas = TileAtlas::new(&TerrainConfig {
+            height_overlay: Some(TerrainHeightOverlay::new(overlay.clone(), -1.0, 5.0)),
+            ..default()
+        })
+        .unwrap();
+
+        let stroke = |radius, strength, mode| TerrainBrushStroke {
+            coordinate: Coordinate::new(0, DVec2::splat(0.5)),
+            radius,
+            strength,
+            mode,
+        };
+        let sample = |tile_atlas: &TileAtlas, x: u32, y: u32| {
+            let coordinate = Coordinate::new(0, (UVec2::new(x, y).as_dvec2() + 0.5) / 5.0);
+
+            tile_atlas
+                .height_overlay
+                .as_ref()
+                .unwrap()
+                .sample(coordinate, &images)
+        };
+
+        // the smoothing reads the heights before the stroke, so the spike is spread onto its neighbours
+        tile_atlas.apply_brush(stroke(10.0, 10.0, TerrainBrushMode::Smooth), &images);
+
+        assert_eq!(sample(&tile_atlas, 2, 2), 0.0);
+        assert_eq!(sample(&tile_atlas, 3, 2), 1.0);
+        assert_eq!(sample(&tile_atlas, 2, 1), 1.0);
+        assert_eq!(sample(&tile_atlas, 3, 3), 0.0);
+
+        // the strength of the raise falls off to zero at the radius
+        tile_atlas.apply_brush(stroke(0.2, 2.0, TerrainBrushMode::Raise), &images);
+
+        assert_eq!(sample(&tile_atlas, 2, 2), 2.0);
+        assert_eq!(sample(&tile_atlas, 3, 2), 1.0);
+
+        // the edits are mirrored on a copy, while the image itself is only edited on the GPU
+        assert!(tile_atlas.height_overlay.as_ref().unwrap().is_edited());
+        assert_eq!(
+            images.get(&overlay).unwrap().data[48..52],
+            4.0f32.to_le_bytes()
+        );
+        assert_eq!(tile_atlas.brush_strokes.len(), 2);
+    }
+
+    #[test]
+    fn heightmap_samples_are_remapped_like_the_atlas() {
+        let size = Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        };
+        // the height is stored in the green channel, while the other channels are ignored
+        let heightmap = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            bytemuck::cast_slice(&[0.1f32, 0.5, 0.9, 1.0]),
+            TextureFormat::Rgba32Float,
+            default(),
+        );
+        let mut images = Assets::<Image>::default();
+        let overlay = images.add(Image::new_fill(
+            size,
+            TextureDimension::D2,
+            bytemuck::bytes_of(&3.0f32),
+            TextureFormat::R32Float,
+            default(),
+        ));
+
+        let mut tile_atlas = TileAtlas::new(&TerrainConfig {
+            model: TerrainModel::planar(DVec3::ZERO, 1.0, 0.0, 100.0),
+            height_channel: 1,
+            height_scale: 2.0,
+            height_offset: 10.0,
+            height_gamma: 2.0,
+            ..default()
+        })
+        .unwrap();
+        let coordinate = Coordinate::new(0, DVec2::new(0.3, 0.6));
+
+        // lerp(10, 210, 0.5²)
+        assert_eq!(
+            tile_atlas.sample_heightmap(coordinate, &heightmap, &images),
+            60.0
+        );
+
+        tile_atlas.height_floor = Some(100.0);
+        assert_eq!(
+            tile_atlas.sample_heightmap(coordinate, &heightmap, &images),
+            100.0
+        );
+
+        tile_atlas.height_overlay = Some(TerrainHeightOverlay::new(overlay, 0.0, 3.0));
+        assert_eq!(
+            tile_atlas.sample_heightmap(coordinate, &heightmap, &images),
+            103.0
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "high_precision"))]
+    fn moving_the_entity_moves_the_model() {
+        let model = TerrainModel::planar(DVec3::ZERO, 2.0, 0.0, 1.0);
+        let tile_atlas = TileAtlas::new(&TerrainConfig {
+            model: model.clone(),
+            ..default()
+        })
+        .unwrap();
+
+        let mut world = World::new();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(TileAtlas::sync_model_transform);
+
+        let terrain = world
+            .spawn((model.transform(), tile_atlas, Aabb::default()))
+            .id();
+
+        // the transform derived from the model does not rebuild it
+        schedule.run(&mut world);
+        assert_eq!(*world.get::<Aabb>(terrain).unwrap(), Aabb::default());
+
+        *world.get_mut::<Transform>(terrain).unwrap() = Transform::from_xyz(10.0, 0.0, -5.0)
+            .with_rotation(Quat::from_rotation_y(1.0))
+            .with_scale(Vec3::splat(4.0));
+        schedule.run(&mut world);
+
+        let tile_atlas = world.get::<TileAtlas>(terrain).unwrap();
+        assert_eq!(tile_atlas.model.translation(), DVec3::new(10.0, 0.0, -5.0));
+        assert_eq!(
+            tile_atlas.model.rotation(),
+            Quat::from_rotation_y(1.0).as_dquat()
+        );
+        assert_eq!(tile_atlas.model.scale_vec(), DVec3::splat(4.0));
+        assert_eq!(
+            *world.get::<Aabb>(terrain).unwrap(),
+            tile_atlas.model.aabb(0.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{
+    formats::tiff::{decode_tiff, TiffLoaderSettings},
+    math::TileCoordinate,
+    terrain_data::{tile_atlas::STORE_PNG, AttachmentFormat},
+};
+use anyhow::{anyhow, ensure, Result};
+use image::ImageReader;
+use std::{fs, sync::Arc};
+
+/// A source, from which the tiles of the terrain attachments are streamed into the [`TileAtlas`](super::tile_atlas::TileAtlas).
+///
+/// Implement this to fetch tiles from somewhere else than the preprocessed files on disk, e.g. from an HTTP tile server.
+/// Fetched TIFF tiles can be decoded with [`decode_tiff`](crate::formats::tiff::decode_tiff).
+// Todo: provide an HTTP implementation, once an HTTP client is part of the dependencies
+pub trait TileSource: Send + Sync + 'static {
+    /// Loads the raw texel data of the tile of the attachment stored at the path.
+    ///
+    /// The data has to consist of `texture_size` × `texture_size` texels of the attachment format.
+    /// This is called on the async compute task pool, so it may block.
+    fn load_tile(
+        &self,
+        path: &str,
+        coordinate: TileCoordinate,
+        texture_size: u32,
+        format: AttachmentFormat,
+    ) -> Result<Vec<u8>>;
+}
+
+/// The default [`TileSource`], which reads the tiles written by the preprocessor from disk.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileTileSource;
+
+impl TileSource for FileTileSource {
+    fn load_tile(
+        &self,
+        path: &str,
+        coordinate: TileCoordinate,
+        _texture_size: u32,
+        _format: AttachmentFormat,
+    ) -> Result<Vec<u8>> {
+        if STORE_PNG {
+            let path = coordinate.path(path, "png");
+
+            let mut reader = ImageReader::open(path)?;
+            reader.no_limits();
+            let image = reader.decode()?;
+            Ok(image.as_bytes().to_vec())
+        } else {
+            let path = coordinate.path(path, "bin");
+
+            Ok(fs::read(path)?)
+        }
+    }
+}
+
+/// A [`TileSource`] of spherical terrains, which forwards the tiles of each cube side to a separate source.
+///
+/// This streams datasets, which consist of one independent image per cube face (e.g. a cube map DEM).
+/// The sources are indexed like the [`SIDE_MATRICES`](crate::math::SIDE_MATRICES).
+#[derive(Clone)]
+pub struct SideTileSource {
+    /// The sources of the six sides, ordered by their side index.
+    pub sources: [Arc<dyn TileSource>; 6],
+}
+
+impl SideTileSource {
+    pub fn new(sources: [Arc<dyn TileSource>; 6]) -> Self {
+        Self { sources }
+    }
+}
+
+impl TileSource for SideTileSource {
+    fn load_tile(
+        &self,
+        path: &str,
+        coordinate: TileCoordinate,
+        texture_size: u32,
+        format: AttachmentFormat,
+    ) -> Result<Vec<u8>> {
+        let source = self.sources.get(coordinate.side as usize).ok_or_else(|| {
+            anyhow!(
+                "the tile {coordinate} lies on the side {}, but only six sides have a source",
+                coordinate.side
+            )
+        })?;
+
+        source.load_tile(path, coordinate, texture_size, format)
+    }
+}
+
+/// A [`TileSource`], which streams the tiles from a directory of pre-tiled TIFF files, instead of the preprocessed tiles.
+///
+/// The file of each tile is named by the pattern, where the placeholders `{path}`, `{side}`, `{lod}`, `{x}` and `{y}`
+/// are replaced by the path of the attachment and the coordinate of the tile, e.g. `{path}/{lod}/tile_{x}_{y}.tif`.
+/// The tiles of each lod form a grid of `2^lod` × `2^lod` tiles per side, where `x` and `y` start at zero in the top left corner,
+/// so lod zero consists of a single tile and each finer lod splits every tile into four.
+/// The tiles have to cover every lod of the terrain, since missing files are reported as errors.
+///
+/// The tiles are decoded with [`decode_tiff`], so they have to store the texels of the attachment format
+/// and match its texture size, including the border, which repeats the texels of the adjacent tiles.
+/// Tiles of a different format or size are reported as errors.
+#[derive(Clone, Debug)]
+pub struct NumberedTileSource {
+    /// The naming pattern of the tile files.
+    pub pattern: String,
+    /// The settings used to decode the tiles.
+    pub settings: TiffLoaderSettings,
+}
+
+impl NumberedTileSource {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            settings: TiffLoaderSettings::default(),
+        }
+    }
+
+    /// Maps the coordinate of the tile to the path of its file.
+    pub fn tile_path(&self, path: &str, coordinate: TileCoordinate) -> String {
+        self.pattern
+            .replace("{path}", path)
+            .replace("{side}", &coordinate.side.to_string())
+            .replace("{lod}", &coordinate.lod.to_string())
+            .replace("{x}", &coordinate.x.to_string())
+            .replace("{y}", &coordinate.y.to_string())
+    }
+}
+
+impl TileSource for NumberedTileSource {
+    fn load_tile(
+        &self,
+        path: &str,
+        coordinate: TileCoordinate,
+        texture_size: u32,
+        format: AttachmentFormat,
+    ) -> Result<Vec<u8>> {
+        let tile_path = self.tile_path(path, coordinate);
+
+        let image = decode_tiff(fs::read(&tile_path)?, self.settings)?;
+
+        // Tiles of a different size or format would be read out of bounds, while generating the mip chain.
+        ensure!(
+            image.texture_descriptor.format == format.render_format(),
+            "the tile {tile_path} has the format {:?}, but the attachment requires {:?}",
+            image.texture_descriptor.format,
+            format.render_format()
+        );
+        ensure!(
+            image.width() == texture_size && image.height() == texture_size,
+            "the tile {tile_path} has the size {}x{}, but the attachment requires {texture_size}x{texture_size}",
+            image.width(),
+            image.height()
+        );
+
+        let byte_count = (texture_size * texture_size * format.pixel_size()) as usize;
+
+        ensure!(
+            image.data.len() == byte_count,
+            "the tile {tile_path} contains {} bytes, but the {format:?} attachment requires {byte_count}",
+            image.data.len()
+        );
+
+        Ok(image.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the side and the path of the requested tile, to identify the source.
+    struct MarkerSource(u8);
+
+    impl TileSource for MarkerSource {
+        fn load_tile(
+            &self,
+            path: &str,
+            coordinate: TileCoordinate,
+            _texture_size: u32,
+            _format: AttachmentFormat,
+        ) -> Result<Vec<u8>> {
+            ensure!(coordinate.side == self.0 as u32);
+
+            Ok([&[self.0], path.as_bytes()].concat())
+        }
+    }
+
+    #[test]
+    fn side_source_forwards_to_the_source_of_the_side() {
+        let source = SideTileSource::new(
+            [0, 1, 2, 3, 4, 5].map(|side| Arc::new(MarkerSource(side)) as Arc<dyn TileSource>),
+        );
+
+        for side in 0..6 {
+            let coordinate = TileCoordinate::new(side, 3, 5, 2);
+            let data = source
+                .load_tile("height", coordinate, 4, AttachmentFormat::R16)
+                .unwrap();
+
+            assert_eq!(data, [&[side as u8], "height".as_bytes()].concat());
+        }
+
+        assert!(source
+            .load_tile(
+                "height",
+                TileCoordinate::new(6, 0, 0, 0),
+                4,
+                AttachmentFormat::R16
+            )
+            .is_err());
+    }
+}
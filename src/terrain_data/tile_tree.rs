@@ -1,13 +1,18 @@
 use crate::{
+    debug::DebugTerrain,
     math::{Coordinate, TerrainModel, TileCoordinate},
-    terrain_data::{sample_height, tile_atlas::TileAtlas, INVALID_ATLAS_INDEX, INVALID_LOD},
-    terrain_view::{TerrainViewComponents, TerrainViewConfig},
+    terrain_data::{
+        sample_resident_height, tile_atlas::TileAtlas, INVALID_ATLAS_INDEX, INVALID_LOD,
+    },
+    terrain_view::{TerrainViewComponents, TerrainViewConfig, TerrainViewConfigError},
     util::inverse_mix,
 };
 use bevy::{
     math::{DVec2, DVec3},
     prelude::*,
+    render::primitives::{Aabb, Frustum},
 };
+use bincode::{Decode, Encode};
 use bytemuck::{Pod, Zeroable};
 use itertools::iproduct;
 use ndarray::{Array2, Array4};
@@ -53,6 +58,12 @@ pub(super) struct TileTreeEntry {
     pub(super) atlas_index: u32,
     /// The atlas lod of the best entry.
     pub(super) atlas_lod: u32,
+    /// The normalized minimum height of the best entry.
+    pub(super) min_height: f32,
+    /// The normalized maximum height of the best entry.
+    pub(super) max_height: f32,
+    /// The progress of the fade-in of the best entry, from zero right after it was loaded to one.
+    pub(super) fade: f32,
 }
 
 impl Default for TileTreeEntry {
@@ -60,6 +71,9 @@ impl Default for TileTreeEntry {
         Self {
             atlas_index: INVALID_ATLAS_INDEX,
             atlas_lod: INVALID_LOD,
+            min_height: 0.0,
+            max_height: 1.0,
+            fade: 1.0,
         }
     }
 }
@@ -80,6 +94,63 @@ impl TileLookup {
     };
 }
 
+/// The tiles requested by a [`TileTree`], e.g. to prewarm the streaming on level loads or for deterministic replays.
+///
+/// It can be stored like the tile config, since it is encoded with bincode.
+#[derive(Clone, Debug, Default, Encode, Decode)]
+pub struct TileTreeState {
+    pub tiles: Vec<TileCoordinate>,
+}
+
+/// The count of rows and columns of the grid of screen cells, into which the lods of the tiling prepass are read back.
+pub const SCREEN_LOD_GRID_SIZE: u32 = 16;
+
+/// The lods selected by the tiling prepass per cell of a grid across the screen of a view,
+/// see [`TerrainViewConfig::screen_lod_readback`].
+///
+/// Each cell stores the finest lod of the tiles, whose screen space bounds overlap it.
+#[derive(Clone, Debug, Default)]
+pub struct ScreenLods {
+    /// The lods of the cells in row-major order, starting at the top left corner of the screen.
+    /// Cells, which are not covered by any tile, store `None`.
+    pub lods: Vec<Option<u32>>,
+}
+
+impl ScreenLods {
+    pub(crate) fn from_cells(cells: &[u32]) -> Self {
+        // the cells store the lods offset by one, so that zero marks cells without any tile
+        Self {
+            lods: cells.iter().map(|&cell| cell.checked_sub(1)).collect(),
+        }
+    }
+
+    /// Returns the lod of the cell at the screen uv, where (0, 0) is the top left and (1, 1) the bottom right corner.
+    pub fn get(&self, uv: Vec2) -> Option<u32> {
+        let cell = (uv * SCREEN_LOD_GRID_SIZE as f32)
+            .as_uvec2()
+            .min(UVec2::splat(SCREEN_LOD_GRID_SIZE - 1));
+
+        self.lods
+            .get((cell.y * SCREEN_LOD_GRID_SIZE + cell.x) as usize)
+            .copied()
+            .flatten()
+    }
+}
+
+/// The maximum count of refinement iterations, whose tile counts are read back, see [`TileCounts`].
+pub const MAX_TILE_COUNT_ITERATIONS: usize = 32;
+
+/// The counts of tiles produced by the tiling prepass of a view, see [`TerrainViewConfig::tile_count_readback`].
+#[derive(Clone, Debug, Default)]
+pub struct TileCounts {
+    /// The count of tiles emitted for rendering, including the ones beyond the geometry tile count, which are dropped.
+    pub final_tile_count: u32,
+    /// The count of tiles, into which each refinement iteration subdivided, up to the last one subdividing any tile.
+    ///
+    /// Only the first [`MAX_TILE_COUNT_ITERATIONS`] iterations are recorded.
+    pub iteration_tile_counts: Vec<u32>,
+}
+
 /// A quadtree-like view of a terrain, that requests and releases tiles from the [`TileAtlas`]
 /// depending on the distance to the viewer.
 ///
@@ -90,6 +161,10 @@ impl TileLookup {
 ///
 /// Each view (camera, shadow-casting light) that should consider the terrain has to
 /// have an associated tile tree.
+/// The tile trees only reference the tiles of the [`TileAtlas`] of the terrain, which is shared by all views.
+/// Per view, the tile tree (`tree_size`² entries per lod and side) and the tile buffers of the
+/// refinement (32 bytes per geometry tile, see [`TerrainViewConfig`]) are allocated.
+/// Views that are close to each other request the same tiles, which are then only loaded once.
 ///
 /// This tile tree is a "cube" with a size of (`tree_size`x`tree_size`x`lod_count`), where each layer
 /// corresponds to a lod. These layers are wrapping (modulo `tree_size`), that means that
@@ -109,8 +184,16 @@ pub struct TileTree {
     pub(super) released_tiles: Vec<TileCoordinate>,
     /// Tiles that are requested to be loaded by this tile_tree.
     pub(super) requested_tiles: Vec<TileCoordinate>,
+    /// Tiles that are queued by [`TileTree::request_prewarm`], but not requested yet.
+    queued_prewarm_tiles: Vec<TileCoordinate>,
+    /// Tiles that are requested by [`TileTree::request_prewarm`], until they have been loaded.
+    prewarm_tiles: Vec<TileCoordinate>,
     /// The internal tile states of the tile_tree.
     tiles: Array4<TileState>,
+    /// The count of requested tiles, as of the last adjustment to the tile atlas.
+    requested_tile_count: u32,
+    /// The count of requested tiles, which have been loaded, as of the last adjustment to the tile atlas.
+    loaded_tile_count: u32,
     /// The count of level of detail layers.
     lod_count: u32,
     /// The count of tiles in x and y direction per layer.
@@ -118,6 +201,8 @@ pub struct TileTree {
     pub(crate) geometry_tile_count: u32,
     pub(crate) refinement_count: u32,
     pub(crate) grid_size: u32,
+    /// Whether the tiles are surrounded by skirts, which adds a ring of vertices to the grid.
+    pub(crate) skirts: bool,
     pub(crate) morph_distance: f64,
     pub(crate) blend_distance: f64,
     pub(crate) load_distance: f64,
@@ -126,22 +211,56 @@ pub struct TileTree {
     pub(crate) morph_range: f32,
     pub(crate) blend_range: f32,
     pub(crate) origin_lod: u32,
+    pub(crate) origin_hysteresis: f64,
+    pub(crate) culling_margin: f32,
+    pub(crate) lod_bias: f32,
+    pub(crate) lod_distances: Option<Vec<f64>>,
+    pub(crate) force_lod: Option<u32>,
+    pub(crate) target_pixel_error: Option<f32>,
+    pub(crate) priority: i32,
+    pub(crate) screen_lod_readback: bool,
+    /// The lods of the last tiling prepass, which has been read back.
+    pub(crate) screen_lods: Option<ScreenLods>,
+    pub(crate) tile_count_readback: bool,
+    /// The tile counts of the last tiling prepass, which has been read back.
+    pub(crate) tile_counts: Option<TileCounts>,
+    /// The count of refinement iterations used by the last tiling prepass, which has been read back.
+    pub(crate) used_refinement_count: Option<u32>,
+    /// The pixel, at which the drawn tile is picked, see [`TileTree::pick_tile`].
+    pub(crate) pick_position: Option<UVec2>,
+    /// The tile drawn at the picked pixel, which has been read back.
+    pub(crate) picked_tile: Option<(UVec2, TileCoordinate)>,
     pub(crate) view_world_position: DVec3,
     pub(crate) approximate_height: f32,
+    /// The pinned approximate height, which replaces the sampled height, see [`TileTree::pin_approximate_height`].
+    pub(crate) pinned_approximate_height: Option<f32>,
+    /// The altitude of the view above the sampled surface of the terrain.
+    pub(crate) altitude_above_surface: Option<f64>,
+    /// Whether the bounds of the terrain intersect the frustum of the view.
+    /// Otherwise, the tiling prepass of the view is skipped entirely.
+    pub(crate) visible: bool,
 }
 
 impl TileTree {
     /// Creates a new tile_tree from a terrain and a terrain view config.
-    pub fn new(tile_atlas: &TileAtlas, view_config: &TerrainViewConfig) -> Self {
+    ///
+    /// Returns the violated invariant, if the view config is invalid (see [`TerrainViewConfig::validate`]).
+    pub fn new(
+        tile_atlas: &TileAtlas,
+        view_config: &TerrainViewConfig,
+    ) -> Result<Self, TerrainViewConfigError> {
+        view_config.validate()?;
+
         let model = &tile_atlas.model;
         let scale = model.scale();
 
-        Self {
+        Ok(Self {
             lod_count: tile_atlas.lod_count,
             tree_size: view_config.tree_size,
             geometry_tile_count: view_config.geometry_tile_count,
             refinement_count: view_config.refinement_count,
             grid_size: view_config.grid_size,
+            skirts: tile_atlas.skirt_depth.is_some(),
             morph_distance: view_config.morph_distance * scale,
             blend_distance: view_config.blend_distance * scale,
             load_distance: view_config.load_distance * scale,
@@ -152,8 +271,27 @@ impl TileTree {
             blend_range: view_config.blend_range,
             precision_threshold_distance: view_config.precision_threshold_distance * scale,
             origin_lod: view_config.origin_lod,
+            origin_hysteresis: view_config.origin_hysteresis,
+            culling_margin: view_config.culling_margin,
+            lod_bias: view_config.lod_bias,
+            lod_distances: view_config.lod_distances.clone(),
+            force_lod: view_config.force_lod,
+            target_pixel_error: view_config.target_pixel_error,
+            priority: view_config.priority,
+            screen_lod_readback: view_config.screen_lod_readback,
+            screen_lods: None,
+            tile_count_readback: view_config.tile_count_readback,
+            tile_counts: None,
+            used_refinement_count: None,
+            pick_position: None,
+            picked_tile: None,
             view_world_position: default(),
-            approximate_height: (model.min_height + model.max_height) / 2.0,
+            approximate_height: view_config
+                .approximate_height
+                .unwrap_or((tile_atlas.min_height() + tile_atlas.max_height()) / 2.0),
+            pinned_approximate_height: view_config.approximate_height,
+            altitude_above_surface: None,
+            visible: true,
             origins: Array2::default((model.side_count() as usize, tile_atlas.lod_count as usize)),
             data: Array4::default((
                 model.side_count() as usize,
@@ -169,7 +307,11 @@ impl TileTree {
             )),
             released_tiles: default(),
             requested_tiles: default(),
-        }
+            queued_prewarm_tiles: default(),
+            prewarm_tiles: default(),
+            requested_tile_count: 0,
+            loaded_tile_count: 0,
+        })
     }
 
     fn compute_tree_xy(coordinate: Coordinate, tile_count: f64) -> DVec2 {
@@ -236,6 +378,120 @@ impl TileTree {
         (lod, ratio)
     }
 
+    /// Overrides the approximate height of the terrain below the view, e.g. after spawning or teleporting the view.
+    ///
+    /// The height positions the terrain relative to the view, while no tile below the view is loaded yet
+    /// (e.g. during a loading screen). It is superseded by the sampled height, once such a tile has been loaded.
+    pub fn set_approximate_height(&mut self, height: f32) {
+        self.approximate_height = height;
+    }
+
+    /// Pins the approximate height of the terrain below the view, see [`TerrainViewConfig::approximate_height`].
+    ///
+    /// Resetting it to `None` samples the height from the loaded tiles again.
+    pub fn pin_approximate_height(&mut self, height: Option<f32>) {
+        self.pinned_approximate_height = height;
+
+        if let Some(height) = height {
+            self.approximate_height = height;
+        }
+    }
+
+    /// Sets the lod of the origin tiles, see [`TerrainViewConfig::origin_lod`].
+    pub fn set_origin_lod(&mut self, origin_lod: u32) {
+        self.origin_lod = origin_lod;
+    }
+
+    /// Sets the maximum projected edge length in pixels of the grid cells, see [`TerrainViewConfig::target_pixel_error`].
+    ///
+    /// Resetting it to `None` keeps the distances derived last.
+    pub fn set_target_pixel_error(&mut self, target_pixel_error: Option<f32>) {
+        self.target_pixel_error = target_pixel_error;
+    }
+
+    /// Records the tiles, which are currently requested by this tile tree.
+    pub fn snapshot(&self) -> TileTreeState {
+        TileTreeState {
+            tiles: self
+                .tiles
+                .iter()
+                .filter(|tile| tile.state == RequestState::Requested)
+                .map(|tile| tile.coordinate)
+                .collect(),
+        }
+    }
+
+    /// Queues the recorded tiles for loading, e.g. during a loading screen, instead of streaming them in from scratch.
+    ///
+    /// The tiles stay requested until they have been loaded. Afterward, they are only kept cached in the
+    /// [`TileAtlas`], until the tile tree requests them again or they are evicted.
+    pub fn request_prewarm(&mut self, state: &TileTreeState) {
+        self.queued_prewarm_tiles.extend(&state.tiles);
+    }
+
+    /// The fraction of the tiles requested by this tile tree, which are loaded, in the range of [0, 1].
+    ///
+    /// Use this to display the loading progress, e.g. after a teleport or at startup.
+    /// Tiles that do not exist in the dataset count as loaded, and the progress is one, if no tiles are requested.
+    pub fn load_progress(&self) -> f32 {
+        if self.requested_tile_count == 0 {
+            1.0
+        } else {
+            self.loaded_tile_count as f32 / self.requested_tile_count as f32
+        }
+    }
+
+    /// The lods selected by the tiling prepass per screen cell, if the readback is enabled in the [`TerrainViewConfig`].
+    ///
+    /// The readback is asynchronous, so the lods lag a few frames behind and are `None` until the first one finished.
+    pub fn screen_lods(&self) -> Option<&ScreenLods> {
+        self.screen_lods.as_ref()
+    }
+
+    /// The tile counts of the last tiling prepass, if the readback is enabled in the [`TerrainViewConfig`].
+    ///
+    /// The readback is asynchronous, so the counts lag a few frames behind and are `None` until the first one finished.
+    pub fn tile_counts(&self) -> Option<&TileCounts> {
+        self.tile_counts.as_ref()
+    }
+
+    /// The count of refinement iterations of the last tiling prepass, which subdivided at least one tile.
+    ///
+    /// This is only read back while the [`DebugTerrain`] resource exists or the tile counts are read back,
+    /// and is `None` until the first readback finished.
+    /// If it is lower than the refinement count of the [`TerrainViewConfig`], the remaining iterations were idle
+    /// and the refinement count can be reduced. If both are equal, the refinement may have been cut short.
+    pub fn used_refinement_count(&self) -> Option<u32> {
+        self.used_refinement_count
+    }
+
+    /// Picks the geometry tile drawn at the pixel of the view, e.g. to find the tile causing an artifact.
+    ///
+    /// The pixel is given in physical pixels from the top left corner of the viewport.
+    /// This only works while [`DebugTerrain::tile_picking`](crate::debug::DebugTerrain::tile_picking) is enabled.
+    /// The tile is read back asynchronously, thus this returns `None` for the first few frames after the pixel changed,
+    /// as well as if no tile is drawn there. Call it every frame, while the pixel should be picked.
+    pub fn pick_tile(&mut self, pixel: UVec2) -> Option<TileCoordinate> {
+        self.pick_position = Some(pixel);
+
+        self.picked_tile
+            .filter(|&(picked_pixel, _)| picked_pixel == pixel)
+            .map(|(_, tile)| tile)
+    }
+
+    /// The altitude of the view above the surface of the terrain, including its heights, e.g. for HUDs or auto-pilots.
+    ///
+    /// It is measured along the surface normal of the terrain model below the view, so it is negative below the surface.
+    /// The height is sampled from the best resident tile under the view, thus this is `None` until such a tile is loaded.
+    pub fn altitude_above_surface(&self) -> Option<f64> {
+        self.altitude_above_surface
+    }
+
+    /// The factor, by which the lod bias scales the subdivision and morph distances.
+    pub(crate) fn lod_bias_scale(&self) -> f64 {
+        2.0_f64.powf(-self.lod_bias as f64)
+    }
+
     pub(super) fn lookup_tile(
         &self,
         world_position: DVec3,
@@ -269,9 +525,24 @@ impl TileTree {
         let model = &tile_atlas.model;
         self.view_world_position = view_position;
 
+        // the prewarm tiles have been requested during a previous update of the tile atlas
+        let released_tiles = &mut self.released_tiles;
+        self.prewarm_tiles.retain(|&tile_coordinate| {
+            let loaded = tile_atlas.is_loaded(tile_coordinate);
+
+            if loaded {
+                released_tiles.push(tile_coordinate);
+            }
+
+            !loaded
+        });
+
+        self.requested_tiles.extend(&self.queued_prewarm_tiles);
+        self.prewarm_tiles.append(&mut self.queued_prewarm_tiles);
+
         let view_coordinate = Coordinate::from_world_position(self.view_world_position, model);
 
-        for side in 0..model.side_count() {
+        for side in model.active_sides() {
             let view_coordinate = view_coordinate.project_to_side(side, model);
 
             for lod in 0..tile_atlas.lod_count {
@@ -291,7 +562,9 @@ impl TileTree {
                     let load_distance =
                         self.load_distance / TileCoordinate::count(tile_coordinate.lod) as f64;
 
-                    let state = if lod == 0 || tile_distance < load_distance {
+                    let below_max_lod = tile_atlas.max_lod.map_or(true, |max_lod| lod <= max_lod);
+
+                    let state = if lod == 0 || (tile_distance < load_distance && below_max_lod) {
                         RequestState::Requested
                     } else {
                         RequestState::Released
@@ -334,6 +607,11 @@ impl TileTree {
 
     /// Traverses all tile_trees and updates the tile states,
     /// while selecting newly requested and released tiles.
+    ///
+    /// With the `high_precision` feature, the view position is read in double precision from its grid cell and translation,
+    /// which is independent of the floating origin. This runs in `Last`, after big_space has recentered the grid cells
+    /// and propagated the global transforms in `PostUpdate`, so the approximation derived from it is consistent
+    /// with the rebased transforms extracted for rendering in the same frame.
     pub(crate) fn compute_requests(
         mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
         tile_atlases: Query<&TileAtlas>,
@@ -358,6 +636,29 @@ impl TileTree {
         }
     }
 
+    /// Culls the terrains as a whole, by testing their bounds against the frustum of each view.
+    ///
+    /// This is a coarse test before the tiles are culled in the tiling prepass,
+    /// which saves the prepass of terrains outside of the view (e.g. distant planets).
+    /// Views without a frustum are never culled.
+    pub(crate) fn compute_visibility(
+        debug: Option<Res<DebugTerrain>>,
+        mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
+        terrains: Query<(&Aabb, &GlobalTransform)>,
+        frusta: Query<&Frustum>,
+    ) {
+        let frustum_culling = debug.map_or(true, |debug| debug.frustum_culling);
+
+        for (&(terrain, view), tile_tree) in tile_trees.iter_mut() {
+            tile_tree.visible = match (terrains.get(terrain), frusta.get(view)) {
+                (Ok((aabb, transform)), Ok(frustum)) if frustum_culling => {
+                    frustum.intersects_obb(aabb, &transform.affine(), true, false)
+                }
+                _ => true,
+            };
+        }
+    }
+
     /// Adjusts all tile_trees to their corresponding tile atlas
     /// by updating the entries with the best available tiles.
     pub(crate) fn adjust_to_tile_atlas(
@@ -367,9 +668,22 @@ impl TileTree {
         for (&(terrain, _view), tile_tree) in tile_trees.iter_mut() {
             let tile_atlas = tile_atlases.get(terrain).unwrap();
 
+            let (mut requested_tile_count, mut loaded_tile_count) = (0, 0);
+
             for (tile, entry) in iter::zip(&tile_tree.tiles, &mut tile_tree.data) {
                 *entry = tile_atlas.get_best_tile(tile.coordinate);
+
+                if tile.state == RequestState::Requested {
+                    requested_tile_count += 1;
+
+                    if tile_atlas.is_loaded(tile.coordinate) {
+                        loaded_tile_count += 1;
+                    }
+                }
             }
+
+            tile_tree.requested_tile_count = requested_tile_count;
+            tile_tree.loaded_tile_count = loaded_tile_count;
         }
     }
 
@@ -380,8 +694,214 @@ impl TileTree {
         for (&(terrain, _view), tile_tree) in tile_trees.iter_mut() {
             let tile_atlas = tile_atlases.get(terrain).unwrap();
 
-            tile_tree.approximate_height =
-                sample_height(tile_tree, tile_atlas, tile_tree.view_world_position);
+            // keep the previous height, until a tile under the view has been loaded
+            if let Some(height) =
+                sample_resident_height(tile_tree, tile_atlas, tile_tree.view_world_position)
+            {
+                tile_tree.approximate_height =
+                    tile_tree.pinned_approximate_height.unwrap_or(height);
+
+                let model = &tile_atlas.model;
+                let coordinate =
+                    Coordinate::from_world_position(tile_tree.view_world_position, model);
+                let surface_position = coordinate.world_position(model, height);
+                let surface_normal =
+                    (coordinate.world_position(model, height + 1.0) - surface_position).normalize();
+
+                tile_tree.altitude_above_surface =
+                    Some((tile_tree.view_world_position - surface_position).dot(surface_normal));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{
+            compute_view_coordinates, generate_terrain_model_approximation,
+            TerrainModelApproximation,
+        },
+        terrain::TerrainConfig,
+    };
+
+    #[test]
+    fn approximates_every_terrain_of_view() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<TerrainViewComponents<TileTree>>()
+            .init_resource::<TerrainViewComponents<TerrainModelApproximation>>()
+            .init_resource::<TerrainViewComponents<Coordinate>>()
+            .add_systems(
+                Update,
+                (
+                    generate_terrain_model_approximation,
+                    compute_view_coordinates,
+                ),
+            );
+
+        // a planar terrain next to a planet, which are both in front of the same view
+        let view = app.world_mut().spawn_empty().id();
+        let view_world_position = DVec3::new(100.0, 50.0, 30.0);
+        let planet_position = DVec3::new(0.0, -2000.0, 0.0);
+
+        let terrains = [
+            TerrainModel::planar(DVec3::ZERO, 1000.0, 0.0, 10.0),
+            TerrainModel::sphere(planet_position, 1000.0, 0.0, 10.0),
+        ]
+        .map(|model| {
+            let tile_atlas = TileAtlas::new(&TerrainConfig { model, ..default() }).unwrap();
+            let mut tile_tree = TileTree::new(&tile_atlas, &TerrainViewConfig::default()).unwrap();
+            tile_tree.view_world_position = view_world_position;
+
+            let terrain = app.world_mut().spawn(tile_atlas).id();
+            app.world_mut()
+                .resource_mut::<TerrainViewComponents<TileTree>>()
+                .insert((terrain, view), tile_tree);
+
+            terrain
+        });
+
+        app.update();
+
+        let approximations = app
+            .world()
+            .resource::<TerrainViewComponents<TerrainModelApproximation>>();
+        let view_coordinates = app.world().resource::<TerrainViewComponents<Coordinate>>();
+
+        let [plane, planet] = terrains;
+        let [plane_approximation, planet_approximation] =
+            terrains.map(|terrain| approximations.get(&(terrain, view)).unwrap());
+
+        // the plane only uses its first side, whose constant coefficient points to the surface below the view
+        assert!(plane_approximation.sides()[0]
+            .c
+            .abs_diff_eq(Vec3::new(0.0, -50.0, 0.0), 1e-4));
+        assert!(plane_approximation.sides()[1..]
+            .iter()
+            .all(|side| side.c_s == Vec3::ZERO));
+
+        // the view is above the top side (two) of the planet, whose surface is projected radially
+        let relative_position = view_world_position - planet_position;
+        let surface_offset = -relative_position * (1.0 - 1000.0 / relative_position.length());
+
+        assert!(planet_approximation.sides()[2]
+            .c
+            .abs_diff_eq(surface_offset.as_vec3(), 1e-3));
+        assert!(planet_approximation
+            .sides()
+            .iter()
+            .all(|side| side.c_s != Vec3::ZERO));
+
+        let plane_coordinate = view_coordinates[&(plane, view)];
+        assert_eq!(plane_coordinate.side, 0);
+        assert!(plane_coordinate
+            .uv
+            .abs_diff_eq(DVec2::new(0.6, 0.53), 1e-12));
+
+        assert_eq!(view_coordinates[&(planet, view)].side, 2);
+    }
+
+    #[cfg(feature = "high_precision")]
+    #[test]
+    fn approximation_is_stable_across_origin_rebase() {
+        use crate::{
+            big_space::{
+                BigSpaceCommands, BigSpacePlugin, FloatingOrigin, GridCell, ReferenceFrame,
+            },
+            terrain::TerrainBundle,
+        };
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, BigSpacePlugin::default()))
+            .init_resource::<TerrainViewComponents<TileTree>>()
+            .init_resource::<TerrainViewComponents<TerrainModelApproximation>>()
+            .add_systems(
+                Last,
+                (
+                    TileTree::compute_requests,
+                    generate_terrain_model_approximation,
+                )
+                    .chain(),
+            );
+
+        let radius = 6_371_000.0;
+        let tile_atlas = TileAtlas::new(&TerrainConfig {
+            model: TerrainModel::sphere(DVec3::new(1.0e7, 0.0, 0.0), radius, 0.0, 1.0),
+            ..default()
+        })
+        .unwrap();
+        let tile_tree = TileTree::new(&tile_atlas, &TerrainViewConfig::default()).unwrap();
+
+        // the view hovers about one kilometer above the planet, close to the edge of its grid cell
+        let mut entities = None;
+        app.world_mut()
+            .commands()
+            .spawn_big_space(ReferenceFrame::new(2000.0, 100.0), |root| {
+                let frame = root.frame().clone();
+                let terrain = root
+                    .spawn_spatial(TerrainBundle::new(tile_atlas, &frame))
+                    .id();
+                let view = root
+                    .spawn_spatial((
+                        FloatingOrigin,
+                        GridCell::new(5000, 3186, 0),
+                        Transform::from_xyz(1050.0, 10.0, 0.0),
+                    ))
+                    .id();
+
+                entities = Some((terrain, view));
+            });
+        app.world_mut().flush();
+
+        let (terrain, view) = entities.unwrap();
+        app.world_mut()
+            .resource_mut::<TerrainViewComponents<TileTree>>()
+            .insert((terrain, view), tile_tree);
+
+        app.update();
+        app.update();
+
+        let before = app
+            .world()
+            .resource::<TerrainViewComponents<TerrainModelApproximation>>()[&(terrain, view)]
+            .clone();
+        let terrain_before = app
+            .world()
+            .get::<GlobalTransform>(terrain)
+            .unwrap()
+            .translation();
+
+        // rebase the floating origin to the adjacent cell, while the view keeps its double precision position
+        let mut view_entity = app.world_mut().entity_mut(view);
+        *view_entity.get_mut::<GridCell>().unwrap() = GridCell::new(5001, 3186, 0);
+        view_entity.get_mut::<Transform>().unwrap().translation = Vec3::new(-950.0, 10.0, 0.0);
+
+        app.update();
+
+        let after = &app
+            .world()
+            .resource::<TerrainViewComponents<TerrainModelApproximation>>()[&(terrain, view)];
+        let terrain_after = app
+            .world()
+            .get::<GlobalTransform>(terrain)
+            .unwrap()
+            .translation();
+
+        // the rendered transforms are shifted by one cell, while the approximation in world space is unchanged
+        assert_eq!(terrain_after - terrain_before, Vec3::new(-2000.0, 0.0, 0.0));
+        assert_eq!(before.origin_lod(), after.origin_lod());
+        assert_eq!(before.approximate_height(), after.approximate_height());
+
+        for (side, (a, b)) in iter::zip(before.sides(), after.sides()).enumerate() {
+            assert_eq!(a.origin_xy, b.origin_xy, "origin tile of side {side}");
+            assert_eq!(a.origin_uv, b.origin_uv, "origin uv of side {side}");
+            assert_eq!(
+                [a.c, a.c_s, a.c_t, a.c_ss, a.c_st, a.c_tt],
+                [b.c, b.c_s, b.c_t, b.c_ss, b.c_st, b.c_tt],
+                "coefficients of side {side}"
+            );
         }
     }
 }
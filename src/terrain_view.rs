@@ -1,6 +1,7 @@
 //! Types for configuring terrain views.
 
 use bevy::{prelude::*, utils::HashMap};
+use std::{error::Error, fmt};
 
 /// Resource that stores components that are associated to a terrain entity and a view entity.
 #[derive(Deref, DerefMut, Resource)]
@@ -19,11 +20,18 @@ impl<C> Default for TerrainViewComponents<C> {
 pub struct TerrainViewConfig {
     /// The count of tiles in x and y direction per tile tree layer.
     pub tree_size: u32,
-    /// The size of the tile buffer.
+    /// The capacity of the tile buffers, which limits the count of tiles selected by the refinement.
+    ///
+    /// Each tile costs 32 bytes of GPU memory (16 bytes in the final and temporary tile buffer each).
+    /// Tiles beyond the capacity are dropped, which is reported as a warning while the [`DebugTerrain`](crate::debug::DebugTerrain) is active.
     pub geometry_tile_count: u32,
     /// The amount of steps the tile list will be refined.
     pub refinement_count: u32,
     /// The number of rows and columns of the tile grid.
+    ///
+    /// This trades the geometric detail (e.g. of silhouettes) for vertex throughput,
+    /// with around 16 suited for low-end and 64 for high-end GPUs.
+    /// It has to be a power of two of at least two, see [`TerrainViewConfig::validate`].
     pub grid_size: u32,
     /// The percentage tolerance added to the morph distance during tile subdivision.
     /// This is required to counteracted the distortion of the subdivision distance estimation near the corners of the cube sphere.
@@ -41,7 +49,64 @@ pub struct TerrainViewConfig {
     pub morph_range: f32,
     /// The blend percentage in the vertex and fragment shader.
     pub blend_range: f32,
+    /// The lod of the origin tiles, relative to which the positions near the view are approximated.
+    ///
+    /// The tiles of this lod should be small compared to the terrain, so that the approximation stays precise.
+    /// Increase it for small terrains (e.g. planar test scenes), which otherwise fit into a single origin tile.
+    /// It can be adjusted at runtime with [`TileTree::set_origin_lod`](crate::terrain_data::tile_tree::TileTree::set_origin_lod).
     pub origin_lod: u32,
+    /// The hysteresis of the origin tile, as a fraction of its size.
+    ///
+    /// The origin tile is the tile of the origin lod under the view, relative to which the positions are approximated.
+    /// It is only switched, once the view leaves it by more than this margin, so that views moving along
+    /// a tile boundary do not flip the origin tile every frame, which causes a jitter of the relative positions.
+    pub origin_hysteresis: f64,
+    /// The safety margin in world units, by which the tile bounds are expanded during frustum culling.
+    /// This keeps tiles near the view conservatively, so that the camera does not clip through the terrain on descent.
+    pub culling_margin: f32,
+    /// Shifts the level of detail of this view, where each unit halves (positive) or doubles (negative)
+    /// the distances at which tiles are subdivided and morphed.
+    /// Use this to render secondary views (e.g. a minimap) much coarser than the main view.
+    pub lod_bias: f32,
+    /// Optional world space distances, which select the lod of the tiles instead of the morph distance.
+    ///
+    /// A tile of lod `i` is subdivided, while the view is closer than the `i`th distance, so the distances should decrease.
+    /// Lods without a distance are not subdivided any further. At most 32 distances are used.
+    /// The vertices still morph according to the morph distance, which should thus roughly match the bands to avoid cracks.
+    pub lod_distances: Option<Vec<f64>>,
+    /// Forces the geometry tiles to be subdivided to at least this lod, regardless of their distance (e.g. for screenshots).
+    ///
+    /// Only the refinement is overridden, the tile tree still loads the data around the view according to the load distance.
+    /// Wait until the region has been loaded with [`TileAtlas::is_region_loaded`](crate::terrain_data::tile_atlas::TileAtlas::is_region_loaded).
+    /// The visible tiles of the lod have to fit into the geometry tile count.
+    pub force_lod: Option<u32>,
+    /// The optional maximum projected edge length in pixels of the grid cells, which the refinement targets.
+    ///
+    /// Lower values select more tiles for a higher quality. When set, the morph and subdivision distances are derived
+    /// from it each frame (see [`TileScreenError`](crate::math::TileScreenError)), overriding the morph distance and the lod bias.
+    /// It can be adjusted at runtime with [`TileTree::set_target_pixel_error`](crate::terrain_data::tile_tree::TileTree::set_target_pixel_error).
+    pub target_pixel_error: Option<f32>,
+    /// The priority of the view in the tiling prepass, where views with a higher priority are processed first.
+    ///
+    /// Views with the same priority are processed in a stable, but arbitrary order.
+    /// Use this to prepare the main camera before secondary views (e.g. reflection probes).
+    pub priority: i32,
+    /// Whether the lods selected by the tiling prepass are read back per cell of a grid across the screen.
+    ///
+    /// This allows region aware logic, like spawning details depending on the lod of the terrain.
+    /// The readback costs a buffer copy each frame, so it is disabled by default.
+    /// Read the lods with [`TileTree::screen_lods`](crate::terrain_data::tile_tree::TileTree::screen_lods).
+    pub screen_lod_readback: bool,
+    /// Whether the counts of tiles produced by the tiling prepass are read back, e.g. to tune the lod settings.
+    ///
+    /// The readback costs a buffer copy each frame, so it is disabled by default.
+    /// Read the counts with [`TileTree::tile_counts`](crate::terrain_data::tile_tree::TileTree::tile_counts).
+    pub tile_count_readback: bool,
+    /// Pins the approximate height of the terrain below the view, instead of sampling it from the loaded tiles.
+    ///
+    /// Use this for views, which never move vertically. It can be adjusted at runtime with
+    /// [`TileTree::pin_approximate_height`](crate::terrain_data::tile_tree::TileTree::pin_approximate_height).
+    pub approximate_height: Option<f32>,
 }
 
 impl Default for TerrainViewConfig {
@@ -59,6 +124,52 @@ impl Default for TerrainViewConfig {
             blend_range: 0.2,
             precision_threshold_distance: 0.001,
             origin_lod: 10,
+            origin_hysteresis: 0.01,
+            culling_margin: 0.0,
+            lod_bias: 0.0,
+            lod_distances: None,
+            force_lod: None,
+            target_pixel_error: None,
+            priority: 0,
+            screen_lod_readback: false,
+            tile_count_readback: false,
+            approximate_height: None,
         }
     }
 }
+
+impl TerrainViewConfig {
+    /// Checks the invariants of the view config, which is run by [`TileTree::new`](crate::terrain_data::tile_tree::TileTree::new).
+    ///
+    /// The checked invariants are:
+    /// - the grid size is a power of two of at least two.
+    pub fn validate(&self) -> Result<(), TerrainViewConfigError> {
+        if self.grid_size < 2 || !self.grid_size.is_power_of_two() {
+            return Err(TerrainViewConfigError::InvalidGridSize {
+                grid_size: self.grid_size,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// The violated invariant of an invalid [`TerrainViewConfig`], see [`TerrainViewConfig::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TerrainViewConfigError {
+    /// The grid size is not a power of two of at least two.
+    InvalidGridSize { grid_size: u32 },
+}
+
+impl fmt::Display for TerrainViewConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidGridSize { grid_size } => write!(
+                f,
+                "the grid size {grid_size} of the view has to be a power of two of at least two"
+            ),
+        }
+    }
+}
+
+impl Error for TerrainViewConfigError {}
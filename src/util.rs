@@ -1,10 +1,32 @@
-use bevy::render::{
-    render_resource::{encase::internal::WriteInto, *},
-    renderer::{RenderDevice, RenderQueue},
+use bevy::{
+    log::error,
+    render::{
+        render_resource::{encase::internal::WriteInto, *},
+        renderer::{RenderDevice, RenderQueue},
+    },
+    utils::HashSet,
 };
 use itertools::Itertools;
 use std::{fmt::Debug, marker::PhantomData, ops::Deref};
 
+/// Logs an error once per pipeline, if the cached compute pipeline failed to compile.
+///
+/// Pipelines, whose shaders are still loading, are queued by the pipeline cache instead,
+/// thus only actual errors (e.g. invalid shaders or driver bugs) are reported.
+/// Otherwise the node skipping the pipeline would silently leave the terrain empty.
+pub(crate) fn report_pipeline_error(
+    pipeline_cache: &PipelineCache,
+    reported: &mut HashSet<CachedComputePipelineId>,
+    pipeline: CachedComputePipelineId,
+    name: &str,
+) {
+    if let CachedPipelineState::Err(err) = pipeline_cache.get_compute_pipeline_state(pipeline) {
+        if reported.insert(pipeline) {
+            error!("The {name} pipeline of the terrain failed to compile: {err}");
+        }
+    }
+}
+
 pub(crate) fn inverse_mix(a: f32, b: f32, value: f32) -> f32 {
     return f32::clamp((value - a) / (b - a), 0.0, 1.0);
 }